@@ -0,0 +1,74 @@
+//! Encoding and solving times of every game, on the sample puzzles in `benches/samples`.
+//!
+//! Each game has a directory there, holding one puzzle per file in the text format of the
+//! game, and the benchmarks are named after the files: adding a sample only takes dropping
+//! a new file next to the others. A single game or sample can
+//! be selected as usual, with `cargo bench -- binero/solve/large`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use multilogic::{binero, kakuro, kdoku, stars, voisimage};
+
+/// Every sample as (game, name, text), listed by the build script
+const SAMPLES: &[(&str, &str, &str)] = &include!(concat!(env!("OUT_DIR"), "/samples.rs"));
+
+/// The samples of a game, as (name, text) pairs sorted by name
+fn samples(game: &str) -> impl Iterator<Item=(&'static str, &'static str)> + '_ {
+    SAMPLES.iter().filter(move |(g, ..)| *g == game).map(|&(_, name, text)| (name, text))
+}
+
+/// Time the encoding and the solving of every sample of `game`. `solve` returns
+/// whether a solution was found, which every sample must have.
+fn bench<P, E>(
+    c: &mut Criterion,
+    game: &str,
+    parse: impl Fn(&str) -> P,
+    encode: impl Fn(&P) -> E,
+    solve: impl Fn(&P) -> bool,
+) {
+    let mut group = c.benchmark_group(game);
+    for (name, text) in samples(game) {
+        let puzzle = parse(text);
+        assert!(solve(&puzzle), "{}/{} has no solution", game, name);
+        group.bench_with_input(BenchmarkId::new("encode", name), &puzzle, |b, p| b.iter(|| encode(p)));
+        group.bench_with_input(BenchmarkId::new("solve", name), &puzzle, |b, p| b.iter(|| solve(p)));
+    }
+    group.finish();
+}
+
+fn binero(c: &mut Criterion) {
+    bench(c, "binero",
+        |s| s.parse::<binero::Problem>().expect("binero sample"),
+        |p| p.encode(),
+        |p| matches!(p.solve(), Ok(Some(_))));
+}
+
+fn kdoku(c: &mut Criterion) {
+    bench(c, "k-doku",
+        |s| kdoku::parse::constraints(s).expect("kdoku sample"),
+        |p| kdoku::BaseGrid::new().encode(p).ok(),
+        |p| kdoku::BaseGrid::new().solve(p).is_ok());
+}
+
+fn stars(c: &mut Criterion) {
+    bench(c, "stars",
+        |s| s.parse::<stars::Problem>().expect("stars sample"),
+        |p| p.encode(),
+        |p| matches!(p.solve(), Ok(Some(_))));
+}
+
+fn voisimage(c: &mut Criterion) {
+    bench(c, "voisimage",
+        |s| s.parse::<voisimage::Problem>().expect("voisimage sample"),
+        |p| p.encode(),
+        |p| matches!(p.solve(), Ok(Some(_))));
+}
+
+fn kakuro(c: &mut Criterion) {
+    bench(c, "kakuro",
+        |s| s.parse::<kakuro::Problem>().expect("kakuro sample"),
+        |p| p.encode(),
+        |p| matches!(p.solve(), Ok(Some(_))));
+}
+
+criterion_group!(benches, binero, kdoku, stars, voisimage, kakuro);
+criterion_main!(benches);
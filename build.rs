@@ -0,0 +1,59 @@
+//! List the sample puzzles of the benchmarks, which `multilogic bench` bundles too.
+//! With the `ipasir` feature, link the SAT solver implementing the IPASIR interface.
+
+use std::{env, fs, path::{Path, PathBuf}};
+
+fn main() {
+    samples();
+
+    println!("cargo:rerun-if-env-changed=IPASIR_LIB");
+    println!("cargo:rerun-if-env-changed=IPASIR_LIB_DIR");
+    if env::var_os("CARGO_FEATURE_IPASIR").is_none() {
+        return
+    }
+
+    if let Ok(dir) = env::var("IPASIR_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", dir);
+    }
+
+    match env::var("IPASIR_LIB") {
+        Ok(lib) => println!("cargo:rustc-link-lib={}", lib),
+        Err(_) => {
+            // CaDiCaL is written in C++, and its library needs the C++ runtime
+            println!("cargo:rustc-link-lib=cadical");
+            match env::var("CARGO_CFG_TARGET_OS").as_deref() {
+                Ok("macos") | Ok("ios") => println!("cargo:rustc-link-lib=c++"),
+                _ => println!("cargo:rustc-link-lib=stdc++"),
+            }
+        },
+    }
+}
+
+/// The files of `dir` sorted by name, leaving out hidden ones
+fn sorted(dir: &Path) -> Vec<PathBuf> {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+    let mut paths: Vec<PathBuf> = entries
+        .map(|entry| entry.expect("readable sample directory").path())
+        .filter(|path| !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.')))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Write `samples.rs`, with the puzzles of `benches/samples/<game>/<name>` as an array of
+/// (game, name, text), sorted by game then name
+fn samples() {
+    let root = Path::new(&env::var("CARGO_MANIFEST_DIR").expect("set by cargo")).join("benches/samples");
+    println!("cargo:rerun-if-changed={}", root.display());
+
+    let mut code = String::from("[\n");
+    for dir in sorted(&root) {
+        for file in sorted(&dir) {
+            let name = |path: &Path| path.file_name().expect("sample file name").to_string_lossy().into_owned();
+            code += &format!("    ({:?}, {:?}, include_str!({:?})),\n", name(&dir), name(&file), file.display().to_string());
+        }
+    }
+    code += "]\n";
+    let out = Path::new(&env::var("OUT_DIR").expect("set by cargo")).join("samples.rs");
+    fs::write(&out, code).unwrap_or_else(|e| panic!("{}: {}", out.display(), e));
+}
@@ -0,0 +1,151 @@
+//! Solving many independent puzzles at once, such as a whole book of them. The puzzles
+//! are spread over the threads of the global rayon pool (one per core unless configured
+//! otherwise), each one getting its own solver, in the [`Scope`] of the calling thread:
+//! with its backend, and stopping once its computation is cancelled.
+
+use rayon::prelude::*;
+
+use crate::util::{backend::BackendError, scope::Scope, stats::{with_stats, SolveStats}};
+use crate::{binero, kakuro, kdoku, stars, voisimage};
+
+/// A puzzle that can be solved on its own
+pub trait Solve: Sync {
+    type Solution<'a>: Send where Self: 'a;
+    /// Why the puzzle could not be solved, other than having no solution
+    type Error: Send;
+
+    /// A solution of the puzzle, if it has any
+    fn solve(&self) -> Result<Option<Self::Solution<'_>>, Self::Error>;
+}
+
+/// What became of every puzzle: a solution, none, or an error
+pub type Solved<'a, P> = Result<Option<<P as Solve>::Solution<'a>>, <P as Solve>::Error>;
+
+/// Solve all the `problems` in parallel. The solutions come in the same order.
+pub fn solve_all<P: Solve>(problems: &[P]) -> Vec<Solved<'_, P>> {
+    solve_all_with_stats(problems).into_iter().map(|(s, _)| s).collect()
+}
+
+/// Like [`solve_all`], along with the statistics of the solvers of every puzzle
+pub fn solve_all_with_stats<P: Solve>(problems: &[P]) -> Vec<(Solved<'_, P>, SolveStats)> {
+    solve_all_with_progress(problems, |_| ())
+}
+
+/// Like [`solve_all_with_stats`], calling `done` with the index of every puzzle once it
+/// is solved, from the thread that solved it
+pub fn solve_all_with_progress<P: Solve>(problems: &[P], done: impl Fn(usize) + Sync) -> Vec<(Solved<'_, P>, SolveStats)> {
+    let scope = Scope::current();
+    problems.par_iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let solved = scope.run(|| with_stats(|| p.solve()));
+            done(i);
+            solved
+        })
+        .collect()
+}
+
+impl Solve for binero::Problem {
+    type Solution<'a> = binero::Solution;
+    type Error = BackendError;
+
+    fn solve(&self) -> Result<Option<binero::Solution>, BackendError> {
+        binero::Problem::solve(self)
+    }
+}
+
+impl Solve for kakuro::Problem {
+    type Solution<'a> = kakuro::Solution;
+    type Error = BackendError;
+
+    fn solve(&self) -> Result<Option<kakuro::Solution>, BackendError> {
+        kakuro::Problem::solve(self)
+    }
+}
+
+/// A K-Doku is given by the constraints on its areas, which may be impossible
+impl Solve for Vec<kdoku::Constraint> {
+    type Solution<'a> = kdoku::Solution;
+    type Error = crate::Error;
+
+    fn solve(&self) -> Result<Option<kdoku::Solution>, crate::Error> {
+        match kdoku::BaseGrid::new().solve(self) {
+            Ok(solution) => Ok(Some(solution)),
+            Err(kdoku::LogicalError::Unsatisfyable) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Solve for stars::Problem {
+    type Solution<'a> = stars::Solution<'a>;
+    type Error = BackendError;
+
+    fn solve(&self) -> Result<Option<stars::Solution<'_>>, BackendError> {
+        stars::Problem::solve(self)
+    }
+}
+
+impl Solve for voisimage::Problem {
+    type Solution<'a> = voisimage::Solution;
+    type Error = BackendError;
+
+    fn solve(&self) -> Result<Option<voisimage::Solution>, BackendError> {
+        voisimage::Problem::solve(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+
+    use super::*;
+    use crate::util::backend::with_backend;
+
+    #[test]
+    fn binero() {
+        let problems: Vec<binero::Problem> = ["10..\n....\n....\n....\n", "000.\n....\n....\n....\n", "....\n.1..\n....\n..0.\n"]
+            .iter()
+            .map(|p| p.parse().unwrap())
+            .collect();
+
+        let solutions: Vec<_> = solve_all(&problems).into_iter().map(Result::unwrap).collect();
+        assert_eq!(solutions.len(), 3);
+        assert!(solutions[1].is_none());
+        for i in [0, 2] {
+            assert!(problems[i].verify(solutions[i].as_ref().unwrap()).is_ok());
+        }
+
+        let done = AtomicUsize::new(0);
+        solve_all_with_progress(&problems, |_| { done.fetch_add(1, Ordering::Relaxed); });
+        assert_eq!(done.load(Ordering::Relaxed), problems.len());
+    }
+
+    #[test]
+    fn kdoku() {
+        let problems = [
+            crate::constraints![ 40+ [ (0,0), (0,1) ], ],
+            crate::constraints![ 3+ [ (0,0), (0,1) ], 3+ [ (0,2), (0,3) ], 3+ [ (0,4), (0,5) ], ],
+        ];
+        let solutions = solve_all(&problems);
+        assert!(matches!(solutions[0], Err(crate::Error::ImpossibleConstraint(_))));
+        assert!(matches!(solutions[1], Ok(None)));
+    }
+
+    #[test]
+    fn backend() {
+        let problems: Vec<binero::Problem> = (0..8).map(|_| "....\n....\n....\n....\n".parse().unwrap()).collect();
+
+        // Every worker thread uses the backend of the caller
+        let created = Arc::new(AtomicUsize::new(0));
+        let counter = created.clone();
+        let factory = move || -> Box<dyn crate::util::backend::SatBackend> {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Box::new(varisat::Solver::new())
+        };
+
+        let solutions = with_backend(factory, || solve_all_with_stats(&problems));
+        assert!(solutions.iter().all(|(s, stats)| matches!(s, Ok(Some(_))) && stats.solvers == 1));
+        assert_eq!(created.load(Ordering::Relaxed), problems.len());
+    }
+}
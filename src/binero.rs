@@ -1,118 +1,506 @@
-use std::{str::FromStr, fmt::{Display, Write}};
+use std::{str::FromStr, fmt::Display, ops::ControlFlow};
 
 use thiserror::Error;
-use varisat::{Solver, ExtendFormula, Var};
+use tracing::{debug, debug_span, info};
+use varisat::{CnfFormula, ExtendFormula, Var, Lit};
 
-use crate::util::{matrix::{Matrix, ShapeError}, solve::DnfFormula};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
+use crate::generate::Generate;
+use crate::render::Scene;
+use crate::transform::Transform;
+use crate::verify::{Violation, verdict, same_shape};
+use crate::util::{backend::{record, BackendError}, cache::FormulaCache, gridsolver::{GridSolver, GridModel}, matrix::{Matrix, umat}, deduce::{deduce, lines, Deduction, Step}, gridparse::GridError, solve::{backbone, minimal_core, random_model, Models, SolveOptions}, card::{CardFormula, Encoding}, symmetry::{Symmetry, SymmetryFormula}, coord::Coord, propagate::{count, propagate, Contradiction, Domains}};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Problem(pub Matrix<Option<bool>>);
 
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Solution(pub Matrix<bool>);
 
+/// A given cell of the problem, and its value
+pub type Given = ((usize, usize), bool);
+
+/// A Binero encoded into a [`GridSolver`], for hypotheses about the value of some cells
+pub struct Session {
+    solver: GridSolver,
+    grid: Matrix<Var>,
+    /// Given cells, along with the literal enabling each of them
+    givens: Vec<(Given, Lit)>,
+}
+
 impl Problem {
-    pub fn solve(&self) -> Option<Solution> {
+    /// The grid to draw, with the given cells
+    pub fn scene(&self) -> Scene {
+        let mut scene = Scene::new(self.0.shape());
+        for (cell, given) in scene.cells.iter_mut().zip(&self.0) {
+            cell.label = given.map(|v| (v as u8).to_string());
+        }
+        scene
+    }
+
+    pub fn solve(&self) -> Result<Option<Solution>, BackendError> {
+        self.session().solve(&[])
+    }
 
-        let size = self.0.shape().0;
-        let k = size / 2;
+    /// Find a solution drawn at random from `seed`, for grids with several solutions.
+    /// See [`random_model`].
+    pub fn solve_random(&self, seed: u64) -> Result<Option<Solution>, BackendError> {
+        self.session().solve_random(&mut StdRng::seed_from_u64(seed))
+    }
 
-        let mut solver = Solver::new();
-        let vars = solver.new_var_iter(self.0.len()).collect();
+    /// Count the solutions of the grid, by enumerating them.
+    /// With `break_symmetry`, symmetric solutions only count once.
+    pub fn count_solutions(&self, options: SolveOptions) -> Result<usize, BackendError> {
+        self.session_with(options).count()
+    }
 
-        let grid = Matrix::new(vars, self.0.shape())
-            .expect("inconsistent len and shape");
+    /// Find a solution that differs from `prior` in at least one cell.
+    /// Returns `None` if `prior` is the only completion of the grid.
+    pub fn solve_excluding(&self, prior: &Solution) -> Result<Option<Solution>, BackendError> {
+        let mut session = self.session();
+        session.exclude(prior);
+        session.solve(&[])
+    }
 
-        // For columns and rows, have at least a 1 and a 0 for all three consecutive cells
-        for (x,y) in grid.indices() {
-            if x >= 2 {
-                not_uniform(&mut solver, &[grid[x-2][y], grid[x-1][y], grid[x][y]]);
+    /// Call `f` with every completion of the grid, see [`Session::solve_with`].
+    pub fn solve_with<B>(&self, f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        self.session().solve_with(f)
+    }
+
+    /// List the empty cells whose value is the same in every solution, in row-major order.
+    /// Returns `None` if the grid has no solution.
+    pub fn hints(&self) -> Result<Option<Vec<((usize, usize), bool)>>, BackendError> {
+        let mut session = self.session();
+        let Some(s) = session.solve(&[])? else { return Ok(None) };
+
+        let candidates = self.0.indices()
+            .filter(|&(x,y)| self.0[(x,y)].is_none())
+            .map(|(x,y)| (((x,y), s.0[(x,y)]), session.grid[(x,y)].lit(s.0[(x,y)])))
+            .collect();
+
+        let givens: Vec<_> = session.givens.iter().map(|&(_, s)| s).collect();
+        Ok(Some(backbone(&mut session.solver, &givens, candidates)?))
+    }
+
+    /// The same grid with every 0 turned into a 1 and conversely
+    pub fn complement(&self) -> Problem {
+        Problem(self.0.map(|c| c.map(|v| !v)))
+    }
+
+    /// Fill the grid using only simple human rules, recording every step:
+    ///  - a cell next to two equal cells, or between them, takes the other value
+    ///  - once a line holds half of its cells with a value, the rest take the other one
+    pub fn deduce(&self) -> Deduction<bool> {
+        deduce(self.0.clone(), &[&no_three, &line_count])
+    }
+
+    /// Check `solution` against the givens and the rules directly, without the SAT solver.
+    /// Lists every broken rule if it is not valid.
+    pub fn verify(&self, solution: &Solution) -> Result<(), Vec<Violation>> {
+        let s = &solution.0;
+        same_shape(self.0.shape(), s.shape())?;
+        let mut violations = vec![];
+
+        for c in self.0.coords() {
+            if self.0[c].is_some_and(|v| v != s[c]) {
+                violations.push(Violation::new(format!("the given {} was changed", !s[c] as u8), vec![c]));
+            }
+        }
+
+        for (name, line) in lines(s.shape()) {
+            for w in line.windows(3) {
+                let [a, b, c] = [w[0], w[1], w[2]].map(|c| s[c]);
+                if a == b && b == c {
+                    violations.push(Violation::new(format!("three consecutive {} in {}", a as u8, name), w.to_vec()));
+                }
             }
-            if y >= 2 {
-                not_uniform(&mut solver, &[grid[x][y-2], grid[x][y-1], grid[x][y]]);
+
+            let ones = line.iter().filter(|&&c| s[c]).count();
+            if 2 * ones != line.len() {
+                violations.push(Violation::new(format!("{} has {} cells set to 1 out of {}", name, ones, line.len()), vec![]));
             }
         }
 
-        // For rows and columns, have exactly 5 cells set
-        for x in 0..size {
-            solver.add_popcount(&grid[x], k);
+        verdict(violations)
+    }
+
+    /// The formula of this grid, to inspect or export it without solving, along with
+    /// the variable of each cell, true for a 1. The givens are unit clauses.
+    pub fn encode(&self) -> (CnfFormula, Matrix<Var>) {
+        let (mut formula, (_, grid, givens)) = record(|| self.encode_solver());
+        for (_, selector) in givens {
+            formula.add_clause(&[selector]);
         }
-        for y in 0..size {
-            let column: Vec<_> = (0..size).map(|x| grid[x][y]).collect();
-            solver.add_popcount(&column, k);
+        (formula, grid)
+    }
+
+    /// Encode the grid into a [`Session`].
+    pub fn session(&self) -> Session {
+        self.session_with(SolveOptions::default())
+    }
+
+    /// Encode the grid into a [`Session`], with the given options.
+    pub fn session_with(&self, options: SolveOptions) -> Session {
+        let (mut solver, grid, givens) = self.encode_solver();
+
+        if options.break_symmetry {
+            let lits = grid.map(|v| v.positive());
+            for (symmetry, swap) in self.symmetries() {
+                let image = symmetry.image(&lits).map(|&l| if swap { !l } else { l });
+                solver.add_lex_leader(&lits, &image);
+            }
+        }
+
+        if options.propagate {
+            match self.propagate() {
+                Ok(forced) => for (x,y) in grid.indices() {
+                    if let (None, Some(v)) = (self.0[(x,y)], forced[(x,y)]) {
+                        solver.add_clause(&[grid[(x,y)].lit(v)]);
+                    }
+                },
+                Err(_) => solver.add_clause(&[]),
+            }
+        }
+
+        Session { solver, grid, givens }
+    }
+
+    /// The cells forced by the givens, through the rules on triples and line counts
+    fn propagate(&self) -> Result<Matrix<Option<bool>>, Contradiction> {
+        let mut domains = Domains::new(&self.0, &[false, true]);
+        let lines: Vec<_> = lines(self.0.shape()).into_iter().map(|(_, line)| line).collect();
+        let rules = |d: &mut Domains<bool>| lines.iter().fold(false, |changed, line| {
+            let half = line.len() / 2;
+            no_three_left(d, line) | count(d, line, &false, half) | count(d, line, &true, half) | changed
+        });
+        propagate(&mut domains, &[&rules])?;
+        Ok(domains.fixed())
+    }
+
+    /// The transformations mapping the givens onto themselves: a symmetry of the grid,
+    /// possibly followed by swapping 0 and 1. The identity is left out.
+    fn symmetries(&self) -> Vec<(Symmetry, bool)> {
+        std::iter::once(Symmetry::default()).chain(Symmetry::of_shape(self.0.shape()))
+            .flat_map(|s| [(s, false), (s, true)])
+            .filter(|&(s, swap)| swap || !s.is_identity())
+            .filter(|&(s, swap)| s.image(&self.0).map(|g| g.map(|v| v ^ swap)) == self.0)
+            .collect()
+    }
+
+    /// When the grid has no solution, find a minimal set of given cells that already
+    /// contradict each other. An empty set means the grid is unsolvable because of its shape.
+    /// Returns `None` if the grid has a solution.
+    pub fn conflict(&self) -> Result<Option<Vec<Given>>, BackendError> {
+        let mut session = self.session();
+        let selectors: Vec<_> = session.givens.iter().map(|&(_, s)| s).collect();
+        let Some(core) = minimal_core(&mut session.solver, &selectors)? else { return Ok(None) };
+
+        Ok(Some(session.givens.into_iter()
+            .filter(|(_, s)| core.contains(s))
+            .map(|(given, _)| given)
+            .collect()))
+    }
+
+    /// Remove redundant givens: find a minimal subset of them that still forces the same
+    /// unique solution. Removing any further given from the result allows another solution.
+    /// Returns `None` if the grid does not have exactly one solution.
+    pub fn minimize(&self) -> Result<Option<Problem>, BackendError> {
+        let mut session = self.session();
+        let Some(s) = session.solve(&[])? else { return Ok(None) };
+
+        // With the only solution forbidden, the givens become contradictory,
+        // and a minimal conflict is a minimal set of givens keeping the solution unique.
+        session.exclude(&s);
+        let selectors: Vec<_> = session.givens.iter().map(|&(_, s)| s).collect();
+        let Some(core) = minimal_core(&mut session.solver, &selectors)? else { return Ok(None) };
+
+        let mut grid = self.0.map(|_| None);
+        for (((x,y), p), _) in session.givens.into_iter().filter(|(_, s)| core.contains(s)) {
+            grid[(x,y)] = Some(p);
         }
+        Ok(Some(Problem(grid)))
+    }
+
+    fn encode_solver(&self) -> (GridSolver, Matrix<Var>, Vec<(Given, Lit)>) {
 
-        // Problem constraints
-        self.0.zip_with(&grid, |(p,c)| {
-            if let Some(p) = p {
-                solver.add_clause(&[c.lit(*p)]);
+        let rules = RULES.get(self.0.shape());
+        let _span = debug_span!("encode constraints").entered();
+        let mut solver = GridSolver::new();
+        solver.add_formula(&rules.0);
+        let grid = rules.1.clone();
+
+        // Problem constraints, each enabled by a selector literal
+        let mut givens = vec![];
+        for (x,y) in grid.indices() {
+            if let Some(p) = self.0[(x,y)] {
+                let selector = solver.new_lit();
+                solver.add_clause(&[!selector, grid[(x,y)].lit(p)]);
+                givens.push((((x,y), p), selector));
             }
-        }).expect("inconsistent shape");
+        }
+
+        info!(shape = ?self.0.shape(), givens = givens.len(), clauses = solver.clauses(), "encoded");
+        (solver, grid, givens)
+
+    }
+}
 
-        solver.solve().expect("solver failure");
-        let m = solver.model()?;
+/// The rules of the game for each shape of grid, only built once
+static RULES: FormulaCache<(usize, usize), (CnfFormula, Matrix<Var>)> = FormulaCache::new(rules);
 
-        let solution = grid.map(|v| m.contains(&v.positive()));
-        Some(Solution(solution))
+/// Encode the rules of the game for an empty grid, returning the variables of the cells
+fn rules(&shape: &(usize, usize)) -> (CnfFormula, Matrix<Var>) {
+    let _span = debug_span!("build base grid", ?shape).entered();
 
+    let k = shape.0 / 2;
+
+    let mut f = CnfFormula::new();
+    let vars = f.new_var_iter(shape.0 * shape.1).collect();
+
+    let grid = Matrix::new(vars, shape)
+        .expect("inconsistent len and shape");
+
+    // For columns and rows, have at least a 1 and a 0 for all three consecutive cells
+    for g in [&grid, &grid.transpose()] {
+        for three in g.lines().flat_map(|line| line.windows(3)) {
+            not_uniform(&mut f, three);
+        }
+    }
+
+    // For rows and columns, have exactly half of the cells set
+    for (_, line) in lines(grid.shape()) {
+        let lits: Vec<_> = line.iter().map(|&c| grid[c].positive()).collect();
+        f.exactly_k(&lits, k, Encoding::default());
+    }
+
+    debug!(clauses = f.len());
+    (f, grid)
+}
+
+impl Session {
+    /// Solve the grid, assuming that every listed cell holds the given value.
+    /// A cell out of the grid cannot hold anything, and makes the grid unsatisfiable.
+    pub fn solve(&mut self, assumptions: &[((usize, usize), bool)]) -> Result<Option<Solution>, BackendError> {
+        let mut lits: Vec<_> = self.givens.iter().map(|&(_, s)| s).collect();
+        for &((x,y), b) in assumptions {
+            let Some(var) = self.grid.get(x, y) else { return Ok(None) };
+            lits.push(var.lit(b));
+        }
+
+        Ok(self.solver.solve(&lits)?.map(|m| self.decode(&m)))
+    }
+
+    /// Solve the grid, drawing a solution at random. See [`random_model`].
+    pub fn solve_random(&mut self, rng: &mut impl Rng) -> Result<Option<Solution>, BackendError> {
+        let givens: Vec<_> = self.givens.iter().map(|&(_, s)| s).collect();
+        let vars: Vec<_> = self.grid.iter().copied().collect();
+        let m = random_model(&mut self.solver, &givens, &vars, rng)?;
+        Ok(m.map(|m| self.decode(&m.into())))
+    }
+
+    /// Count the solutions, excluding each of them once found. See [`Models`].
+    pub fn count(&mut self) -> Result<usize, BackendError> {
+        let givens: Vec<_> = self.givens.iter().map(|&(_, s)| s).collect();
+        let vars: Vec<_> = self.grid.iter().copied().collect();
+        Models::new(&mut self.solver, vars).assuming(&givens).try_count()
+    }
+
+    fn decode(&self, model: &GridModel) -> Solution {
+        Solution(model.bools(&self.grid))
+    }
+
+    /// Call `f` with every completion of the grid left, see [sessions](crate::util::solve#sessions).
+    pub fn solve_with<B>(&mut self, mut f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        while let Some(s) = self.solve(&[])? {
+            self.exclude(&s);
+            if let ControlFlow::Break(b) = f(s) { return Ok(ControlFlow::Break(b)) }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Rule out the completion `prior` from now on.
+    pub fn exclude(&mut self, prior: &Solution) {
+        let blocking: Vec<_> = self.grid.iter().zip(&prior.0)
+            .map(|(v, &b)| v.lit(!b))
+            .collect();
+        self.solver.add_clause(&blocking);
     }
 }
 
 #[derive(Debug, Error)]
 pub enum ParseError {
-    #[error("Empty grid")]
-    EmptyGrid,
-    #[error("Invalid char {0}")]
-    InvalidChar(char),
-    #[error("Building matrix: {0}")]
-    Build(#[from] ShapeError)
+    #[error(transparent)]
+    Grid(#[from] GridError),
+    #[error("the grid is {0}x{1}, but must be square with an even side")]
+    Shape(usize, usize),
+}
+
+
+/// Bineros are generated from the values of some cells of a random solution.
+/// The shape is the side of the square grid, which must be even.
+impl Generate for Problem {
+    type Shape = usize;
+    type Solution = Solution;
+    type Clue = Given;
+
+    fn random_solution(&size: &usize, rng: &mut StdRng) -> Result<Option<Solution>, BackendError> {
+        Problem(umat![None; (size, size)]).session().solve_random(rng)
+    }
+
+    fn clues(_: &usize, solution: &Solution) -> Vec<Given> {
+        solution.0.enumerate().map(|(pos, &v)| (pos, v)).collect()
+    }
+
+    fn from_clues(&size: &usize, _: &Solution, clues: &[Given]) -> Self {
+        let mut grid = umat![None; (size, size)];
+        for &((x,y), v) in clues {
+            grid[(x,y)] = Some(v);
+        }
+        Problem(grid)
+    }
+
+    fn is_unique(&self) -> Result<bool, BackendError> {
+        let Some(s) = self.solve()? else { return Ok(false) };
+        Ok(self.solve_excluding(&s)?.is_none())
+    }
+
+    fn is_easy(&self) -> bool {
+        self.deduce().is_complete()
+    }
 }
 
+/// The rules treat 0 and 1 alike, so swapping them gives the same puzzle
+impl Transform for Problem {
+    fn transform(&self, symmetry: Symmetry) -> Self {
+        Problem(symmetry.image(&self.0))
+    }
+
+    fn relabelings(&self) -> Vec<Self> {
+        vec![self.clone(), self.complement()]
+    }
+}
+
+impl Transform for Solution {
+    fn transform(&self, symmetry: Symmetry) -> Self {
+        Solution(symmetry.image(&self.0))
+    }
+
+    fn relabelings(&self) -> Vec<Self> {
+        vec![self.clone(), self.complement()]
+    }
+}
 
 impl FromStr for Problem {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut cells = vec![];
-        let mut h = 0;
-        for line in s.lines() {
-            for c in line.chars() {
-                cells.push(match c {
-                    '1' => Some(true),
-                    '0' => Some(false),
-                    '.'|' '|'-' => None,
-                    other => return Err(ParseError::InvalidChar(other))
-                })
-            }
-            h += 1;
+        let grid: Matrix<Option<bool>> = s.parse()?;
+        match grid.shape() {
+            (h, w) if h == w && h % 2 == 0 => Ok(Problem(grid)),
+            (h, w) => Err(ParseError::Shape(h, w)),
         }
-        let w = cells.len() / h;
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Parses a grid of `0` and `1`, as printed by the `Display` implementation
+impl FromStr for Solution {
+    type Err = ParseError;
 
-        Ok(Problem(Matrix::new(cells, (h,w))?))
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Solution(s.parse()?))
+    }
+}
+
+impl Solution {
+    /// The value of every cell, `true` for a 1
+    pub fn grid(&self) -> &Matrix<bool> {
+        &self.0
+    }
+
+    /// The same solution with every 0 turned into a 1 and conversely
+    pub fn complement(&self) -> Solution {
+        Solution(self.0.map(|v| !v))
+    }
+
+    /// The grid to draw, with the digit of every cell
+    pub fn scene(&self) -> Scene {
+        Scene::labeled(&self.0.map(|&v| v as u8))
     }
 }
 
 impl Display for Solution {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for line in self.0.lines() {
-            for cell in line {
-                f.write_char(if *cell { '1' } else { '0' })?;
+        self.0.fmt(f)
+    }
+}
+
+fn no_three(grid: &Matrix<Option<bool>>) -> Option<Step<bool>> {
+    for (_, line) in lines(grid.shape()) {
+        for w in line.windows(3) {
+            let known: Vec<_> = w.iter()
+                .filter_map(|&c| Some((c, grid[c]?)))
+                .collect();
+            let [(a, va), (b, vb)] = known[..] else { continue };
+            if va != vb { continue }
+            let Some(&cell) = w.iter().find(|&&c| grid[c].is_none()) else { continue };
+            return Some(Step {
+                cell,
+                value: !va,
+                reason: format!("{} and {} are both {}", a, b, va as u8),
+            })
+        }
+    }
+    None
+}
+
+fn line_count(grid: &Matrix<Option<bool>>) -> Option<Step<bool>> {
+    for (name, line) in lines(grid.shape()) {
+        let Some(&cell) = line.iter().find(|&&c| grid[c].is_none()) else { continue };
+        for v in [false, true] {
+            let count = line.iter().filter(|&&c| grid[c] == Some(v)).count();
+            if count == line.len() / 2 {
+                return Some(Step {
+                    cell,
+                    value: !v,
+                    reason: format!("{} already has {} cells set to {}", name, count, v as u8),
+                })
             }
-            writeln!(f)?;
         }
-        Ok(())
     }
+    None
 }
 
-fn not_uniform(solver: &mut Solver, vars: &[Var]) {
-    solver.add_clause(&vars.iter().copied().map(Var::positive).collect::<Vec<_>>());
-    solver.add_clause(&vars.iter().copied().map(Var::negative).collect::<Vec<_>>());
+/// Two equal cells among three consecutive ones leave the other value to the third
+fn no_three_left(d: &mut Domains<bool>, line: &[Coord]) -> bool {
+    let mut changed = false;
+    for w in line.windows(3) {
+        for v in [false, true] {
+            if w.iter().filter(|&&c| d.value(c) == Some(&v)).count() == 2 {
+                for &c in w {
+                    if d.value(c) != Some(&v) { changed |= d.remove(c, &v) }
+                }
+            }
+        }
+    }
+    changed
+}
+
+fn not_uniform(f: &mut impl ExtendFormula, vars: &[Var]) {
+    f.add_clause(&vars.iter().copied().map(Var::positive).collect::<Vec<_>>());
+    f.add_clause(&vars.iter().copied().map(Var::negative).collect::<Vec<_>>());
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    #[test]
-    fn sample() {
-        let p = "\
+    const SAMPLE: &str = "\
 .0...00..1
 ..00.1..0.
 ...0......
@@ -125,7 +513,7 @@ mod test {
 0.0.00..0.
 ";
 
-        let s = "\
+    const SOLUTION: &str = "\
 1001100101
 1100110100
 0010011011
@@ -138,14 +526,275 @@ mod test {
 0101001101
 ";
 
+    #[test]
+    fn sample() {
         assert_eq!(
-            p.parse::<Problem>()
+            SAMPLE.parse::<Problem>()
              .unwrap()
-             .solve()
+             .solve().unwrap()
              .unwrap()
              .to_string()
-        , s);
+        , SOLUTION);
+
+    }
+
+    #[test]
+    fn shape() {
+        assert!(matches!("01.\n...\n".parse::<Problem>(), Err(ParseError::Shape(2, 3))));
+        assert!(matches!("0..\n...\n...\n".parse::<Problem>(), Err(ParseError::Shape(3, 3))));
+        assert!("01\n..\n".parse::<Problem>().is_ok());
+    }
+
+    #[test]
+    fn encode() {
+        let p: Problem = SAMPLE.parse().unwrap();
+        let (formula, grid) = p.encode();
+
+        let mut solver = GridSolver::new();
+        solver.add_formula(&formula);
+        let solution = Solution(solver.solve(&[]).unwrap().unwrap().bools(&grid));
+        assert_eq!(solution.to_string(), SOLUTION);
+    }
+
+    #[test]
+    fn propagation() {
+        let p: Problem = SAMPLE.parse().unwrap();
+        let solution: Solution = SOLUTION.parse().unwrap();
+
+        // Propagation only fixes cells to their value in the solution, and more than the givens
+        let forced = p.propagate().unwrap();
+        for c in forced.coords() {
+            assert!(forced[c].is_none_or(|v| v == solution.0[c]), "{}", c);
+        }
+        let count = |g: &Matrix<Option<bool>>| g.iter().filter(|v| v.is_some()).count();
+        assert!(count(&forced) > count(&p.0));
+
+        let options = SolveOptions { propagate: true, ..Default::default() };
+        assert_eq!(p.session_with(options).solve(&[]).unwrap().unwrap().to_string(), SOLUTION);
+
+        // Three ones in a row of four
+        let p: Problem = "111.\n....\n....\n....\n".parse().unwrap();
+        assert!(p.propagate().is_err());
+        assert!(p.session_with(options).solve(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn stream() {
+        let p: Problem = "10..\n....\n....\n....\n".parse().unwrap();
+        let mut seen = vec![];
+        let flow = p.solve_with(|s| { seen.push(s); ControlFlow::<()>::Continue(()) }).unwrap();
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(seen.len(), p.count_solutions(SolveOptions::default()).unwrap());
+        assert!(seen.iter().all(|s| p.verify(s).is_ok()));
+
+        let mut n = 0;
+        let flow = p.solve_with(|_| { n += 1; if n == 3 { ControlFlow::Break(n) } else { ControlFlow::Continue(()) } }).unwrap();
+        assert_eq!(flow, ControlFlow::Break(3));
+    }
+
+    #[test]
+    fn alternative() {
+        let p: Problem = "\
+10..
+....
+....
+....
+".parse().unwrap();
+
+        let first = p.solve().unwrap().unwrap();
+        let second = p.solve_excluding(&first).unwrap().unwrap();
+        assert_ne!(first.0, second.0);
+        assert_eq!(second.0[0][..2], [true, false]);
+
+        let p: Problem = "\
+00..
+00..
+....
+....
+".parse().unwrap();
+        let s = p.solve().unwrap().unwrap();
+        assert!(p.solve_excluding(&s).unwrap().is_none());
+    }
+
+    #[test]
+    fn symmetry() {
+        let p: Problem = "....\n....\n....\n....\n".parse().unwrap();
+        let total = p.count_solutions(SolveOptions::default()).unwrap();
+        assert_eq!(total, 90);
+
+        // Each remaining solution stands for all of its distinct images
+        let mut session = p.session_with(SolveOptions { break_symmetry: true, ..Default::default() });
+        let mut orbits = 0;
+        while let Some(s) = session.solve(&[]).unwrap() {
+            let mut images: Vec<_> = p.symmetries().into_iter()
+                .map(|(sym, swap)| sym.image(&s.0).map(|&v| v ^ swap))
+                .chain([s.0.clone()])
+                .map(|m| m.iter().copied().collect::<Vec<_>>())
+                .collect();
+            images.sort();
+            images.dedup();
+            orbits += images.len();
+            session.exclude(&s);
+        }
+        assert_eq!(orbits, total);
+        assert!(p.count_solutions(SolveOptions { break_symmetry: true, ..Default::default() }).unwrap() < total);
+
+        // A given is only symmetric to itself under a flip of the rows
+        let p: Problem = "...1\n....\n....\n...1\n".parse().unwrap();
+        assert_eq!(p.symmetries(), vec![(Symmetry { transpose: false, flip_rows: true, flip_columns: false }, false)]);
+    }
+
+    #[test]
+    fn random() {
+        let p: Problem = "\
+10..
+....
+....
+....
+".parse().unwrap();
+
+        let solutions: Vec<_> = (0..8).map(|seed| p.solve_random(seed).unwrap().unwrap()).collect();
+        for s in &solutions {
+            assert!(p.verify(s).is_ok());
+        }
+        assert!(solutions.iter().any(|s| s.0 != solutions[0].0));
+        assert_eq!(p.solve_random(3).unwrap().unwrap().0, solutions[3].0);
+    }
+
+    #[test]
+    fn assumptions() {
+        let p: Problem = "\
+00..
+....
+....
+....
+".parse().unwrap();
+        let mut session = p.session();
+
+        let s = session.solve(&[((3,3), false)]).unwrap().unwrap();
+        assert!(!s.0[3][3]);
+        let s = session.solve(&[((3,3), true)]).unwrap().unwrap();
+        assert!(s.0[3][3]);
+
+        assert!(session.solve(&[((0,2), false)]).unwrap().is_none());
+        assert!(session.solve(&[]).unwrap().is_some());
+
+        // Cells out of the grid cannot hold anything
+        assert!(session.solve(&[((4,0), true)]).unwrap().is_none());
+        assert!(session.solve(&[((0,4), false)]).unwrap().is_none());
+    }
+
+    #[test]
+    fn hints() {
+        let p: Problem = "\
+00..
+....
+....
+....
+".parse().unwrap();
+        assert_eq!(p.hints().unwrap().unwrap(), vec![((0,2), true), ((0,3), true)]);
+
+        let p: Problem = "\
+000.
+....
+....
+....
+".parse().unwrap();
+        assert!(p.hints().unwrap().is_none());
+    }
+
+    #[test]
+    fn deduction() {
+        let p: Problem = SAMPLE.parse().unwrap();
+        let d = p.deduce();
+        assert!(d.is_complete());
+        assert_eq!(d.steps[0], Step { cell: Coord::new(0,4), value: true, reason: "(0,5) and (0,6) are both 0".into() });
+        assert_eq!(Solution(d.grid.map(|c| c.unwrap())).to_string(), SOLUTION);
+
+        let p: Problem = "\
+00..
+....
+....
+....
+".parse().unwrap();
+        let d = p.deduce();
+        assert_eq!(d.steps.len(), 2);
+        assert!(!d.is_complete());
+    }
+
+    #[test]
+    fn minimize() {
+        let m = SAMPLE.parse::<Problem>().unwrap().minimize().unwrap().unwrap();
+        let s = m.solve().unwrap().unwrap();
+        assert_eq!(s.to_string(), SOLUTION);
+        assert!(m.solve_excluding(&s).unwrap().is_none());
+
+        // Every remaining given is needed
+        for (x,y) in m.0.indices().filter(|&(x,y)| m.0[(x,y)].is_some()) {
+            let mut fewer = Problem(m.0.clone());
+            fewer.0[(x,y)] = None;
+            let s = fewer.solve().unwrap().unwrap();
+            assert!(fewer.solve_excluding(&s).unwrap().is_some());
+        }
+
+        // Round trip through the text format
+        assert_eq!(m.to_string().parse::<Problem>().unwrap().0, m.0);
+    }
+
+    #[test]
+    fn generate() {
+        use crate::generate::{generate, Difficulty};
+
+        for difficulty in [Difficulty::Easy, Difficulty::Hard] {
+            let p: Problem = generate(&6, difficulty, 42).unwrap().unwrap();
+            assert!(p.meets(difficulty).unwrap());
+
+            // Reproducible from the seed
+            let again: Problem = generate(&6, difficulty, 42).unwrap().unwrap();
+            assert_eq!(p.0, again.0);
+        }
+
+        // When only uniqueness matters, no given can be dropped
+        let p: Problem = generate(&6, Difficulty::Hard, 7).unwrap().unwrap();
+        assert_eq!(p.minimize().unwrap().unwrap().0, p.0);
+    }
+
+    #[test]
+    fn verify() {
+        let p: Problem = SAMPLE.parse().unwrap();
+        let s: Solution = SOLUTION.parse().unwrap();
+        assert_eq!(p.verify(&s), Ok(()));
+
+        // Swapping two cells of a row breaks a given and the balance of the columns
+        let mut s = s;
+        s.0[0][0] = false;
+        s.0[0][1] = true;
+        assert_eq!(p.verify(&s).unwrap_err(), vec![
+            Violation::new("the given 0 was changed", vec![Coord::new(0,1)]),
+            Violation::new("column 0 has 4 cells set to 1 out of 10", vec![]),
+            Violation::new("column 1 has 6 cells set to 1 out of 10", vec![]),
+        ]);
+
+        s.0[1][1] = false;
+        s.0[2][1] = false;
+        assert!(p.verify(&s).unwrap_err().contains(&Violation::new("three consecutive 0 in column 1", vec![Coord::new(1,1), Coord::new(2,1), Coord::new(3,1)])));
+
+        let wide: Solution = "0101\n1010\n".parse().unwrap();
+        assert_eq!(p.verify(&wide).unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn conflict() {
+        let p: Problem = SAMPLE.parse().unwrap();
+        assert!(p.conflict().unwrap().is_none());
 
+        let p: Problem = "\
+0.00
+....
+..1.
+1...
+".parse().unwrap();
+        assert_eq!(p.conflict().unwrap().unwrap(), vec![((0,0), false), ((0,2), false), ((0,3), false)]);
     }
 
 }
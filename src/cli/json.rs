@@ -0,0 +1,179 @@
+//! The puzzles of every game as JSON, for programs that would rather not write the text
+//! formats. A puzzle is an object naming its game, along with:
+//!  - Binero: `"grid"`, rows of 0, 1 or null for the empty cells
+//!  - K-Doku: `"cages"`, a list of `{"op": "+", "result": 7, "cells": [[0, 0], [0, 1]]}`
+//!  - Kakuro: `"grid"`, rows of null for the cells to fill, and objects for the blocks,
+//!    with the sums `"down"` and `"right"` of the runs starting below and to the right
+//!    of them, if any
+//!  - Stars: `"stars"`, the number of stars per unit, and `"regions"`, rows of the
+//!    number of the region of every cell
+//!  - Voisimage: `"grid"`, rows of hints from 0 to 9, or null for the cells without one
+//!
+//! The commands still work on the text formats: the puzzles read as JSON are built with
+//! the types of the library, then printed in the format of their game.
+
+use anyhow::{bail, Result};
+use multilogic::*;
+use multilogic::util::{coord::Coord, matrix::Matrix, region::Region};
+use serde_json::{json, Map, Value};
+
+use crate::{Failure, Game};
+
+fn invalid(message: impl Into<String>) -> anyhow::Error {
+    Failure::BadInput(message.into()).into()
+}
+
+/// The puzzle as JSON, from its text
+pub fn to_json(game: &Game, text: &str) -> Result<Value> {
+    Ok(match game {
+        Game::Binero => {
+            let problem: binero::Problem = text.parse()?;
+            let grid = rows(&problem.0, |cell| cell.map_or(Value::Null, |b| json!(b as u8)));
+            json!({ "game": "binero", "grid": grid })
+        },
+        Game::KDoku => {
+            let cages: Vec<Value> = crate::parse_kdoku(text)?.iter()
+                .map(|c| json!({
+                    "op": c.op.to_string(),
+                    "result": c.result,
+                    "cells": c.cells.0.iter().map(|cell| [cell.x, cell.y]).collect::<Vec<_>>(),
+                }))
+                .collect();
+            json!({ "game": "k-doku", "cages": cages })
+        },
+        Game::Kakuro => {
+            let problem: kakuro::Problem = text.parse()?;
+            json!({ "game": "kakuro", "grid": rows(&problem.grid(), kakuro_cell) })
+        },
+        Game::Stars => {
+            let problem: stars::Problem = text.parse()?;
+            json!({ "game": "stars", "stars": problem.stars, "regions": rows(&problem.regions, |&region| json!(region)) })
+        },
+        Game::Voisimage { .. } => {
+            let problem: voisimage::Problem = text.parse()?;
+            json!({ "game": "voisimage", "grid": rows(problem.grid(), |hint| hint.map_or(Value::Null, |d| json!(d))) })
+        },
+        _ => bail!(Failure::Unsupported),
+    })
+}
+
+fn rows<T>(grid: &Matrix<T>, cell: impl Fn(&T) -> Value) -> Vec<Vec<Value>> {
+    grid.lines().map(|line| line.iter().map(&cell).collect()).collect()
+}
+
+fn kakuro_cell(cell: &kakuro::Cell) -> Value {
+    let kakuro::Cell::Block { down, right } = *cell else { return Value::Null };
+    let mut block = Map::new();
+    for (key, sum) in [("down", down), ("right", right)] {
+        if let Some(sum) = sum { block.insert(key.to_string(), json!(sum)); }
+    }
+    Value::Object(block)
+}
+
+/// The text of the puzzles read as JSON: a puzzle, or an array of puzzles, which are
+/// separated by empty lines as in batch mode. Their game is the one given, or else the
+/// one they name.
+pub fn to_text(input: &str, game: Option<&Game>) -> Result<(Game, String)> {
+    let value: Value = serde_json::from_str(input).map_err(|e| invalid(format!("invalid JSON: {}", e)))?;
+    let puzzles = match value {
+        Value::Array(puzzles) => puzzles,
+        puzzle => vec![puzzle],
+    };
+    let Some(first) = puzzles.first() else { bail!(invalid("no puzzle in the JSON array")) };
+    let name = match (game, first.get("game")) {
+        (Some(game), _) => name(game).ok_or(Failure::Unsupported)?,
+        (None, Some(Value::String(name))) => name.as_str(),
+        (None, _) => bail!(invalid("the puzzle does not name its game, expected e.g. {\"game\": \"binero\", ...}")),
+    };
+    let game = Game::named(name).ok_or_else(|| invalid(format!("unknown game {:?}", name)))?;
+
+    let mut text = String::new();
+    for (i, puzzle) in puzzles.iter().enumerate() {
+        match puzzle.get("game") {
+            Some(Value::String(other)) if other != name => bail!(invalid(format!("puzzle {} is a {} puzzle, not {}", i + 1, other, name))),
+            _ => (),
+        }
+        if i > 0 { text.push('\n') }
+        text.push_str(&puzzle_text(&game, puzzle).map_err(|e| invalid(format!("puzzle {}: {}", i + 1, e)))?);
+    }
+    Ok((game, text))
+}
+
+/// The name of the game in the JSON puzzles
+pub fn name(game: &Game) -> Option<&'static str> {
+    Some(match game {
+        Game::Binero => "binero",
+        Game::KDoku => "k-doku",
+        Game::Kakuro => "kakuro",
+        Game::Stars => "stars",
+        Game::Voisimage { .. } => "voisimage",
+        _ => return None,
+    })
+}
+
+/// The rows of `key`, with every cell read by `cell`
+fn grid<T>(puzzle: &Value, key: &str, cell: impl Fn(&Value) -> Option<T>) -> Result<Matrix<T>, String> {
+    let not_rows = || format!("{:?} is not a list of rows", key);
+    let rows = puzzle.get(key).ok_or_else(|| format!("missing {:?}", key))?.as_array().ok_or_else(not_rows)?;
+    let rows: Vec<Vec<T>> = rows.iter()
+        .map(|row| row.as_array().ok_or_else(not_rows)?
+            .iter()
+            .map(|c| cell(c).ok_or_else(|| format!("invalid cell {}", c)))
+            .collect())
+        .collect::<Result<_, _>>()?;
+    Matrix::from_rows(rows).map_err(|_| format!("the rows of {:?} are empty or not of the same length", key))
+}
+
+fn puzzle_text(game: &Game, puzzle: &Value) -> Result<String, String> {
+    let field = |key: &str| puzzle.get(key).ok_or_else(|| format!("missing {:?}", key));
+    let number = |c: &Value| c.as_u64().and_then(|n| usize::try_from(n).ok());
+
+    Ok(match game {
+        Game::Binero => binero::Problem(grid(puzzle, "grid", |c| match c {
+            Value::Null => Some(None),
+            _ => c.as_u64().filter(|&b| b <= 1).map(|b| Some(b == 1)),
+        })?).to_string(),
+        Game::Voisimage { .. } => {
+            let hints = grid(puzzle, "grid", |c| match c {
+                Value::Null => Some(None),
+                _ => c.as_u64().filter(|&d| d <= 9).map(|d| Some(d as u8)),
+            })?;
+            voisimage::Problem::new(hints.shape(), hints.into_iter().collect()).expect("the shape of the hints").to_string()
+        },
+        Game::Stars => {
+            let stars = number(field("stars")?).ok_or("\"stars\" is not a number")?;
+            stars::Problem::new(grid(puzzle, "regions", number)?, stars).to_string()
+        },
+        Game::Kakuro => {
+            let cells = grid(puzzle, "grid", |c| match c {
+                Value::Null => Some(kakuro::Cell::White),
+                Value::Object(block) => {
+                    let sum = |key| block.get(key).map_or(Some(None), |s| number(s).map(Some));
+                    Some(kakuro::Cell::Block { down: sum("down")?, right: sum("right")? })
+                },
+                _ => None,
+            })?;
+            kakuro::Problem::from_grid(&cells).map_err(|e| e.to_string())?.to_string()
+        },
+        Game::KDoku => {
+            let cages = field("cages")?.as_array().ok_or("\"cages\" is not a list")?;
+            let constraints: Vec<kdoku::Constraint> = cages.iter()
+                .map(|cage| {
+                    let bad = || format!("invalid cage {}", cage);
+                    let op = cage.get("op").and_then(Value::as_str).and_then(|op| op.parse().ok()).ok_or_else(bad)?;
+                    let result = cage.get("result").and_then(Value::as_u64).and_then(|r| u8::try_from(r).ok()).ok_or_else(bad)?;
+                    let cells = cage.get("cells").and_then(Value::as_array).ok_or_else(bad)?
+                        .iter()
+                        .map(|cell| match cell.as_array().map(|c| c.iter().map(number).collect::<Vec<_>>()).as_deref() {
+                            Some([Some(x), Some(y)]) => Ok(Coord::new(*x, *y)),
+                            _ => Err(bad()),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    Ok(kdoku::Constraint { op, result, cells: Region(cells) })
+                })
+                .collect::<Result<_, String>>()?;
+            kdoku::parse::format(&constraints)
+        },
+        _ => return Err(Failure::Unsupported.to_string()),
+    })
+}
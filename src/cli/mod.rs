@@ -0,0 +1,6 @@
+//! The ways of the command line to talk to other programs: puzzles as JSON, and the
+//! same commands over HTTP.
+
+pub mod json;
+#[cfg(feature = "serve")]
+pub mod serve;
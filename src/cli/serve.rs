@@ -0,0 +1,207 @@
+//! The `serve` command: the solvers behind a small HTTP API, for programs that would
+//! otherwise run the command line once per puzzle. Requests are answered by a fixed
+//! number of worker threads, with the solver backend of the command line. When they are
+//! all busy and enough requests are waiting, new ones are turned away with status 503.
+//!
+//! Every request is cancelled after its timeout, by a single thread watching the
+//! deadlines of all of them. As for the command line, a call to the SAT solver that has
+//! already started still runs to its end.
+
+use std::{num::NonZeroUsize, panic::{catch_unwind, AssertUnwindSafe}, sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, TrySendError}, Mutex}, time::{Duration, Instant}};
+
+use anyhow::{anyhow, bail, Context, Result};
+use multilogic::*;
+use multilogic::util::{backend::Backend, cancel::{with_cancel, CancelToken}};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{Failure, Game, Options, BAD_INPUT_STATUS, UNSOLVED_STATUS};
+
+/// Requests waiting for a worker, beyond which new ones are turned away
+const QUEUE: usize = 16;
+
+/// The time a request may take without `--timeout`, in seconds
+const DEFAULT_TIMEOUT: u64 = 60;
+
+/// How often the deadlines of the requests are checked
+const TICK: Duration = Duration::from_millis(100);
+
+/// The largest size of the grids made by `/generate`
+const MAX_SIZE: usize = 30;
+
+/// The request being answered by a worker, with its deadline
+type Deadline = Mutex<Option<(Instant, CancelToken)>>;
+
+pub fn serve(host: &str, port: u16, options: &Options) -> Result<()> {
+    let server = Server::http((host, port))
+        .map_err(|e| anyhow!(e))
+        .with_context(|| format!("cannot listen on {}:{}", host, port))?;
+    let workers = options.threads
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    note!("Listening on http://{}:{}, with {} workers", host, port, workers);
+
+    let backend = Backend::current();
+    let timeout = Duration::from_secs(options.timeout.unwrap_or(DEFAULT_TIMEOUT));
+    let (sender, receiver) = mpsc::sync_channel::<Request>(QUEUE);
+    let receiver = Mutex::new(receiver);
+    let deadlines: Vec<Deadline> = (0..workers).map(|_| Mutex::new(None)).collect();
+    let stopped = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        for deadline in &deadlines {
+            let (backend, receiver) = (&backend, &receiver);
+            scope.spawn(move || loop {
+                // The lock is only held while waiting for a request
+                let Ok(request) = receiver.lock().unwrap().recv() else { return };
+                let token = CancelToken::new();
+                *deadline.lock().unwrap() = Some((Instant::now() + timeout, token.clone()));
+                backend.run(|| respond(request, &token, options.seed));
+                *deadline.lock().unwrap() = None;
+            });
+        }
+        scope.spawn(|| while !stopped.load(Ordering::Relaxed) {
+            std::thread::sleep(TICK);
+            for deadline in &deadlines {
+                let mut deadline = deadline.lock().unwrap();
+                if deadline.as_ref().is_some_and(|(at, _)| *at <= Instant::now()) {
+                    if let Some((_, token)) = deadline.take() { token.cancel() }
+                }
+            }
+        });
+
+        for request in server.incoming_requests() {
+            if let Err(TrySendError::Full(request)) = sender.try_send(request) {
+                send(request, 503, json!({ "error": "too many requests, try again later" }));
+            }
+        }
+        drop(sender);
+        stopped.store(true, Ordering::Relaxed);
+    });
+    Ok(())
+}
+
+/// Answer a request with JSON, stopping once `token` is cancelled
+fn respond(mut request: Request, token: &CancelToken, seed: Option<u64>) {
+    let mut body = String::new();
+    let (status, answer) = match request.as_reader().read_to_string(&mut body) {
+        Ok(_) => answer(request.method(), request.url(), &body, token, seed),
+        Err(e) => (400, json!({ "error": e.to_string() })),
+    };
+    send(request, status, answer)
+}
+
+fn send(request: Request, status: u16, answer: Value) {
+    tracing::info!(method = %request.method(), url = request.url(), status, "request");
+
+    let header = Header::from_bytes("Content-Type", "application/json").expect("a valid header");
+    let response = Response::from_string(answer.to_string()).with_status_code(status).with_header(header);
+    if let Err(e) = request.respond(response) {
+        tracing::warn!(%e, "cannot answer");
+    }
+}
+
+/// The status and the body of the answer
+fn answer(method: &Method, url: &str, body: &str, token: &CancelToken, seed: Option<u64>) -> (u16, Value) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let (endpoint, game) = path.trim_start_matches('/').split_once('/').unwrap_or((path, ""));
+    if !["solve", "check", "generate"].contains(&endpoint) {
+        return (404, json!({ "error": format!("no such endpoint {}", path) }))
+    }
+    let Some(game) = Game::named(game) else {
+        return (404, json!({ "error": format!("unknown game {:?}", game) }))
+    };
+    if *method != Method::Post {
+        return (405, json!({ "error": "expected a POST request" }))
+    }
+
+    let result = catch_unwind(AssertUnwindSafe(|| with_cancel(token, || match endpoint {
+        "solve" => solve(&game, body, seed),
+        "check" => check(&game, body),
+        _ => generate(&game, query, body),
+    })));
+    match result {
+        Ok(Ok(Ok(answer))) => (200, answer),
+        Ok(Ok(Err(e))) => {
+            let status = match crate::exit_status(&e) {
+                BAD_INPUT_STATUS => 400,
+                UNSOLVED_STATUS => 422,
+                _ => 500,
+            };
+            (status, json!({ "error": format!("{:#}", e) }))
+        },
+        Ok(Err(_)) => (503, json!({ "error": "timed out" })),
+        // The panic hook already printed the details
+        Err(_) => (500, json!({ "error": "internal error" })),
+    }
+}
+
+fn solve(game: &Game, input: &str, seed: Option<u64>) -> Result<Value> {
+    Ok(match game {
+        Game::Binero => {
+            let problem: binero::Problem = input.parse()?;
+            match seed.map_or_else(|| problem.solve(), |seed| problem.solve_random(seed))? {
+                Some(s) => crate::json_solution(&s.grid().map(|&b| b as u8)),
+                None => crate::json_conflict(crate::binero_conflict(&problem)?),
+            }
+        },
+        Game::KDoku => {
+            let constraints = crate::parse_kdoku(input)?;
+            let solution = match seed {
+                Some(seed) => kdoku::BaseGrid::new().solve_random(&constraints, seed),
+                None => kdoku::BaseGrid::new().solve(&constraints),
+            };
+            match solution {
+                Ok(s) => crate::json_solution(&s.grid()),
+                Err(kdoku::LogicalError::Unsatisfyable) => {
+                    let cages = kdoku::BaseGrid::new().conflict(&constraints).map_err(Error::from)?;
+                    crate::json_conflict(cages.unwrap_or_default())
+                },
+                Err(e) => return Err(Error::from(e).into()),
+            }
+        },
+        Game::Stars => {
+            let problem: stars::Problem = input.parse()?;
+            match seed.map_or_else(|| problem.solve(), |seed| problem.solve_random(seed))? {
+                Some(s) => crate::json_solution(s.stars()),
+                None => crate::json_conflict(problem.conflict()?.unwrap_or_default()),
+            }
+        },
+        Game::Voisimage { .. } => {
+            let problem: voisimage::Problem = input.parse()?;
+            match seed.map_or_else(|| problem.solve(), |seed| problem.solve_random(seed))? {
+                Some(s) => crate::json_solution(s.grid()),
+                None => crate::json_conflict(crate::voisimage_conflict(&problem)?),
+            }
+        },
+        _ => bail!(Failure::Unsupported),
+    })
+}
+
+/// The body holds the puzzle, an empty line, then the solution
+fn check(game: &Game, body: &str) -> Result<Value> {
+    let [puzzle, solution] = &crate::split_puzzles(body)[..] else {
+        bail!(Failure::BadInput("expected the puzzle and the solution separated by an empty line".to_string()))
+    };
+    let violations = crate::verify(game, puzzle, solution)?;
+    Ok(json!({ "valid": violations.is_empty(), "violations": violations }))
+}
+
+/// Parameters from the query, the body holding the picture of a Voisimage
+fn generate(game: &Game, query: &str, body: &str) -> Result<Value> {
+    let (mut size, mut difficulty, mut seed) = (10, generate::Difficulty::Hard, rand::random());
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let bad = || Failure::BadInput(format!("invalid parameter {:?}", pair));
+        match pair.split_once('=').ok_or_else(bad)? {
+            ("size", value) => size = value.parse().map_err(|_| bad())?,
+            ("difficulty", value) => difficulty = value.parse()?,
+            ("seed", value) => seed = value.parse().map_err(|_| bad())?,
+            _ => bail!(bad()),
+        }
+    }
+    if size > MAX_SIZE {
+        bail!(Failure::BadInput(format!("size {} is too large, at most {}", size, MAX_SIZE)))
+    }
+    let puzzle = crate::new_puzzle(game, size, difficulty, seed, || Ok(body.to_string()))?;
+    Ok(json!({ "puzzle": puzzle, "seed": seed }))
+}
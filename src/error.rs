@@ -0,0 +1,79 @@
+//! A single error type for the whole library. Every game keeps its own error types,
+//! which say exactly what can go wrong with each function; [`Error`] gathers them all,
+//! for applications that handle several games and want to match on the kind of error
+//! rather than on a message.
+
+use thiserror::Error;
+
+use crate::util::{backend::BackendError, gridparse::GridError, matrix::ShapeError, region::RegionError};
+use crate::{binero, generate::DifficultyError, kakuro, kdoku, stars, voisimage};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid binero: {0}")]
+    Binero(#[from] binero::ParseError),
+    #[error("invalid kakuro: {0}")]
+    Kakuro(#[from] kakuro::ParseError),
+    #[error("invalid k-doku: {0}")]
+    KDoku(#[from] kdoku::ParseError),
+    #[error("invalid stars grid: {0}")]
+    Stars(#[from] stars::ParseError),
+    #[error("invalid voisimage: {0}")]
+    Voisimage(#[from] voisimage::ParseError),
+
+    #[error(transparent)]
+    Grid(#[from] GridError),
+    #[error(transparent)]
+    Shape(#[from] ShapeError),
+    #[error(transparent)]
+    Region(#[from] RegionError),
+    #[error(transparent)]
+    Difficulty(#[from] DifficultyError),
+
+    /// A K-Doku area whose operation no values can satisfy
+    #[error("impossible constraint {0}")]
+    ImpossibleConstraint(kdoku::Constraint),
+    /// A K-Doku area whose operation cannot apply to its number of cells
+    #[error("unsupported constraint {0}")]
+    UnsupportedConstraint(kdoku::Constraint),
+    #[error("no solution")]
+    Unsatisfiable,
+    #[error("SAT solver error: {0}")]
+    Solver(#[from] BackendError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// K-Doku errors borrow the offending constraint, which is copied here
+impl From<kdoku::LogicalError<'_>> for Error {
+    fn from(e: kdoku::LogicalError<'_>) -> Self {
+        use kdoku::LogicalError::*;
+        match e {
+            ImpossibleConstraint(c) => Error::ImpossibleConstraint(c.clone()),
+            UnsupportedConstraint(c) => Error::UnsupportedConstraint(c.clone()),
+            Unsatisfyable => Error::Unsatisfiable,
+            SolverError(e) => Error::Solver(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constraints;
+
+    fn parse_binero(s: &str) -> Result<binero::Problem> {
+        Ok(s.parse()?)
+    }
+
+    #[test]
+    fn conversions() {
+        let e = parse_binero("01\n0x\n").err().unwrap();
+        assert!(matches!(e, Error::Binero(binero::ParseError::Grid(GridError::InvalidChar { ch: 'x', .. }))));
+        assert_eq!(e.to_string(), "invalid binero: invalid character 'x' at (1,1)");
+
+        let constraints = constraints![ 40+ [ (0,0), (0,1) ], ];
+        let e: Error = kdoku::BaseGrid::new().solve(&constraints).unwrap_err().into();
+        assert!(matches!(e, Error::ImpossibleConstraint(ref c) if *c == constraints[0]));
+    }
+}
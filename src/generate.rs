@@ -0,0 +1,168 @@
+//! Puzzle generation. A complete solution is drawn at random with the SAT solver,
+//! and described by all the clues it supports. Clues are then taken, in random order,
+//! until the puzzle has a unique solution at the requested difficulty, and finally
+//! every clue that can be dropped without losing these properties is removed.
+//!
+//! Games take part by implementing [`Generate`], which only deals with their own
+//! solutions and clues; the search itself is shared.
+
+use std::{fmt::Display, str::FromStr};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use thiserror::Error;
+
+use crate::util::{backend::BackendError, deduce::Deduction};
+
+/// How hard the generated puzzle should be
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    /// Can be solved with the simple rules of the deduction engine alone
+    Easy,
+    /// Only guaranteed to have a unique solution, so it may need deeper reasoning
+    #[default]
+    Hard,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown difficulty {0:?}, expected easy or hard")]
+pub struct DifficultyError(String);
+
+impl FromStr for Difficulty {
+    type Err = DifficultyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "hard" => Ok(Difficulty::Hard),
+            other => Err(DifficultyError(other.to_owned())),
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Hard => "hard",
+        })
+    }
+}
+
+/// How far the deduction engine gets on a puzzle, which tells how hard it is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rating {
+    /// Number of solutions, only counted up to 2
+    pub solutions: usize,
+    /// Number of cells filled by the rules of the deduction engine
+    pub steps: usize,
+    /// Number of cells the deduction engine could not fill, left to search
+    pub remaining: usize,
+    /// Number of cells of the grid
+    pub cells: usize,
+}
+
+impl Rating {
+    /// Rate a puzzle from what the deduction engine made of it, and its number of solutions
+    pub fn new<T>(deduction: &Deduction<T>, solutions: usize) -> Self {
+        Rating {
+            solutions: solutions.min(2),
+            steps: deduction.steps.len(),
+            remaining: deduction.grid.iter().filter(|c| c.is_none()).count(),
+            cells: deduction.grid.len(),
+        }
+    }
+
+    /// The difficulty a generated puzzle would need to meet to be this one, or `None`
+    /// if it does not have a unique solution
+    pub fn difficulty(&self) -> Option<Difficulty> {
+        if self.solutions != 1 { return None }
+        Some(if self.remaining == 0 { Difficulty::Easy } else { Difficulty::Hard })
+    }
+}
+
+/// A game whose puzzles can be generated from clues about a random solution.
+pub trait Generate: Sized {
+    /// Parameters of the grid to generate, such as its size
+    type Shape;
+    /// A complete solution of the game
+    type Solution;
+    /// A single piece of information given to the player, such as the value of a cell
+    type Clue: Clone;
+
+    /// Draw a random solution of an empty grid, using `rng` for every choice.
+    /// Returns `None` if no grid of this shape can be solved.
+    fn random_solution(shape: &Self::Shape, rng: &mut StdRng) -> Result<Option<Self::Solution>, BackendError>;
+
+    /// Every clue that could be given about `solution`. Together, they must
+    /// leave no other solution.
+    fn clues(shape: &Self::Shape, solution: &Self::Solution) -> Vec<Self::Clue>;
+
+    /// The puzzle consisting of these clues only, taken from `solution`
+    fn from_clues(shape: &Self::Shape, solution: &Self::Solution, clues: &[Self::Clue]) -> Self;
+
+    /// True if the puzzle has exactly one solution
+    fn is_unique(&self) -> Result<bool, BackendError>;
+
+    /// True if the deduction engine solves the puzzle completely
+    fn is_easy(&self) -> bool;
+
+    fn meets(&self, difficulty: Difficulty) -> Result<bool, BackendError> {
+        Ok(self.is_unique()? && (difficulty == Difficulty::Hard || self.is_easy()))
+    }
+}
+
+/// Generate a puzzle of the given shape and difficulty. The same seed always
+/// produces the same puzzle. Returns `None` if no solution could be drawn,
+/// or if even all of its clues together do not reach the difficulty target.
+pub fn generate<G: Generate>(shape: &G::Shape, difficulty: Difficulty, seed: u64) -> Result<Option<G>, BackendError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let Some(solution) = G::random_solution(shape, &mut rng)? else { return Ok(None) };
+    let mut clues = G::clues(shape, &solution);
+    clues.shuffle(&mut rng);
+
+    let meets = |clues: &[G::Clue]| G::from_clues(shape, &solution, clues).meets(difficulty);
+    if !meets(&clues)? { return Ok(None) }
+
+    // Adding clues only narrows down the solutions, so the shortest prefix
+    // reaching the target can be found by bisection.
+    let (mut lo, mut hi) = (0, clues.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if meets(&clues[..mid])? { hi = mid } else { lo = mid + 1 }
+    }
+    clues.truncate(lo);
+
+    // Clues taken early may have been made redundant by later ones
+    let mut i = 0;
+    while i < clues.len() {
+        let clue = clues.remove(i);
+        if !meets(&clues)? {
+            clues.insert(i, clue);
+            i += 1;
+        }
+    }
+
+    Ok(Some(G::from_clues(shape, &solution, &clues)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binero;
+
+    #[test]
+    fn rating() {
+        let rate = |problem: &binero::Problem| Rating::new(&problem.deduce(), problem.count_solutions(Default::default()).unwrap());
+
+        let easy = rate(&generate::<binero::Problem>(&6, Difficulty::Easy, 1).unwrap().unwrap());
+        assert_eq!((easy.solutions, easy.remaining, easy.cells), (1, 0, 36));
+        assert!(easy.steps > 0);
+        assert_eq!(easy.difficulty(), Some(Difficulty::Easy));
+
+        let open = rate(&"00..\n....\n....\n....\n".parse().unwrap());
+        assert_eq!((open.solutions, open.steps, open.remaining), (2, 2, 12));
+        assert_eq!(open.difficulty(), None);
+        assert_eq!(Difficulty::Hard.to_string().parse::<Difficulty>().unwrap(), Difficulty::Hard);
+    }
+}
@@ -1,7 +1,15 @@
-use std::{ops::Range, fmt::Display};
-use crate::util::{matrix::{Matrix, umat}, integer};
+use std::{ops::{ControlFlow, Range}, fmt::Display, str::FromStr};
 
-use super::util::integer::Var;
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use thiserror::Error;
+use tracing::{debug_span, info};
+use varisat::CnfFormula;
+
+use crate::util::{backend::BackendError, coord::Coord, gridparse::GridError, masked::{MaskedMatrix, Run}, matrix::{Matrix, umat}, integer};
+use crate::verify::{Violation, verdict, same_shape};
+
+use super::util::integer::{Encoding, Var};
 
 struct Constraint {
     vertical: bool,
@@ -16,57 +24,472 @@ pub struct Problem {
 }
 pub struct Solution(Matrix<Option<usize>>);
 
+/// A cell of the clue grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    /// A cell to fill
+    White,
+    /// A block, with the sums of the runs starting below it and to its right, if any
+    Block { down: Option<usize>, right: Option<usize> },
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error(transparent)]
+    Grid(#[from] GridError),
+    #[error("invalid cell {token:?} at {at}")]
+    InvalidCell { token: String, at: Coord },
+    #[error("the clue at {0} has no cell to fill")]
+    EmptyRun(Coord),
+    #[error("the cell at {0} is in no run with a clue")]
+    Unclued(Coord),
+}
+
+/// A Kakuro encoded into an [`integer::Session`], for hypotheses about the digits in some cells
+pub struct Session {
+    solver: integer::Session,
+    grid: Matrix<Option<Var>>,
+}
+
 impl Constraint {
-    fn cells(&self) -> impl Iterator<Item=(usize,usize)> + '_{
+    fn cells(&self) -> impl Iterator<Item=Coord> + '_{
         self.range.clone()
             .map(|x| {
-                if self.vertical { (x, self.index) } else { (self.index, x) }
+                if self.vertical { Coord::new(x, self.index) } else { Coord::new(self.index, x) }
             })
     }
 }
 
 impl Problem {
-    pub fn solve(&self) -> Option<Solution> {
+    /// The puzzle with the clues of `grid`. Every cell to fill must be in a run with a clue.
+    pub fn from_grid(grid: &Matrix<Cell>) -> Result<Self, ParseError> {
+        let white = grid.map(|&c| c == Cell::White);
+        let mut constraints = vec![];
+        for at in grid.coords() {
+            let Cell::Block { down, right } = grid[at] else { continue };
+            for (vertical, clue) in [(true, down), (false, right)] {
+                let Some(target) = clue else { continue };
+                let (index, start) = if vertical { (at.y, at.x + 1) } else { (at.x, at.y + 1) };
+                let end = (start..)
+                    .find(|&i| {
+                        let (x, y) = if vertical { (i, index) } else { (index, i) };
+                        white.get(x, y) != Some(&true)
+                    })
+                    .expect("runs end at the edge of the grid");
+                if end == start { return Err(ParseError::EmptyRun(at)) }
+                constraints.push(Constraint { vertical, index, range: start..end, target });
+            }
+        }
+
+        let problem = Problem { shape: grid.shape(), constraints };
+        if let Some(cell) = white.coords().find(|&c| white[c] && !problem.constraints.iter().any(|r| r.cells().any(|p| p == c))) {
+            return Err(ParseError::Unclued(cell))
+        }
+        Ok(problem)
+    }
+
+    /// The clue grid, as parsed and printed
+    pub fn grid(&self) -> Matrix<Cell> {
+        let mut grid = umat![Cell::Block { down: None, right: None }; self.shape];
+        for c in &self.constraints {
+            for pos in c.cells() {
+                grid[pos] = Cell::White;
+            }
+        }
+
+        for c in &self.constraints {
+            let Coord { x, y } = c.cells().next().expect("runs are not empty");
+            let Some(at) = (if c.vertical { x.checked_sub(1).map(|x| (x,y)) } else { y.checked_sub(1).map(|y| (x,y)) }) else { continue };
+            if let Cell::Block { down, right } = &mut grid[at] {
+                *(if c.vertical { down } else { right }) = Some(c.target);
+            }
+        }
+        grid
+    }
 
-        let shape = self.shape;
-        let mut grid: Matrix<Option<Var>> = umat![None; shape];
+    pub fn solve(&self) -> Result<Option<Solution>, BackendError> {
+        self.session().solve(&[])
+    }
+
+    /// Find a filling drawn at random from `seed`, for grids with several.
+    /// See [`random_model`](crate::util::solve::random_model).
+    pub fn solve_random(&self, seed: u64) -> Result<Option<Solution>, BackendError> {
+        self.session().solve_random(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Find a filling of the grid that differs from `prior` in at least one cell.
+    /// Returns `None` if `prior` is the only solution.
+    pub fn solve_excluding(&self, prior: &Solution) -> Result<Option<Solution>, BackendError> {
+        let mut session = self.session();
+        session.exclude(prior);
+        session.solve(&[])
+    }
 
-        let mut solver = integer::Problem::new();
+    /// Call `f` with every filling of the grid, see [`Session::solve_with`].
+    pub fn solve_with<B>(&self, f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        self.session().solve_with(f)
+    }
+
+    /// List the cells whose digit is the same in every solution, in row-major order.
+    /// Returns `None` if the grid has no solution.
+    pub fn hints(&self) -> Result<Option<Vec<((usize, usize), usize)>>, BackendError> {
+        let mut session = self.session();
+        let Some(s) = session.solve(&[])? else { return Ok(None) };
+
+        let grid = &session.grid;
+        let candidates = grid.indices()
+            .filter_map(|(x,y)| {
+                let (var, v) = (grid[(x,y)].as_ref()?, s.0[(x,y)]?);
+                Some((((x,y), v), var.lit(v)?))
+            })
+            .collect();
 
-        for constraint in &self.constraints {
+        Ok(Some(session.solver.backbone(candidates)?))
+    }
 
-            let mut cells = vec![];
-            let mut sum: Option<Var> = None;
+    /// Check `solution` against the sums and the rules directly, without the SAT solver.
+    /// Lists every broken rule if it is not valid.
+    pub fn verify(&self, solution: &Solution) -> Result<(), Vec<Violation>> {
+        let s = &solution.0;
+        same_shape(self.shape, s.shape())?;
+        let mut violations = vec![];
 
-            for (x,y) in constraint.cells() {
-                cells.push(grid[x][y].get_or_insert_with(|| solver.new_var(1..=9)).clone());
+        for pos in s.coords() {
+            let in_run = self.constraints.iter().any(|c| c.cells().any(|cell| cell == pos));
+            match s[pos] {
+                None if in_run => violations.push(Violation::new("empty cell", vec![pos])),
+                Some(v) if !in_run => violations.push(Violation::new(format!("{} in a block", v), vec![pos])),
+                Some(v) if !(1..=9).contains(&v) => violations.push(Violation::new(format!("{} is not a digit", v), vec![pos])),
+                _ => {},
             }
+        }
 
-            for (i, cell) in cells.iter().enumerate() {
+        for c in &self.constraints {
+            let cells: Vec<_> = c.cells().collect();
+            let digits: Option<Vec<usize>> = cells.iter().map(|&cell| s[cell]).collect();
 
-                // Include cell in sum constraint
-                sum = sum.map(|s| solver.sum(&s,cell));
-                sum.get_or_insert_with(|| cell.clone());
+            // Empty cells are already reported
+            let Some(digits) = digits else { continue };
 
-                // Mutually exclusive with all other cells
-                for other in &cells[..i] {
-                    solver.not_equals(cell, other);
+            let sum: usize = digits.iter().sum();
+            if sum != c.target {
+                violations.push(Violation::new(format!("the run sums to {} instead of {}", sum, c.target), cells.clone()));
+            }
+
+            for ((a, va), (b, vb)) in cells.iter().zip(&digits).tuple_combinations() {
+                if va == vb {
+                    violations.push(Violation::new(format!("{} appears twice in the run", va), vec![*a, *b]));
                 }
             }
+        }
+
+        verdict(violations)
+    }
+
+    /// The formula of this grid, to inspect or export it without solving, along with
+    /// the integer variable of each white cell.
+    pub fn encode(&self) -> (CnfFormula, Matrix<Option<Var>>) {
+        let (problem, grid) = self.encode_problem();
+        (problem.formula().clone(), grid)
+    }
+
+    /// Encode the grid into a [`Session`].
+    pub fn session(&self) -> Session {
+        let (solver, grid) = self.encode_problem();
+        Session { solver: solver.session(), grid }
+    }
+
+    fn encode_problem(&self) -> (integer::Problem, Matrix<Option<Var>>) {
+        let _span = debug_span!("encode constraints", shape = ?self.shape, runs = self.constraints.len()).entered();
+        let (solver, grid, _) = self.encode_cells(true);
+        info!(shape = ?self.shape, runs = self.constraints.len(), clauses = solver.formula().len(), "encoded");
+        (solver, grid)
+    }
+
+    /// Encode the digits of the runs, leaving their sums free.
+    /// Also returns the variable holding the sum of each run.
+    fn encode_runs(&self) -> (integer::Problem, Matrix<Option<Var>>, Vec<Var>) {
+        let (mut solver, grid, runs) = self.encode_cells(false);
+
+        let sums = runs.iter()
+            .map(|cells| {
+                // Mutually exclusive cells
+                solver.all_different(cells);
+                solver.sum_all(cells)
+            })
+            .collect();
+
+        (solver, grid, sums)
+    }
+
+    /// The variables of the white cells, and those of the cells of each run.
+    /// With `known_sums`, each run must also hold different digits summing to its target,
+    /// and each cell only gets the digits that fit the targets of its runs.
+    fn encode_cells(&self, known_sums: bool) -> (integer::Problem, Matrix<Option<Var>>, Vec<Vec<Var>>) {
+
+        // Run sums go up to 45, binary sums make generation several times faster
+        let mut solver = integer::Problem::with_encoding(Encoding::Binary);
+        let mut declared: Matrix<Option<usize>> = umat![None; self.shape];
+
+        let runs: Vec<Vec<usize>> = self.constraints.iter()
+            .map(|constraint| constraint.cells()
+                .map(|pos| *declared[pos].get_or_insert_with(|| solver.declare(1..=9)))
+                .collect())
+            .collect();
+
+        // Only the sets of digits with the right sum, as in the tables of Kakuro players
+        if known_sums {
+            for (constraint, cells) in self.constraints.iter().zip(&runs) {
+                solver.post_distinct_sum(cells, constraint.target);
+            }
+        }
+
+        let vars = solver.define();
+        let grid = declared.map(|&cell| cell.map(|i| vars[i].clone()));
+        let runs = runs.iter()
+            .map(|cells| cells.iter().map(|&i| vars[i].clone()).collect())
+            .collect();
+
+        (solver, grid, runs)
+    }
+}
+
+/// Generate a kakuro on a `size`×`size` grid, including the first row and column
+/// which only hold clues. The same seed always gives the same grid.
+///
+/// A random symmetric layout of blocks is drawn, with runs of 2 to 5 cells. Runs are
+/// then given their sums one at a time, in random order, each time preferring sums
+/// with few combinations of digits, among those that still fit a filling of the grid.
+/// When another filling has the same sums, this set of sums is ruled out and new
+/// ones are chosen. If this keeps failing, a cell where the fillings differ is
+/// turned into a block.
+pub fn generate(size: usize, seed: u64) -> Result<Problem, BackendError> {
+    assert!(size >= 3, "no room for a run");
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    loop {
+        let mut white = umat![false; (size, size)];
+        for (x,y) in white.indices().filter(|&(x,y)| x > 0 && y > 0) {
+            if x <= y && rng.gen_ratio(4, 5) {
+                white[(x,y)] = true;
+                white[y][x] = true;
+            }
+        }
 
-            solver.equals(sum.as_ref().unwrap(), constraint.target);
+        loop {
+            white = prune(white, MAX_RUN);
+            if white.iter().all(|w| !w) { break }
+
+            let mut problem = Problem { shape: white.shape(), constraints: runs(&white) };
+            match unique_sums(&problem, 2 * size, &mut rng)? {
+                Ok(targets) => {
+                    for (c, t) in problem.constraints.iter_mut().zip(targets) { c.target = t }
+                    return Ok(problem)
+                },
+                Err((x,y)) => white[(x,y)] = false,
+            }
         }
+    }
+}
 
-        let model = solver.solve()?;
+/// Longer runs make for more ambiguous sums, and are kept out of generated grids
+const MAX_RUN: usize = 5;
 
-        
-        /* 
-        let cells = cells.into_iter()
-            .map(|cell| cell.map(|var| model.value(&var) ))
+/// Find sums for the runs of `layout` that have a single filling, trying at most `tries`
+/// sets of sums. On failure, returns a cell where two fillings with the same sums differ.
+fn unique_sums(layout: &Problem, tries: usize, rng: &mut StdRng)
+    -> Result<Result<Vec<usize>, (usize, usize)>, BackendError>
+{
+    let (solver, grid, sums) = layout.encode_runs();
+    let mut session = solver.session();
+    let mut differs = vec![];
+
+    for _ in 0..tries {
+        let Some((targets, filling)) = choose_sums(layout, &mut session, &sums, rng)? else { break };
+
+        // Fillings are ruled out once tried, the one found stays unique if nothing else fits
+        let cells: Vec<_> = grid.iter().flatten()
+            .map(|var| (var, filling.value(var)))
+            .collect();
+        session.exclude(cells.iter().cloned());
+
+        let assumptions = sums.iter().zip(targets.iter().copied());
+        let Some(other) = session.solve(assumptions.clone())? else { return Ok(Ok(targets)) };
+        session.exclude(assumptions);
+
+        differs = cells.into_iter()
+            .zip(grid.enumerate().filter(|(_, var)| var.is_some()).map(|(cell, _)| cell))
+            .filter(|((var, v), _)| other.value(var) != *v)
+            .map(|(_, cell)| cell)
             .collect();
-        */
-        Some(Solution(grid.map(|cell| cell.as_ref().map(|var| model.value(var)))))
-        
+    }
+
+    Ok(Err(*differs.choose(rng).expect("some filling was tried")))
+}
+
+/// Give every run a sum with few combinations of digits, among those that still
+/// fit a filling of the grid. Returns the sums along with such a filling, or `None`
+/// if every set of sums has been ruled out.
+fn choose_sums(layout: &Problem, session: &mut integer::Session, sums: &[Var], rng: &mut StdRng)
+    -> Result<Option<(Vec<usize>, integer::Model)>, BackendError>
+{
+    let mut order: Vec<_> = (0..sums.len()).collect();
+    order.shuffle(rng);
+    let mut targets = vec![0; sums.len()];
+    let mut chosen: Vec<(&Var, usize)> = vec![];
+
+    for r in order {
+        let len = layout.constraints[r].range.len();
+        let combinations = |t: usize| (1..=9).combinations(len).filter(|c| c.iter().sum::<usize>() == t).count();
+
+        // The sum in any filling fits, only a couple of rarer ones are worth trying
+        let Some(model) = session.solve(chosen.iter().cloned())? else { return Ok(None) };
+        let current = model.value(&sums[r]);
+
+        let mut rarer: Vec<_> = sums[r].range()
+            .filter(|&t| (1..combinations(current)).contains(&combinations(t)))
+            .collect();
+        rarer.shuffle(rng);
+        rarer.sort_by_key(|&t| combinations(t));
+
+        targets[r] = current;
+        for t in rarer.into_iter().take(2) {
+            if session.solve(chosen.iter().cloned().chain([(&sums[r], t)]))?.is_some() {
+                targets[r] = t;
+                break
+            }
+        }
+        chosen.push((&sums[r], targets[r]));
+    }
+
+    Ok(session.solve(chosen)?.map(|filling| (targets, filling)))
+}
+
+/// Turn into blocks the cells that are not part of both a row run and a column run,
+/// and split the runs longer than `max` cells, until every run is valid.
+fn prune(mut white: Matrix<bool>, max: usize) -> Matrix<bool> {
+    loop {
+        let runs = runs(&white);
+
+        let lonely = white.coords().find(|&pos| {
+            white[pos] && runs.iter().filter(|r| r.cells().any(|c| c == pos)).count() < 2
+        });
+        let long = || runs.iter().find(|r| r.range.len() > max)
+            .map(|r| r.cells().nth(r.range.len() / 2).unwrap());
+
+        let Some(pos) = lonely.or_else(long) else { break white };
+        white[pos] = false;
+    }
+}
+
+/// The maximal segments of at least 2 white cells, in rows then in columns,
+/// with no target yet
+fn runs(white: &Matrix<bool>) -> Vec<Constraint> {
+    MaskedMatrix::from_mask(white, |_, _| ()).runs().into_iter()
+        .filter(|r| r.len() >= 2)
+        .map(|Run { vertical, index, range }| Constraint { vertical, index, range, target: 0 })
+        .collect()
+}
+
+impl Session {
+    /// Solve the grid, assuming that every listed cell holds the given digit.
+    /// Panics if one of the cells is not part of a run.
+    pub fn solve(&mut self, assumptions: &[((usize, usize), usize)]) -> Result<Option<Solution>, BackendError> {
+        let grid = &self.grid;
+        let model = self.solver.solve(assumptions.iter().map(|&((x,y), v)| {
+            (grid[(x,y)].as_ref().expect("assumption on a blank cell"), v)
+        }))?;
+        Ok(model.map(|m| self.decode(&m)))
+    }
+
+    /// Solve the grid, drawing a filling at random.
+    pub fn solve_random(&mut self, rng: &mut impl Rng) -> Result<Option<Solution>, BackendError> {
+        let model = self.solver.solve_random(self.grid.iter().flatten(), rng)?;
+        Ok(model.map(|m| self.decode(&m)))
+    }
+
+    fn decode(&self, model: &integer::Model) -> Solution {
+        Solution(model.values(&self.grid))
+    }
+
+    /// Call `f` with every filling of the grid left, see [sessions](crate::util::solve#sessions).
+    pub fn solve_with<B>(&mut self, mut f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        while let Some(s) = self.solve(&[])? {
+            self.exclude(&s);
+            if let ControlFlow::Break(b) = f(s) { return Ok(ControlFlow::Break(b)) }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Rule out the filling `prior` from now on.
+    pub fn exclude(&mut self, prior: &Solution) {
+        let grid = &self.grid;
+        self.solver.exclude(grid.indices().filter_map(|(x,y)| {
+            Some((grid[(x,y)].as_ref()?, prior.0[(x,y)]?))
+        }));
+    }
+}
+
+/// Displays the clue grid: `.` for a cell to fill, `#` for a block, and `down\right`
+/// for a block holding the sums of the runs below it and to its right, either of
+/// them being left out when there is no such run. Runs starting on the edge of the
+/// grid have no room for their clue, and are left out.
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sum = |s: Option<usize>| s.map_or(String::new(), |s| s.to_string());
+        let grid = self.grid().map(|cell| match *cell {
+            Cell::White => String::from("."),
+            Cell::Block { down: None, right: None } => String::from("#"),
+            Cell::Block { down, right } => format!("{}\\{}", sum(down), sum(right)),
+        });
+
+        let width = grid.iter().map(String::len).max().unwrap_or(0);
+        for line in grid.lines() {
+            let line: Vec<_> = line.iter().map(|c| format!("{:>width$}", c, width = width)).collect();
+            writeln!(f, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the clue grid printed by the `Display` implementation, with the cells
+/// separated by whitespace. Every cell to fill must be in a run with a clue.
+impl FromStr for Problem {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<Vec<&str>> = s.lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let w = rows.first().ok_or(GridError::Empty)?.len();
+        if let Some((row, r)) = rows.iter().enumerate().find(|(_, r)| r.len() != w) {
+            return Err(GridError::UnequalLine { row, expected: w, found: r.len() }.into())
+        }
+        let tokens = Matrix::from_rows(rows).expect("rows have the same length");
+
+        let mut cells = vec![];
+        for at in tokens.coords() {
+            let token = tokens[at];
+            let invalid = || ParseError::InvalidCell { token: token.to_string(), at };
+            cells.push(match token {
+                "." => Cell::White,
+                "#" => Cell::Block { down: None, right: None },
+                _ => {
+                    let (down, right) = token.split_once('\\').ok_or_else(invalid)?;
+                    let sum = |clue: &str| if clue.is_empty() { Ok(None) } else { clue.parse().map(Some).map_err(|_| invalid()) };
+                    Cell::Block { down: sum(down)?, right: sum(right)? }
+                },
+            });
+        }
+        Problem::from_grid(&Matrix::new(cells, tokens.shape()).expect("one cell per token"))
+    }
+}
+
+impl Solution {
+    /// The digit of every white cell, `None` for the blocks
+    pub fn grid(&self) -> &Matrix<Option<usize>> {
+        &self.0
     }
 }
 
@@ -108,10 +531,97 @@ mod test {
             ],
         };
 
-        let s = k.solve().unwrap();
+        let s = k.solve().unwrap().unwrap();
         assert_eq!(k.shape, s.0.shape());
         assert_eq!(s.0, Matrix::new(vec![Some(1),Some(2),Some(4),Some(6),Some(8),Some(9)], (2,3)).unwrap());
 
+        assert!(k.solve_excluding(&s).unwrap().is_none());
+
+        let mut session = k.session();
+        assert!(session.solve(&[((0,0), 1)]).unwrap().is_some());
+        assert!(session.solve(&[((0,0), 2)]).unwrap().is_none());
+        assert!(session.solve(&[((0,0), 9)]).unwrap().is_none());
+
+        let hints = k.hints().unwrap().unwrap();
+        assert_eq!(hints.len(), 6);
+        assert_eq!(hints[5], ((1,2), 9));
+
+        // Two different digits never sum to 2
+        let impossible = Problem {
+            shape: (1, 2),
+            constraints: vec![Constraint { vertical: false, index: 0, range: 0..2, target: 2 }],
+        };
+        assert!(impossible.solve().unwrap().is_none());
+
+    }
 
+    #[test]
+    fn verify() {
+        let k = Problem {
+            shape: (2, 3),
+            constraints: vec![
+                Constraint { vertical: true, index: 0, range: 0..2, target: 7 },
+                Constraint { vertical: true, index: 1, range: 0..2, target: 10 },
+                Constraint { vertical: true, index: 2, range: 0..2, target: 13 },
+                Constraint { vertical: false, index: 0, range: 0..3, target: 7 },
+                Constraint { vertical: false, index: 1, range: 0..3, target: 23 },
+            ],
+        };
+
+        let mut s = k.solve().unwrap().unwrap();
+        assert_eq!(k.verify(&s), Ok(()));
+
+        s.0[0][1] = Some(1);
+        assert_eq!(k.verify(&s).unwrap_err(), vec![
+            Violation::new("the run sums to 9 instead of 10", vec![Coord::new(0,1), Coord::new(1,1)]),
+            Violation::new("the run sums to 6 instead of 7", vec![Coord::new(0,0), Coord::new(0,1), Coord::new(0,2)]),
+            Violation::new("1 appears twice in the run", vec![Coord::new(0,0), Coord::new(0,1)]),
+        ]);
+
+        s.0[1][2] = None;
+        assert!(k.verify(&s).unwrap_err().contains(&Violation::new("empty cell", vec![Coord::new(1,2)])));
+    }
+
+    #[test]
+    fn generate() {
+        let k = super::generate(5, 1).unwrap();
+        assert!(!k.constraints.is_empty());
+        assert!(k.constraints.iter().all(|c| (2..=MAX_RUN).contains(&c.range.len())));
+
+        let s = k.solve().unwrap().unwrap();
+        assert!(k.solve_excluding(&s).unwrap().is_none());
+
+        // Every run has its clue
+        let text = k.to_string();
+        let clues = text.split(|c: char| !c.is_ascii_digit()).filter(|n| !n.is_empty()).count();
+        assert_eq!(clues, k.constraints.len());
+
+        let parsed: Problem = text.parse().unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.solve().unwrap().unwrap().0, s.0);
+
+        assert_eq!(text, super::generate(5, 1).unwrap().to_string());
+    }
+
+    #[test]
+    fn parse() {
+        let p: Problem = "  #  7\\ 10\\ 13\\\n\\7   .    .    .\n\\23   .    .    .\n".parse().unwrap();
+        assert_eq!(p.solve().unwrap().unwrap().to_string(), "    \n 124\n 689\n");
+
+        assert!(matches!("# .\n. .\n".parse::<Problem>(), Err(ParseError::Unclued(_))));
+        assert!(matches!("# 3\\\n# #\n".parse::<Problem>(), Err(ParseError::EmptyRun(c)) if c == Coord::new(0, 1)));
+        assert!(matches!("# x\n".parse::<Problem>(), Err(ParseError::InvalidCell { .. })));
+        assert!(matches!("# #\n#\n".parse::<Problem>(), Err(ParseError::Grid(GridError::UnequalLine { row: 1, .. }))));
+    }
+
+    #[test]
+    fn grid() {
+        let p: Problem = "#  3\\
+\\3 .
+".parse().unwrap();
+        let block = |down, right| Cell::Block { down, right };
+        let grid = Matrix::new(vec![block(None, None), block(Some(3), None), block(None, Some(3)), Cell::White], (2, 2)).unwrap();
+        assert_eq!(p.grid(), grid);
+        assert_eq!(Problem::from_grid(&grid).unwrap().to_string(), p.to_string());
     }
 }
@@ -0,0 +1,169 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use crate::{generate::{self, Difficulty, Generate}, util::{backend::BackendError, coord::Coord, region::Region}};
+use super::{BaseGrid, Constraint, LogicalError, Op, deduce};
+
+type Square = [[u8; 6]; 6];
+
+/// Generate a K-Doku with a unique solution. The same seed always gives the same puzzle.
+///
+/// A random Latin square is cut into areas by random walks. The clues are cells left
+/// alone in their own area, the rest of their area being split into its connected
+/// parts. Each area gets an operation chosen among those that fit its values.
+pub fn generate(difficulty: Difficulty, seed: u64) -> Result<Vec<Constraint>, BackendError> {
+    Ok(generate::generate(&(), difficulty, seed)?
+        .expect("isolating every cell gives the whole grid"))
+}
+
+/// A solution of the grid, with the areas that the puzzle will cut it into
+#[derive(Clone, Debug)]
+pub struct Sketch {
+    square: Square,
+    areas: Vec<Region>,
+    /// Picks the operations, so that an area gets the same one in every attempt
+    seed: u64,
+}
+
+impl Generate for Vec<Constraint> {
+    type Shape = ();
+    type Solution = Sketch;
+    type Clue = Coord;
+
+    fn random_solution(_: &(), rng: &mut StdRng) -> Result<Option<Sketch>, BackendError> {
+        let square = latin_square(rng)?;
+        let areas = partition(rng);
+        Ok(Some(Sketch { square, areas, seed: rng.gen() }))
+    }
+
+    fn clues(_: &(), _: &Sketch) -> Vec<Coord> {
+        itertools::iproduct!(0..6, 0..6).map(Coord::from).collect()
+    }
+
+    fn from_clues(_: &(), sketch: &Sketch, clues: &[Coord]) -> Self {
+        let mut areas: Vec<_> = clues.iter().map(|&c| vec![c]).collect();
+        for Region(area) in &sketch.areas {
+            let rest: Vec<_> = area.iter().copied().filter(|c| !clues.contains(c)).collect();
+            areas.extend(Region(rest).components().into_iter().map(|Region(cells)| cells));
+        }
+
+        let mut constraints: Vec<_> = areas.into_iter()
+            .map(|mut cells| {
+                cells.sort();
+                let seed = cells.iter().fold(sketch.seed, |h, c| h.wrapping_mul(37).wrapping_add((c.x * 6 + c.y) as u64));
+                operation(&sketch.square, &cells, &mut StdRng::seed_from_u64(seed))
+            })
+            .collect();
+        constraints.sort_by_key(|c| c.cells.0[0]);
+        constraints
+    }
+
+    fn is_unique(&self) -> Result<bool, BackendError> {
+        let s = match BaseGrid::new().solve(self) {
+            Ok(s) => s,
+            Err(LogicalError::SolverError(e)) => return Err(e),
+            Err(_) => return Ok(false),
+        };
+        match BaseGrid::new().solve_excluding(self, &s) {
+            Err(LogicalError::Unsatisfyable) => Ok(true),
+            Err(LogicalError::SolverError(e)) => Err(e),
+            _ => Ok(false),
+        }
+    }
+
+    fn is_easy(&self) -> bool {
+        deduce(self).is_complete()
+    }
+}
+
+/// Draw a random Latin square with the SAT solver
+fn latin_square(rng: &mut StdRng) -> Result<Square, BackendError> {
+    let mut session = BaseGrid::new().session(&[]).expect("no constraints");
+    let solution = match session.solve_random(rng) {
+        Ok(s) => s,
+        Err(LogicalError::SolverError(e)) => return Err(e),
+        Err(e) => panic!("Latin squares exist: {e}"),
+    };
+    Ok(solution.0.map(|line| line.map(|v| v.0)))
+}
+
+/// Cut the grid into areas of 1 to 4 cells, each grown by a random walk
+fn partition(rng: &mut StdRng) -> Vec<Region> {
+    let mut starts: Vec<_> = itertools::iproduct!(0..6, 0..6).map(Coord::from).collect();
+    starts.shuffle(rng);
+
+    let mut taken = [[false; 6]; 6];
+    let mut areas = vec![];
+
+    for start in starts {
+        if taken[start.x][start.y] { continue }
+        taken[start.x][start.y] = true;
+
+        let size = *[1, 2, 2, 2, 3, 3, 3, 4].choose(rng).unwrap();
+        let mut area = vec![start];
+        let mut at = start;
+
+        while area.len() < size {
+            let next: Vec<_> = at.adjacent((6, 6)).filter(|c| !taken[c.x][c.y]).collect();
+            let Some(&next) = next.choose(rng) else { break };
+            taken[next.x][next.y] = true;
+            area.push(next);
+            at = next;
+        }
+
+        areas.push(Region(area));
+    }
+
+    areas
+}
+
+/// Pick a random operation that holds for the values of the cells
+fn operation(square: &Square, cells: &[Coord], rng: &mut StdRng) -> Constraint {
+    let values: Vec<u8> = cells.iter().map(|c| square[c.x][c.y]).collect();
+    let sum = values.iter().map(|&v| v as u16).sum::<u16>() as u8;
+    let product = values.iter().map(|&v| v as u16).product::<u16>();
+
+    let mut ops = vec![(Op::Plus, sum)];
+    if let Ok(product) = u8::try_from(product) {
+        if values.len() > 1 { ops.push((Op::Times, product)) }
+    }
+    if let &[a, b] = &values[..] {
+        let (lo, hi) = (a.min(b), a.max(b));
+        ops.push((Op::Minus, hi - lo));
+        if hi % lo == 0 { ops.push((Op::Div, hi / lo)) }
+    }
+
+    let (op, result) = ops[rng.gen_range(0..ops.len())];
+    Constraint { op, result, cells: Region(cells.to_vec()) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::kdoku::parse;
+    use crate::util::region::Partition;
+
+    #[test]
+    fn unique() {
+        for difficulty in [Difficulty::Easy, Difficulty::Hard] {
+            let constraints = generate(difficulty, 1).unwrap();
+            assert_eq!(constraints, generate(difficulty, 1).unwrap());
+
+            // Areas cover the grid exactly once
+            let areas: Vec<_> = constraints.iter().map(|c| c.cells.clone()).collect();
+            assert!(Partition::from_regions((6, 6), &areas).is_ok());
+            assert!(areas.iter().all(Region::is_connected));
+
+            // Round trip through the text format
+            let parsed: Vec<_> = constraints.iter()
+                .map(|c| parse::constraint(&c.to_string()).unwrap().1)
+                .collect();
+            assert_eq!(parsed, constraints);
+
+            let s = BaseGrid::new().solve(&constraints).unwrap();
+            assert!(BaseGrid::new().solve_excluding(&constraints, &s).is_err());
+
+            if difficulty == Difficulty::Easy {
+                assert!(deduce(&constraints).is_complete());
+            }
+        }
+    }
+}
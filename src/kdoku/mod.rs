@@ -1,28 +1,37 @@
-use std::str::FromStr;
+use std::{ops::ControlFlow, str::FromStr};
 
-use varisat::{CnfFormula, ExtendFormula, Var, Lit, Solver, solver::SolverError};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use varisat::{CnfFormula, ExtendFormula, Var, Lit};
 use itertools::Itertools;
+use rayon::prelude::*;
 use thiserror::Error;
+use tracing::{debug, debug_span, info};
+
+use crate::util::{backend::{Solver, BackendError}, cache::FormulaCache, coord::Coord, gridsolver::{GridSolver, GridModel}, latin::Latin, solve::{backbone, minimal_core, random_model, DnfFormula}, matrix::{Matrix, umat}, region::Region, deduce::{lines, Deduction, Step}, propagate::{area, distinct, Domains}, symmetry::Symmetry};
+use crate::render::Scene;
+use crate::transform::Transform;
+use crate::verify::{Violation, verdict};
 
 /// Text format for representing K-dokus
 pub mod parse;
 
-/// Initialize an array with a closure called multiple times. With the 
-/// default `[v; n]` notation, Rust will evaluate `v` once and clone the result.
-macro_rules! ary {
-    ($f:expr ; $size:literal) => { [(); $size].map(|_| $f) };
-}
+/// Random puzzles with a unique solution
+pub mod generate;
 
 /// An integer between 1 and 6
-#[derive(Clone,Copy,Debug)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord)]
 pub struct U6(u8);
 
+/// A cell position, and a value between 1 and 6 for that cell
+pub type Assignment = ((usize, usize), u8);
+
 /// A solution is a 6x6 matrix of integers between 1 and 6
-#[derive(Clone,Copy,Debug)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord)]
 pub struct Solution([[U6; 6]; 6]);
 
 /// Possible operators for the hints
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Op { Plus, Minus, Times, Div }
 
 #[derive(Debug, Error)]
@@ -37,24 +46,44 @@ pub enum LogicalError<'e> {
     Unsatisfyable,
     
     #[error("SAT Solver error")]
-    SolverError(#[from] SolverError),
+    SolverError(#[from] BackendError),
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("expected 6 lines of 6 digits")]
+    Shape,
+    #[error("Invalid digit {0:?}")]
+    InvalidDigit(char),
+    #[error("Invalid constraint on line {0}")]
+    InvalidConstraint(usize),
 }
 
+/// The variables of the grid: `vars[x][y][z]` is true iff the cell in position (x,y) contains z+1
+pub type Vars = [[[Var; 6]; 6]; 6];
+
 /// A SAT representation of the puzzle.
-/// vars[x][y][z] is true iff the cell in position (x,y) contains z
 #[derive(Clone, Debug)]
 pub struct BaseGrid {
     formula: CnfFormula,
-    vars: [[[Var; 6]; 6]; 6],
+    vars: Vars,
+}
+
+/// A K-Doku encoded into a [`GridSolver`], for hypotheses about the values of some cells
+pub struct Session {
+    solver: GridSolver,
+    vars: Vars,
+    /// For each constraint, the literal enabling it
+    selectors: Vec<Lit>,
 }
 
-/// A K-Doku constraint is a list of cells. The fold of the cell values over
+/// A K-Doku constraint is an area of the grid. The fold of the cell values over
 /// the operator must equal a given result.
-#[derive(Clone,Debug, PartialEq, Eq)]
+#[derive(Clone,Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Constraint {
     pub op: Op,
     pub result: u8,
-    pub cells: Vec<(usize, usize)>
+    pub cells: Region
 }
 
 #[macro_export]
@@ -68,7 +97,7 @@ macro_rules! op {
 /// For embedding K-Doku puzzles in rust code
 #[macro_export]
 macro_rules! constraints {
-    ( $( $r:tt $op:tt [ $( $c:expr ),* ], )* ) => { vec![ $( $crate::kdoku::Constraint { op: op!($op), result: $r, cells: vec![ $( $c ),* ] } ),* ] };
+    ( $( $r:tt $op:tt [ $( $c:expr ),* ], )* ) => { vec![ $( $crate::kdoku::Constraint { op: $crate::op!($op), result: $r, cells: $crate::util::region::Region(vec![ $( $crate::util::coord::Coord::from($c) ),* ]) } ),* ] };
 }
 
 impl FromStr for Op {
@@ -85,6 +114,109 @@ impl FromStr for Op {
     }
 }
 
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Op::Plus => "+",
+            Op::Minus => "-",
+            Op::Times => "*",
+            Op::Div => "/",
+        })
+    }
+}
+
+/// Displays in the input format, e.g. `7+ [(0,0),(0,1),(1,1)]`
+impl std::fmt::Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{} [", self.result, self.op)?;
+        for (i, cell) in self.cells.0.iter().enumerate() {
+            if i > 0 { f.write_str(",")? }
+            write!(f, "{}", cell)?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl Solution {
+    /// The digit of every cell, between 1 and 6
+    pub fn grid(&self) -> Matrix<u8> {
+        Matrix::from_fn((6, 6), |x, y| self.0[x][y].0)
+    }
+
+    /// The same grid with the digits renamed, `d` becoming `digits[d-1]`.
+    /// `digits` must be a permutation of 1 to 6.
+    pub fn relabel(&self, digits: [u8; 6]) -> Solution {
+        Solution(self.0.map(|line| line.map(|v| U6(digits[v.0 as usize - 1]))))
+    }
+
+    /// The grid to draw, with the digit of every cell in the areas of the constraints
+    pub fn scene(&self, constraints: &[Constraint]) -> Scene {
+        let mut scene = scene(constraints);
+        for (cell, digit) in scene.cells.iter_mut().zip(self.grid()) {
+            cell.label = Some(digit.to_string());
+        }
+        scene
+    }
+}
+
+/// The empty grid to draw, with the areas of the constraints outlined and their
+/// operation noted in their first cell
+pub fn scene(constraints: &[Constraint]) -> Scene {
+    let mut scene = Scene::new((6, 6));
+    let mut areas = Matrix::from_fn((6, 6), |_, _| constraints.len());
+    for (i, c) in constraints.iter().enumerate() {
+        for &cell in &c.cells.0 {
+            areas[cell] = i;
+        }
+        if let Some(&first) = c.cells.0.iter().min() {
+            scene.notes.push((first, format!("{}{}", c.result, c.op)));
+        }
+    }
+    scene.areas = Some(areas);
+    scene
+}
+
+/// Rotating or reflecting the areas gives the same puzzle. Digits cannot be renamed,
+/// since the operations depend on them, so relabeling only puts the areas in order.
+impl Transform for Vec<Constraint> {
+    fn transform(&self, symmetry: Symmetry) -> Self {
+        self.iter()
+            .map(|c| Constraint {
+                cells: Region(c.cells.0.iter().map(|&cell| symmetry.apply(cell.into(), (6, 6)).into()).collect()),
+                ..c.clone()
+            })
+            .collect()
+    }
+
+    fn relabelings(&self) -> Vec<Self> {
+        let mut constraints = self.clone();
+        for c in &mut constraints { c.cells.0.sort() }
+        constraints.sort();
+        vec![constraints]
+    }
+}
+
+/// Renaming the digits of a Latin square gives another one. Canonically, the first row reads `123456`.
+impl Transform for Solution {
+    fn transform(&self, symmetry: Symmetry) -> Self {
+        let mut cells = self.0;
+        for (x, y) in itertools::iproduct!(0..6, 0..6) {
+            let (a, b) = symmetry.apply((x, y), (6, 6));
+            cells[a][b] = self.0[x][y];
+        }
+        Solution(cells)
+    }
+
+    fn relabelings(&self) -> Vec<Self> {
+        let mut digits = [0; 6];
+        for (i, v) in self.0[0].iter().enumerate() {
+            digits[v.0 as usize - 1] = i as u8 + 1;
+        }
+        if digits.contains(&0) { return vec![*self] }
+        vec![self.relabel(digits)]
+    }
+}
+
 impl std::fmt::Display for Solution {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for line in &self.0 {
@@ -97,130 +229,323 @@ impl std::fmt::Display for Solution {
     }
 }
 
+/// Parses 6 lines of 6 digits between 1 and 6, as printed by the `Display` implementation
+impl FromStr for Solution {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<_> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+        if lines.len() != 6 { return Err(ParseError::Shape) }
+
+        let mut solution = [[U6(0); 6]; 6];
+        for (row, line) in solution.iter_mut().zip(lines) {
+            let digits: Vec<_> = line.trim().chars().collect();
+            if digits.len() != 6 { return Err(ParseError::Shape) }
+            for (cell, c) in row.iter_mut().zip(digits) {
+                match c {
+                    '1'..='6' => *cell = U6(c as u8 - b'0'),
+                    other => return Err(ParseError::InvalidDigit(other)),
+                }
+            }
+        }
+        Ok(Solution(solution))
+    }
+}
+
 impl Default for BaseGrid {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// The rules are the same for every grid, and only built once
+static RULES: FormulaCache<(), BaseGrid> = FormulaCache::new(|_| BaseGrid::build());
+
 impl BaseGrid {
 
-    /// Creates a new puzzle instance and initialize the universal SAT constraints for the problem
+    /// Creates a new puzzle instance, with the universal SAT constraints for the problem.
+    /// These are only built once, and then copied for every instance.
     pub fn new() -> Self {
+        BaseGrid::clone(&RULES.get(()))
+    }
+
+    fn build() -> Self {
+        let _span = debug_span!("build base grid").entered();
 
         let mut f = CnfFormula::new();
 
-        let vars = ary![ ary![ ary![f.new_var(); 6]; 6]; 6];
-    
-        // Loop over every location
-        for x in 0..6 {
-            for y in 0..6 {
-    
-                let cell = &vars[x][y];
-    
-                // Each cell has at least one value
-                f.add_clause(&cell.map(|v| v.lit(true)));
-    
-                // Each cell has at most one value
-                for v1 in 0..6 {
-                    for v2 in 0..6 {
-                        if v1 != v2 {
-                            f.add_clause(&[ cell[v1].lit(false), cell[v2].lit(false) ])
-                        }
-                    }
-                }
-    
-            }
-        }
-    
-        //Each row contains each number at least once
-        for x in 0..6 {
-            for v in 0..6 {
-                f.add_clause(&vars[x].map(|vs| vs[v].lit(true )))
-            }
-        }
-    
-        //Each column contains each number at least once
-        for y in 0..6 {
-            for v in 0..6 {
-                f.add_clause(&vars.map(|vs| vs[y][v].lit(true)))
-            }
-        }
+        let latin = Latin::new(&mut f, 6);
+        let vars = std::array::from_fn(|x| std::array::from_fn(|y| std::array::from_fn(|z| latin.var(x, y, z))));
 
-        //No need to have a constraint for not having the same value twice in a row or column
-        //it is implied from the two previous constraints by the pigeonhole principle
-    
+        debug!(clauses = f.len());
         BaseGrid { formula: f, vars }
 
     }
 
     // Solve a grid given some logical constraints
-    pub fn solve(mut self, constraints: &[Constraint]) -> Result<Solution, LogicalError> {
-        for c in constraints { self.add_constraint(c)? };
+    pub fn solve(self, constraints: &[Constraint]) -> Result<Solution, LogicalError<'_>> {
+        self.session(constraints)?.solve(&[])
+    }
 
-        let mut solver = Solver::new();
-        solver.add_formula(&self.formula);
-        solver.solve()?;
+    /// Find a solution drawn at random from `seed`, for constraints allowing several.
+    /// See [`random_model`].
+    pub fn solve_random(self, constraints: &[Constraint], seed: u64) -> Result<Solution, LogicalError<'_>> {
+        self.session(constraints)?.solve_random(&mut StdRng::seed_from_u64(seed))
+    }
 
-        let mut solution = [[U6(0); 6]; 6];
+    /// Find a solution to the constraints that differs from `prior` in at least one cell.
+    /// Fails with `Unsatisfyable` if `prior` is the only solution.
+    pub fn solve_excluding<'c>(self, constraints: &'c [Constraint], prior: &Solution) -> Result<Solution, LogicalError<'c>> {
+        let mut session = self.session(constraints)?;
+        session.exclude(prior);
+        session.solve(&[])
+    }
 
-        let model = solver.model().ok_or(LogicalError::Unsatisfyable)?;
+    /// Call `f` with every solution to the constraints, see [`Session::solve_with`].
+    pub fn solve_with<B>(self, constraints: &[Constraint], f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, LogicalError<'_>> {
+        self.session(constraints)?.solve_with(f)
+    }
 
-        for x in 0..6 {
-            for y in 0..6 {
-                for v in 0..6 {
-                    if model.contains(&self.vars[x][y][v].lit(true)) {
-                        solution[x][y] = U6(v as u8 + 1)
-                    }
-                }
+    /// List the cells whose value is the same in every solution, in row-major order.
+    pub fn hints(self, constraints: &[Constraint]) -> Result<Vec<Assignment>, LogicalError<'_>> {
+        let mut session = self.session(constraints)?;
+        let s = session.solve(&[])?;
+
+        let mut candidates = vec![];
+        for (x, line) in s.0.iter().enumerate() {
+            for (y, v) in line.iter().enumerate() {
+                candidates.push((((x,y), v.0), session.vars[x][y][v.0 as usize - 1].lit(true)));
             }
         }
 
-        Ok(Solution(solution))
+        Ok(backbone(&mut session.solver, &session.selectors, candidates)?)
+    }
+
+    /// When the constraints cannot be satisfied together, find a minimal subset of them
+    /// that is already contradictory. Returns `None` if the grid has a solution.
+    pub fn conflict(self, constraints: &[Constraint]) -> Result<Option<Vec<&Constraint>>, LogicalError<'_>> {
+        let mut session = self.session(constraints)?;
+        let Some(core) = minimal_core(&mut session.solver, &session.selectors)? else { return Ok(None) };
+
+        Ok(Some(constraints.iter()
+            .zip(&session.selectors)
+            .filter(|(_, s)| core.contains(s))
+            .map(|(c, _)| c)
+            .collect()))
+    }
+
+    /// The formula of the grid with the constraints, to inspect or export it without
+    /// solving, along with its variables.
+    pub fn encode(mut self, constraints: &[Constraint]) -> Result<(CnfFormula, Vars), LogicalError<'_>> {
+        for selector in self.add_constraints(constraints)? {
+            self.formula.add_clause(&[selector]);
+        }
+        Ok((self.formula, self.vars))
+    }
+
+    /// Add the constraints, and encode the grid into a [`Session`].
+    pub fn session(mut self, constraints: &[Constraint]) -> Result<Session, LogicalError<'_>> {
+        let span = debug_span!("encode constraints", constraints = constraints.len()).entered();
+        let selectors = self.add_constraints(constraints)?;
+        info!(constraints = constraints.len(), clauses = self.formula.len(), "encoded");
+        drop(span);
+
+        let mut solver = GridSolver::new();
+        solver.add_formula(&self.formula);
+        Ok(Session { solver, vars: self.vars, selectors })
+    }
 
+    /// Add the clauses for the constraints, and return the literals that enable them, in
+    /// the same order. Enumerating the terms of the constraints is where encoding spends
+    /// its time, so it is spread over all the cores, and the terms are then added in one batch.
+    fn add_constraints<'c>(&mut self, constraints: &'c [Constraint]) -> Result<Vec<Lit>, LogicalError<'c>> {
+        let terms: Vec<_> = constraints.par_iter()
+            .map(|c| self.terms(c))
+            .collect();
+
+        terms.into_iter()
+            .map(|terms| {
+                let selector = self.formula.new_lit();
+                self.formula.add_dnf_if(selector, terms?);
+                Ok(selector)
+            })
+            .collect()
     }
 
-    fn add_constraint<'c>(&mut self, constraint: &'c Constraint) -> Result<(), LogicalError<'c>> {
+    /// The terms of the DNF for a constraint, one for each way to fill its area
+    fn terms<'c>(&self, constraint: &'c Constraint) -> Result<Vec<Vec<Lit>>, LogicalError<'c>> {
         
-        let vars: Vec<_> = constraint.cells.iter().map(|(x,y)| self.vars[*x][*y]).collect();
+        let vars: Vec<_> = constraint.cells.0.iter().map(|c| self.vars[c.x][c.y]).collect();
 
         let terms = match constraint.op {
-            Op::Plus => make_associative_constraint(&vars[..], |a,b| a+b, 0, constraint.result as u16),
-            Op::Minus => make_binary_constraint(&vars[..], |a,b| a + constraint.result == b || b + constraint.result == a),
-            Op::Times => make_associative_constraint(&vars[..], |a,b| a*b, 1, constraint.result as u16),
-            Op::Div => make_binary_constraint(&vars[..], |a,b| a * constraint.result == b || b * constraint.result == a),
+            Op::Plus => make_associative_constraint(&vars[..], u16::saturating_add, 0, constraint.result as u16),
+            Op::Minus => make_binary_constraint(&vars[..], |a,b| Op::Minus.check(constraint.result, &[a, b])),
+            Op::Times => make_associative_constraint(&vars[..], u16::saturating_mul, 1, constraint.result as u16),
+            Op::Div => make_binary_constraint(&vars[..], |a,b| Op::Div.check(constraint.result, &[a, b])),
         }.ok_or(LogicalError::ImpossibleConstraint(constraint))?;
 
         if terms.is_empty() { return Err(LogicalError::ImpossibleConstraint(constraint))}
 
-        self.add_dnf(terms);
+        Ok(terms)
 
-        Ok(())
+    }
+
+}
+
+impl Session {
+
+    /// Solve the grid, assuming that every listed cell holds the given number (1-6).
+    /// A number out of that range, or a cell out of the grid, cannot be held, and makes
+    /// the grid unsatisfiable.
+    pub fn solve(&mut self, assumptions: &[Assignment]) -> Result<Solution, LogicalError<'static>> {
+        let mut lits = self.selectors.clone();
+        for &((x,y), v) in assumptions {
+            let cell = self.vars.get(x).and_then(|line| line.get(y));
+            let var = v.checked_sub(1).and_then(|z| cell?.get(z as usize));
+            lits.push(var.ok_or(LogicalError::Unsatisfyable)?.lit(true));
+        }
+
+        // Through the inner solver, whose errors are reported rather than panicking
+        self.solver.assume(&lits);
+        Solver::solve(&mut self.solver)?;
+
+        let model = self.solver.model().ok_or(LogicalError::Unsatisfyable)?;
+        Ok(self.decode(&model.into()))
+    }
 
+    /// Solve the grid, drawing a solution at random. See [`random_model`].
+    pub fn solve_random(&mut self, rng: &mut impl Rng) -> Result<Solution, LogicalError<'static>> {
+        let vars: Vec<Var> = self.vars.iter().flatten().flatten().copied().collect();
+        let model = random_model(&mut self.solver, &self.selectors, &vars, rng)?
+            .ok_or(LogicalError::Unsatisfyable)?;
+        Ok(self.decode(&model.into()))
     }
 
-    /// Add a clause in DNF form, by translating it into helper variables
-    fn add_dnf<T>(&mut self, dnf: impl IntoIterator<Item=T>)
-        where T: IntoIterator<Item=Lit>
-    {
+    fn decode(&self, model: &GridModel) -> Solution {
+        Solution(std::array::from_fn(|x| std::array::from_fn(|y| {
+            let v = (0..6).find(|&v| model.holds(self.vars[x][y][v].positive()))
+                .expect("every cell holds a number");
+            U6(v as u8 + 1)
+        })))
+    }
 
-        let mut helpers = vec![];
+    /// Call `f` with every solution to the constraints left, see [sessions](crate::util::solve#sessions).
+    pub fn solve_with<B>(&mut self, mut f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, LogicalError<'static>> {
+        loop {
+            let s = match self.solve(&[]) {
+                Ok(s) => s,
+                Err(LogicalError::Unsatisfyable) => return Ok(ControlFlow::Continue(())),
+                Err(e) => return Err(e),
+            };
+            self.exclude(&s);
+            if let ControlFlow::Break(b) = f(s) { return Ok(ControlFlow::Break(b)) }
+        }
+    }
 
-        for product in dnf {
-            let hv = self.formula.new_var();
-            helpers.push(hv.lit(true));
+    /// Rule out the solution `prior` from now on.
+    pub fn exclude(&mut self, prior: &Solution) {
+        let mut blocking = vec![];
+        for x in 0..6 {
+            for y in 0..6 {
+                let v = prior.0[x][y].0 as usize - 1;
+                blocking.push(self.vars[x][y][v].lit(false));
+            }
+        }
+        self.solver.add_clause(&blocking);
+    }
+}
 
-            let not_hv = hv.lit(false);
+/// Fill the grid using only simple human rules, recording every step:
+///  - naked singles: a cell where a single value is allowed by its row, column and area
+///  - hidden singles: a value that fits in a single cell of a row or column
+pub fn deduce(constraints: &[Constraint]) -> Deduction<u8> {
+    let naked = |g: &Matrix<Option<u8>>| naked_single(constraints, g);
+    let hidden = |g: &Matrix<Option<u8>>| hidden_single(constraints, g);
+    crate::util::deduce::deduce(umat![None; (6, 6)], &[&naked, &hidden])
+}
 
-            for term in product {
-                self.formula.add_clause(&[not_hv, term])
+/// Check `solution` against the rules and the areas directly, without the SAT solver.
+/// Lists every broken rule if it is not valid.
+pub fn verify(constraints: &[Constraint], solution: &Solution) -> Result<(), Vec<Violation>> {
+    let value = |c: Coord| solution.0[c.x][c.y].0;
+    let mut violations = vec![];
+
+    for (name, line) in lines((6, 6)) {
+        for v in 1..=6 {
+            let cells: Vec<_> = line.iter().copied().filter(|&c| value(c) == v).collect();
+            if cells.len() > 1 {
+                violations.push(Violation::new(format!("{} appears {} times in {}", v, cells.len(), name), cells));
             }
         }
+    }
+
+    for c in constraints {
+        let values: Vec<_> = c.cells.0.iter().map(|&cell| value(cell)).collect();
+        if !c.op.check(c.result, &values) {
+            violations.push(Violation::new(format!("{}{} does not hold", c.result, c.op), c.cells.0.clone()));
+        }
+    }
+
+    verdict(violations)
+}
+
+impl Op {
+    /// Check whether the values of an area satisfy this operation
+    fn check(self, result: u8, values: &[u8]) -> bool {
+        let result = result as u16;
+        let values: Vec<u16> = values.iter().map(|&v| v as u16).collect();
+        match (self, &values[..]) {
+            // Large areas can overflow, and then cannot match a u8 result anyway
+            (Op::Plus, _) => values.iter().try_fold(0u16, |a, &v| a.checked_add(v)) == Some(result),
+            (Op::Times, _) => values.iter().try_fold(1u16, |a, &v| a.checked_mul(v)) == Some(result),
+            (Op::Minus, &[a, b]) => a + result == b || b + result == a,
+            (Op::Div, &[a, b]) => a * result == b || b * result == a,
+            _ => false,
+        }
+    }
+}
 
-        self.formula.add_clause(&helpers);
+/// Values that each cell of the grid can still take, given the values already placed
+/// in its row and column, and the possible completions of its area.
+fn candidates(constraints: &[Constraint], grid: &Matrix<Option<u8>>) -> Matrix<Vec<u8>> {
+    let mut domains = Domains::new(grid, &[1, 2, 3, 4, 5, 6]);
+    for (_, line) in lines((6, 6)) {
+        distinct(&mut domains, &line);
+    }
 
+    for c in constraints {
+        let cells = &c.cells.0;
+        area(&mut domains, cells, |values| {
+            let clash = cells.iter().zip(values).tuple_combinations()
+                .any(|((a, va), (b, vb))| va == vb && (a.x == b.x || a.y == b.y));
+            !clash && c.op.check(c.result, values)
+        });
     }
 
+    domains.0
+}
+
+fn naked_single(constraints: &[Constraint], grid: &Matrix<Option<u8>>) -> Option<Step<u8>> {
+    let cands = candidates(constraints, grid);
+    let cell = grid.coords().find(|&c| grid[c].is_none() && cands[c].len() == 1)?;
+    Some(Step {
+        cell,
+        value: cands[cell][0],
+        reason: "only value allowed by its row, column and area".into(),
+    })
+}
+
+fn hidden_single(constraints: &[Constraint], grid: &Matrix<Option<u8>>) -> Option<Step<u8>> {
+    let cands = candidates(constraints, grid);
+    for (name, line) in lines((6, 6)) {
+        for v in 1..=6 {
+            if line.iter().any(|&c| grid[c] == Some(v)) { continue }
+            if let [cell] = line.iter().copied().filter(|&c| cands[c].contains(&v)).collect::<Vec<_>>()[..] {
+                return Some(Step { cell, value: v, reason: format!("only place for a {} in {}", v, name) })
+            }
+        }
+    }
+    None
 }
 
 /// Generate a DNF constraint for an arithmetic operation
@@ -252,16 +577,7 @@ fn make_binary_constraint<F>(vars: &[[Var; 6]], op: F) -> Option<Vec<Vec<Lit>>>
 fn make_associative_constraint(vars: &[[Var; 6]], op: fn(u16,u16) -> u16, z: u16, r: u16) -> Option<Vec<Vec<Lit>>> {
 
     let mut terms = vec![];
-
-    for chosen in vars.iter().map(|_| 0..6).multi_cartesian_product() {
-        if chosen.iter().map(|&x| x as u16 + 1).fold(z, op) == r {
-            let term = chosen.iter()
-                .zip(vars)
-                .map(|(&x, &v)| v[x].lit(true))
-                .collect();
-            terms.push(term);
-        }
-    }
+    associative_terms(vars, op, z, r, &mut Vec::with_capacity(vars.len()), &mut terms);
 
     if terms.is_empty() { return None }
 
@@ -269,6 +585,31 @@ fn make_associative_constraint(vars: &[[Var; 6]], op: fn(u16,u16) -> u16, z: u16
 
 }
 
+/// Extend `term`, whose digits combine into `acc`, with every choice of digits for `vars`
+/// that reaches `r`. Sums and products of digits only grow with each digit, so a branch
+/// is cut as soon as `r` is out of reach, even with only ones or only sixes left.
+fn associative_terms(vars: &[[Var; 6]], op: fn(u16,u16) -> u16, acc: u16, r: u16, term: &mut Vec<Lit>, terms: &mut Vec<Vec<Lit>>) {
+
+    let Some((cell, rest)) = vars.split_first() else {
+        if acc == r { terms.push(term.clone()) }
+        return
+    };
+
+    for (x, v) in cell.iter().enumerate() {
+        let acc = op(acc, x as u16 + 1);
+        let low = rest.iter().fold(acc, |a, _| op(a, 1));
+        let high = rest.iter().fold(acc, |a, _| op(a, 6));
+        // Larger digits for this cell would only overshoot further
+        if low > r { break }
+        if high < r { continue }
+
+        term.push(v.lit(true));
+        associative_terms(rest, op, acc, r, term, terms);
+        term.pop();
+    }
+
+}
+
 
 
 
@@ -297,4 +638,139 @@ fn test_sample_grid() {
     let solution = BaseGrid::new().solve(&constraints[..]).unwrap();
     eprintln!("{}", solution);
 
+    let other = BaseGrid::new().solve_excluding(&constraints[..], &solution);
+    assert!(matches!(other, Err(LogicalError::Unsatisfyable)));
+
+    let mut session = BaseGrid::new().session(&constraints[..]).unwrap();
+    assert!(session.solve(&[((1,4), 2)]).is_ok());
+    assert!(matches!(session.solve(&[((1,4), 3)]), Err(LogicalError::Unsatisfyable)));
+    assert!(matches!(session.solve(&[((1,4), 0)]), Err(LogicalError::Unsatisfyable)));
+    assert!(matches!(session.solve(&[((1,4), 7)]), Err(LogicalError::Unsatisfyable)));
+    assert!(matches!(session.solve(&[((6,0), 1)]), Err(LogicalError::Unsatisfyable)));
+
+    let d = deduce(&constraints);
+    assert!(d.is_complete());
+    for (x, line) in solution.0.iter().enumerate() {
+        for (y, v) in line.iter().enumerate() {
+            assert_eq!(d.grid[x][y], Some(v.0));
+        }
+    }
+
+    assert!(BaseGrid::new().conflict(&constraints).unwrap().is_none());
+
+    let parsed: Solution = solution.to_string().parse().unwrap();
+    assert!(verify(&constraints, &parsed).is_ok());
+
+    let hints = BaseGrid::new().hints(&constraints[..]).unwrap();
+    assert_eq!(hints.len(), 36);
+    assert!(hints.contains(&((1,4), 2)));
+
+}
+
+#[test]
+fn test_candidates() {
+    let constraints = constraints![
+        3+ [ (0,0), (1,0) ],
+        5+ [ (2,0), (3,0) ],
+    ];
+    let mut grid = umat![None; (6, 6)];
+    grid[(0,3)] = Some(2);
+    grid[(2,5)] = Some(4);
+
+    let cands = candidates(&constraints, &grid);
+    // 1+2, with the 2 already in the first row
+    assert_eq!(cands[(0,0)], [1]);
+    assert_eq!(cands[(1,0)], [2]);
+    // 1+4, 2+3 or 3+2, the third row already holding a 4
+    assert_eq!(cands[(2,0)], [1, 2, 3]);
+    assert_eq!(cands[(3,0)], [2, 3, 4]);
+    // Only the placed digits in its row and column
+    assert_eq!(cands[(0,4)], [1, 3, 4, 5, 6]);
+    assert_eq!(cands[(4,4)], [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn test_verify() {
+
+    let constraints = constraints![
+        3+ [ (0,0), (0,1) ],
+        2/ [ (1,0), (1,1) ],
+    ];
+
+    // A cyclic Latin square, shifted by one on each row
+    let solution: Solution = "123456\n234561\n345612\n456123\n561234\n612345\n".parse().unwrap();
+    let violations = verify(&constraints, &solution).unwrap_err();
+    assert_eq!(violations[0], Violation::new("2/ does not hold", vec![Coord::new(1,0), Coord::new(1,1)]));
+    assert_eq!(violations.len(), 1);
+
+    let swapped: Solution = "213456\n234561\n345612\n456123\n561234\n612345\n".parse().unwrap();
+    let violations = verify(&constraints, &swapped).unwrap_err();
+    assert!(violations.contains(&Violation::new("2 appears 2 times in column 0", vec![Coord::new(0,0), Coord::new(1,0)])));
+
+    assert!(matches!("12345\n".parse::<Solution>(), Err(ParseError::Shape)));
+
+}
+
+#[test]
+fn test_verify_large_product() {
+
+    // Seven sixes multiply past the range of a u16
+    let constraints = constraints![
+        240* [ (0,0), (0,1), (0,2), (0,3), (0,4), (0,5), (1,0) ],
+    ];
+
+    let solution: Solution = "666666\n666666\n345612\n456123\n561234\n612345\n".parse().unwrap();
+    let violations = verify(&constraints, &solution).unwrap_err();
+    assert!(violations.contains(&Violation::new("240* does not hold", constraints[0].cells.0.clone())));
+    assert!(!Op::Times.check(240, &[6; 7]));
+    assert!(!Op::Plus.check(6, &[6; 7]));
+
+}
+
+#[test]
+fn test_large_binary_result() {
+
+    // The difference and the quotient are computed past the range of a u8
+    for constraints in [constraints![ 250- [ (0,0), (0,1) ], ], constraints![ 250/ [ (0,0), (0,1) ], ]] {
+        assert!(matches!(BaseGrid::new().solve(&constraints), Err(LogicalError::ImpossibleConstraint(_))));
+    }
+
+}
+
+#[test]
+fn test_conflict() {
+
+    let constraints = constraints![
+        10+ [ (0,0), (1,0) ],
+         3+ [ (0,1), (0,2) ],
+         9+ [ (1,1), (1,2) ],
+        11+ [ (2,0), (3,0) ],
+    ];
+
+    // Both areas need a 6 in the same line
+    let conflict = BaseGrid::new().conflict(&constraints).unwrap().unwrap();
+    assert_eq!(conflict, [&constraints[0], &constraints[3]]);
+
+}
+
+#[test]
+fn test_associative_pruning() {
+
+    let mut f = CnfFormula::new();
+    let vars: Vec<[Var; 6]> = (0..5).map(|_| std::array::from_fn(|_| f.new_var())).collect();
+
+    // The pruned enumeration finds the same terms, in the same order, as the full product
+    for k in 1..=5 {
+        for (op, z) in [(u16::saturating_add as fn(u16, u16) -> u16, 0), (u16::saturating_mul, 1)] {
+            for r in 1..=250 {
+                let expected: Vec<Vec<Lit>> = (0..k).map(|_| 0..6).multi_cartesian_product()
+                    .filter(|chosen| chosen.iter().fold(z, |a, &x| op(a, x as u16 + 1)) == r)
+                    .map(|chosen| chosen.iter().zip(&vars).map(|(&x, v)| v[x].positive()).collect())
+                    .collect();
+                let found = make_associative_constraint(&vars[..k], op, z, r).unwrap_or_default();
+                assert_eq!(found, expected, "{} cells, result {}", k, r);
+            }
+        }
+    }
+
 }
@@ -1,9 +1,11 @@
-use super::{Op, Constraint};
+use super::{Op, Constraint, ParseError};
+use crate::util::{coord::Coord, region::Region};
 
 use nom::{
     IResult,
     character::complete::one_of,
     bytes::complete::is_a,
+    combinator::map_res,
     multi::separated_list1,
     Parser, sequence::{delimited, separated_pair},
 };
@@ -15,19 +17,36 @@ fn char(c: char) -> impl Fn(&str) -> IResult<&str, char> {
     }
 }
 
+/// Parse a list of constraints, one per line, skipping empty lines.
+/// Lines are numbered from 1 in errors, which include cells outside of the 6x6 grid.
+pub fn constraints(input: &str) -> Result<Vec<Constraint>, ParseError> {
+    input.lines().enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| match constraint(l) {
+            Ok((rest, c)) if rest.trim().is_empty() && c.cells.0.iter().all(|c| c.x < 6 && c.y < 6) => Ok(c),
+            _ => Err(ParseError::InvalidConstraint(i + 1)),
+        })
+        .collect()
+}
+
+/// Print constraints in the format read by [`constraints`], one per line
+pub fn format(constraints: &[Constraint]) -> String {
+    constraints.iter().map(|c| format!("{}\n", c)).collect()
+}
+
 pub fn constraint(input: &str) -> IResult<&str, Constraint> {
     let (input, result) = u8(input)?;
     let (input, op) = op(input)?;
     let (input, cells) = cells(input)?;
-    Ok((input, Constraint { cells, op, result }))
+    Ok((input, Constraint { cells: Region(cells), op, result }))
 }
 
-fn cell(input: &str) -> IResult<&str, (usize,usize)> {
+fn cell(input: &str) -> IResult<&str, Coord> {
     let input = input.trim_start();
-    delimited(char('('), separated_pair(usize, char(','), usize), char(')')).parse(input)
+    delimited(char('('), separated_pair(usize, char(','), usize), char(')')).map(Coord::from).parse(input)
 }
 
-fn cells(input: &str) -> IResult<&str, Vec<(usize,usize)>> {
+fn cells(input: &str) -> IResult<&str, Vec<Coord>> {
     let input = input.trim_start();
     delimited(char('['),
               separated_list1(char(','), cell),
@@ -47,15 +66,26 @@ pub fn op(input: &str) -> IResult<&str, Op> {
 
 fn usize(input: &str) -> IResult<&str, usize> {
     let input = input.trim_start();
-    is_a("0123456789").map(|s: &str| s.parse().unwrap()).parse(input)    
+    map_res(is_a("0123456789"), str::parse)(input)
 }
 
 fn u8(input: &str) -> IResult<&str, u8> {
     let input = input.trim_start();
-    is_a("0123456789").map(|s: &str| s.parse().unwrap()).parse(input)
+    map_res(is_a("0123456789"), str::parse)(input)
+}
+
+#[test]
+fn test_round_trip() {
+    let text = "3+ [(0,0),(0,1)]\n\n2/ [(1,0),(1,1)]\n";
+    let parsed = constraints(text).unwrap();
+    assert_eq!(parsed, crate::constraints![ 3+ [ (0,0), (0,1) ], 2/ [ (1,0), (1,1) ], ]);
+    assert_eq!(format(&parsed), text.replace("\n\n", "\n"));
+    assert!(matches!(constraints("3+ [(0,0)]\n3+ [(0,0) x\n"), Err(ParseError::InvalidConstraint(2))));
+    assert!(matches!(constraints("3+ [(0,0),(0,6)]\n"), Err(ParseError::InvalidConstraint(1))));
+    assert!(matches!(constraints("300+ [(0,0),(0,1)]\n"), Err(ParseError::InvalidConstraint(1))));
 }
 
 #[test]
 fn test_parser() {
-    assert_eq!(constraint("30* [ (0,3), (1,3), (2,2), (2,3) ]").unwrap(), ("", Constraint { op: Op::Times, result: 30, cells: vec![ (0,3), (1,3), (2,2), (2,3)] } ));
+    assert_eq!(constraint("30* [ (0,3), (1,3), (2,2), (2,3) ]").unwrap(), ("", Constraint { op: Op::Times, result: 30, cells: Region(vec![ Coord::new(0,3), Coord::new(1,3), Coord::new(2,2), Coord::new(2,3)]) } ));
 }
\ No newline at end of file
@@ -1,4 +1,13 @@
 pub mod util;
+pub mod generate;
+pub mod verify;
+pub mod batch;
+pub mod transform;
+pub mod error;
+pub mod render;
+pub mod puzzlink;
+
+pub use error::{Error, Result};
 
 pub mod binero;
 pub mod kakuro;
@@ -1,25 +1,561 @@
-use std::io::{stdin, Read};
+use std::{fs::File, io::{stdin, stdout, BufWriter, IsTerminal, Read, Write}, sync::Mutex, fmt::Display, num::NonZeroUsize, ops::ControlFlow, panic::{catch_unwind, AssertUnwindSafe}, path::PathBuf, process::ExitCode, str::FromStr, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, OnceLock}, time::{Duration, Instant}};
 
 use multilogic::*;
-use clap::Parser;
-use anyhow::{anyhow, Result};
-use termcolor::BufferWriter;
+use multilogic::util::{cancel::CancelToken, stats::SolveStats};
+use multilogic::render::Renderer;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use anyhow::{bail, Context, Result};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rayon::prelude::*;
+use serde_json::json;
 
 #[derive(Parser)]
-#[command()]
+#[command(after_long_help = ENVIRONMENT)]
+struct Cli {
+    #[command(flatten)]
+    options: Options,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+// Options shared by all the commands
+#[derive(Args)]
+struct Options {
+    /// When solving, print the number of solutions instead of a solution.
+    ///
+    /// With a value, stop counting there: `--count=2` is enough to tell a puzzle
+    /// with a unique solution from the others. Only supported for Binero, Kakuro, K-Doku,
+    /// Stars and Voisimage so far.
+    #[arg(long, global = true, value_name = "MAX", num_args = 0..=1, require_equals = true)]
+    count: Option<Option<NonZeroUsize>>,
+
+    /// When solving, print the result as JSON on a single line.
+    ///
+    /// A solution is printed as `{"solution": rows}`, each row being a list of cells:
+    /// 0 or 1 for Binero, digits for K-Doku, and booleans for Stars (true for a star)
+    /// and Voisimage (true for a painted cell). A puzzle without solution gives
+    /// `{"solution": null, "conflict": clues}`, with clues that cannot be satisfied
+    /// together, and `--count` gives `{"count": n, "complete": bool}`. Errors are
+    /// printed as `{"error": message}`.
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// How to print solutions
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text, conflicts_with = "json")]
+    format: Format,
+
+    /// When to print text in color. `auto` uses colors on terminals, unless the
+    /// NO_COLOR environment variable is set.
+    #[arg(long, global = true, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+
+    /// Format of the puzzles read: the text format of every game, or JSON as printed by
+    /// `--json echo`, which names the game of the puzzle. In batch mode, the JSON puzzles
+    /// are read from an array.
+    #[arg(long, global = true, value_enum, default_value_t = Input::Text, conflicts_with = "url")]
+    input: Input,
+
+    /// Draw the puzzle itself instead of its solution. With `--format text`, the puzzle
+    /// is drawn as a grid of characters, in color on terminals.
+    #[arg(long, global = true)]
+    puzzle: bool,
+
+    /// Print the time spent building and solving the formulas on stderr, after each
+    /// puzzle. In batch mode, the puzzles are timed one by one.
+    #[arg(long, global = true)]
+    time: bool,
+
+    /// Print statistics about the solvers on stderr, after each puzzle: the size of
+    /// their formulas, the number of calls to the solver, and the times of `--time`
+    #[arg(long, global = true)]
+    stats: bool,
+
+    /// Seed of the random choices, the same seed giving the same result.
+    ///
+    /// When solving, pick a solution at random among all of them, rather than the one
+    /// the solver happens to reach first. When generating without a seed, one is picked
+    /// at random and reported on stderr.
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// Give up after this many seconds, exiting with status 124
+    #[arg(long, global = true, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Read the puzzle from a puzz.link or pzv.jp URL instead of stdin.
+    ///
+    /// Only supported for Stars so far, with Star Battle URLs such as
+    /// `https://puzz.link/p?starbattle/6/6/1/...`.
+    #[arg(long, global = true)]
+    url: Option<String>,
+
+    /// Write the results to this file instead of stdout. In batch mode, a name with
+    /// `{}` gives a file per solved puzzle, `{}` standing for its number from 1.
+    #[arg(short, long, global = true, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Log what is being done on stderr: `-v` for the size of the puzzle and of its
+    /// formula, `-vv` for every call to the solver and the time spent in every phase
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only print errors on stderr, without logs, progress or remarks such as the
+    /// number of solutions found
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Number of threads working in parallel, in batch mode, when encoding K-Doku
+    /// areas, and answering requests in `serve`. Defaults to the number of cores; with 1, everything runs on a single
+    /// thread, in the same order every time.
+    #[arg(long, global = true, value_name = "N")]
+    threads: Option<NonZeroUsize>,
+}
+
+/// The puzzle decoded from `--url`, or read from the file given to `solve`, read
+/// instead of stdin
+static INPUT: OnceLock<String> = OnceLock::new();
+
+/// What the command reads on stdin, to prompt for it on a terminal
+static PROMPT: OnceLock<String> = OnceLock::new();
+
+/// Set by `--quiet`
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Like `eprintln!`, for remarks that `--quiet` leaves out
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !$crate::QUIET.load(std::sync::atomic::Ordering::Relaxed) { $crate::suspended(|| eprintln!($($arg)*)) }
+    };
+}
+
+mod cli;
+use cli::json;
+
+/// The file of `--output`, written instead of stdout
+static OUTPUT: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+/// Writes to the file of `--output`, or to stdout
+struct Output;
+
+impl Output {
+    fn is_terminal() -> bool {
+        OUTPUT.lock().expect("output lock").is_none() && stdout().is_terminal()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut *OUTPUT.lock().expect("output lock") {
+            Some(file) => file.write(buf),
+            None if stdout().is_terminal() => suspended(|| stdout().write(buf)),
+            None => stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut *OUTPUT.lock().expect("output lock") {
+            Some(file) => file.flush(),
+            None => stdout().flush(),
+        }
+    }
+}
+
+/// The progress shown on stderr, if any
+static PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Run `print` with the progress out of the way, as it shares the terminal
+fn suspended<R>(print: impl FnOnce() -> R) -> R {
+    let progress = PROGRESS.lock().expect("progress lock").clone();
+    match progress {
+        Some(bar) => bar.suspend(print),
+        None => print(),
+    }
+}
+
+/// Shows on stderr that a long command is at work, rather than hanging. Nothing is
+/// drawn for commands done within [`Progress::DELAY`], or unless stderr is a terminal
+/// free of logs. Cleared when dropped.
+struct Progress(ProgressBar);
+
+impl Progress {
+    const DELAY: Duration = Duration::from_millis(500);
+
+    /// A spinner, counting the calls to the solver within [`Progress::observe`]
+    fn spinner(options: &Options) -> Self {
+        Self::new(ProgressBar::new_spinner(), "{spinner} {elapsed} {msg}", options)
+    }
+
+    /// A bar, counting up to `len` puzzles
+    fn bar(len: usize, options: &Options) -> Self {
+        Self::new(ProgressBar::new(len as u64), "{bar:40} {pos}/{len} puzzles, {elapsed}", options)
+    }
+
+    fn new(bar: ProgressBar, template: &str, options: &Options) -> Self {
+        bar.set_draw_target(ProgressDrawTarget::hidden());
+        bar.set_style(ProgressStyle::with_template(template).expect("a valid template"));
+        *PROGRESS.lock().expect("progress lock") = Some(bar.clone());
+        let logged = options.verbose > 0 || std::env::var_os("MULTILOGIC_LOG").is_some();
+        if !options.quiet && !logged && std::io::stderr().is_terminal() {
+            let bar = bar.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Self::DELAY);
+                // Unless dropped in the meantime
+                let progress = PROGRESS.lock().expect("progress lock");
+                if !bar.is_finished() && progress.is_some() {
+                    bar.set_draw_target(ProgressDrawTarget::stderr());
+                    bar.enable_steady_tick(Duration::from_millis(100));
+                }
+            });
+        }
+        Progress(bar)
+    }
+
+    fn inc(&self) {
+        self.0.inc(1)
+    }
+
+    /// Run `f`, counting the calls it makes to the solver
+    fn observe<R>(&self, f: impl FnOnce() -> R) -> R {
+        let (bar, calls) = (self.0.clone(), AtomicUsize::new(0));
+        let observer = move |_| {
+            let calls = calls.fetch_add(1, Ordering::Relaxed) + 1;
+            bar.set_message(format!("{} calls to the solver", calls));
+        };
+        util::progress::with_progress(observer, f)
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        let mut progress = PROGRESS.lock().expect("progress lock");
+        self.0.finish_and_clear();
+        *progress = None;
+    }
+}
+
+/// Like `print!`, to the output
+macro_rules! out {
+    ($($arg:tt)*) => {
+        write!($crate::Output, $($arg)*)?
+    };
+}
+
+/// Like `println!`, to the output
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        writeln!($crate::Output, $($arg)*)?
+    };
+}
+
+/// Exit status after `--timeout`, the same as the `timeout` command
+const TIMEOUT_STATUS: i32 = 124;
+
+const UNSOLVED_STATUS: u8 = 1;
+const BAD_INPUT_STATUS: u8 = 2;
+const INTERNAL_STATUS: u8 = 3;
+
+/// Why a command failed, beyond the errors of the library
+#[derive(Debug, thiserror::Error)]
+enum Failure {
+    /// The puzzle has no solution, and the clues in conflict were already reported
+    #[error("unsolvable grid")]
+    Conflict,
+    /// The puzzle has no solution, not a unique one, or the solution given to `check`
+    /// breaks the rules
+    #[error("{0}")]
+    Unsolved(String),
+    /// The input or the options make no sense
+    #[error("{0}")]
+    BadInput(String),
+    #[error("not supported for this game yet")]
+    Unsupported,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Text grids, in color on terminals for some games
+    Text,
+    /// A standalone SVG drawing
+    Svg,
+    /// A TikZ picture, to include in a LaTeX document loading the `tikz` package
+    Latex,
+    /// An HTML table, to include in a web page
+    Html,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Input {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    /// Whether to use colors on a stream, given whether it is a terminal
+    fn enabled(self, terminal: bool) -> bool {
+        match self {
+            Color::Always => true,
+            Color::Never => false,
+            Color::Auto => terminal && std::env::var_os("NO_COLOR").map_or(true, |v| v.is_empty()),
+        }
+    }
+}
+
+/// Help shared by the commands that only handle some of the games
+const GRID_GAMES: &str = "Only supported for Binero, K-Doku, Stars and Voisimage so far.";
+
+#[derive(Subcommand)]
 enum Command {
+    #[command(flatten)]
+    Solve(Game),
+
+    /// Solve puzzles of any game, recognized from their format.
+    ///
+    /// Reads the puzzle from the file, or from stdin, in the format for solving: K-Doku
+    /// areas, Binero grids of `0` and `1`, Stars grids of numbers separated by spaces,
+    /// or Voisimage grids of digits. When it could be the puzzle of several games, asks
+    /// which one if stdin is a terminal, and fails otherwise.
+    ///
+    /// Given several files or a directory, solves all the files in parallel, searching
+    /// directories recursively. The solution of every puzzle is written next to it,
+    /// with `.solution` appended to its name, or under the directory given with
+    /// `--output`. Then prints a table of the files with their game, status, number of
+    /// solutions (counted up to 2) and solving time.
+    #[command(name = "solve")]
+    Detect {
+        /// Files containing the puzzles, or directories of them
+        #[arg(conflicts_with = "url")]
+        files: Vec<PathBuf>,
+    },
+
+    /// Reveal a single cell whose value is forced, instead of the whole solution.
+    ///
+    /// The input format is the same as for solving. Prints the coordinates of the
+    /// first empty cell (in row-major order) that holds the same value in every
+    /// solution, along with that value.
+    Hint {
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Solve step by step with simple human rules, explaining every deduction.
+    ///
+    /// The input format is the same as for solving. Prints one line per filled cell,
+    /// with the reason it was forced. Harder puzzles may not be solved completely
+    /// by these rules alone.
+    Explain {
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Estimate the difficulty of a puzzle.
+    ///
+    /// The input format is the same as for solving. Prints whether the puzzle is easy
+    /// (solved by the rules shown by `explain` alone) or hard, along with the number of
+    /// solutions (counted up to 2), the number of cells filled by these rules, and the
+    /// number of cells they leave to search.
+    Rate {
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Strip redundant clues from a grid with a unique solution.
+    ///
+    /// The input format is the same as for solving. Prints the same grid, keeping only
+    /// a minimal set of clues that still forces the same unique solution.
+    /// Only supported for games whose clues are individual cells (Binero, Voisimage).
+    Minimize {
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Solve many puzzles at once, in parallel.
+    ///
+    /// The puzzles are read on stdin in the same format as for solving, separated by
+    /// empty lines or by lines reading `---`. Prints the solutions in the same order,
+    /// separated by empty lines, with `No solution` for the puzzles that have none,
+    /// then the number of solved and unsolvable puzzles on stderr.
+    #[command(after_help = GRID_GAMES)]
+    Batch {
+        /// Solve the puzzles one after the other, on a single thread, as with `--threads 1`
+        #[arg(long)]
+        sequential: bool,
+
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Read a puzzle and print it back, to check how it was understood.
+    ///
+    /// The input format is the same as for solving, and the puzzle is printed in the
+    /// same format, normalized: for instance K-Doku areas are printed one per line
+    /// without extra spaces. Kakuro grids are read in the format printed by `generate`.
+    /// With `--json`, the puzzle is printed in the JSON format of `--input json`.
+    Echo {
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Print every solution of a puzzle, as soon as it is found.
+    ///
+    /// The input format is the same as for solving. The solutions are printed in the
+    /// same format as when solving, separated by empty lines, and their number is
+    /// reported on stderr at the end.
+    #[command(after_help = GRID_GAMES)]
+    Enumerate {
+        /// Stop after this many solutions
+        #[arg(short, long)]
+        limit: Option<NonZeroUsize>,
+
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Compare several solutions of an ambiguous puzzle.
+    ///
+    /// The input format is the same as for solving. Finds up to the given number of
+    /// solutions, and prints the grid with the value of the cells where they all agree,
+    /// and `?` for the cells where two of them differ. The number of solutions found
+    /// is reported on stderr.
+    #[command(after_help = GRID_GAMES)]
+    Diff {
+        /// Maximum number of solutions to compare
+        #[arg(short, long, default_value_t = 2)]
+        count: usize,
+
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Print the formula of a puzzle in DIMACS CNF format, without solving it.
+    ///
+    /// The input format is the same as for solving. The formula is preceded by comment
+    /// lines giving the variable of each cell: `c (x,y) v` for the games with boolean
+    /// cells, where `v` is true for a 1, a star or a painted cell, and `c (x,y)=d v`
+    /// for K-Doku, where `v` is true if the cell holds the digit `d`.
+    #[command(after_help = GRID_GAMES)]
+    Dimacs {
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Write the formula of a puzzle to a DIMACS CNF file, for other SAT tools.
+    ///
+    /// The input format is the same as for solving. The formula is written without
+    /// comments, and the variable of each cell to a second file, named after the first
+    /// one with `.map` appended, one cell per line in the format of the comments of
+    /// `dimacs`.
+    #[command(after_help = GRID_GAMES)]
+    ExportCnf {
+        /// File to write the formula to
+        file: PathBuf,
+
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Check a solution against the rules, without the solver.
+    ///
+    /// The puzzle is read on stdin in the same format as for solving, and the solution
+    /// from the given file, in the output format of the solver: `*` and `.` for Stars,
+    /// `#` or `█` and `.` or `░` for Voisimage. Without a file, stdin holds the puzzle,
+    /// an empty line, then the solution. Prints every broken rule along with
+    /// the cells involved, and fails if there is any.
+    #[command(after_help = GRID_GAMES)]
+    Check {
+        /// File containing the solution
+        solution: Option<PathBuf>,
+
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Generate a new puzzle with a unique solution.
+    ///
+    /// Prints the puzzle in the same format as the input for solving.
+    /// Only supported for Binero, Kakuro, K-Doku, Stars and Voisimage so far.
+    /// The difficulty is ignored for Kakuro and Stars.
+    ///
+    /// Voisimages are made from a picture read on stdin, with `#` or `█` for painted
+    /// cells and `.` or `░` for blank ones. The size is then ignored.
+    Generate {
+        /// Side of the grid, for games where it can vary
+        #[arg(short, long, default_value_t = 10)]
+        size: usize,
+
+        /// `easy` puzzles can be solved with the rules shown by `explain`,
+        /// `hard` ones only have a unique solution
+        #[arg(short, long, default_value = "hard")]
+        difficulty: generate::Difficulty,
+
+        #[command(subcommand)]
+        game: Game,
+    },
+
+    /// Time the solving of sample puzzles bundled with the program.
+    ///
+    /// Every sample is parsed and solved several times in a row, and a table gives the
+    /// size of its formula and its fastest and median times, to compare machines or
+    /// solvers, or to notice when a change slows a game down. `--json` prints
+    /// `{"samples": [...], "total_ms": t}` instead, the times in milliseconds.
+    Bench {
+        /// Only the samples whose name, such as `binero/large`, contains this
+        filter: Option<String>,
+
+        /// Number of times every sample is solved
+        #[arg(short, long, default_value = "5")]
+        runs: NonZeroUsize,
+    },
+
+    /// Answer HTTP requests, to use the solvers from other programs.
+    ///
+    /// Every request is a POST, with the input of the matching command in its body, and
+    /// is answered with JSON:
+    ///  - `/solve/{game}` answers as `--json`, with the solution or the clues in conflict
+    ///  - `/check/{game}` takes the puzzle, an empty line and the solution, and answers
+    ///    `{"valid": bool, "violations": [...]}`
+    ///  - `/generate/{game}?size=10&difficulty=easy&seed=1` answers `{"puzzle": text,
+    ///    "seed": seed}`, all the parameters being optional
+    ///
+    /// Games are named as on the command line, such as `k-doku`. Errors are answered as
+    /// `{"error": message}`, with status 400 for invalid input. `--timeout` applies to
+    /// every request on its own, 60 seconds by default, and `--seed` to every solve.
+    /// `--threads` requests are answered at once, and status 503 means that too many
+    /// are waiting. Generated grids have a size of at most 30.
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        /// Address to listen on, only reachable from this machine by default
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum Game {
     /// Islands connected with a given number of bridges.
     Archipel,
 
     /// Balanced squares of bits.
-    /// 
+    ///
     /// Bineros are square grids filled with 0 or 1, such that:
     ///  - no three consecutive cells contain the same value
     ///  - every row and every column has half 0 cells and half 1 cells.
-    /// 
-    /// Input: A grid of N lines of length N containing the characters `0`, `1` or ` `.
-    /// 
-    /// Output: A valid completion of the same grid, with all the spaces filled; or nothing.
+    ///
+    /// Input: A grid of N lines of length N, N being even, containing the characters
+    /// `0`, `1` or `.` for an empty cell.
+    ///
+    /// Output: A valid completion of the same grid, with all the empty cells filled; or nothing.
     Binero,
 
     /// Magic squares of pairs.
@@ -32,34 +568,34 @@ enum Command {
     Kakuro,
 
     /// Grid of numbers with arithmetical constraints.
-    /// 
+    ///
     /// KDokus are 6x6 grids where every row and every column is a permutation
     /// of the numbers 1-6. In addition, the grid is divided into irregular areas,
     /// each associated with an operation (`+`,`-`,`*`,`/`) and a result, such that
     /// the sum, product, difference or quotient of the cells in the area is equal to
     /// the given result.
-    /// 
+    ///
     /// `-` and `/` operators can only be applied to areas containing exactly 2 cells.
-    /// 
+    ///
     /// Input: A list of area descriptions, one per line.
-    /// 
+    ///
     /// The contraints are in format: 7+ [(0,0),(0,1),(1,1)]
-    /// 
+    ///
     /// First comes the result, then the operation code, then a list of all
     /// the cell coordinate pairs. Coordinates are in the 0-5 range.
-    /// 
+    ///
     /// Output: A solution to the grid, or nothing.
     KDoku,
 
     /// Place stars on a colored grid.
-    /// 
+    ///
     /// Stars are N*N grids divided into N colored areas. The goal of the game is to place
     /// N stars on the grid such that there is exactly one star per line, per column, and per
     /// colored area.
-    /// 
+    ///
     /// Input: N lines containing N whitespace-separated integers in the range [0;N[.
     /// The integer indicates the color of the cell.
-    /// 
+    ///
     /// Output: A N*N colored text grid for a valid solution, with star locations indicated by a `*` character;
     /// or nothing.
     Stars,
@@ -67,14 +603,14 @@ enum Command {
     Tectonic,
 
     /// Paint a grid, from hints about local neighborhoods.
-    /// 
+    ///
     /// Voisimage is a rectangular grid of binary cells, with some cells containing a number. When the number
     /// is present, it indicates the number of active adjacent cells, present cell included. The numbers are
     /// in the range `0-9` (`0-6` on the edges, `0-4` in the corners)
-    /// 
+    ///
     /// Input: A rectangular grid of digits in the range `0-9` or the character
     /// `.` for an empty cell.
-    /// 
+    ///
     /// Output: The same grid, with the cells colored according to a valid solution.
     Voisimage {
         /// Output using Unicode block drawing characters.
@@ -86,80 +622,1208 @@ enum Command {
     }
 }
 
-fn main() -> Result<()> {
-    use Command::*;
-    match Command::parse() {
-        Binero => binero(),
-        KDoku => kdoku(),
-        Stars => stars(),
-        Voisimage { box_drawing } => voisimage(box_drawing),
-        _ => panic!("game not yet implemented")
+const ENVIRONMENT: &str = "\
+Set MULTILOGIC_SOLVER to a command line such as `kissat -q` to use an external SAT solver.
+It is run with the path of a DIMACS CNF file as its last argument, and must print its
+answer in the format of the SAT competitions.
+
+When built with the `ipasir` feature, MULTILOGIC_SOLVER=ipasir uses the linked solver instead.
+
+Set MULTILOGIC_SIMPLIFY to remove duplicate and subsumed clauses before solving.
+
+Set MULTILOGIC_LOG to a filter such as `debug` or `multilogic=debug` to trace the encoding
+and solving phases on stderr, with the time spent in each of them. It overrides `-v` and `-q`.
+
+Exit status: 0 when the command succeeds, 1 when the puzzle has no solution (or not a
+unique one, or the solution given to `check` is wrong), 2 on invalid input or options,
+3 on internal errors such as a failing SAT solver, and 124 after `--timeout`.";
+
+fn main() -> ExitCode {
+    let Cli { options, command } = Cli::parse();
+
+    QUIET.store(options.quiet, Ordering::Relaxed);
+    let level = match (options.quiet, options.verbose) {
+        (true, _) => "off",
+        (_, 0) => "warn",
+        (_, 1) => "info",
+        (_, 2) => "debug",
+        _ => "trace",
+    };
+    let filter = tracing_subscriber::EnvFilter::try_from_env("MULTILOGIC_LOG")
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .with_ansi(options.color.enabled(std::io::stderr().is_terminal()))
+        .init();
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.get())
+            .build_global()
+            .expect("the pool is only set up here");
+    }
+    let token = CancelToken::new();
+    // The server applies the timeout to every request instead
+    if let (Some(secs), false) = (options.timeout, command.serves()) {
+        let (token, json) = (token.clone(), options.json);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(secs));
+            token.cancel();
+            // A call to the solver cannot be interrupted, give it a moment to return
+            // before stopping it for good
+            std::thread::sleep(Duration::from_secs(1));
+            timed_out(secs, json)
+        });
     }
 
+    // A panic was already printed by the panic hook
+    let Ok(result) = catch_unwind(AssertUnwindSafe(|| execute(command, &options, &token))) else {
+        return ExitCode::from(INTERNAL_STATUS)
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // The reader went away, as with `| head`: there is nobody left to tell
+        Err(e) if broken_pipe(&e) => ExitCode::SUCCESS,
+        Err(e) => {
+            report_error(&e, options.json);
+            ExitCode::from(exit_status(&e))
+        },
+    }
 }
 
-fn binero() -> Result<()> {
-    use binero::*;
+/// Run the command with the solver backend chosen in the environment
+fn execute(command: Command, options: &Options, token: &CancelToken) -> Result<()> {
+    let simplify = std::env::var_os("MULTILOGIC_SIMPLIFY").is_some();
+    let run_command = |command: Command| {
+        // Batch mode reports every puzzle on its own
+        let batch = matches!(command, Command::Batch { .. });
+        let start = Instant::now();
+        let cancellable = util::cancel::with_cancel(token, || util::stats::with_stats(|| {
+            if simplify { util::simplify::with_simplification(|| run(command, options)) } else { run(command, options) }
+        }));
+        let Ok((result, stats)) = cancellable else { timed_out(options.timeout.unwrap_or_default(), options.json) };
+        if !batch { report_stats(options, "", &stats, Some(start.elapsed())) }
+        result
+    };
+    let Ok(line) = std::env::var("MULTILOGIC_SOLVER") else { return run_command(command) };
+
+    #[cfg(feature = "ipasir")]
+    if line.trim() == "ipasir" {
+        return util::backend::with_backend(|| Box::new(util::ipasir::Ipasir::new()), || run_command(command));
+    }
+
+    if util::external::External::from_command_line(&line).is_none() {
+        bail!(Failure::BadInput("MULTILOGIC_SOLVER is empty".to_string()));
+    }
+    let factory = move || -> Box<dyn util::backend::SatBackend> {
+        Box::new(util::external::External::from_command_line(&line).expect("checked above"))
+    };
+    util::backend::with_backend(factory, || run_command(command))
+}
+
+/// Whether the output was closed before it was all written
+fn broken_pipe(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| matches!(cause.downcast_ref::<std::io::Error>(), Some(io) if io.kind() == std::io::ErrorKind::BrokenPipe))
+}
+
+/// Print an error on stderr, or as JSON, unless it was already explained
+fn report_error(e: &anyhow::Error, json: bool) {
+    if let Some(Failure::Conflict) = e.downcast_ref() { return }
+    if json {
+        println!("{}", json!({ "error": format!("{:#}", e) }));
+    } else {
+        eprintln!("Error: {:#}", e);
+    }
+}
+
+/// The exit status of a failed command, from the first error along the chain of
+/// causes that tells what went wrong. Other errors are internal.
+fn exit_status(e: &anyhow::Error) -> u8 {
+    for cause in e.chain() {
+        if let Some(failure) = cause.downcast_ref::<Failure>() {
+            return match failure {
+                Failure::Conflict | Failure::Unsolved(_) => UNSOLVED_STATUS,
+                Failure::BadInput(_) | Failure::Unsupported => BAD_INPUT_STATUS,
+            }
+        }
+        if let Some(error) = cause.downcast_ref::<multilogic::Error>() {
+            return match error {
+                multilogic::Error::Unsatisfiable => UNSOLVED_STATUS,
+                multilogic::Error::Solver(_) => INTERNAL_STATUS,
+                _ => BAD_INPUT_STATUS,
+            }
+        }
+        let bad_input = cause.is::<binero::ParseError>()
+            || cause.is::<kakuro::ParseError>()
+            || cause.is::<kdoku::ParseError>()
+            || cause.is::<stars::ParseError>()
+            || cause.is::<voisimage::ParseError>()
+            || cause.is::<util::gridparse::GridError>()
+            || cause.is::<util::matrix::ShapeError>()
+            || cause.is::<util::region::RegionError>()
+            || cause.is::<generate::DifficultyError>()
+            || cause.is::<puzzlink::UrlError>()
+            || cause.is::<std::string::FromUtf8Error>()
+            // Reading the input
+            || cause.is::<std::io::Error>();
+        if bad_input { return BAD_INPUT_STATUS }
+    }
+    INTERNAL_STATUS
+}
+
+fn timed_out(secs: u64, json: bool) -> ! {
+    if let Some(bar) = PROGRESS.lock().expect("progress lock").take() { bar.finish_and_clear() }
+    let message = format!("timed out after {} seconds", secs);
+    if json {
+        println!("{}", json!({ "error": message }));
+    } else {
+        eprintln!("Error: {}", message);
+    }
+    std::process::exit(TIMEOUT_STATUS)
+}
+
+impl Command {
+    /// The game the command is about, unless it can be any of them
+    fn game(&self) -> Option<&Game> {
+        Some(match self {
+            Command::Solve(game)
+            | Command::Hint { game }
+            | Command::Explain { game }
+            | Command::Rate { game }
+            | Command::Minimize { game }
+            | Command::Batch { game, .. }
+            | Command::Echo { game }
+            | Command::Enumerate { game, .. }
+            | Command::Diff { game, .. }
+            | Command::Dimacs { game }
+            | Command::ExportCnf { game, .. }
+            | Command::Check { game, .. }
+            | Command::Generate { game, .. } => game,
+            #[cfg(feature = "serve")]
+            Command::Serve { .. } => return None,
+            Command::Detect { .. } | Command::Bench { .. } => return None,
+        })
+    }
+
+    /// Whether the command keeps answering requests until it is stopped
+    fn serves(&self) -> bool {
+        #[cfg(feature = "serve")]
+        if let Command::Serve { .. } = self { return true }
+        false
+    }
+
+    /// A description of the input, when the user types it
+    fn prompt(&self) -> Option<String> {
+        let end = if cfg!(windows) { "Ctrl-Z then Enter" } else { "Ctrl-D" };
+        let input = match self {
+            Command::Batch { game, .. } => format!("{}, with an empty line between puzzles", game.input()),
+            Command::Check { solution: None, game } => format!("{}, then an empty line and the solution", game.input()),
+            Command::Generate { game: Game::Voisimage { .. }, .. } => "a picture, with `#` for painted cells and `.` for blank ones".to_string(),
+            Command::Detect { files } if files.is_empty() => "a puzzle of any game".to_string(),
+            command => command.game()?.input().to_string(),
+        };
+        Some(format!("Enter {}, and end with {}:", input, end))
+    }
+}
+
+impl Game {
+    /// The games that can be solved, named as on the command line
+    const SOLVABLE: [(&'static str, Game); 4] = [
+        ("binero", Game::Binero),
+        ("k-doku", Game::KDoku),
+        ("stars", Game::Stars),
+        ("voisimage", Game::Voisimage { box_drawing: false }),
+    ];
+
+    /// The games as named on the command line, among those that can be solved
+    fn named(name: &str) -> Option<Game> {
+        Some(match name {
+            "binero" => Game::Binero,
+            "kakuro" => Game::Kakuro,
+            "k-doku" => Game::KDoku,
+            "stars" => Game::Stars,
+            "voisimage" => Game::Voisimage { box_drawing: false },
+            _ => return None,
+        })
+    }
+
+    /// Whether the input is a valid puzzle of this game
+    fn reads(&self, input: &str) -> bool {
+        match self {
+            Game::Binero => input.parse::<binero::Problem>().is_ok(),
+            Game::KDoku => kdoku::parse::constraints(input).is_ok_and(|c| !c.is_empty()),
+            Game::Stars => input.parse::<stars::Problem>().is_ok(),
+            Game::Voisimage { .. } => input.parse::<voisimage::Problem>().is_ok(),
+            _ => false,
+        }
+    }
+
+    /// The format of its puzzles, in a few words
+    fn input(&self) -> &'static str {
+        match self {
+            Game::Binero => "a square grid of `0`, `1` and `.` for empty cells",
+            Game::Kakuro => "a grid in the format printed by `generate kakuro`",
+            Game::KDoku => "one area per line, such as `7+ [(0,0),(0,1),(1,1)]`",
+            Game::Stars => "N lines of N numbers, the color of each cell",
+            Game::Voisimage { .. } => "a grid of hints, with `.` for cells without one",
+            _ => "the puzzle",
+        }
+    }
+}
+
+fn run(command: Command, options: &Options) -> Result<()> {
+    if let Some(url) = &options.url {
+        let input = match (command.game(), &command) {
+            (Some(Game::Stars), _) | (_, Command::Detect { .. }) => puzzlink::stars(url)?.to_string(),
+            _ => bail!(Failure::BadInput("puzz.link URLs are only supported for Stars so far".to_string())),
+        };
+        INPUT.set(input).expect("a single command runs");
+    }
+    if let Some(prompt) = command.prompt() {
+        PROMPT.set(prompt).expect("a single command runs");
+    }
+    // The commands read the text format. Pictures to generate Voisimages from have no
+    // JSON format.
+    if let (Input::Json, Some(game)) = (options.input, command.game()) {
+        if !matches!(command, Command::Generate { .. }) {
+            let (_, text) = json::to_text(&read_input()?, Some(game))?;
+            INPUT.set(text).expect("a single command runs");
+        }
+    }
+    // Unless the solutions are written to a file per puzzle
+    let numbered = numbered_output(&command, options);
+    let archive = matches!(&command, Command::Detect { files } if files.len() > 1 || files.iter().any(|f| f.is_dir()));
+    if let (Some(path), None, false) = (&options.output, &numbered, archive) {
+        let file = File::create(path).with_context(|| path.display().to_string())?;
+        *OUTPUT.lock().expect("output lock") = Some(BufWriter::new(file));
+    }
+    let counted = archive || matches!(command, Command::Batch { .. });
+    let serves = command.serves();
+
+    let dispatch = move || match command {
+        Command::Solve(game) => match options.count {
+            Some(max) => count(game, max, options.json),
+            None => solve(game, options),
+        },
+        Command::Detect { files } if archive => solve_files(&files, options),
+        Command::Detect { files } => {
+            let game = detect(files.into_iter().next(), options.input)?;
+            match options.count {
+                Some(max) => count(game, max, options.json),
+                None => solve(game, options),
+            }
+        },
+        Command::Hint { game } => hint(game),
+        Command::Explain { game } => explain(game),
+        Command::Rate { game } => rate(game, options.json),
+        Command::Minimize { game } => minimize(game),
+        Command::Batch { sequential, game } => batch(game, sequential || options.threads == NonZeroUsize::new(1), numbered, options),
+        Command::Echo { game } => echo(game, options.json),
+        Command::Enumerate { limit, game } => enumerate(game, limit),
+        Command::Diff { count, game } => diff(game, count),
+        Command::Dimacs { game } => dimacs(game),
+        Command::ExportCnf { file, game } => export_cnf(game, file),
+        Command::Check { solution, game } => check(game, solution),
+        Command::Generate { size, difficulty, game } => generate(game, size, difficulty, options.seed),
+        Command::Bench { filter, runs } => bench(filter, runs, options.json),
+        #[cfg(feature = "serve")]
+        Command::Serve { port, host } => cli::serve::serve(&host, port, options),
+    };
+    // Batch mode and archives count their puzzles instead
+    let spinner = (!counted && !serves).then(|| Progress::spinner(options));
+    let result = match &spinner {
+        Some(spinner) => spinner.observe(dispatch),
+        None => dispatch(),
+    };
+    drop(spinner);
+    Output.flush()?;
+    result
+}
+
+/// The name of the files of `--output` in batch mode, with `{}` for the number of the puzzle
+fn numbered_output(command: &Command, options: &Options) -> Option<String> {
+    let path = options.output.as_ref()?.to_str()?;
+    (matches!(command, Command::Batch { .. }) && path.contains("{}")).then(|| path.to_string())
+}
+
+fn solve(game: Game, options: &Options) -> Result<()> {
+    use Game::*;
+    match game {
+        Binero => binero(options),
+        KDoku => kdoku(options),
+        Stars => stars(options),
+        Voisimage { box_drawing } => voisimage(box_drawing, options),
+        _ => bail!(Failure::Unsupported)
+    }
+
+}
+
+/// Find out the game of the puzzle in the file or on stdin, which is then read by
+/// [`read_input`]
+fn detect(file: Option<PathBuf>, format: Input) -> Result<Game> {
+    let input = match &file {
+        Some(path) => std::fs::read_to_string(path).with_context(|| path.display().to_string())?,
+        None => read_input()?,
+    };
+    // JSON puzzles name their game
+    if format == Input::Json {
+        let (game, text) = json::to_text(&input, None)?;
+        INPUT.get_or_init(|| text);
+        return Ok(game)
+    }
+    let mut candidates = detect_games(&input);
+    INPUT.get_or_init(|| input);
+
+    let names: Vec<&str> = candidates.iter().map(|(name, _)| *name).collect();
+    let chosen = match candidates.len() {
+        0 => bail!(Failure::BadInput("not a puzzle of any game that can be solved".to_string())),
+        1 => 0,
+        // Only ask when the answer does not have to be read from the input
+        _ if file.is_some() && stdin().is_terminal() => {
+            eprint!("This could be a puzzle of {}. Which game is it? ", names.join(" or "));
+            let mut answer = String::new();
+            stdin().read_line(&mut answer)?;
+            let answer = answer.trim();
+            names.iter().position(|&name| name == answer)
+                .ok_or_else(|| Failure::BadInput(format!("expected {}, not {:?}", names.join(" or "), answer)))?
+        },
+        _ => bail!(Failure::BadInput(format!("this could be a puzzle of {}, run the command of the game instead", names.join(" or ")))),
+    };
+    let (name, game) = candidates.swap_remove(chosen);
+    tracing::info!(game = name, "detected");
+    Ok(game)
+}
+
+/// The games the input could be a puzzle of, with their names
+fn detect_games(input: &str) -> Vec<(&'static str, Game)> {
+    let mut candidates: Vec<_> = Game::SOLVABLE.into_iter().filter(|(_, game)| game.reads(input)).collect();
+    // Numbers separated by spaces are the areas of Stars, rather than hints between
+    // empty cells
+    let spaced = input.lines().filter(|l| !l.trim().is_empty()).all(|l| l.trim().contains(char::is_whitespace));
+    if spaced && candidates.iter().any(|(_, game)| matches!(game, Game::Stars)) {
+        candidates.retain(|(_, game)| matches!(game, Game::Stars));
+    }
+    candidates
+}
+
+/// What became of a file solved by [`solve_files`]
+struct Report {
+    path: PathBuf,
+    game: &'static str,
+    status: String,
+    /// Counted up to 2
+    solutions: Option<usize>,
+    time: Duration,
+}
+
+fn solve_files(paths: &[PathBuf], options: &Options) -> Result<()> {
+    let mut files = vec![];
+    for path in paths {
+        list_files(path, path.file_name().map(PathBuf::from).unwrap_or_default(), &mut files)?;
+    }
+    let scope = util::scope::Scope::current();
+    let progress = Progress::bar(files.len(), options);
+    let reports: Vec<Report> = files.par_iter()
+        .map(|(file, relative)| {
+            let report = scope.run(|| solve_file(file, relative, options.output.as_deref(), options.input));
+            progress.inc();
+            report
+        })
+        .collect();
+    drop(progress);
+
+    let width = reports.iter().map(|r| r.path.display().to_string().chars().count()).max().unwrap_or(0).max(4);
+    outln!("{:<width$}  {:<9}  {:>9}  {:>9}  status", "file", "game", "solutions", "time");
+    for r in &reports {
+        let solutions = match r.solutions {
+            None => "-".to_string(),
+            Some(n) if n >= 2 => "2+".to_string(),
+            Some(n) => n.to_string(),
+        };
+        outln!("{:<width$}  {:<9}  {:>9}  {:>9}  {}", r.path.display(), r.game, solutions, format!("{:.1?}", r.time), r.status);
+    }
+    let solved = reports.iter().filter(|r| r.status == "solved").count();
+    note!("{} files: {} solved, {} not solved", reports.len(), solved, reports.len() - solved);
+    if solved < reports.len() { bail!(Failure::Conflict) }
+    Ok(())
+}
+
+/// The files under `path`, along with their path relative to the directory given on the
+/// command line. Hidden files and solutions are left out, and so are links to directories
+/// below the top, which could lead back to it.
+fn list_files(path: &std::path::Path, relative: PathBuf, files: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+    if !path.is_dir() {
+        files.push((path.to_path_buf(), relative));
+        return Ok(())
+    }
+    let mut entries = std::fs::read_dir(path)
+        .and_then(|dir| dir.collect::<std::io::Result<Vec<_>>>())
+        .with_context(|| path.display().to_string())?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        let skipped = name.to_str().map_or(true, |n| n.starts_with('.') || n.ends_with(".solution"));
+        let linked_dir = entry.file_type().with_context(|| entry.path().display().to_string())?.is_symlink()
+            && entry.path().is_dir();
+        if linked_dir {
+            note!("{}: skipping a link to a directory", entry.path().display());
+        } else if !skipped {
+            list_files(&entry.path(), relative.join(&name), files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Solve the puzzle of a file, writing its solution next to it, or under `output`
+fn solve_file(file: &std::path::Path, relative: &std::path::Path, output: Option<&std::path::Path>, input: Input) -> Report {
+    let start = Instant::now();
+    let mut report = Report { path: file.to_path_buf(), game: "?", status: String::new(), solutions: None, time: Duration::ZERO };
+    let mut solve = || -> Result<&str> {
+        let text = std::fs::read_to_string(file)?;
+        let (name, game, text) = match input {
+            Input::Json => {
+                let (game, text) = json::to_text(&text, None)?;
+                (json::name(&game).expect("read from JSON"), game, text)
+            },
+            Input::Text => {
+                let mut candidates = detect_games(&text);
+                if candidates.len() != 1 {
+                    let names: Vec<&str> = candidates.iter().map(|(name, _)| *name).collect();
+                    bail!(if names.is_empty() { "unknown game".to_string() } else { format!("could be {}", names.join(" or ")) })
+                }
+                let (name, game) = candidates.remove(0);
+                (name, game, text)
+            },
+        };
+        report.game = name;
+        let (count, first) = tally_solutions(&game, &text, 2)?;
+        report.solutions = Some(count);
+        let Some(solution) = first else { return Ok("unsolvable") };
+
+        let mut target = match output {
+            Some(dir) => dir.join(relative),
+            None => file.to_path_buf(),
+        }.into_os_string();
+        target.push(".solution");
+        let target = PathBuf::from(target);
+        if let Some(parent) = target.parent() { std::fs::create_dir_all(parent)? }
+        std::fs::write(&target, solution).with_context(|| target.display().to_string())?;
+        Ok("solved")
+    };
+    report.status = match solve() {
+        Ok(status) => status.to_string(),
+        Err(e) => format!("{:#}", e),
+    };
+    report.time = start.elapsed();
+    report
+}
+
+fn count(game: Game, max: Option<NonZeroUsize>, json: bool) -> Result<()> {
+    let max = max.map_or(usize::MAX, NonZeroUsize::get);
+    let found = count_solutions(&game, &read_input()?, max)?;
+    let complete = found < max;
+    if json {
+        outln!("{}", json!({ "count": found, "complete": complete }));
+    } else {
+        if !complete { note!("Stopped counting at {}", found) }
+        outln!("{}", found);
+    }
+    Ok(())
+}
+
+/// Count the solutions of a puzzle, stopping at `max`
+fn count_solutions(game: &Game, input: &str, max: usize) -> Result<usize> {
+    Ok(tally_solutions(game, input, max)?.0)
+}
+
+/// Count the solutions of a puzzle, stopping at `max`, along with the first one
+fn tally_solutions(game: &Game, input: &str, max: usize) -> Result<(usize, Option<String>)> {
+    use Game::*;
+    let mut found = 0;
+    let mut first = None;
+    let mut tally = |s: &dyn Display| {
+        first.get_or_insert_with(|| s.to_string());
+        found += 1;
+        if found < max { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+    };
+
+    let _ = match game {
+        Binero => input.parse::<binero::Problem>()?.solve_with(|s| tally(&s))?,
+        Kakuro => input.parse::<kakuro::Problem>()?.solve_with(|s| tally(&s))?,
+        KDoku => kdoku::BaseGrid::new().solve_with(&parse_kdoku(input)?, |s| tally(&s)).map_err(multilogic::Error::from)?,
+        Stars => input.parse::<stars::Problem>()?.solve_with(|s| tally(&s))?,
+        Voisimage { .. } => input.parse::<voisimage::Problem>()?.solve_with(|s| tally(&s))?,
+        _ => bail!(Failure::Unsupported)
+    };
+    Ok((found, first))
+}
+
+fn rate(game: Game, json: bool) -> Result<()> {
+    use Game::*;
+    use generate::Rating;
+    let input = read_input()?;
+    let solutions = count_solutions(&game, &input, 2)?;
+    let rating = match game {
+        Binero => Rating::new(&input.parse::<binero::Problem>()?.deduce(), solutions),
+        KDoku => Rating::new(&kdoku::deduce(&parse_kdoku(&input)?), solutions),
+        Stars => Rating::new(&input.parse::<stars::Problem>()?.deduce(), solutions),
+        Voisimage { .. } => Rating::new(&input.parse::<voisimage::Problem>()?.deduce(), solutions),
+        _ => bail!(Failure::Unsupported)
+    };
+
+    let difficulty = match (rating.difficulty(), rating.solutions) {
+        (Some(difficulty), _) => difficulty.to_string(),
+        (None, 0) => "unsolvable".to_string(),
+        (None, _) => "several solutions".to_string(),
+    };
+    if json {
+        outln!("{}", json!({
+            "difficulty": difficulty,
+            "solutions": rating.solutions,
+            "steps": rating.steps,
+            "remaining": rating.remaining,
+            "cells": rating.cells,
+        }));
+    } else {
+        outln!("difficulty: {}", difficulty);
+        outln!("solutions: {}", if rating.solutions < 2 { rating.solutions.to_string() } else { "2 or more".to_string() });
+        outln!("forced steps: {}", rating.steps);
+        outln!("left to search: {} of {} cells", rating.remaining, rating.cells);
+    }
+    Ok(())
+}
+
+fn hint(game: Game) -> Result<()> {
+    use Game::*;
+    let input = read_input()?;
+    match game {
+        Binero => print_hint(input.parse::<binero::Problem>()?.hints()?, |b| if b { '1' } else { '0' }),
+        KDoku => {
+            let constraints = parse_kdoku(&input)?;
+            let hints = match kdoku::BaseGrid::new().hints(&constraints) {
+                Ok(hints) => Some(hints),
+                Err(kdoku::LogicalError::Unsatisfyable) => None,
+                Err(e) => return Err(multilogic::Error::from(e).into()),
+            };
+            print_hint(hints, |v| v)
+        },
+        Stars => print_hint(input.parse::<stars::Problem>()?.hints()?, |b| if b { '*' } else { '.' }),
+        Voisimage { .. } => print_hint(input.parse::<voisimage::Problem>()?.hints()?, |b| if b { '█' } else { '░' }),
+        _ => bail!(Failure::Unsupported)
+    }
+}
+
+fn print_hint<T, D: Display>(hints: Option<Vec<((usize, usize), T)>>, show: impl Fn(T) -> D) -> Result<()> {
+    let hints = hints.ok_or_else(|| Failure::Unsolved("unsolvable grid".to_string()))?;
+    if let Some(((x, y), v)) = hints.into_iter().next() {
+        outln!("({},{}) {}", x, y, show(v));
+    } else {
+        note!("No forced cell");
+    }
+    Ok(())
+}
+
+fn explain(game: Game) -> Result<()> {
+    use Game::*;
+    let input = read_input()?;
+    match game {
+        Binero => print_steps(input.parse::<binero::Problem>()?.deduce(), |b| if b { '1' } else { '0' })?,
+        KDoku => print_steps(kdoku::deduce(&parse_kdoku(&input)?), |v| v)?,
+        Stars => print_steps(input.parse::<stars::Problem>()?.deduce(), |b| if b { '*' } else { '.' })?,
+        Voisimage { .. } => print_steps(input.parse::<voisimage::Problem>()?.deduce(), |b| if b { '█' } else { '░' })?,
+        _ => bail!(Failure::Unsupported)
+    }
+    Ok(())
+}
+
+fn minimize(game: Game) -> Result<()> {
+    use Game::*;
+    let input = read_input()?;
+    let minimized = match game {
+        Binero => input.parse::<binero::Problem>()?.minimize()?.map(|p| p.to_string()),
+        Voisimage { .. } => input.parse::<voisimage::Problem>()?.minimize()?.map(|p| p.to_string()),
+        _ => bail!(Failure::Unsupported)
+    };
+    out!("{}", minimized.ok_or_else(|| Failure::Unsolved("the grid does not have a unique solution".to_string()))?);
+    Ok(())
+}
+
+fn batch(game: Game, sequential: bool, numbered: Option<String>, options: &Options) -> Result<()> {
+    use Game::*;
+    let input = read_input()?;
+    let puzzles = split_puzzles(&input);
+    let solutions = match game {
+        Binero => show_all(&parse_all::<binero::Problem>(&puzzles)?, sequential, options)?,
+        KDoku => show_all(&puzzles.iter().map(|p| parse_kdoku(p)).collect::<Result<Vec<_>>>()?, sequential, options)?,
+        Stars => show_all(&parse_all::<stars::Problem>(&puzzles)?, sequential, options)?,
+        Voisimage { .. } => show_all(&parse_all::<voisimage::Problem>(&puzzles)?, sequential, options)?,
+        _ => bail!(Failure::Unsupported)
+    };
+
+    for (i, (solution, stats)) in solutions.iter().enumerate() {
+        match (&numbered, solution) {
+            (Some(name), Some(solution)) => {
+                let path = name.replace("{}", &(i + 1).to_string());
+                std::fs::write(&path, solution).with_context(|| path.clone())?;
+            },
+            // The summary tells how many puzzles have no file
+            (Some(_), None) => (),
+            (None, _) => {
+                if i > 0 { outln!() }
+                outln!("{}", solution.as_deref().map_or("No solution", str::trim_end));
+            },
+        }
+        report_stats(options, &format!("Puzzle {}: ", i + 1), stats, None);
+    }
+    let solved = solutions.iter().filter(|(s, _)| s.is_some()).count();
+    note!("{} puzzles: {} solved, {} unsolvable", solutions.len(), solved, solutions.len() - solved);
+    if solved < solutions.len() { bail!(Failure::Conflict) }
+    Ok(())
+}
+
+/// The sample puzzles of the criterion benchmarks, for `bench`: game, name and puzzle,
+/// listed by the build script
+const SAMPLES: &[(&str, &str, &str)] = &include!(concat!(env!("OUT_DIR"), "/samples.rs"));
+
+fn bench(filter: Option<String>, runs: NonZeroUsize, json: bool) -> Result<()> {
+    let samples: Vec<(String, Game, &str)> = SAMPLES.iter()
+        .map(|&(game, name, puzzle)| (format!("{}/{}", game, name), Game::named(game).expect("a sample directory per game"), puzzle))
+        .filter(|(name, ..)| filter.as_ref().map_or(true, |f| name.contains(f.as_str())))
+        .collect();
+    if samples.is_empty() {
+        bail!(Failure::BadInput(format!("no sample matches {:?}", filter.unwrap_or_default())))
+    }
+    let millis = |time: Duration| time.as_secs_f64() * 1000.0;
+
+    let width = samples.iter().map(|(name, ..)| name.len()).max().unwrap_or(0).max(6);
+    if !json {
+        outln!("{:<width$}  {:>9}  {:>9}  {:>9}  {:>9}", "sample", "variables", "clauses", "min", "median");
+    }
+    let (mut rows, mut total) = (vec![], Duration::ZERO);
+    for (name, game, puzzle) in &samples {
+        let mut times = vec![];
+        let mut stats = SolveStats::default();
+        for _ in 0..runs.get() {
+            let start = Instant::now();
+            let (tally, run_stats) = util::stats::with_stats(|| tally_solutions(game, puzzle, 1));
+            times.push(start.elapsed());
+            if tally?.0 == 0 { bail!("the sample {} has no solution", name) }
+            stats = run_stats;
+        }
+        times.sort();
+        let (min, median) = (times[0], times[times.len() / 2]);
+        total += median;
+        if json {
+            rows.push(json!({
+                "sample": name, "variables": stats.variables, "clauses": stats.clauses,
+                "min_ms": millis(min), "median_ms": millis(median),
+            }));
+        } else {
+            outln!("{:<width$}  {:>9}  {:>9}  {:>9}  {:>9}", name, stats.variables, stats.clauses, format!("{:.1?}", min), format!("{:.1?}", median));
+        }
+    }
+    if json {
+        outln!("{}", json!({ "samples": rows, "total_ms": millis(total) }));
+    } else {
+        outln!("{:<width$}  {:>9}  {:>9}  {:>9}  {:>9}", "total", "", "", "", format!("{:.1?}", total));
+    }
+    Ok(())
+}
+
+fn echo(game: Game, json: bool) -> Result<()> {
+    use Game::*;
+    let input = read_input()?;
+    if json {
+        outln!("{}", json::to_json(&game, &input)?);
+        return Ok(())
+    }
+    let puzzle = match game {
+        Binero => input.parse::<binero::Problem>()?.to_string(),
+        Kakuro => input.parse::<kakuro::Problem>()?.to_string(),
+        KDoku => kdoku::parse::format(&parse_kdoku(&input)?),
+        Stars => input.parse::<stars::Problem>()?.to_string(),
+        Voisimage { .. } => input.parse::<voisimage::Problem>()?.to_string(),
+        _ => bail!(Failure::Unsupported)
+    };
+    out!("{}", puzzle);
+    Ok(())
+}
+
+fn enumerate(game: Game, limit: Option<NonZeroUsize>) -> Result<()> {
+    use Game::*;
+    let input = read_input()?;
+    let limit = limit.map_or(usize::MAX, NonZeroUsize::get);
+    let mut found = 0;
+    // A failed write stops the search, and is reported once it returns
+    let mut failed = None;
+    let mut print = |s: &dyn Display| {
+        let written = (|| -> std::io::Result<()> {
+            if found > 0 { outln!() }
+            outln!("{}", s.to_string().trim_end());
+            Ok(())
+        })();
+        if let Err(e) = written {
+            failed = Some(e);
+            return ControlFlow::Break(())
+        }
+        found += 1;
+        if found < limit { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+    };
+
+    // Whether the limit was reached or not, the solutions are already printed
+    let _ = match game {
+        Binero => input.parse::<binero::Problem>()?.solve_with(|s| print(&s))?,
+        KDoku => kdoku::BaseGrid::new().solve_with(&parse_kdoku(&input)?, |s| print(&s)).map_err(multilogic::Error::from)?,
+        Stars => input.parse::<stars::Problem>()?.solve_with(|s| print(&s))?,
+        Voisimage { .. } => input.parse::<voisimage::Problem>()?.solve_with(|s| print(&s))?,
+        _ => bail!(Failure::Unsupported)
+    };
+    if let Some(e) = failed { return Err(e.into()) }
+    note!("{} solutions", found);
+    Ok(())
+}
+
+fn diff(game: Game, count: usize) -> Result<()> {
+    use Game::*;
+    let input = read_input()?;
+    match game {
+        Binero => {
+            let mut session = input.parse::<binero::Problem>()?.session();
+            let grids = find_solutions(count, || {
+                let Some(s) = session.solve(&[])? else { return Ok(None) };
+                session.exclude(&s);
+                Ok(Some(s.grid().clone()))
+            })?;
+            print_diff(&grids, |b| if b { '1' } else { '0' })
+        },
+        KDoku => {
+            let constraints = parse_kdoku(&input)?;
+            let mut session = kdoku::BaseGrid::new().session(&constraints).map_err(multilogic::Error::from)?;
+            let grids = find_solutions(count, || {
+                let s = match session.solve(&[]) {
+                    Ok(s) => s,
+                    Err(kdoku::LogicalError::Unsatisfyable) => return Ok(None),
+                    Err(e) => return Err(multilogic::Error::from(e).into()),
+                };
+                session.exclude(&s);
+                Ok(Some(s.grid()))
+            })?;
+            print_diff(&grids, |v| v)
+        },
+        Stars => {
+            let problem: stars::Problem = input.parse()?;
+            let mut session = problem.session();
+            let grids = find_solutions(count, || {
+                let Some(s) = session.solve(&[])? else { return Ok(None) };
+                session.exclude(&s);
+                Ok(Some(s.stars().clone()))
+            })?;
+            print_diff(&grids, |b| if b { '*' } else { '.' })
+        },
+        Voisimage { .. } => {
+            let mut session = input.parse::<voisimage::Problem>()?.session();
+            let grids = find_solutions(count, || {
+                let Some(s) = session.solve(&[])? else { return Ok(None) };
+                session.exclude(&s);
+                Ok(Some(s.grid().clone()))
+            })?;
+            print_diff(&grids, |b| if b { '█' } else { '░' })
+        },
+        _ => bail!(Failure::Unsupported)
+    }
+}
+
+/// Call `next` until it has given `count` solutions, has no more, or fails
+fn find_solutions<T>(count: usize, mut next: impl FnMut() -> Result<Option<T>>) -> Result<Vec<T>> {
+    std::iter::from_fn(|| next().transpose()).take(count).collect()
+}
+
+fn print_diff<T: Clone + PartialEq, D: Display>(grids: &[util::matrix::Matrix<T>], show: impl Fn(T) -> D) -> Result<()> {
+    let grids: Vec<_> = grids.iter().collect();
+    let agreement = util::matrix::Matrix::agreement(&grids).map_err(|_| Failure::Unsolved("unsolvable grid".to_string()))?;
+    note!("{} solutions", grids.len());
+    for line in agreement.lines() {
+        let line: String = line.iter()
+            .map(|cell| cell.clone().map_or_else(|| "?".to_string(), |v| show(v).to_string()))
+            .collect();
+        outln!("{}", line);
+    }
+    Ok(())
+}
+
+fn dimacs(game: Game) -> Result<()> {
+    let (formula, cells) = encode(&game, &read_input()?)?;
+    let mut out = BufWriter::new(Output);
+    for cell in cells {
+        writeln!(out, "c {}", cell)?;
+    }
+    varisat::dimacs::write_dimacs(&mut out, &formula)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn export_cnf(game: Game, output: PathBuf) -> Result<()> {
+    let (formula, cells) = encode(&game, &read_input()?)?;
+    let mut map = output.clone().into_os_string();
+    map.push(".map");
+    let map = PathBuf::from(map);
+
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&output).with_context(|| output.display().to_string())?);
+    varisat::dimacs::write_dimacs(&mut out, &formula)?;
+    out.flush()?;
+    let cells: String = cells.iter().map(|c| format!("{}\n", c)).collect();
+    std::fs::write(&map, cells).with_context(|| map.display().to_string())?;
+    note!("{} variables, {} clauses written to {}, cells to {}", formula.var_count(), formula.len(), output.display(), map.display());
+    Ok(())
+}
+
+/// The formula of a puzzle, and a description of the variable of every cell
+fn encode(game: &Game, input: &str) -> Result<(varisat::CnfFormula, Vec<String>)> {
+    use Game::*;
+    Ok(match game {
+        Binero => bool_cells(input.parse::<binero::Problem>()?.encode()),
+        KDoku => {
+            let (formula, vars) = kdoku::BaseGrid::new().encode(&parse_kdoku(input)?).map_err(multilogic::Error::from)?;
+            let cells = itertools::iproduct!(0..6, 0..6, 0..6)
+                .map(|(x, y, z)| format!("({},{})={} {}", x, y, z + 1, vars[x][y][z].to_dimacs()))
+                .collect();
+            (formula, cells)
+        },
+        Stars => bool_cells(input.parse::<stars::Problem>()?.encode()),
+        Voisimage { .. } => bool_cells(input.parse::<voisimage::Problem>()?.encode()),
+        _ => bail!(Failure::Unsupported)
+    })
+}
+
+fn bool_cells((formula, grid): (varisat::CnfFormula, util::matrix::Matrix<varisat::Var>)) -> (varisat::CnfFormula, Vec<String>) {
+    let cells = grid.indices()
+        .map(|(x, y)| format!("({},{}) {}", x, y, grid[(x,y)].to_dimacs()))
+        .collect();
+    (formula, cells)
+}
+
+/// Cut the input into puzzles at every empty line, and every line reading `---`
+fn split_puzzles(input: &str) -> Vec<String> {
+    let mut puzzles = vec![];
+    let mut current = String::new();
+    for line in input.lines().chain([""]) {
+        if !line.is_empty() && line.trim() != "---" {
+            current.push_str(line);
+            current.push('\n');
+        } else if !current.is_empty() {
+            puzzles.push(std::mem::take(&mut current));
+        }
+    }
+    puzzles
+}
+
+fn parse_all<P>(puzzles: &[String]) -> Result<Vec<P>>
+    where P: FromStr, P::Err: Into<anyhow::Error>
+{
+    puzzles.iter().enumerate()
+        .map(|(i, p)| p.parse().map_err(Into::into).with_context(|| format!("puzzle {}", i + 1)))
+        .collect()
+}
+
+/// The solutions of the puzzles as text, failing on the first puzzle that could not be solved
+fn show_all<P: batch::Solve>(problems: &[P], sequential: bool, options: &Options) -> Result<Vec<(Option<String>, SolveStats)>>
+    where for<'a> P::Solution<'a>: Display, P::Error: Into<anyhow::Error>
+{
+    let progress = Progress::bar(problems.len(), options);
+    let solutions = if sequential {
+        problems.iter().map(|p| {
+            let solved = util::stats::with_stats(|| p.solve());
+            progress.inc();
+            solved
+        }).collect()
+    } else {
+        batch::solve_all_with_progress(problems, |_| progress.inc())
+    };
+    solutions.into_iter()
+        .enumerate()
+        .map(|(i, (s, stats))| match s {
+            Ok(s) => Ok((s.map(|s| s.to_string()), stats)),
+            Err(e) => Err(e.into().context(format!("puzzle {}", i + 1))),
+        })
+        .collect()
+}
+
+/// Print the statistics asked for in the options on stderr, after `prefix`
+fn report_stats(options: &Options, prefix: &str, stats: &SolveStats, total: Option<Duration>) {
+    let total = total.map(|t| format!(", total {:.1?}", t)).unwrap_or_default();
+    if options.stats {
+        eprintln!("{}{}{}", prefix, stats, total);
+    } else if options.time {
+        eprintln!("{}encoding {:.1?}, solving {:.1?}{}", prefix, stats.encoding, stats.solving, total);
+    }
+}
+
+fn check(game: Game, path: Option<PathBuf>) -> Result<()> {
+    let (input, solution) = match path {
+        Some(path) => (read_input()?, std::fs::read_to_string(&path).with_context(|| path.display().to_string())?),
+        None => match &split_puzzles(&read_input()?)[..] {
+            [input, solution] => (input.clone(), solution.clone()),
+            sections => bail!(Failure::BadInput(format!("expected the puzzle and the solution separated by an empty line, found {} sections", sections.len()))),
+        },
+    };
+    let violations = verify(&game, &input, &solution)?;
+    if !violations.is_empty() {
+        for v in &violations {
+            outln!("{}", v);
+        }
+        bail!(Failure::Unsolved(format!("invalid solution, {} broken rules", violations.len())));
+    }
+    outln!("Valid solution");
+    Ok(())
+}
+
+/// The rules broken by a solution of the puzzle, none if it is valid
+fn verify(game: &Game, input: &str, solution: &str) -> Result<Vec<String>> {
+    use Game::*;
+    fn messages<V: Display>(verdict: Result<(), Vec<V>>) -> Vec<String> {
+        verdict.err().unwrap_or_default().iter().map(|v| v.to_string()).collect()
+    }
+    Ok(match game {
+        Binero => messages(input.parse::<binero::Problem>()?.verify(&solution.parse()?)),
+        KDoku => messages(kdoku::verify(&parse_kdoku(input)?, &solution.parse()?)),
+        Stars => {
+            let problem: stars::Problem = input.parse()?;
+            messages(problem.verify(&problem.parse_solution(solution)?))
+        },
+        Voisimage { .. } => messages(input.parse::<voisimage::Problem>()?.verify(&solution.parse()?)),
+        _ => bail!(Failure::Unsupported)
+    })
+}
+
+fn generate(game: Game, size: usize, difficulty: generate::Difficulty, seed: Option<u64>) -> Result<()> {
+    let seed = seed.unwrap_or_else(|| {
+        let seed = rand::random();
+        note!("seed: {}", seed);
+        seed
+    });
+    out!("{}", new_puzzle(&game, size, difficulty, seed, read_input)?);
+    Ok(())
+}
+
+/// A new puzzle with a unique solution, in the input format. Voisimages are made from
+/// the picture given by `picture`.
+fn new_puzzle(game: &Game, size: usize, difficulty: generate::Difficulty, seed: u64, picture: impl FnOnce() -> Result<String>) -> Result<String> {
+    use Game::*;
+    let puzzle = match game {
+        Binero => {
+            if size == 0 { bail!(Failure::BadInput("binero grids need a size of at least 2".to_string())) }
+            if size % 2 != 0 { bail!(Failure::BadInput("binero grids must have an even size".to_string())) }
+            generate::generate::<binero::Problem>(&size, difficulty, seed)?.map(|p| p.to_string())
+        },
+        KDoku => Some(kdoku::parse::format(&kdoku::generate::generate(difficulty, seed)?)),
+        Kakuro => {
+            if size < 3 { bail!(Failure::BadInput("kakuro grids need a size of at least 3".to_string())) }
+            Some(kakuro::generate(size, seed)?.to_string())
+        },
+        Stars => stars::generate(size, seed)?.map(|p| p.to_string()),
+        Voisimage { .. } => {
+            let picture: voisimage::Solution = picture()?.parse()?;
+            let puzzle: Option<voisimage::Problem> = generate::generate(picture.grid(), difficulty, seed)?;
+            puzzle.map(|p| p.to_string())
+        },
+        _ => bail!(Failure::Unsupported)
+    };
+    Ok(puzzle.ok_or_else(|| Failure::Unsolved("no puzzle with a unique solution".to_string()))?)
+}
+
+fn print_steps<T, D: Display>(deduction: util::deduce::Deduction<T>, show: impl Fn(T) -> D) -> Result<()> {
+    let left = deduction.grid.iter().filter(|c| c.is_none()).count();
+    for step in deduction.steps {
+        outln!("{} {}: {}", step.cell, show(step.value), step.reason);
+    }
+    if left > 0 {
+        note!("No further simple deduction, {} cells left", left);
+    }
+    Ok(())
+}
+
+/// Explain why a grid has no solution, by listing a minimal set of clues
+/// that cannot be satisfied together.
+fn report_conflict<D: Display>(what: &str, clues: impl IntoIterator<Item=D>, json: bool) -> Result<()> {
+    if json {
+        outln!("{}", json_conflict(clues));
+        return Ok(())
+    }
+    eprintln!("Unsolvable grid, these {} cannot be satisfied together:", what);
+    for clue in clues {
+        eprintln!("  {}", clue);
+    }
+    Ok(())
+}
+
+/// A solution as JSON, as a list of rows
+fn json_solution<T: Clone + Into<serde_json::Value>>(grid: &util::matrix::Matrix<T>) -> serde_json::Value {
+    let rows: Vec<Vec<serde_json::Value>> = grid.lines()
+        .map(|line| line.iter().cloned().map(Into::into).collect())
+        .collect();
+    json!({ "solution": rows })
+}
+
+/// A grid without solution as JSON, with clues that cannot be satisfied together
+fn json_conflict<D: Display>(clues: impl IntoIterator<Item=D>) -> serde_json::Value {
+    let clues: Vec<String> = clues.into_iter().map(|c| c.to_string()).collect();
+    json!({ "solution": null, "conflict": clues })
+}
+
+/// Print a solution in the format chosen in the options. `grid` is the solution printed
+/// as JSON, `puzzle` and `solution` are the scenes drawn by the other formats, and
+/// `text` prints the solution as text.
+fn print_solution<T: Clone + Into<serde_json::Value>>(
+    options: &Options,
+    grid: &util::matrix::Matrix<T>,
+    puzzle: impl FnOnce() -> render::Scene,
+    solution: impl FnOnce() -> render::Scene,
+    text: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    if options.json {
+        outln!("{}", json_solution(grid));
+        return Ok(())
+    }
+    let renderer: &dyn render::Renderer = match options.format {
+        // The format of every game, which `check` reads back
+        Format::Text if !options.puzzle => return text(),
+        Format::Text if options.color.enabled(Output::is_terminal()) => &render::Ansi,
+        Format::Text => &render::Text,
+        Format::Svg => &render::Svg,
+        Format::Latex => &render::Tikz,
+        Format::Html => &render::Html,
+    };
+    let scene = if options.puzzle { puzzle() } else { solution() };
+    let mut drawing = String::new();
+    renderer.render(&scene, &mut drawing)?;
+    out!("{}", drawing);
+    Ok(())
+}
+
+/// Print the scene in color to the output if colors are enabled for it, or else the
+/// solution in the format of its game
+fn print_colored(options: &Options, scene: impl FnOnce() -> render::Scene, text: impl Display) -> Result<()> {
+    if options.color.enabled(Output::is_terminal()) {
+        out!("{}", render::Ansi.display(&scene()));
+    } else {
+        out!("{}", text);
+    }
+    Ok(())
+}
+
+fn read_input() -> Result<String> {
+    if let Some(input) = INPUT.get() { return Ok(input.clone()) }
+    // Rather than waiting silently for input that was meant to be piped
+    if let (true, Some(prompt)) = (stdin().is_terminal(), PROMPT.get()) {
+        note!("{}", prompt);
+    }
     let mut buf = vec![];
-    stdin().lock().read_to_end(&mut buf)?;
-    let p = std::str::from_utf8(&buf)?;
-    if let Some(s) = p.parse::<Problem>()?.solve() {
-        println!("{}", s);
+    // Not counting the time spent typing
+    suspended(|| stdin().lock().read_to_end(&mut buf))?;
+    if let Some(bar) = &*PROGRESS.lock().expect("progress lock") { bar.reset_elapsed() }
+    let input = String::from_utf8(buf)?;
+    tracing::info!(bytes = input.len(), lines = input.lines().count(), "read input");
+    Ok(input)
+}
+
+fn parse_kdoku(input: &str) -> Result<Vec<kdoku::Constraint>> {
+    Ok(kdoku::parse::constraints(input)?)
+}
+
+fn binero(options: &Options) -> Result<()> {
+    use binero::*;
+    let p = read_input()?;
+    let problem: Problem = p.parse()?;
+    let solution = match options.seed {
+        Some(seed) => problem.solve_random(seed)?,
+        None => problem.solve()?,
+    };
+    if let Some(s) = solution {
+        print_solution(options, &s.grid().map(|&b| b as u8), || problem.scene(), || s.scene(), || { outln!("{}", s); Ok(()) })?;
     } else {
-        eprintln!("No solution");
+        report_conflict("givens", binero_conflict(&problem)?, options.json)?;
+        bail!(Failure::Conflict)
     }
     Ok(())
 }
 
-fn kdoku() -> Result<()> {
+/// The givens of a Binero that cannot be satisfied together
+fn binero_conflict(problem: &binero::Problem) -> Result<Vec<String>> {
+    let givens = problem.conflict()?.unwrap_or_default();
+    Ok(givens.iter().map(|((x, y), b)| format!("({},{}) {}", x, y, *b as u8)).collect())
+}
+
+fn kdoku(options: &Options) -> Result<()> {
     use kdoku::*;
-    let constraints: Vec<kdoku::Constraint> = stdin()
-        .lines()
-        .map(|l| l.unwrap())
-        .filter(|l| l.trim() != "")
-        .map(|l| kdoku::parse::constraint(&l).expect("parse error").1)
-        .collect();
+    let constraints = parse_kdoku(&read_input()?)?;
 
-    let grid = BaseGrid::new();
-    let solution = grid.solve(&constraints[..]).expect("unsolvable");
-    println!("{}", solution);
+    let solution = match options.seed {
+        Some(seed) => BaseGrid::new().solve_random(&constraints, seed),
+        None => BaseGrid::new().solve(&constraints),
+    };
+    match solution {
+        Ok(solution) => print_solution(options, &solution.grid(), || scene(&constraints), || solution.scene(&constraints), || { outln!("{}", solution); Ok(()) })?,
+        Err(LogicalError::Unsatisfyable) => {
+            let cages = BaseGrid::new().conflict(&constraints)
+                .map_err(multilogic::Error::from)?
+                .unwrap_or_default();
+            report_conflict("areas", cages, options.json)?;
+            bail!(Failure::Conflict)
+        },
+        Err(e) => return Err(multilogic::Error::from(e).into()),
+    }
     Ok(())
 }
 
-fn stars() -> Result<()> {
+fn stars(options: &Options) -> Result<()> {
     use stars::*;
-    let mut buf = vec![];
-    stdin().lock().read_to_end(&mut buf)?;
-    let buf = std::str::from_utf8(&buf)?;
+    let buf = read_input()?;
 
     let problem: Problem = buf.parse()?;
-    if let Some(s) = problem.solve() {
-        let w = BufferWriter::stdout(termcolor::ColorChoice::Auto);
-        s.color_fmt(w)?;
+    let solution = match options.seed {
+        Some(seed) => problem.solve_random(seed)?,
+        None => problem.solve()?,
+    };
+    if let Some(s) = solution {
+        print_solution(options, s.stars(), || problem.scene(), || s.scene(), || print_colored(options, || s.scene(), &s))?;
     } else {
-        eprintln!("Unsolvable grid");
+        let regions = problem.conflict()?.unwrap_or_default();
+        report_conflict("colors", regions, options.json)?;
+        bail!(Failure::Conflict)
     }
     Ok(())
 
 }
 
-fn voisimage(unicode: bool) -> Result<()> {
+/// The hints of a Voisimage that cannot be satisfied together
+fn voisimage_conflict(problem: &voisimage::Problem) -> Result<Vec<String>> {
+    let hints = problem.conflict()?.unwrap_or_default();
+    Ok(hints.iter().map(|(x, y)| format!("({},{})", x, y)).collect())
+}
+
+fn voisimage(unicode: bool, options: &Options) -> Result<()> {
     use voisimage::*;
-    let mut buf = vec![];
-    stdin().lock().read_to_end(&mut buf)?;
-    let buf = std::str::from_utf8(&buf)?;
+    let buf = read_input()?;
 
     let problem: Problem = buf.parse()?;
 
-    let solution = problem.solve()
-       .ok_or_else(|| anyhow!("unsolvable grid"))?;
+    let solution = match options.seed {
+        Some(seed) => problem.solve_random(seed)?,
+        None => problem.solve()?,
+    };
+    let Some(solution) = solution else {
+        report_conflict("hints", voisimage_conflict(&problem)?, options.json)?;
+        bail!(Failure::Conflict)
+    };
 
-    if unicode {
-        println!("{}", solution);
-    } else {
-        let w = BufferWriter::stdout(termcolor::ColorChoice::Auto);
-        color::Pretty(&problem, &solution).color_fmt(w)?;
-    }
+    print_solution(options, solution.grid(), || problem.scene(), || solution.scene(&problem), || {
+        if unicode {
+            outln!("{}", solution);
+        } else {
+            print_colored(options, || solution.scene(&problem), &solution)?;
+        }
         Ok(())
+    })
 
 }
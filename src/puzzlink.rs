@@ -0,0 +1,147 @@
+//! Puzzles shared as puzz.link or pzv.jp URLs, such as
+//! `https://puzz.link/p?starbattle/6/6/1/...`, which is where most players find them.
+//! After the `?`, the URL names the type of puzzle, the width and height of the grid,
+//! and encodes the board, mostly as bits packed five to a character in base 32.
+//!
+//! Only the types of puzzles matching a game of this crate can be decoded: Star Battle
+//! so far.
+
+use thiserror::Error;
+
+use crate::stars;
+use crate::util::matrix::{umat, Matrix};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UrlError {
+    #[error("not a puzz.link URL, expected e.g. https://puzz.link/p?starbattle/6/6/1/...")]
+    NotPuzzLink,
+    #[error("this is a {found} URL, expected {expected}")]
+    WrongType { expected: &'static str, found: String },
+    #[error("missing or invalid {0}")]
+    Field(&'static str),
+    #[error("invalid character {0:?} in the board")]
+    Character(char),
+    #[error("the board is cut short")]
+    Truncated,
+    #[error("{0}")]
+    Board(String),
+}
+
+/// The fields of a URL after the `?`, separated by slashes, the first one naming the
+/// type of puzzle
+fn fields(url: &str) -> Result<Vec<&str>, UrlError> {
+    let (_, query) = url.trim().split_once('?').ok_or(UrlError::NotPuzzLink)?;
+    let fields: Vec<&str> = query.split('/').collect();
+    if fields.len() < 3 { return Err(UrlError::NotPuzzLink) }
+    Ok(fields)
+}
+
+/// The largest side of a grid read from a URL, far above any puzzle meant for people
+const MAX_SIDE: usize = 100;
+
+fn number(field: Option<&&str>, name: &'static str) -> Result<usize, UrlError> {
+    field.and_then(|f| f.parse().ok()).ok_or(UrlError::Field(name))
+}
+
+/// The walls of a grid, decoded as in `decodeBorder` of pzprjs. Walls between two cells
+/// of the same row come first, row by row, then those between two rows, again row by
+/// row. Returns the walls on the right of every cell and below every cell.
+fn borders(data: &str, (h, w): (usize, usize)) -> Result<(Matrix<bool>, Matrix<bool>), UrlError> {
+    let too_large = || UrlError::Board(format!("the grid is too large, {}x{}", h, w));
+    let vertical = h.checked_mul(w - 1).ok_or_else(too_large)?;
+    let total = (h - 1).checked_mul(w).and_then(|t| t.checked_add(vertical)).ok_or_else(too_large)?;
+    // Five bits per character, checked before allocating for all of them
+    if data.len().saturating_mul(5) < total { return Err(UrlError::Truncated) }
+
+    let mut bits = Vec::with_capacity(total);
+    for c in data.chars() {
+        if bits.len() >= total { break }
+        let value = c.to_digit(32).ok_or(UrlError::Character(c))?;
+        bits.extend((0..5).rev().map(|b| value >> b & 1 == 1));
+    }
+    if bits.len() < total { return Err(UrlError::Truncated) }
+
+    let right = Matrix::from_fn((h, w), |x, y| y + 1 < w && bits[x * (w - 1) + y]);
+    let below = Matrix::from_fn((h, w), |x, y| x + 1 < h && bits[vertical + x * w + y]);
+    Ok((right, below))
+}
+
+/// Number the areas enclosed by the walls, in the order their first cell appears
+fn areas(right: &Matrix<bool>, below: &Matrix<bool>) -> Matrix<usize> {
+    let (h, w) = right.shape();
+    let mut areas: Matrix<Option<usize>> = umat![None; (h, w)];
+    let mut count = 0;
+    for start in right.indices() {
+        if areas[start].is_some() { continue }
+        areas[start] = Some(count);
+        let mut stack = vec![start];
+        while let Some((x, y)) = stack.pop() {
+            let mut open = vec![];
+            if x > 0 && !below[(x - 1, y)] { open.push((x - 1, y)) }
+            if x + 1 < h && !below[(x, y)] { open.push((x + 1, y)) }
+            if y > 0 && !right[(x, y - 1)] { open.push((x, y - 1)) }
+            if y + 1 < w && !right[(x, y)] { open.push((x, y + 1)) }
+            for next in open {
+                if areas[next].is_none() {
+                    areas[next] = Some(count);
+                    stack.push(next);
+                }
+            }
+        }
+        count += 1;
+    }
+    areas.map(|a| a.expect("every cell is reached"))
+}
+
+/// Decode a Star Battle URL, `starbattle/width/height/stars/walls`
+pub fn stars(url: &str) -> Result<stars::Problem, UrlError> {
+    let fields = fields(url)?;
+    if fields[0] != "starbattle" {
+        return Err(UrlError::WrongType { expected: "starbattle", found: fields[0].to_string() })
+    }
+    let w = number(fields.get(1), "width")?;
+    let h = number(fields.get(2), "height")?;
+    let stars = number(fields.get(3), "number of stars")?;
+    if w == 0 || w != h { return Err(UrlError::Board(format!("the grid is {}x{}, not square", h, w))) }
+    if w > MAX_SIDE { return Err(UrlError::Board(format!("the grid has side {}, at most {} is supported", w, MAX_SIDE))) }
+    if stars == 0 { return Err(UrlError::Field("number of stars")) }
+
+    let (right, below) = borders(fields.get(4).ok_or(UrlError::Truncated)?, (h, w))?;
+    let areas = areas(&right, &below);
+    let count = areas.iter().max().map_or(0, |&m| m + 1);
+    if count != h {
+        return Err(UrlError::Board(format!("{} regions in a grid of side {}", count, h)))
+    }
+    Ok(stars::Problem::new(areas, stars))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::matrix::mat;
+
+    #[test]
+    fn star_battle() {
+        // Regions of a 4x4 grid: columns 0-1 of rows 0-1, columns 2-3 of rows 0-1,
+        // row 2 and row 3. Walls on the right, row by row: 010 010 000 000, then
+        // below, row by row: 0000 1111 1111. Packed: 01001 00000 00000 11111 1111(0)
+        let problem = stars("https://puzz.link/p?starbattle/4/4/1/900vu").unwrap();
+        assert_eq!(problem.regions, mat![0, 0, 1, 1; 0, 0, 1, 1; 2, 2, 2, 2; 3, 3, 3, 3]);
+        assert_eq!(problem.stars, 1);
+
+        assert_eq!(stars("https://puzz.link/p?nurikabe/4/4/abc").unwrap_err(), UrlError::WrongType { expected: "starbattle", found: "nurikabe".into() });
+        assert_eq!(stars("https://puzz.link/p?starbattle/4/4/1/99").unwrap_err(), UrlError::Truncated);
+        assert_eq!(stars("https://puzz.link/p?starbattle/4/4/1/900v!").unwrap_err(), UrlError::Character('!'));
+        assert!(matches!(stars("https://puzz.link/p?starbattle/4/4/1/00000"), Err(UrlError::Board(_))));
+        assert_eq!(stars("puzz.link").unwrap_err(), UrlError::NotPuzzLink);
+    }
+
+    #[test]
+    fn large() {
+        // Rejected before allocating anything for the grid
+        assert!(matches!(stars("https://puzz.link/p?starbattle/100000/100000/1/x"), Err(UrlError::Board(_))));
+        assert_eq!(borders("x", (100000, 100000)).unwrap_err(), UrlError::Truncated);
+        assert!(matches!(borders("x", (usize::MAX, usize::MAX)), Err(UrlError::Board(_))));
+        assert!(matches!(borders("x", (3, usize::MAX / 2)), Err(UrlError::Board(_))));
+    }
+}
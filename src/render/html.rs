@@ -0,0 +1,80 @@
+//! HTML tables, to include in web pages. Unlike an SVG drawing, the labels stay text
+//! that can be selected and searched. Loops are left out, as a table cannot draw them.
+
+use std::fmt::{Result, Write};
+
+use super::{Fill, Renderer, Scene, PALETTE};
+
+/// Side of a cell, in pixels
+const CELL: usize = 40;
+
+/// Renders a scene as a `table` element, styled inline so that it needs no stylesheet
+#[derive(Clone, Copy, Debug)]
+pub struct Html;
+
+/// Escape the characters with a meaning in HTML
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl Renderer for Html {
+    fn render(&self, scene: &Scene, f: &mut dyn Write) -> Result {
+        let (h, w) = scene.shape();
+        // Thick walls between cells of different areas
+        let wall = |a: (usize, usize), b: (usize, usize)| scene.areas.as_ref().is_some_and(|areas| areas[a] != areas[b]);
+        let border = |thick: bool| if thick { "3px solid black" } else { "1px solid #999999" };
+
+        writeln!(f, r#"<table style="border-collapse: collapse; border: 3px solid black; font-family: sans-serif">"#)?;
+        for x in 0..h {
+            writeln!(f, "<tr>")?;
+            for y in 0..w {
+                let cell = &scene.cells[(x, y)];
+                write!(f, r#"<td style="position: relative; width: {CELL}px; height: {CELL}px; padding: 0; text-align: center; font-size: {}px"#, CELL * 6 / 10)?;
+                write!(f, "; border-right: {}", border(y + 1 < w && wall((x, y), (x, y + 1))))?;
+                write!(f, "; border-bottom: {}", border(x + 1 < h && wall((x, y), (x + 1, y))))?;
+                match cell.fill {
+                    Fill::Blank => (),
+                    Fill::Shaded => write!(f, "; background: #222222; color: white")?,
+                    Fill::Color(i) => write!(f, "; background: {}", PALETTE[i % PALETTE.len()])?,
+                }
+                f.write_str(r#"">"#)?;
+                for (_, note) in scene.notes.iter().filter(|(c, _)| (c.x, c.y) == (x, y)) {
+                    write!(f, r#"<span style="position: absolute; top: 1px; left: 3px; font-size: {}px">{}</span>"#, CELL * 28 / 100, escape(note))?;
+                }
+                if cell.star { f.write_str("&#9733;")? }
+                if let Some(label) = &cell.label { f.write_str(&escape(label))? }
+                writeln!(f, "</td>")?;
+            }
+            writeln!(f, "</tr>")?;
+        }
+        writeln!(f, "</table>")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{render::Cell, util::{coord::Coord, matrix::mat}};
+
+    #[test]
+    fn table() {
+        let mut scene = Scene::new((2, 3));
+        scene.cells[(0, 1)] = Cell { fill: Fill::Shaded, label: Some("<1>".to_string()), star: false };
+        scene.cells[(1, 0)].fill = Fill::Color(1);
+        scene.cells[(1, 2)].star = true;
+        scene.areas = Some(mat![0, 0, 1; 0, 1, 1]);
+        scene.notes.push((Coord::new(0, 0), "6+".to_string()));
+
+        let html = Html.display(&scene).to_string();
+        assert!(html.starts_with("<table"));
+        assert!(html.trim_end().ends_with("</table>"));
+        assert_eq!(html.matches("<tr>").count(), 2);
+        assert_eq!(html.matches("<td").count(), 6);
+        assert!(html.contains("; background: #222222; color: white\">&lt;1&gt;</td>"));
+        assert!(html.contains("; background: #a6c8f4\"></td>"));
+        assert!(html.contains(">6+</span></td>"));
+        assert!(html.contains("&#9733;</td>"));
+        // The wall between (0, 1) and (0, 2), and the one below (0, 1)
+        assert!(html.contains("border-right: 3px solid black; border-bottom: 3px solid black; background: #222222"));
+    }
+}
@@ -0,0 +1,143 @@
+//! Drawing solved grids. Every game describes its solution as a [`Scene`]: what each cell
+//! holds (a fill, a label, a star), which areas the grid is split into, and the loops
+//! running through it. The output formats are [`Renderer`]s that only know about scenes,
+//! so that a new format works for every game at once, and a new game for every format.
+
+pub mod html;
+pub mod svg;
+pub mod text;
+pub mod tikz;
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::util::{coord::Coord, matrix::Matrix};
+
+pub use html::Html;
+pub use svg::Svg;
+#[cfg(feature = "color")]
+pub use text::Ansi;
+pub use text::Text;
+pub use tikz::Tikz;
+
+/// An output format for scenes
+pub trait Renderer {
+    /// Write the whole scene in this format
+    fn render(&self, scene: &Scene, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    /// The scene in this format, to print with `{}`
+    fn display<'a>(&'a self, scene: &'a Scene) -> Rendered<'a, Self> where Self: Sized {
+        Rendered(self, scene)
+    }
+}
+
+/// A scene displayed with a renderer, see [`Renderer::display`]
+#[derive(Debug)]
+pub struct Rendered<'a, R>(&'a R, &'a Scene);
+
+impl<R: Renderer> Display for Rendered<'_, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.render(self.1, f)
+    }
+}
+
+/// How the background of a cell is painted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fill {
+    #[default]
+    Blank,
+    /// Painted black, the label being drawn in white
+    Shaded,
+    /// A light color, taken from [`PALETTE`] by index
+    Color(usize),
+}
+
+/// Light background colors, distinct enough to tell neighboring areas apart
+pub const PALETTE: [&str; 8] = [
+    "#f4a6a6", "#a6c8f4", "#b5e6a2", "#f4e3a1", "#dbb0f0", "#a6ece6", "#f6c79a", "#cfcfcf",
+];
+
+/// What is drawn in a cell
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cell {
+    pub fill: Fill,
+    /// Centered in the cell, such as a digit
+    pub label: Option<String>,
+    pub star: bool,
+}
+
+/// A grid ready to be drawn
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scene {
+    pub cells: Matrix<Cell>,
+    /// The area of every cell, if the grid is split into areas. Thick walls are drawn
+    /// between cells of different areas.
+    pub areas: Option<Matrix<usize>>,
+    /// Small labels in the top-left corner of some cells, such as the clues of cages
+    pub notes: Vec<(Coord, String)>,
+    /// Closed paths going through the centers of the listed cells, in order
+    pub loops: Vec<Vec<Coord>>,
+}
+
+impl Scene {
+    /// A grid of blank cells
+    pub fn new(shape: (usize, usize)) -> Self {
+        Scene { cells: Matrix::from_fn(shape, |_, _| Cell::default()), areas: None, notes: vec![], loops: vec![] }
+    }
+
+    /// A grid of blank cells, each with a label
+    pub fn labeled<T: ToString>(labels: &Matrix<T>) -> Self {
+        let mut scene = Scene::new(labels.shape());
+        for (cell, label) in scene.cells.iter_mut().zip(labels) {
+            cell.label = Some(label.to_string());
+        }
+        scene
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.cells.shape()
+    }
+
+    /// The walls between cells of different areas, as segments between grid corners.
+    /// Corner `(x, y)` is the top-left corner of cell `(x, y)`. The outline of the
+    /// grid is not included.
+    pub fn walls(&self) -> Vec<((usize, usize), (usize, usize))> {
+        let Some(areas) = &self.areas else { return vec![] };
+        let (h, w) = areas.shape();
+        let mut walls = vec![];
+        for ((x, y), a) in areas.enumerate() {
+            if y + 1 < w && areas[(x, y + 1)] != *a {
+                walls.push(((x, y + 1), (x + 1, y + 1)));
+            }
+            if x + 1 < h && areas[(x + 1, y)] != *a {
+                walls.push(((x + 1, y), (x + 1, y + 1)));
+            }
+        }
+        walls
+    }
+}
+
+/// The ten corners of a five-pointed star pointing up, given its center and the radii
+/// of its outer and inner corners
+fn star(center: (f64, f64), outer: f64, inner: f64) -> Vec<(f64, f64)> {
+    (0..10).map(|i| {
+        let r = if i % 2 == 0 { outer } else { inner };
+        let angle = (i as f64 * 36.0 - 90.0).to_radians();
+        (center.0 + r * angle.cos(), center.1 + r * angle.sin())
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::matrix::mat;
+
+    #[test]
+    fn walls() {
+        let mut scene = Scene::labeled(&mat![1, 2; 3, 4]);
+        assert_eq!(scene.cells[(1, 0)].label.as_deref(), Some("3"));
+        assert!(scene.walls().is_empty());
+
+        scene.areas = Some(mat![0, 0; 1, 0]);
+        assert_eq!(scene.walls(), [((1, 0), (1, 1)), ((1, 1), (2, 1))]);
+    }
+}
@@ -0,0 +1,130 @@
+//! Scalable vector drawings, to embed in web pages or to print
+
+use std::fmt::{Result, Write};
+
+use super::{Fill, Renderer, Scene, PALETTE};
+
+/// Side of a cell, in pixels
+const CELL: f64 = 40.0;
+
+/// Blank space around the grid, leaving room for the outline
+const MARGIN: f64 = 4.0;
+
+/// Renders a scene as a standalone SVG document
+#[derive(Clone, Copy, Debug)]
+pub struct Svg;
+
+/// Pixel position of a grid corner
+fn corner(x: usize, y: usize) -> (f64, f64) {
+    (MARGIN + y as f64 * CELL, MARGIN + x as f64 * CELL)
+}
+
+/// Pixel position of the center of a cell
+fn center(x: usize, y: usize) -> (f64, f64) {
+    let (left, top) = corner(x, y);
+    (left + CELL / 2.0, top + CELL / 2.0)
+}
+
+/// Escape the characters with a meaning in XML
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// The points of a five-pointed star in a cell
+fn star(x: usize, y: usize) -> String {
+    let points: Vec<String> = super::star(center(x, y), 0.4 * CELL, 0.16 * CELL).iter()
+        .map(|(px, py)| format!("{:.1},{:.1}", px, py))
+        .collect();
+    points.join(" ")
+}
+
+impl Renderer for Svg {
+    fn render(&self, scene: &Scene, f: &mut dyn Write) -> Result {
+        let (h, w) = scene.shape();
+        let (width, height) = (w as f64 * CELL + 2.0 * MARGIN, h as f64 * CELL + 2.0 * MARGIN);
+        writeln!(f, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#)?;
+
+        for ((x, y), cell) in scene.cells.enumerate() {
+            let color = match cell.fill {
+                Fill::Blank => continue,
+                Fill::Shaded => "#222222",
+                Fill::Color(i) => PALETTE[i % PALETTE.len()],
+            };
+            let (left, top) = corner(x, y);
+            writeln!(f, r#"<rect x="{left}" y="{top}" width="{CELL}" height="{CELL}" fill="{color}"/>"#)?;
+        }
+
+        // The thin lines between every cell
+        let mut grid = String::new();
+        for x in 1..h {
+            let (left, top) = corner(x, 0);
+            write!(grid, "M{left} {top}h{} ", w as f64 * CELL)?;
+        }
+        for y in 1..w {
+            let (left, top) = corner(0, y);
+            write!(grid, "M{left} {top}v{} ", h as f64 * CELL)?;
+        }
+        writeln!(f, r##"<path d="{}" stroke="#999999" stroke-width="1" fill="none"/>"##, grid.trim_end())?;
+
+        let walls = scene.walls();
+        if !walls.is_empty() {
+            let mut d = String::new();
+            for ((x0, y0), (x1, y1)) in walls {
+                let ((l0, t0), (l1, t1)) = (corner(x0, y0), corner(x1, y1));
+                write!(d, "M{l0} {t0}L{l1} {t1} ")?;
+            }
+            writeln!(f, r#"<path d="{}" stroke="black" stroke-width="3" stroke-linecap="square" fill="none"/>"#, d.trim_end())?;
+        }
+        let (left, top) = corner(0, 0);
+        writeln!(f, r#"<rect x="{left}" y="{top}" width="{}" height="{}" stroke="black" stroke-width="3" fill="none"/>"#, w as f64 * CELL, h as f64 * CELL)?;
+
+        for path in &scene.loops {
+            let points: Vec<String> = path.iter()
+                .map(|c| { let (cx, cy) = center(c.x, c.y); format!("{cx},{cy}") })
+                .collect();
+            writeln!(f, r##"<polygon points="{}" stroke="#1f5fbf" stroke-width="4" stroke-linejoin="round" fill="none"/>"##, points.join(" "))?;
+        }
+
+        for ((x, y), cell) in scene.cells.enumerate() {
+            if cell.star {
+                writeln!(f, r#"<polygon points="{}" fill="black"/>"#, star(x, y))?;
+            }
+            if let Some(label) = &cell.label {
+                let (cx, cy) = center(x, y);
+                let color = if cell.fill == Fill::Shaded { "white" } else { "black" };
+                writeln!(f, r#"<text x="{cx}" y="{cy}" font-family="sans-serif" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{color}">{}</text>"#, CELL * 0.6, escape(label))?;
+            }
+        }
+
+        for (c, note) in &scene.notes {
+            let (left, top) = corner(c.x, c.y);
+            writeln!(f, r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}">{}</text>"#, left + 3.0, top + 11.0, CELL * 0.28, escape(note))?;
+        }
+
+        writeln!(f, "</svg>")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{render::Cell, util::{coord::Coord, matrix::mat}};
+
+    #[test]
+    fn document() {
+        let mut scene = Scene::new((2, 3));
+        scene.cells[(0, 1)] = Cell { fill: Fill::Shaded, label: Some("<1>".to_string()), star: false };
+        scene.cells[(1, 2)].star = true;
+        scene.areas = Some(mat![0, 0, 1; 0, 1, 1]);
+        scene.notes.push((Coord::new(0, 0), "6+".to_string()));
+
+        let svg = Svg.display(&scene).to_string();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="88""#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(r##"<rect x="44" y="4" width="40" height="40" fill="#222222"/>"##));
+        assert!(svg.contains(r#"fill="white">&lt;1&gt;</text>"#));
+        assert!(svg.contains(">6+</text>"));
+        assert_eq!(svg.matches("<polygon").count(), 1);
+        assert!(svg.contains("M84 4L84 44 M44 44L84 44 M44 44L44 84"));
+    }
+}
@@ -0,0 +1,112 @@
+//! Scenes as text, a cell per character or per column of labels, for terminals. Only
+//! the cells are shown: areas can be told apart by their fills with [`Ansi`], but notes
+//! and loops are left out.
+
+use std::fmt::{Result, Write};
+
+#[cfg(feature = "color")]
+use termcolor::{Buffer, Color, ColorSpec, WriteColor};
+
+use super::{Cell, Fill, Renderer, Scene};
+#[cfg(feature = "color")]
+use super::PALETTE;
+
+/// Renders a scene as plain text: the label of every cell, or `*` for a star, `#` for a
+/// shaded cell and `.` for a blank one. Cells are separated by spaces when some labels
+/// are wider than a character.
+#[derive(Clone, Copy, Debug)]
+pub struct Text;
+
+/// Renders a scene like [`Text`], with the fills painted as background colors with
+/// ANSI escape codes
+#[cfg(feature = "color")]
+#[derive(Clone, Copy, Debug)]
+pub struct Ansi;
+
+fn symbol(cell: &Cell) -> &str {
+    match (&cell.label, cell.star, cell.fill) {
+        (Some(label), _, _) => label,
+        (None, true, _) => "*",
+        (None, false, Fill::Shaded) => "#",
+        _ => ".",
+    }
+}
+
+/// Write the cells row by row, each one padded to the same width by `cell`
+fn rows(scene: &Scene, f: &mut dyn Write, mut cell: impl FnMut(&mut dyn Write, &Cell, &str) -> Result) -> Result {
+    let width = scene.cells.iter().map(|c| symbol(c).chars().count()).max().unwrap_or(1);
+    for line in scene.cells.lines() {
+        for (y, c) in line.iter().enumerate() {
+            if y > 0 && width > 1 { f.write_char(' ')? }
+            cell(f, c, &format!("{:>width$}", symbol(c)))?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+impl Renderer for Text {
+    fn render(&self, scene: &Scene, f: &mut dyn Write) -> Result {
+        rows(scene, f, |f, _, symbol| f.write_str(symbol))
+    }
+}
+
+/// The colors of a fill: white on black for shaded cells, black on the palette for
+/// colored ones
+#[cfg(feature = "color")]
+fn paint(fill: Fill) -> Option<ColorSpec> {
+    let mut spec = ColorSpec::new();
+    match fill {
+        Fill::Blank => return None,
+        // Bright white, as black is already the background
+        Fill::Shaded => spec.set_fg(Some(Color::Ansi256(15))).set_bg(Some(Color::Black)),
+        Fill::Color(i) => {
+            let hex = PALETTE[i % PALETTE.len()];
+            let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).expect("palette colors are #rrggbb");
+            spec.set_fg(Some(Color::Black)).set_bg(Some(Color::Rgb(component(1), component(3), component(5))))
+        },
+    };
+    Some(spec)
+}
+
+/// The symbol between the escape codes of `spec`
+#[cfg(feature = "color")]
+fn painted(spec: &ColorSpec, symbol: &str) -> std::io::Result<String> {
+    let mut buffer = Buffer::ansi();
+    buffer.set_color(spec)?;
+    std::io::Write::write_all(&mut buffer, symbol.as_bytes())?;
+    buffer.reset()?;
+    Ok(String::from_utf8(buffer.into_inner()).expect("escape codes around a string"))
+}
+
+#[cfg(feature = "color")]
+impl Renderer for Ansi {
+    fn render(&self, scene: &Scene, f: &mut dyn Write) -> Result {
+        rows(scene, f, |f, cell, symbol| {
+            match paint(cell.fill) {
+                Some(spec) => f.write_str(&painted(&spec, symbol).map_err(|_| std::fmt::Error)?),
+                None => f.write_str(symbol),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grids() {
+        let mut scene = Scene::new((2, 3));
+        scene.cells[(0, 1)].fill = Fill::Shaded;
+        scene.cells[(1, 0)].fill = Fill::Color(1);
+        scene.cells[(1, 2)].star = true;
+        assert_eq!(Text.display(&scene).to_string(), ".#.\n..*\n");
+        #[cfg(feature = "color")]
+        assert_eq!(Ansi.display(&scene).to_string(),
+            ".\x1b[0m\x1b[38;5;15m\x1b[40m#\x1b[0m.\n\x1b[0m\x1b[30m\x1b[48;2;166;200;244m.\x1b[0m.*\n");
+
+        scene.cells[(0, 0)].label = Some("12".to_string());
+        assert_eq!(Text.display(&scene).to_string(), "12  #  .\n .  .  *\n");
+    }
+}
@@ -0,0 +1,106 @@
+//! TikZ pictures, to typeset puzzles and their solutions in LaTeX documents. The
+//! output is a `tikzpicture` environment, to include in a document loading the `tikz`
+//! package, one unit being the side of a cell.
+
+use std::fmt::{Result, Write};
+
+use super::{Fill, Renderer, Scene, PALETTE};
+
+/// Renders a scene as a `tikzpicture` environment
+#[derive(Clone, Copy, Debug)]
+pub struct Tikz;
+
+/// A color of the palette, in the syntax of the `xcolor` package
+fn color(hex: &str) -> String {
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).expect("palette colors are #rrggbb");
+    format!("{{rgb,255:red,{};green,{};blue,{}}}", component(1), component(3), component(5))
+}
+
+/// Escape the characters with a meaning in LaTeX
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => { escaped.push('\\'); escaped.push(c) },
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Renderer for Tikz {
+    fn render(&self, scene: &Scene, f: &mut dyn Write) -> Result {
+        let (h, w) = scene.shape();
+        // Rows grow downwards, as on screen
+        writeln!(f, "\\begin{{tikzpicture}}[x=1cm, y=-1cm]")?;
+
+        for ((x, y), cell) in scene.cells.enumerate() {
+            let fill = match cell.fill {
+                Fill::Blank => continue,
+                Fill::Shaded => "black!85".to_string(),
+                Fill::Color(i) => color(PALETTE[i % PALETTE.len()]),
+            };
+            writeln!(f, "  \\fill[fill={}] ({},{}) rectangle +(1,1);", fill, y, x)?;
+        }
+
+        writeln!(f, "  \\draw[gray!60, thin] (0,0) grid ({},{});", w, h)?;
+        for ((x0, y0), (x1, y1)) in scene.walls() {
+            writeln!(f, "  \\draw[line width=1.5pt, line cap=rect] ({},{}) -- ({},{});", y0, x0, y1, x1)?;
+        }
+        writeln!(f, "  \\draw[line width=1.5pt] (0,0) rectangle ({},{});", w, h)?;
+
+        for path in &scene.loops {
+            let points: Vec<String> = path.iter().map(|c| format!("({}.5,{}.5)", c.y, c.x)).collect();
+            writeln!(f, "  \\draw[blue!70!black, line width=2pt, line join=round] {} -- cycle;", points.join(" -- "))?;
+        }
+
+        for ((x, y), cell) in scene.cells.enumerate() {
+            if cell.star {
+                let points: Vec<String> = super::star((y as f64 + 0.5, x as f64 + 0.5), 0.4, 0.16).iter()
+                    .map(|(px, py)| format!("({:.3},{:.3})", px, py))
+                    .collect();
+                writeln!(f, "  \\fill {} -- cycle;", points.join(" -- "))?;
+            }
+            if let Some(label) = &cell.label {
+                let color = if cell.fill == Fill::Shaded { "white" } else { "black" };
+                writeln!(f, "  \\node[text={}, font=\\Large] at ({}.5,{}.5) {{{}}};", color, y, x, escape(label))?;
+            }
+        }
+
+        for (c, note) in &scene.notes {
+            writeln!(f, "  \\node[anchor=north west, inner sep=2pt, font=\\scriptsize] at ({},{}) {{{}}};", c.y, c.x, escape(note))?;
+        }
+
+        writeln!(f, "\\end{{tikzpicture}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{render::Cell, util::{coord::Coord, matrix::mat}};
+
+    #[test]
+    fn picture() {
+        let mut scene = Scene::new((2, 3));
+        scene.cells[(0, 1)] = Cell { fill: Fill::Shaded, label: Some("1%".to_string()), star: false };
+        scene.cells[(1, 0)].fill = Fill::Color(1);
+        scene.cells[(1, 2)].star = true;
+        scene.areas = Some(mat![0, 0, 1; 0, 1, 1]);
+        scene.notes.push((Coord::new(0, 0), "6+".to_string()));
+
+        let tikz = Tikz.display(&scene).to_string();
+        assert!(tikz.starts_with("\\begin{tikzpicture}"));
+        assert!(tikz.trim_end().ends_with("\\end{tikzpicture}"));
+        assert!(tikz.contains("\\fill[fill=black!85] (1,0) rectangle +(1,1);"));
+        assert!(tikz.contains("\\fill[fill={rgb,255:red,166;green,200;blue,244}] (0,1) rectangle +(1,1);"));
+        assert!(tikz.contains("\\draw[gray!60, thin] (0,0) grid (3,2);"));
+        assert!(tikz.contains("line cap=rect] (2,0) -- (2,1);"));
+        assert!(tikz.contains("\\node[text=white, font=\\Large] at (1.5,0.5) {1\\%};"));
+        assert!(tikz.contains("at (0,0) {6+};"));
+        assert_eq!(tikz.matches("-- cycle;").count(), 1);
+    }
+}
@@ -1,94 +1,557 @@
-use std::{str::FromStr, fmt::{Display, Write}, num::ParseIntError};
+use std::{str::FromStr, fmt::{Display, Write}, num::ParseIntError, ops::ControlFlow};
 
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use thiserror::Error;
-use varisat::{Solver, ExtendFormula};
-
-use crate::util::{matrix::{Matrix, ShapeError}, pair};
-
-pub struct Problem(pub Matrix<usize>);
+use tracing::{debug_span, info};
+use varisat::{CnfFormula, ExtendFormula, Lit, Var};
+
+use crate::util::{backend::{record, BackendError}, gridsolver::{GridSolver, GridModel}, coord::{Coord, Neighborhood}, matrix::{Matrix, ShapeError, umat}, region::Region, card::{CardFormula, Encoding}, integer::IntFormula, solve::{DnfFormula, backbone, minimal_core, random_model, Models, SolveOptions}, deduce::{deduce, lines, Deduction, Step}, gridparse::{parse_grid, GridError}, symmetry::{Symmetry, SymmetryFormula, same_regions}, propagate::{confine, count, propagate, Contradiction, Domains}};
+use crate::render::{Fill, Scene};
+use crate::transform::Transform;
+use crate::verify::{Violation, verdict};
+
+/// The colors of the cells, and the number of stars in every row, column and region
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Problem {
+    /// The color of every cell, from 0 to the size of the grid
+    pub regions: Matrix<usize>,
+    pub stars: usize,
+}
 
 pub struct Solution<'p> {
     problem: &'p Problem,
     solution: Matrix<bool>,
 }
 
+/// A Stars grid encoded into a [`GridSolver`], for hypotheses about the stars in some cells
+pub struct Session<'p> {
+    problem: &'p Problem,
+    solver: GridSolver,
+    grid: Matrix<Var>,
+    /// For each color, the literal enabling the constraints of its region
+    regions: Vec<Lit>,
+}
 
 impl Problem {
+    /// The grid with the given colors, and `stars` stars per unit
+    pub fn new(regions: Matrix<usize>, stars: usize) -> Self {
+        Problem { regions, stars }
+    }
 
     pub fn size(&self) -> usize {
-        self.0.shape().0
+        self.regions.shape().0
+    }
+
+    /// The number of stars in every row, column and region
+    pub fn stars(&self) -> usize {
+        self.stars
     }
 
-    pub fn colors(&self) -> Vec<Vec<(usize,usize)>> {
-        let mut r = vec![ vec![]; self.size() ];
+    /// The cells of every color, in row-major order
+    pub fn colors(&self) -> Vec<Region> {
+        let mut r = vec![ Region::default(); self.size() ];
 
-        for (x,y) in self.0.indices() {
-            r[self.0[x][y]].push((x,y));
+        for c in self.regions.coords() {
+            r[self.regions[c]].0.push(c);
         };
 
         r
     }
 
-    pub fn solve(&self) -> Option<Solution> {
+    /// The grid to draw, with the regions colored and outlined
+    pub fn scene(&self) -> Scene {
+        let mut scene = Scene::new(self.regions.shape());
+        for (cell, &color) in scene.cells.iter_mut().zip(&self.regions) {
+            cell.fill = Fill::Color(color);
+        }
+        scene.areas = Some(self.regions.clone());
+        scene
+    }
+
+    /// The same grid with the colors renamed, color `c` becoming `colors[c]`
+    pub fn relabel(&self, colors: &[usize]) -> Problem {
+        Problem::new(self.regions.map(|&c| colors[c]), self.stars)
+    }
+
+    pub fn solve(&self) -> Result<Option<Solution<'_>>, BackendError> {
+        self.session().solve(&[])
+    }
+
+    /// Find a solution drawn at random from `seed`, for grids with several solutions.
+    /// See [`random_model`].
+    pub fn solve_random(&self, seed: u64) -> Result<Option<Solution<'_>>, BackendError> {
+        self.session().solve_random(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Count the star placements, by enumerating them.
+    /// With `break_symmetry`, symmetric placements only count once.
+    pub fn count_solutions(&self, options: SolveOptions) -> Result<usize, BackendError> {
+        self.session_with(options).count()
+    }
+
+    /// Find a star placement that differs from `prior`.
+    /// Returns `None` if `prior` is the only solution of the grid.
+    pub fn solve_excluding(&self, prior: &Solution) -> Result<Option<Solution<'_>>, BackendError> {
+        let mut session = self.session();
+        session.exclude(prior);
+        session.solve(&[])
+    }
+
+    /// Call `f` with every placement of the stars, see [`Session::solve_with`].
+    pub fn solve_with<B>(&self, f: impl FnMut(Solution<'_>) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        self.session().solve_with(f)
+    }
+
+    /// List the cells whose content (star or not) is the same in every solution,
+    /// in row-major order. Returns `None` if the grid has no solution.
+    pub fn hints(&self) -> Result<Option<Vec<((usize, usize), bool)>>, BackendError> {
+        let mut session = self.session();
+        let Some(s) = session.solve(&[])? else { return Ok(None) };
+
+        let candidates = self.regions.indices()
+            .map(|(x,y)| {
+                let star = s.solution[(x,y)];
+                let lit = session.grid[(x,y)].positive();
+                (((x,y), star), if star { lit } else { !lit })
+            })
+            .collect();
+
+        Ok(Some(backbone(&mut session.solver, &session.regions, candidates)?))
+    }
 
-        let size = self.0.shape().0;
-        let mut solver = Solver::new();
-        let cells = solver.new_var_iter(self.0.len()).map(|v| v.positive()).collect();
-        let grid = Matrix::new(cells, self.0.shape()).unwrap();
+    /// When the grid has no solution, find a minimal set of colored regions that already
+    /// contradict each other. Returns `None` if the grid has a solution.
+    pub fn conflict(&self) -> Result<Option<Vec<usize>>, BackendError> {
+        let mut session = self.session();
+        let Some(core) = minimal_core(&mut session.solver, &session.regions)? else { return Ok(None) };
 
-        // lines
-        for line in grid.lines() {
-            // At least one star per line)
-            solver.add_clause(line);
+        Ok(Some((0..session.regions.len())
+            .filter(|&c| core.contains(&session.regions[c]))
+            .collect()))
+    }
 
-            // Never two stars in same line
-            for (x,y) in pair(0..size) {
-                solver.add_clause(&[!line[x], !line[y]])
+    /// Place the stars using only simple human rules, recording every step:
+    ///  - no star next to a star, or in the same row, column or region
+    ///  - a row, column or region with a single free cell left holds its star there
+    ///  - when the free cells of a row, column or region all lie within another one,
+    ///    the star of the latter must be among them
+    ///  - a cell cannot hold a star if that would leave no room for the star of another unit
+    ///
+    /// These rules only hold with one star per unit, no deduction is made with more.
+    pub fn deduce(&self) -> Deduction<bool> {
+        if self.stars > 1 { return deduce(umat![None; self.regions.shape()], &[]) }
+
+        let units = self.units();
+        let exclusion = |g: &Matrix<Option<bool>>| exclusion(&units, g);
+        let last_cell = |g: &Matrix<Option<bool>>| last_cell(&units, g);
+        let confinement = |g: &Matrix<Option<bool>>| confinement(&units, g);
+        let crowding = |g: &Matrix<Option<bool>>| crowding(&units, g);
+        deduce(umat![None; self.regions.shape()], &[&exclusion, &last_cell, &confinement, &crowding])
+    }
+
+    /// Read a star placement for this grid, with `*` for stars and `.` elsewhere,
+    /// one row per line, as printed by the solver. Terminal color codes and whitespace
+    /// within lines are ignored.
+    pub fn parse_solution(&self, s: &str) -> Result<Solution<'_>, ParseError> {
+        let mut plain = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => { chars.by_ref().find(|&c| c == 'm'); },
+                '\n' => plain.push(c),
+                c if c.is_whitespace() => {},
+                c => plain.push(c),
             }
         }
 
-        // at least one star per column
-        for idx in 0..size {
-            let column: Vec<_> = (0..size).map(|x| grid[x][idx]).collect();
-            solver.add_clause(&column)
+        let solution = parse_grid(&plain, |c| match c {
+            '*' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        })?;
+        if solution.shape() != self.regions.shape() { return Err(ShapeError.into()) }
+        Ok(Solution { problem: self, solution })
+    }
+
+    /// Check `solution` against the rules directly, without the SAT solver.
+    /// Lists every broken rule if it is not valid.
+    pub fn verify(&self, solution: &Solution) -> Result<(), Vec<Violation>> {
+        let stars = &solution.solution;
+        let mut violations = vec![];
+
+        for (name, cells) in self.units() {
+            let found: Vec<_> = cells.iter().copied().filter(|&c| stars[c]).collect();
+            match found.len() {
+                n if n == self.stars => {},
+                0 => violations.push(Violation::new(format!("{} has no star", name), vec![])),
+                1 => violations.push(Violation::new(format!("{} has 1 star", name), found)),
+                n => violations.push(Violation::new(format!("{} has {} stars", name, n), found)),
+            }
         }
 
-        // Never two stars in the same column
-        for (a,b) in pair(0..size) {
-            for (&x,&y) in grid[a].iter().zip(grid[b].iter()) {
-                solver.add_clause(&[!x, !y])
+        for c in stars.coords().filter(|&c| stars[c]) {
+            for n in stars.neighbors(c).into_iter().filter(|&n| n > c) {
+                if stars[n] {
+                    violations.push(Violation::new("stars touch", vec![c, n]));
+                }
             }
         }
 
-        // colors
-        for cells in self.colors() {
-            let cells: Vec<_> = cells.iter().map(|(x, y)| grid[*x][*y]).collect();
+        verdict(violations)
+    }
+
+    /// Rows, columns and regions, with their names
+    fn units(&self) -> Vec<(String, Vec<Coord>)> {
+        let mut units = lines(self.regions.shape());
+        for (i, Region(cells)) in self.colors().into_iter().enumerate() {
+            units.push((format!("region {}", i), cells));
+        }
+        units
+    }
+
+    /// The formula of this grid, to inspect or export it without solving, along with
+    /// the variable of each cell, true for a star. The regions are unit clauses.
+    pub fn encode(&self) -> (CnfFormula, Matrix<Var>) {
+        let (mut formula, (_, grid, regions)) = record(|| self.encode_solver());
+        for selector in regions {
+            formula.add_clause(&[selector]);
+        }
+        (formula, grid)
+    }
 
-            // At least one star per color
-            solver.add_clause(&cells);
+    /// Encode the grid into a [`Session`].
+    pub fn session(&self) -> Session<'_> {
+        self.session_with(SolveOptions::default())
+    }
 
-            // Never two stars in the same color
-            for (x,y) in pair(0..cells.len()) {
-                solver.add_clause(&[!cells[x], !cells[y]])
+    /// Encode the grid into a [`Session`], with the given options.
+    pub fn session_with(&self, options: SolveOptions) -> Session<'_> {
+        let (mut solver, grid, regions) = self.encode_solver();
+
+        if options.break_symmetry {
+            for symmetry in self.symmetries() {
+                let lits = grid.map(|v| v.positive());
+                solver.add_lex_leader(&lits, &symmetry.image(&lits));
             }
         }
 
-        // proximity for diagonals
-        for x in 0..size-1 {
-            for y in 0..size-1 {
-                solver.add_clause(&[!grid[x][y], !grid[x+1][y+1]]);
-                solver.add_clause(&[!grid[x][y+1], !grid[x+1][y]]);
+        if options.propagate {
+            match self.propagate() {
+                Ok(forced) => for c in grid.coords() {
+                    if let Some(star) = forced[c] {
+                        solver.add_clause(&[grid[c].lit(star)]);
+                    }
+                },
+                Err(_) => solver.add_clause(&[]),
+            }
+        }
+
+        Session { problem: self, solver, grid, regions }
+    }
+
+    /// The cells forced by counting the stars of every row, column and region, by
+    /// confining the stars of a unit to the part of it inside another one, and by
+    /// clearing the neighbors of every star
+    fn propagate(&self) -> Result<Matrix<Option<bool>>, Contradiction> {
+        let mut domains = Domains::new(&self.regions.map(|_| None), &[false, true]);
+        let units = self.units();
+        let rules = |d: &mut Domains<bool>| {
+            let mut changed = units.iter().fold(false, |changed, (_, cells)| count(d, cells, &true, self.stars) | changed);
+            for ((_, inner), (_, outer)) in units.iter().tuple_combinations() {
+                changed |= confine(d, inner, outer, &true) | confine(d, outer, inner, &true);
+            }
+            let stars: Vec<_> = self.regions.coords().filter(|&c| d.value(c) == Some(&true)).collect();
+            for c in stars {
+                for n in self.regions.neighborhood(c, &Neighborhood::KING) {
+                    changed |= d.remove(n, &true);
+                }
+            }
+            changed
+        };
+        propagate(&mut domains, &[&rules])?;
+        Ok(domains.fixed())
+    }
+
+    /// The symmetries of the grid mapping every region onto a region
+    fn symmetries(&self) -> Vec<Symmetry> {
+        Symmetry::of_shape(self.regions.shape()).into_iter()
+            .filter(|s| same_regions(&self.regions, &s.image(&self.regions)))
+            .collect()
+    }
+
+    fn encode_solver(&self) -> (GridSolver, Matrix<Var>, Vec<Lit>) {
+        let _span = debug_span!("encode constraints", size = self.size()).entered();
+
+        let size = self.regions.shape().0;
+        let mut solver = GridSolver::new();
+        let vars = solver.bool_grid(self.regions.shape());
+        let grid = vars.map(|v| v.positive());
+
+        // Exactly k stars per line and per column
+        for line in grid.lines().chain(grid.transpose().lines()) {
+            exactly_k(&mut *solver, line, self.stars);
+        }
+
+        // Exactly k stars per color, each enabled by a selector literal
+        let mut regions = vec![];
+        for Region(cells) in self.colors() {
+            let selector = solver.new_lit();
+            let cells: Vec<_> = cells.iter().map(|&c| grid[c]).collect();
+            solver.add_implied(selector, |f| exactly_k(f, &cells, self.stars));
+            regions.push(selector);
+        }
+
+        // Stars never touch, not even diagonally. With one star per line, stars
+        // in the same row or column are already ruled out.
+        for x in 0..size {
+            for y in 0..size {
+                let mut neighbors = vec![(x+1, y+1)];
+                if y > 0 { neighbors.push((x+1, y-1)) }
+                if self.stars > 1 { neighbors.extend([(x, y+1), (x+1, y)]) }
+                for (i, j) in neighbors.into_iter().filter(|&(i, j)| i < size && j < size) {
+                    solver.add_clause(&[!grid[(x,y)], !grid[i][j]]);
+                }
             }
         }
 
-        solver.solve().expect("solver failure");
+        info!(size, stars = self.stars, clauses = solver.clauses(), "encoded");
+        (solver, vars, regions)
+    }
+
+}
+
+/// Regions grown from scratch by [`generate`] before it gives up
+const MAX_ATTEMPTS: usize = 100;
+
+/// Generate a grid of size `n` with a unique star placement. The same seed always
+/// gives the same grid. Returns `None` if no star placement fits (`n` < 4), or if
+/// no grid was found after `MAX_ATTEMPTS` attempts.
+///
+/// Stars are placed at random, and every region grows from one of them by taking
+/// random adjacent cells. While another placement fits, a cell holding one of its stars
+/// is handed over to a neighboring region, as long as its own region stays connected.
+pub fn generate(n: usize, seed: u64) -> Result<Option<Problem>, BackendError> {
+    if n < 4 { return Ok(None) }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let Some(stars) = placement(n, &mut rng) else { return Ok(None) };
+
+    for _ in 0..MAX_ATTEMPTS {
+        let mut regions = grow(&stars, &mut rng);
+
+        for _ in 0..n*n {
+            let problem = Problem::new(regions.clone(), 1);
+            let mut session = problem.session();
+            session.exclude(&Solution { problem: &problem, solution: stars.clone() });
+            let Some(other) = session.solve(&[])? else { return Ok(Some(problem)) };
+
+            let grid = &regions;
+            let moves: Vec<_> = regions.coords()
+                .filter(|&c| other.solution[c] && !stars[c])
+                .filter(|&c| connected_without(grid, c))
+                .flat_map(|c| grid.adjacent(c).into_iter()
+                    .map(|n| grid[n])
+                    .filter(move |&r| r != grid[c])
+                    .map(move |r| (c, r)))
+                .collect();
+
+            let Some(&(c, r)) = moves.choose(&mut rng) else { break };
+            regions[c] = r;
+        }
+    }
+    Ok(None)
+}
+
+/// Exactly `k` of the `lits` are true: a single one with the at-most-one encodings,
+/// more with a sequential counter
+fn exactly_k<F: ExtendFormula>(f: &mut F, lits: &[Lit], k: usize) {
+    if k == 1 {
+        f.add_exactly_one(lits)
+    } else {
+        f.exactly_k(lits, k, Encoding::Sequential)
+    }
+}
+
+/// Place one star per row and per column, never touching each other
+fn placement(n: usize, rng: &mut StdRng) -> Option<Matrix<bool>> {
+    fn place(columns: &mut Vec<usize>, n: usize, rng: &mut StdRng) -> bool {
+        if columns.len() == n { return true }
+
+        let mut free: Vec<_> = (0..n)
+            .filter(|c| !columns.contains(c))
+            .filter(|&c| columns.last().is_none_or(|&l| c.abs_diff(l) > 1))
+            .collect();
+        free.shuffle(rng);
+
+        for c in free {
+            columns.push(c);
+            if place(columns, n, rng) { return true }
+            columns.pop();
+        }
+        false
+    }
+
+    let mut columns = vec![];
+    if !place(&mut columns, n, rng) { return None }
+
+    let mut stars = umat![false; (n, n)];
+    for (x, y) in columns.into_iter().enumerate() {
+        stars[(x,y)] = true;
+    }
+    Some(stars)
+}
+
+/// Grow one region around each star, numbered by row, until they cover the grid
+fn grow(stars: &Matrix<bool>, rng: &mut StdRng) -> Matrix<usize> {
+    let mut regions = stars.map(|_| None);
+    for (i, (x,y)) in stars.indices().filter(|&(x,y)| stars[(x,y)]).enumerate() {
+        regions[(x,y)] = Some(i);
+    }
 
-        let m = solver.model()?;
-        let solution = grid.map(|cell| m.contains(cell));
+    loop {
+        let frontier: Vec<_> = regions.indices()
+            .filter(|&(x,y)| regions[(x,y)].is_none())
+            .flat_map(|c| regions.adjacent(c).into_iter()
+                .filter_map(|n| regions[n])
+                .map(move |r| (c, r)))
+            .collect();
 
-        Some(Solution{ problem: self, solution })
+        let Some(&((x,y), r)) = frontier.choose(rng) else { break };
+        regions[(x,y)] = Some(r);
+    }
 
+    regions.map(|r| r.expect("regions cover the grid"))
+}
+
+/// True if the region of `cell` remains connected once `cell` is removed from it
+fn connected_without(regions: &Matrix<usize>, cell: Coord) -> bool {
+    let color = regions[cell];
+    Region(regions.coords()
+        .filter(|&c| regions[c] == color && c != cell)
+        .collect()).is_connected()
+}
+
+impl<'p> Session<'p> {
+    /// Solve the grid, assuming that every listed cell contains a star
+    /// or not, according to the associated boolean. A cell out of the grid
+    /// cannot hold anything, and makes the grid unsatisfiable.
+    pub fn solve(&mut self, assumptions: &[((usize, usize), bool)]) -> Result<Option<Solution<'p>>, BackendError> {
+        let mut lits: Vec<_> = self.regions.clone();
+        for &((x,y), star) in assumptions {
+            let Some(var) = self.grid.get(x, y) else { return Ok(None) };
+            lits.push(var.lit(star));
+        }
+
+        Ok(self.solver.solve(&lits)?.map(|m| self.decode(&m)))
+    }
+
+    /// Solve the grid, drawing a solution at random. See [`random_model`].
+    pub fn solve_random(&mut self, rng: &mut impl Rng) -> Result<Option<Solution<'p>>, BackendError> {
+        let vars: Vec<_> = self.grid.iter().copied().collect();
+        let m = random_model(&mut self.solver, &self.regions, &vars, rng)?;
+        Ok(m.map(|m| self.decode(&m.into())))
+    }
+
+    /// Count the solutions, excluding each of them once found. See [`Models`].
+    pub fn count(&mut self) -> Result<usize, BackendError> {
+        let vars: Vec<_> = self.grid.iter().copied().collect();
+        Models::new(&mut self.solver, vars).assuming(&self.regions).try_count()
+    }
+
+    fn decode(&self, model: &GridModel) -> Solution<'p> {
+        Solution { problem: self.problem, solution: model.bools(&self.grid) }
+    }
+
+    /// Call `f` with every placement of the stars left, see [sessions](crate::util::solve#sessions).
+    pub fn solve_with<B>(&mut self, mut f: impl FnMut(Solution<'p>) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        while let Some(s) = self.solve(&[])? {
+            self.exclude(&s);
+            if let ControlFlow::Break(b) = f(s) { return Ok(ControlFlow::Break(b)) }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Rule out the placement `prior` from now on.
+    pub fn exclude(&mut self, prior: &Solution) {
+        // Every solution has the same number of stars, so it is enough
+        // to require that one of the prior stars is missing.
+        let blocking: Vec<_> = self.grid.indices()
+            .filter(|&(x,y)| prior.solution[(x,y)])
+            .map(|(x,y)| self.grid[(x,y)].negative())
+            .collect();
+        self.solver.add_clause(&blocking);
+    }
+}
+
+type Units = [(String, Vec<Coord>)];
+
+fn exclusion(units: &Units, grid: &Matrix<Option<bool>>) -> Option<Step<bool>> {
+    for star in grid.coords().filter(|&c| grid[c] == Some(true)) {
+        if let Some(cell) = grid.neighbors(star).into_iter().find(|&c| grid[c].is_none()) {
+            return Some(Step { cell, value: false, reason: format!("next to the star at {}", star) })
+        }
+
+        for (name, cells) in units.iter().filter(|(_, cells)| cells.contains(&star)) {
+            if let Some(&cell) = cells.iter().find(|&&c| grid[c].is_none()) {
+                return Some(Step { cell, value: false, reason: format!("{} has its star at {}", name, star) })
+            }
+        }
+    }
+    None
+}
+
+fn last_cell(units: &Units, grid: &Matrix<Option<bool>>) -> Option<Step<bool>> {
+    for (name, cells) in units {
+        let free: Vec<_> = cells.iter().filter(|&&c| grid[c].is_none()).collect();
+        if let [&cell] = free[..] {
+            if cells.iter().all(|&c| grid[c] != Some(true)) {
+                return Some(Step { cell, value: true, reason: format!("last free cell in {}", name) })
+            }
+        }
     }
+    None
+}
+
+fn confinement(units: &Units, grid: &Matrix<Option<bool>>) -> Option<Step<bool>> {
+    let free = |cells: &[Coord]| -> Vec<Coord> {
+        cells.iter().copied().filter(|&c| grid[c].is_none()).collect()
+    };
+
+    for (inner, inner_cells) in units {
+        let candidates = free(inner_cells);
+        if candidates.is_empty() { continue }
+
+        for (outer, outer_cells) in units {
+            if outer == inner || !candidates.iter().all(|c| outer_cells.contains(c)) { continue }
+            if let Some(cell) = free(outer_cells).into_iter().find(|c| !inner_cells.contains(c)) {
+                return Some(Step {
+                    cell,
+                    value: false,
+                    reason: format!("{} must have its star in {}", inner, outer),
+                })
+            }
+        }
+    }
+    None
+}
+
+fn crowding(units: &Units, grid: &Matrix<Option<bool>>) -> Option<Step<bool>> {
+    for cell in grid.coords().filter(|&c| grid[c].is_none()) {
+        // Cells that a star in this cell would rule out
+        let mut blocked = grid.neighbors(cell);
+        for (_, cells) in units.iter().filter(|(_, cells)| cells.contains(&cell)) {
+            blocked.extend(cells);
+        }
+
+        for (name, cells) in units.iter().filter(|(_, cells)| !cells.contains(&cell)) {
+            let starless = cells.iter().all(|&c| grid[c] != Some(true));
+            let free = cells.iter().filter(|&&c| grid[c].is_none());
+            if starless && free.clone().all(|c| blocked.contains(c)) {
+                return Some(Step { cell, value: false, reason: format!("a star here would leave no room in {}", name) })
+            }
+        }
+    }
+    None
 }
 
 #[derive(Debug, Error)]
@@ -99,15 +562,28 @@ pub enum ParseError {
     TextError(#[from] ParseIntError),
     #[error("bound error")]
     BoundError,
+    #[error(transparent)]
+    Grid(#[from] GridError),
 }
 
 impl FromStr for Problem {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // An optional first line such as `2 stars` (or `1 star`), for more than one star per unit
+        let mut lines = s.lines().peekable();
+        let mut stars = 1;
+        if let Some(count) = lines.peek().and_then(|l| { let l = l.trim(); l.strip_suffix("stars").or(l.strip_suffix("star")) }) {
+            stars = count.trim().parse()?;
+            lines.next();
+        }
+        if stars == 0 {
+            return Err(ParseError::BoundError)
+        }
+
         let mut grid = vec![];
         let mut height = 0;
-        for line in s.lines() {
+        for line in lines {
             for cell in line.split_whitespace() {
                 grid.push(cell.parse()?)
             }
@@ -118,15 +594,42 @@ impl FromStr for Problem {
             return Err(ParseError::BoundError)
         }
 
-        Ok(Self(Matrix::new(grid, (height, height))?))
+        Ok(Problem::new(Matrix::new(grid, (height, height))?, stars))
     }
 }
 
-impl Display for Solution<'_> {
+/// Colors are canonically numbered in the order they first appear, row by row
+impl Transform for Problem {
+    fn transform(&self, symmetry: Symmetry) -> Self {
+        Problem::new(symmetry.image(&self.regions), self.stars)
+    }
+
+    fn relabelings(&self) -> Vec<Self> {
+        let mut colors = vec![None; self.size()];
+        let mut next = 0;
+        for &c in &self.regions {
+            if colors[c].is_none() {
+                colors[c] = Some(next);
+                next += 1;
+            }
+        }
+        // Colors without a cell are never looked up, any number will do
+        let colors: Vec<_> = colors.into_iter().map(|c| c.unwrap_or(0)).collect();
+        vec![self.relabel(&colors)]
+    }
+}
+
+/// Displays in the input format, one line of color numbers per row,
+/// after the number of stars if there is more than one
+impl Display for Problem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for line in self.solution.lines() {
-            for c in line {
-                f.write_char(if *c {'*'} else {'.'})?;
+        if self.stars > 1 {
+            writeln!(f, "{} stars", self.stars)?;
+        }
+        for line in self.regions.lines() {
+            for (i, c) in line.iter().enumerate() {
+                if i > 0 { f.write_char(' ')? }
+                write!(f, "{}", c)?;
             }
             f.write_char('\n')?;
         }
@@ -134,36 +637,36 @@ impl Display for Solution<'_> {
     }
 }
 
-mod color {
-    use termcolor::{ColorSpec, BufferWriter, WriteColor, Color};
-    use std::io::Write;
-    use Color::*;
-
-    pub const COLOR_TABLE: [Color; 8] = [
-        Red, Blue, Green, Yellow, Magenta, Cyan, White, Black
-    ];
-
-    use super::Solution;
-    impl Solution<'_> {
+impl<'p> Solution<'p> {
+    /// The grid this solution belongs to
+    pub fn problem(&self) -> &'p Problem {
+        self.problem
+    }
 
-        pub fn color_fmt(&self, w: BufferWriter) -> Result<(), std::io::Error> {
-            let mut buf = w.buffer();
+    /// The cells holding a star
+    pub fn stars(&self) -> &Matrix<bool> {
+        &self.solution
+    }
 
-            for (ps, ss) in self.problem.0.lines().zip(self.solution.lines()) {
-                for (p, s) in ps.iter().zip(ss) {
-                    let mut color = ColorSpec::new();
-                    color.set_bold(true)
-                         .set_fg(Some(Color::White))
-                         .set_bg(Some(COLOR_TABLE[*p]));
+    /// The grid to draw, with the stars in their regions
+    pub fn scene(&self) -> Scene {
+        let mut scene = self.problem.scene();
+        for (cell, &star) in scene.cells.iter_mut().zip(&self.solution) {
+            cell.star = star;
+        }
+        scene
+    }
+}
 
-                    buf.set_color(&color)?;
-                    write!(buf, "{}", if *s {'*'} else {'.'})?;
-                }
-                buf.reset()?;
-                writeln!(buf)?;
+impl Display for Solution<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in self.solution.lines() {
+            for c in line {
+                f.write_char(if *c {'*'} else {'.'})?;
             }
-            w.print(&buf)
+            f.write_char('\n')?;
         }
+        Ok(())
     }
 }
 
@@ -197,11 +700,168 @@ mod test {
         assert_eq!(solution, &      
         problem.parse::<Problem>()
                .expect("parse error")
-               .solve()
+               .solve().unwrap()
                .expect("could not solve sample")
                .to_string());
 
 
 
     }
+
+    #[test]
+    fn unique() {
+        let problem: Problem = "0 0 0 2 2 3 3 3
+        0 0 0 2 3 3 3 1
+        0 0 0 2 3 4 3 1
+        0 5 5 5 4 4 1 1
+        0 0 0 7 4 1 1 7
+        7 7 7 7 6 6 1 7
+        7 7 7 6 6 7 7 7
+        7 7 7 7 7 7 7 7".parse().unwrap();
+
+        let s = problem.solve().unwrap().unwrap();
+        assert!(problem.solve_excluding(&s).unwrap().is_none());
+
+        let mut session = problem.session();
+        assert!(session.solve(&[((0,7), true)]).unwrap().is_some());
+        assert!(session.solve(&[((0,7), false)]).unwrap().is_none());
+        assert!(session.solve(&[((0,0), true)]).unwrap().is_none());
+        assert!(session.solve(&[((8,0), false)]).unwrap().is_none());
+
+        // With a unique solution, every cell is forced
+        let hints = problem.hints().unwrap().unwrap();
+        assert_eq!(hints.len(), 64);
+        assert!(hints.contains(&((0,7), true)));
+    }
+
+    #[test]
+    fn deduction() {
+        let problem: Problem = "0 0 0 2 2 3 3 3
+        0 0 0 2 3 3 3 1
+        0 0 0 2 3 4 3 1
+        0 5 5 5 4 4 1 1
+        0 0 0 7 4 1 1 7
+        7 7 7 7 6 6 1 7
+        7 7 7 6 6 7 7 7
+        7 7 7 7 7 7 7 7".parse().unwrap();
+
+        let d = problem.deduce();
+        assert!(d.is_complete());
+        assert_eq!(d.grid.map(|c| c.unwrap()), problem.solve().unwrap().unwrap().solution);
+    }
+
+    #[test]
+    fn verify() {
+        let problem: Problem = "0 0 0 2 2 3 3 3
+        0 0 0 2 3 3 3 1
+        0 0 0 2 3 4 3 1
+        0 5 5 5 4 4 1 1
+        0 0 0 7 4 1 1 7
+        7 7 7 7 6 6 1 7
+        7 7 7 6 6 7 7 7
+        7 7 7 7 7 7 7 7".parse().unwrap();
+
+        let s = problem.solve().unwrap().unwrap();
+        let parsed = problem.parse_solution(&s.to_string()).unwrap();
+        assert_eq!(parsed.solution, s.solution);
+        assert_eq!(problem.verify(&parsed), Ok(()));
+
+        // Moving the star of row 0 next to the one of row 1
+        let moved = problem.parse_solution("\
+..*.....
+...*....
+.....*..
+..*.....
+*.......
+......*.
+....*...
+.*......
+").unwrap();
+        assert_eq!(problem.verify(&moved).unwrap_err(), vec![
+            Violation::new("column 2 has 2 stars", vec![Coord::new(0,2), Coord::new(3,2)]),
+            Violation::new("column 7 has no star", vec![]),
+            Violation::new("region 0 has 2 stars", vec![Coord::new(0,2), Coord::new(4,0)]),
+            Violation::new("region 3 has no star", vec![]),
+            Violation::new("stars touch", vec![Coord::new(0,2), Coord::new(1,3)]),
+        ]);
+
+        assert!(problem.parse_solution("*.").is_err());
+        let colored = s.to_string().replace('*', "\x1b[0m\x1b[1;37;41m*\x1b[0m");
+        assert_eq!(problem.parse_solution(&colored).unwrap().solution, s.solution);
+    }
+
+    #[test]
+    fn propagation() {
+        let problem: Problem = "0 0 0 2 2 3 3 3
+        0 0 0 2 3 3 3 1
+        0 0 0 2 3 4 3 1
+        0 5 5 5 4 4 1 1
+        0 0 0 7 4 1 1 7
+        7 7 7 7 6 6 1 7
+        7 7 7 6 6 7 7 7
+        7 7 7 7 7 7 7 7".parse().unwrap();
+        let solution = problem.solve().unwrap().unwrap().solution;
+
+        // Confining regions to rows and columns places the star of row 5, and clears its neighbors
+        let forced = problem.propagate().unwrap();
+        assert_eq!(forced[5][6], Some(true));
+        assert_eq!(forced[4][7], Some(false));
+        for c in forced.coords() {
+            assert!(forced[c].is_none_or(|star| star == solution[c]), "{}", c);
+        }
+
+        let options = SolveOptions { propagate: true, ..Default::default() };
+        assert_eq!(problem.session_with(options).solve(&[]).unwrap().unwrap().solution, solution);
+    }
+
+    #[test]
+    fn conflict() {
+        // Regions 0 and 1 both need their star in the first column
+        let problem: Problem = "0 2 2 2 2
+        1 2 2 2 2
+        2 2 2 2 2
+        3 3 3 3 3
+        4 4 4 4 4".parse().unwrap();
+
+        assert_eq!(problem.conflict().unwrap().unwrap(), vec![0, 1]);
+    }
+
+    #[test]
+    fn several_stars() {
+        // Regions made of rows, so that any placement with 3 stars per row and column fits
+        let rows = Matrix::from_fn((14, 14), |x, _| x);
+        let problem = Problem::new(rows, 3);
+        let s = problem.solve().unwrap().unwrap();
+        assert_eq!(problem.verify(&s), Ok(()));
+        assert!(s.solution.coords().all(|c| !s.solution[c] || s.solution.adjacent(c).iter().all(|&n| !s.solution[n])));
+
+        let parsed: Problem = problem.to_string().parse().unwrap();
+        assert_eq!(parsed, problem);
+        assert!(problem.to_string().starts_with("3 stars\n0 0 0"));
+        assert!("0 stars\n0".parse::<Problem>().is_err());
+        assert_eq!("1 star\n0 0\n1 1\n".parse::<Problem>().unwrap(), "0 0\n1 1\n".parse().unwrap());
+
+        // Five stars never fit in a row of 8 without touching
+        let rows = Matrix::from_fn((8, 8), |x, _| x);
+        assert!(Problem::new(rows, 5).solve().unwrap().is_none());
+    }
+
+    #[test]
+    fn generate() {
+        for n in 0..4 {
+            assert!(super::generate(n, 0).unwrap().is_none());
+        }
+
+        for n in 4..=8 {
+            let problem = super::generate(n, n as u64).unwrap().unwrap();
+            assert_eq!(problem.regions, super::generate(n, n as u64).unwrap().unwrap().regions);
+
+            // Round trip through the text format
+            let parsed: Problem = problem.to_string().parse().unwrap();
+            assert_eq!(parsed.regions, problem.regions);
+
+            let s = problem.solve().unwrap().unwrap();
+            assert!(problem.solve_excluding(&s).unwrap().is_none());
+        }
+    }
 }
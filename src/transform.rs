@@ -0,0 +1,101 @@
+//! Rotations, reflections and relabelings of puzzles. Moving a puzzle around on its grid,
+//! or renaming its digits or colors, gives the same puzzle for the player, with the
+//! solutions transformed the same way. Taking the smallest of all these variants gives
+//! a canonical form, which tells whether two puzzles are really the same one.
+//!
+//! Games take part by implementing [`Transform`] for their problems and solutions.
+//! Kakuro grids are left out: their clues sit to the left and above the runs,
+//! which reflections would break.
+
+pub use crate::util::symmetry::Symmetry;
+
+pub trait Transform: Ord + Sized {
+    /// The puzzle moved by `symmetry`. With a transposition, the shape of a rectangular
+    /// grid is transposed too.
+    fn transform(&self, symmetry: Symmetry) -> Self;
+
+    /// The puzzle with its labels renamed in every way that keeps it the same puzzle,
+    /// or only in a canonical way when there is one, such as numbering the colors in
+    /// order of appearance.
+    fn relabelings(&self) -> Vec<Self>;
+}
+
+/// The smallest of the rotations, reflections and relabelings of `puzzle`
+pub fn canonical<P: Transform>(puzzle: &P) -> P {
+    Symmetry::all().into_iter()
+        .flat_map(|s| puzzle.transform(s).relabelings())
+        .min()
+        .expect("the identity is a symmetry")
+}
+
+/// True if one of the puzzles is a rotation, reflection or relabeling of the other
+pub fn isomorphic<P: Transform>(a: &P, b: &P) -> bool {
+    canonical(a) == canonical(b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binero, constraints, kdoku, stars, voisimage};
+
+    #[test]
+    fn binero() {
+        let p: binero::Problem = "1...\n..0.\n....\n....\n".parse().unwrap();
+        let rotated = p.transform(Symmetry::ROTATE_90);
+        assert_eq!(rotated.to_string(), "...1\n....\n..0.\n....\n");
+        assert_eq!(rotated.transform(Symmetry::ROTATE_270), p);
+
+        // Swapping 0 and 1 keeps the puzzle the same
+        assert!(isomorphic(&p, &rotated.complement()));
+        let other: binero::Problem = "11..\n....\n....\n....\n".parse().unwrap();
+        assert!(!isomorphic(&p, &other));
+
+        // Solutions follow their puzzle
+        let s = p.solve().unwrap().unwrap();
+        assert!(rotated.verify(&s.transform(Symmetry::ROTATE_90)).is_ok());
+    }
+
+    #[test]
+    fn stars() {
+        let p: stars::Problem = "0 0 1 1\n0 2 2 1\n3 3 2 1\n3 3 3 1\n".parse().unwrap();
+        let flipped = p.transform(Symmetry { flip_columns: true, ..Symmetry::default() });
+        assert_eq!(flipped.to_string(), "1 1 0 0\n1 2 2 0\n1 2 3 3\n1 3 3 3\n");
+
+        let renamed = flipped.relabel(&[3, 0, 2, 1]);
+        assert!(isomorphic(&p, &renamed));
+        assert_eq!(canonical(&renamed), canonical(&p));
+        assert_eq!(canonical(&p).regions[0][0], 0);
+
+        // Colors 1 and 3 are unused, the others are numbered in order
+        let gaps: stars::Problem = "0 0 2 2\n0 0 2 2\n0 0 2 2\n0 0 2 2\n".parse().unwrap();
+        let packed: stars::Problem = "0 0 1 1\n0 0 1 1\n0 0 1 1\n0 0 1 1\n".parse().unwrap();
+        assert_eq!(gaps.relabelings(), [packed.clone()]);
+        assert!(isomorphic(&gaps, &packed));
+    }
+
+    #[test]
+    fn kdoku() {
+        let constraints = constraints![
+            3+ [ (0,0), (0,1) ],
+            2/ [ (1,0), (1,1) ],
+        ];
+        let rotated = constraints.transform(Symmetry::ROTATE_180);
+        assert_eq!(rotated, constraints![ 3+ [ (5,5), (5,4) ], 2/ [ (4,5), (4,4) ], ]);
+        assert!(isomorphic(&constraints, &rotated));
+        assert!(!isomorphic(&constraints, &constraints![ 3+ [ (0,0), (1,0) ], 3/ [ (0,1), (1,1) ], ]));
+
+        let square: kdoku::Solution = "123456\n234561\n345612\n456123\n561234\n612345\n".parse().unwrap();
+        let renamed = square.relabel([6, 5, 4, 3, 2, 1]).transform(Symmetry::ROTATE_90);
+        assert!(isomorphic(&square, &renamed));
+        assert_eq!(canonical(&renamed).to_string().lines().next(), Some("123456"));
+    }
+
+    #[test]
+    fn voisimage() {
+        let p: voisimage::Problem = "4..\n...\n..1\n".parse().unwrap();
+        let q: voisimage::Problem = "1..\n...\n..4\n".parse().unwrap();
+        assert!(isomorphic(&p, &q));
+        assert_eq!(p.transform(Symmetry::ROTATE_180), q);
+        assert!(!isomorphic(&p, &"4..\n...\n..2\n".parse().unwrap()));
+    }
+}
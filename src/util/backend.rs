@@ -0,0 +1,306 @@
+//! The SAT solver behind the games. Games build their formulas in a [`Solver`], which
+//! forwards everything to a [`SatBackend`]. varisat is used by default, and another
+//! backend can be chosen for the solvers created within [`with_backend`], without
+//! the games knowing about it.
+//!
+//! The same mechanism gives the formula of a game without solving it: within [`record`],
+//! the solvers only copy their clauses.
+//!
+//! Every call to `solve` is traced in a `SAT solve` span, at the debug level, recording
+//! the number of clauses and assumptions, and the outcome. It is also counted in the
+//! statistics collected by [`with_stats`](super::stats::with_stats), and reported to
+//! the observer of [`with_progress`](super::progress::with_progress).
+
+use std::{cell::RefCell, sync::{Arc, Mutex}, time::Instant};
+
+use thiserror::Error;
+use tracing::debug;
+use varisat::{CnfFormula, ExtendFormula, Lit, Var};
+
+use super::{cancel, progress, scope::scoped, stats};
+
+/// An incremental SAT solver
+pub trait SatBackend {
+    /// A fresh variable. Clauses may mention variables that were not created by this
+    /// method, which must then never be returned by it.
+    fn new_var(&mut self) -> Var;
+
+    fn add_clause(&mut self, clause: &[Lit]);
+
+    /// Add all the clauses of a formula, including its variables
+    fn add_formula(&mut self, formula: &CnfFormula) {
+        for clause in formula.iter() {
+            self.add_clause(clause);
+        }
+    }
+
+    /// Literals assumed true for the next call to `solve` only
+    fn assume(&mut self, assumptions: &[Lit]);
+
+    /// True if the formula is satisfiable under the current assumptions
+    fn solve(&mut self) -> Result<bool, BackendError>;
+
+    /// The model found by the last successful call to `solve`
+    fn model(&self) -> Option<Vec<Lit>>;
+
+    /// After an unsatisfiable call to `solve`, a subset of the assumptions
+    /// that is already contradictory
+    fn failed_core(&self) -> Option<Vec<Lit>>;
+}
+
+#[derive(Debug, Error)]
+pub enum BackendError {
+    #[error("varisat: {0}")]
+    Varisat(#[from] varisat::solver::SolverError),
+    #[error("external solver: {0}")]
+    External(String),
+    #[error("external solver: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("the formula is only recorded, not solved")]
+    Recording,
+}
+
+impl SatBackend for varisat::Solver<'_> {
+    fn new_var(&mut self) -> Var {
+        ExtendFormula::new_var(self)
+    }
+
+    fn add_clause(&mut self, clause: &[Lit]) {
+        ExtendFormula::add_clause(self, clause)
+    }
+
+    fn add_formula(&mut self, formula: &CnfFormula) {
+        varisat::Solver::add_formula(self, formula)
+    }
+
+    fn assume(&mut self, assumptions: &[Lit]) {
+        varisat::Solver::assume(self, assumptions)
+    }
+
+    fn solve(&mut self) -> Result<bool, BackendError> {
+        Ok(varisat::Solver::solve(self)?)
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        varisat::Solver::model(self)
+    }
+
+    fn failed_core(&self) -> Option<Vec<Lit>> {
+        varisat::Solver::failed_core(self).map(<[Lit]>::to_vec)
+    }
+}
+
+type Factory = Arc<dyn Fn() -> Box<dyn SatBackend> + Send + Sync>;
+
+thread_local! {
+    static FACTORY: RefCell<Option<Factory>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, with every solver it creates (on this thread) using the backend built by `factory`
+pub fn with_backend<R>(factory: impl Fn() -> Box<dyn SatBackend> + Send + Sync + 'static, f: impl FnOnce() -> R) -> R {
+    Backend(Some(Arc::new(factory))).run(f)
+}
+
+/// Copies the clauses into a shared formula, and cannot solve
+struct Recorder(Arc<Mutex<CnfFormula>>);
+
+impl SatBackend for Recorder {
+    fn new_var(&mut self) -> Var {
+        self.0.lock().unwrap().new_var()
+    }
+
+    fn add_clause(&mut self, clause: &[Lit]) {
+        self.0.lock().unwrap().add_clause(clause)
+    }
+
+    fn assume(&mut self, _assumptions: &[Lit]) {}
+
+    fn solve(&mut self) -> Result<bool, BackendError> {
+        Err(BackendError::Recording)
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        None
+    }
+
+    fn failed_core(&self) -> Option<Vec<Lit>> {
+        None
+    }
+}
+
+/// Run `f`, returning the clauses given to the solvers it creates (on this thread)
+/// instead of solving them. With several solvers, their clauses are mixed in the
+/// same formula, so `f` should only build one.
+pub fn record<R>(f: impl FnOnce() -> R) -> (CnfFormula, R) {
+    let formula = Arc::new(Mutex::new(CnfFormula::new()));
+    let shared = formula.clone();
+    let result = with_backend(move || Box::new(Recorder(shared.clone())), f);
+    let formula = std::mem::replace(&mut *formula.lock().unwrap(), CnfFormula::new());
+    (formula, result)
+}
+
+/// The backend used by the solvers of a thread, that can be carried over to other threads
+#[derive(Clone, Default)]
+pub struct Backend(Option<Factory>);
+
+impl Backend {
+    /// The backend of the current thread
+    pub fn current() -> Self {
+        Backend(FACTORY.with(|current| current.borrow().clone()))
+    }
+
+    /// A new instance of this backend, varisat by default
+    pub fn build(&self) -> Box<dyn SatBackend> {
+        match &self.0 {
+            Some(factory) => factory(),
+            None => Box::new(varisat::Solver::new()),
+        }
+    }
+
+    /// Run `f`, with every solver it creates (on this thread) using this backend
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        scoped(&FACTORY, self.0.clone(), f).0
+    }
+}
+
+/// A solver using the current backend
+pub struct Solver {
+    backend: Box<dyn SatBackend>,
+    clauses: usize,
+    /// Number of variables used so far, created or mentioned by a formula
+    variables: usize,
+    assumptions: usize,
+    /// When the solver was created, until its first call to `solve`
+    created: Option<Instant>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        stats::update(|s| s.solvers += 1);
+        Solver { backend: Backend::current().build(), clauses: 0, variables: 0, assumptions: 0, created: Some(Instant::now()) }
+    }
+
+    /// Number of clauses added so far
+    pub fn clauses(&self) -> usize {
+        self.clauses
+    }
+
+    /// Count the variables up to `count`, if they were not already
+    fn use_variables(&mut self, count: usize) {
+        if count <= self.variables { return }
+        stats::update(|s| s.variables += count - self.variables);
+        self.variables = count;
+    }
+
+    pub fn add_formula(&mut self, formula: &CnfFormula) {
+        self.clauses += formula.len();
+        stats::update(|s| s.clauses += formula.len());
+        self.use_variables(formula.var_count());
+        self.backend.add_formula(formula)
+    }
+
+    pub fn assume(&mut self, assumptions: &[Lit]) {
+        self.assumptions = assumptions.len();
+        self.backend.assume(assumptions)
+    }
+
+    /// Unwinds instead, if the computation was cancelled (see [`cancel`])
+    pub fn solve(&mut self) -> Result<bool, BackendError> {
+        cancel::check();
+        let assumptions = std::mem::take(&mut self.assumptions);
+        let _span = tracing::debug_span!("SAT solve", clauses = self.clauses, assumptions).entered();
+        let start = Instant::now();
+        let result = self.backend.solve();
+        debug!(variables = self.variables, clauses = self.clauses, assumptions, ?result, time = ?start.elapsed(), "solved");
+        let encoding = self.created.take().map(|created| start - created);
+        stats::update(|s| {
+            s.solves += 1;
+            s.satisfiable += matches!(result, Ok(true)) as usize;
+            s.encoding += encoding.unwrap_or_default();
+            s.solving += start.elapsed();
+        });
+        progress::solved(matches!(result, Ok(true)));
+        result
+    }
+
+    pub fn model(&self) -> Option<Vec<Lit>> {
+        self.backend.model()
+    }
+
+    pub fn failed_core(&self) -> Option<Vec<Lit>> {
+        self.backend.failed_core()
+    }
+}
+
+impl ExtendFormula for Solver {
+    fn add_clause(&mut self, literals: &[Lit]) {
+        self.clauses += 1;
+        stats::update(|s| s.clauses += 1);
+        self.backend.add_clause(literals)
+    }
+
+    fn new_var(&mut self) -> Var {
+        let var = self.backend.new_var();
+        self.use_variables(var.index() + 1);
+        var
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::binero;
+
+    /// Counts the calls to `solve`, and otherwise behaves like varisat
+    struct Counting(varisat::Solver<'static>, Arc<AtomicUsize>);
+
+    impl SatBackend for Counting {
+        fn new_var(&mut self) -> Var { SatBackend::new_var(&mut self.0) }
+        fn add_clause(&mut self, clause: &[Lit]) { SatBackend::add_clause(&mut self.0, clause) }
+        fn assume(&mut self, assumptions: &[Lit]) { SatBackend::assume(&mut self.0, assumptions) }
+        fn model(&self) -> Option<Vec<Lit>> { SatBackend::model(&self.0) }
+        fn failed_core(&self) -> Option<Vec<Lit>> { SatBackend::failed_core(&self.0) }
+
+        fn solve(&mut self) -> Result<bool, BackendError> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            SatBackend::solve(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn swap() {
+        let problem: binero::Problem = "00..\n....\n....\n....\n".parse().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let counter = calls.clone();
+        let solution = with_backend(move || Box::new(Counting(varisat::Solver::new(), counter.clone())), || problem.solve());
+        assert!(solution.unwrap().is_some());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Back to the default backend
+        problem.solve().unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn recording() {
+        let (formula, mut solver) = record(|| {
+            let mut solver = Solver::new();
+            let (a, b) = (solver.new_lit(), solver.new_lit());
+            solver.add_clause(&[a, b]);
+            solver.add_clause(&[!a]);
+            solver
+        });
+        assert_eq!(formula.len(), 2);
+        assert_eq!(formula.var_count(), 2);
+        assert!(matches!(solver.solve(), Err(BackendError::Recording)));
+    }
+}
@@ -0,0 +1,177 @@
+//! Boolean matrices packed as bits, 64 cells per word. Each row starts on a new word,
+//! so that counting the cells set in a row, or comparing two rows, goes a word at a
+//! time. Large pictures take an eighth of the memory of a `Matrix<bool>`.
+
+use std::ops::Index;
+
+use super::{coord::Coord, matrix::{Matrix, ShapeError}};
+
+const BITS: usize = u64::BITS as usize;
+
+/// A matrix of booleans, with the same layout and indexing as a [`Matrix<bool>`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BitMatrix {
+    shape: (usize, usize),
+    /// Words per row
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl BitMatrix {
+    /// A matrix of the given shape with every cell unset
+    pub fn new(shape: (usize, usize)) -> Self {
+        let stride = shape.1.div_ceil(BITS);
+        BitMatrix { shape, stride, words: vec![0; shape.0 * stride] }
+    }
+
+    /// Create a new matrix from a vector of elements in row-major order.
+    /// Will fail if the length of `vec` doesn't match the requested shape.
+    pub fn from_vec(vec: Vec<bool>, shape: (usize, usize)) -> Result<Self, ShapeError> {
+        if vec.len() != shape.0 * shape.1 { return Err(ShapeError) }
+        let mut m = BitMatrix::new(shape);
+        for (i, v) in vec.into_iter().enumerate() {
+            m.set(i / shape.1, i % shape.1, v);
+        }
+        Ok(m)
+    }
+
+    /// Total number of elements in the matrix. Equal to `shape.0 * shape.1`
+    pub fn len(&self) -> usize {
+        self.shape.0 * self.shape.1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Shape of the matrix
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// The word holding (x,y), and the bit of that cell in it
+    fn locate(&self, x: usize, y: usize) -> (usize, u64) {
+        assert!(x < self.shape.0 && y < self.shape.1, "({},{}) out of a {}x{} matrix", x, y, self.shape.0, self.shape.1);
+        (x * self.stride + y / BITS, 1 << (y % BITS))
+    }
+
+    /// The element at (x,y), or `None` if that is out of the matrix
+    pub fn get(&self, x: usize, y: usize) -> Option<bool> {
+        if x >= self.shape.0 || y >= self.shape.1 { return None }
+        let (word, bit) = self.locate(x, y);
+        Some(self.words[word] & bit != 0)
+    }
+
+    /// Set the element at (x,y). Panics if that is out of the matrix.
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        let (word, bit) = self.locate(x, y);
+        if value { self.words[word] |= bit } else { self.words[word] &= !bit }
+    }
+
+    /// The words of row `x`, the unused bits of the last one being unset
+    fn row(&self, x: usize) -> &[u64] {
+        &self.words[x * self.stride..][..self.stride]
+    }
+
+    /// Number of cells set in row `x`
+    pub fn count_row(&self, x: usize) -> usize {
+        self.row(x).iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Number of cells set in the whole matrix
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// True if rows `a` and `b` hold the same values
+    pub fn rows_equal(&self, a: usize, b: usize) -> bool {
+        self.row(a) == self.row(b)
+    }
+
+    /// Iterates over the matrix rows, each as an iterator over its cells
+    pub fn lines(&self) -> impl Iterator<Item=impl Iterator<Item=bool> + '_> + '_ {
+        (0..self.shape.0).map(move |x| (0..self.shape.1).map(move |y| self[(x, y)]))
+    }
+
+    /// Iterate over all the elements in row-major order
+    pub fn iter(&self) -> impl Iterator<Item=bool> + '_ {
+        self.lines().flatten()
+    }
+
+    /// Iterate over all the coordinate pairs in row-major order
+    pub fn indices(&self) -> impl Iterator<Item=(usize, usize)> {
+        let (h, w) = self.shape;
+        (0..h).flat_map(move |x| (0..w).map(move |y| (x, y)))
+    }
+
+    /// Iterate over all the cells in row-major order
+    pub fn coords(&self) -> impl Iterator<Item=Coord> {
+        self.indices().map(Coord::from)
+    }
+}
+
+impl Index<(usize, usize)> for BitMatrix {
+    type Output = bool;
+
+    fn index(&self, (x, y): (usize, usize)) -> &bool {
+        let (word, bit) = self.locate(x, y);
+        if self.words[word] & bit != 0 { &true } else { &false }
+    }
+}
+
+impl Index<Coord> for BitMatrix {
+    type Output = bool;
+
+    fn index(&self, c: Coord) -> &bool {
+        &self[(c.x, c.y)]
+    }
+}
+
+impl From<&Matrix<bool>> for BitMatrix {
+    fn from(m: &Matrix<bool>) -> Self {
+        let mut bits = BitMatrix::new(m.shape());
+        for ((x, y), &v) in m.enumerate() {
+            bits.set(x, y, v);
+        }
+        bits
+    }
+}
+
+impl From<&BitMatrix> for Matrix<bool> {
+    fn from(m: &BitMatrix) -> Self {
+        Matrix::from_fn(m.shape(), |x, y| m[(x, y)])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bits() {
+        // Rows longer than a word
+        let m = Matrix::from_fn((3, 70), |x, y| x != 1 && (y % 3 == 0 || y == 69));
+        let bits = BitMatrix::from(&m);
+        assert_eq!(bits.shape(), (3, 70));
+        assert_eq!(Matrix::from(&bits), m);
+        assert_eq!(bits.iter().collect::<Vec<_>>(), m.iter().copied().collect::<Vec<_>>());
+        assert!(bits[(0, 69)] && !bits[Coord::new(1, 0)]);
+        assert_eq!(bits.get(3, 0), None);
+        assert_eq!(bits.get(0, 70), None);
+
+        assert_eq!(bits.count_row(0), 24);
+        assert_eq!(bits.count_row(1), 0);
+        assert_eq!(bits.count(), 48);
+        assert!(bits.rows_equal(0, 2));
+        assert!(!bits.rows_equal(0, 1));
+
+        let mut bits = bits;
+        bits.set(2, 68, true);
+        bits.set(2, 69, false);
+        assert!(!bits.rows_equal(0, 2));
+        assert_eq!(bits.count_row(2), 24);
+
+        assert_eq!(BitMatrix::from_vec(vec![true, false, false, true], (2, 2)).unwrap().count(), 2);
+        assert_eq!(BitMatrix::from_vec(vec![true], (2, 2)), Err(ShapeError));
+    }
+}
@@ -0,0 +1,90 @@
+//! Most of the formula of a puzzle only depends on its size: the rules of the game are
+//! the same for every instance, and only the clues differ. A [`FormulaCache`] builds
+//! that part once per size, and hands out shared copies that each instance can clone
+//! and extend with its own clues. Caches are meant to be statics, shared by all threads.
+//!
+//! Values are built outside of the lock, so that threads asking for other keys are not
+//! kept waiting, and only [`CAPACITY`] of them are kept, the least recently used being
+//! dropped first.
+
+use std::{collections::BTreeMap, sync::{Arc, Mutex, OnceLock}};
+
+/// The number of values kept by a cache
+pub const CAPACITY: usize = 32;
+
+type Slot<T> = Arc<OnceLock<Arc<T>>>;
+
+/// The slot of every key, with the time it was last asked for
+type Slots<K, T> = BTreeMap<K, (Slot<T>, u64)>;
+
+/// Values built once for each key, on first use
+pub struct FormulaCache<K, T> {
+    build: fn(&K) -> T,
+    /// The current time, counted in calls to `get`, and the slots
+    slots: Mutex<(u64, Slots<K, T>)>,
+}
+
+impl<K: Ord + Clone, T> FormulaCache<K, T> {
+    pub const fn new(build: fn(&K) -> T) -> Self {
+        FormulaCache { build, slots: Mutex::new((0, BTreeMap::new())) }
+    }
+
+    /// The value for `key`, built by the first call asking for it. Other calls asking
+    /// for the same key meanwhile wait for it to be built.
+    pub fn get(&self, key: K) -> Arc<T> {
+        let slot = {
+            let mut guard = self.slots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let (clock, slots) = &mut *guard;
+            *clock += 1;
+            if !slots.contains_key(&key) && slots.len() >= CAPACITY {
+                let oldest = slots.iter().min_by_key(|(_, (_, used))| *used).map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest { slots.remove(&oldest); }
+            }
+            let (slot, used) = slots.entry(key.clone()).or_default();
+            *used = *clock;
+            slot.clone()
+        };
+        slot.get_or_init(|| Arc::new((self.build)(&key))).clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    static BUILT: AtomicUsize = AtomicUsize::new(0);
+
+    fn squares(n: &usize) -> Vec<usize> {
+        BUILT.fetch_add(1, Ordering::Relaxed);
+        (0..*n).map(|i| i * i).collect()
+    }
+
+    static SQUARES: FormulaCache<usize, Vec<usize>> = FormulaCache::new(squares);
+
+    #[test]
+    fn once_per_key() {
+        assert_eq!(*SQUARES.get(3), [0, 1, 4]);
+        assert_eq!(*SQUARES.get(3), [0, 1, 4]);
+        assert_eq!(BUILT.load(Ordering::Relaxed), 1);
+
+        assert_eq!(SQUARES.get(4).len(), 4);
+        assert_eq!(BUILT.load(Ordering::Relaxed), 2);
+    }
+
+    static CUBES: FormulaCache<usize, usize> = FormulaCache::new(|n| n * n * n);
+
+    #[test]
+    fn bounded() {
+        let first = CUBES.get(0);
+        for n in 1..=CAPACITY {
+            assert_eq!(*CUBES.get(n), n * n * n);
+        }
+        assert_eq!(CUBES.slots.lock().unwrap().1.len(), CAPACITY);
+        // The least recently used key was dropped, and is built again
+        assert!(!CUBES.slots.lock().unwrap().1.contains_key(&0));
+        assert!(!Arc::ptr_eq(&first, &CUBES.get(0)));
+        assert!(Arc::ptr_eq(&CUBES.get(CAPACITY), &CUBES.get(CAPACITY)));
+    }
+}
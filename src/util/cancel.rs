@@ -0,0 +1,112 @@
+//! Stopping a computation from another thread, such as a solve taking too long. The
+//! games never check for cancellation themselves: within [`with_cancel`], every
+//! [`Solver`] of the current thread checks the token before solving, and unwinds back
+//! to `with_cancel` once it is cancelled.
+//!
+//! Cancellation is only checked between calls: a call to the backend that has already
+//! started is not interrupted, and runs to its end, however long that takes. A single
+//! hard instance cannot be stopped this way. The command line program exits the process
+//! when a timeout has passed anyway, but libraries and the server cannot do the same:
+//! their threads keep solving, and only stop at the next call.
+//!
+//! The token belongs to one thread. Work spread over other threads, such as with rayon,
+//! runs in the [`Scope`] of the thread it comes from, which carries the token over.
+//!
+//! [`Solver`]: super::backend::Solver
+//! [`Scope`]: super::scope::Scope
+
+use std::{cell::RefCell, panic::{catch_unwind, resume_unwind, AssertUnwindSafe}, sync::{atomic::{AtomicBool, Ordering}, Arc}};
+
+use thiserror::Error;
+
+use super::scope::scoped;
+
+/// Shared between the computation and the threads that may cancel it
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+/// The computation was cancelled before its end
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[error("cancelled")]
+pub struct Cancelled;
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+thread_local! {
+    static TOKEN: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, stopping at its next call to a solver (on this thread) once `token` is
+/// cancelled
+pub fn with_cancel<R>(token: &CancelToken, f: impl FnOnce() -> R) -> Result<R, Cancelled> {
+    let (result, _) = scoped(&TOKEN, Some(token.clone()), || catch_unwind(AssertUnwindSafe(f)));
+    match result {
+        Ok(result) => Ok(result),
+        Err(payload) if payload.is::<Cancelled>() => Err(Cancelled),
+        Err(payload) => resume_unwind(payload),
+    }
+}
+
+/// The token of the computation running on this thread
+pub(crate) fn current() -> Option<CancelToken> {
+    TOKEN.with(|current| current.borrow().clone())
+}
+
+/// Run `f` on this thread under `token`, if any, taken from the thread whose work `f`
+/// takes part in. Once cancelled, unwinds out of `f` instead of returning.
+pub(crate) fn within<R>(token: Option<&CancelToken>, f: impl FnOnce() -> R) -> R {
+    let Some(token) = token else { return f() };
+    match with_cancel(token, f) {
+        Ok(result) => result,
+        Err(Cancelled) => resume_unwind(Box::new(Cancelled)),
+    }
+}
+
+/// Unwind to [`with_cancel`] if the computation of this thread was cancelled
+pub(crate) fn check() {
+    if TOKEN.with(|current| current.borrow().as_ref().is_some_and(CancelToken::is_cancelled)) {
+        // Unlike a panic, does not go through the panic hook, which would print a message
+        resume_unwind(Box::new(Cancelled))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binero;
+
+    #[test]
+    fn cancel() {
+        let problem: binero::Problem = "10..\n....\n..1.\n....\n".parse().unwrap();
+        let token = CancelToken::new();
+        assert!(with_cancel(&token, || problem.solve()).unwrap().unwrap().is_some());
+
+        token.cancel();
+        assert_eq!(with_cancel(&token, || problem.solve()).map(|_| ()), Err(Cancelled));
+        // Only within `with_cancel`
+        assert!(problem.solve().unwrap().is_some());
+    }
+
+    #[test]
+    fn workers() {
+        let problems: Vec<binero::Problem> = (0..8).map(|_| "....\n....\n....\n....\n".parse().unwrap()).collect();
+        let token = CancelToken::new();
+        assert!(with_cancel(&token, || crate::batch::solve_all(&problems)).is_ok());
+
+        // The workers of the pool stop as well
+        token.cancel();
+        assert_eq!(with_cancel(&token, || crate::batch::solve_all(&problems)).map(|_| ()), Err(Cancelled));
+    }
+}
@@ -0,0 +1,243 @@
+//! Cardinality constraints: bounds on the number of true literals in a set,
+//! which counting puzzles (Binero lines, Tents, Battleships...) are made of.
+//!
+//! Several encodings are available, with different trade-offs between the size of the
+//! formula and the number of helper variables. The caller picks one with [`Encoding`].
+
+use varisat::{ExtendFormula, Lit};
+
+use super::choice::Choose;
+
+/// How cardinality constraints are turned into clauses
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Binomial encoding: one clause for every set of `k+1` literals, forbidding them all
+    /// to be true. No helper variable, but there are `C(n, k+1)` clauses. For `k = 1`,
+    /// this is the pairwise encoding.
+    Binomial,
+    /// Sequential counter: `s[i][j]` holds when at least `j+1` of the first `i+1`
+    /// literals are true. About `n*k` helper variables and clauses.
+    Sequential,
+    /// Totalizer: a tree of unary adders counting the true literals. About `n*log(n)`
+    /// helper variables, and the count can be bounded on both sides at once.
+    #[default]
+    Totalizer,
+}
+
+pub trait CardFormula: ExtendFormula {
+
+    /// At most `k` of the `lits` are true
+    fn at_most_k(&mut self, lits: &[Lit], k: usize, encoding: Encoding) {
+        if k >= lits.len() { return }
+        match encoding {
+            Encoding::Binomial => binomial(self, lits, k),
+            Encoding::Sequential => sequential(self, lits, k),
+            Encoding::Totalizer => {
+                let count = totalizer(self, lits);
+                self.add_clause(&[!count[k]]);
+            },
+        }
+    }
+
+    /// At most one of the `lits` is true. Small sets get a clause for every pair, larger
+    /// ones the product encoding, which only needs about `2n` clauses.
+    fn at_most_one(&mut self, lits: &[Lit]) {
+        if lits.len() <= PAIRWISE_LIMIT {
+            binomial(self, lits, 1)
+        } else {
+            product(self, lits)
+        }
+    }
+
+    /// At least `k` of the `lits` are true
+    fn at_least_k(&mut self, lits: &[Lit], k: usize, encoding: Encoding) {
+        if k > lits.len() { return self.add_clause(&[]) }
+        let negated: Vec<_> = lits.iter().map(|&l| !l).collect();
+        self.at_most_k(&negated, lits.len() - k, encoding)
+    }
+
+    /// Exactly `k` of the `lits` are true
+    fn exactly_k(&mut self, lits: &[Lit], k: usize, encoding: Encoding) {
+        if k > lits.len() { return self.add_clause(&[]) }
+        // Both bounds on the same counter
+        let count = match encoding {
+            Encoding::Binomial => {
+                self.at_most_k(lits, k, encoding);
+                return self.at_least_k(lits, k, encoding)
+            },
+            Encoding::Sequential => counter(self, lits, k + 1),
+            Encoding::Totalizer => totalizer(self, lits),
+        };
+        if k > 0 { self.add_clause(&[count[k-1]]) }
+        if k < lits.len() { self.add_clause(&[!count[k]]) }
+    }
+}
+
+impl<T: ExtendFormula> CardFormula for T {}
+
+/// The largest set for which [`CardFormula::at_most_one`] uses one clause per pair
+const PAIRWISE_LIMIT: usize = 8;
+
+/// Product encoding of at-most-one: the literals are laid out on a grid, each of them
+/// implies its row and its column, and at most one row and one column can be selected.
+fn product<F: ExtendFormula>(f: &mut F, lits: &[Lit]) {
+    let rows = (1..).find(|r| r * r >= lits.len()).expect("some square is large enough");
+    let columns = lits.len().div_ceil(rows);
+    let row: Vec<Lit> = (0..rows).map(|_| f.new_lit()).collect();
+    let column: Vec<Lit> = (0..columns).map(|_| f.new_lit()).collect();
+
+    for (i, &x) in lits.iter().enumerate() {
+        f.add_clause(&[!x, row[i / columns]]);
+        f.add_clause(&[!x, column[i % columns]]);
+    }
+
+    f.at_most_one(&row);
+    f.at_most_one(&column);
+}
+
+fn binomial<F: ExtendFormula>(f: &mut F, lits: &[Lit], k: usize) {
+    Choose::new(lits.len(), k + 1).for_each(|subset| {
+        let clause: Vec<_> = subset.iter().zip(lits)
+            .filter(|&(&b, _)| b)
+            .map(|(_, &l)| !l)
+            .collect();
+        f.add_clause(&clause);
+    });
+}
+
+fn sequential<F: ExtendFormula>(f: &mut F, lits: &[Lit], k: usize) {
+    if k == 0 {
+        for &l in lits { f.add_clause(&[!l]) }
+        return
+    }
+
+    let n = lits.len();
+    let mut prev: Vec<Lit> = vec![];
+
+    for (i, &x) in lits.iter().enumerate() {
+        if i > 0 {
+            // Already k true literals before this one
+            f.add_clause(&[!x, !prev[k-1]]);
+        }
+        if i == n - 1 { break }
+
+        let s: Vec<Lit> = (0..k).map(|_| f.new_lit()).collect();
+        f.add_clause(&[!x, s[0]]);
+        for j in 0..k {
+            if i > 0 {
+                f.add_clause(&[!prev[j], s[j]]);
+                if j > 0 { f.add_clause(&[!x, !prev[j-1], s[j]]) }
+            } else if j > 0 {
+                f.add_clause(&[!s[j]]);
+            }
+        }
+        prev = s;
+    }
+}
+
+/// Build a sequential unary counter of the true `lits`, up to `width`: the `j`-th literal
+/// of the result holds if and only if at least `j+1` of them are true. Unlike [`sequential`],
+/// the registers are defined in both directions, so that the count can be bounded from below.
+/// About `n*width` helper variables, and four times as many clauses.
+fn counter<F: ExtendFormula>(f: &mut F, lits: &[Lit], width: usize) -> Vec<Lit> {
+    let mut prev: Vec<Lit> = vec![];
+
+    for (i, &x) in lits.iter().enumerate() {
+        let s: Vec<Lit> = (0..width.min(i + 1)).map(|_| f.new_lit()).collect();
+        for (j, &sj) in s.iter().enumerate() {
+            // Reached before x, or one less before x and x is true
+            let before = prev.get(j).copied();
+            let below = if j > 0 { Some(prev[j-1]) } else { None };
+
+            if let Some(p) = before { f.add_clause(&[!p, sj]) }
+            match below {
+                Some(p) => f.add_clause(&[!x, !p, sj]),
+                None => f.add_clause(&[!x, sj]),
+            }
+            let mut clause = vec![!sj, x];
+            clause.extend(before);
+            f.add_clause(&clause);
+            if let Some(p) = below {
+                let mut clause = vec![!sj, p];
+                clause.extend(before);
+                f.add_clause(&clause);
+            }
+        }
+        prev = s;
+    }
+
+    prev
+}
+
+/// Build a unary counter of the true `lits`: the `j`-th literal of the result
+/// holds if and only if at least `j+1` of them are true.
+fn totalizer<F: ExtendFormula>(f: &mut F, lits: &[Lit]) -> Vec<Lit> {
+    if lits.len() <= 1 { return lits.to_vec() }
+
+    let (left, right) = lits.split_at(lits.len() / 2);
+    let (a, b) = (totalizer(f, left), totalizer(f, right));
+    let sum: Vec<Lit> = (0..lits.len()).map(|_| f.new_lit()).collect();
+
+    // With a[i] standing for "at least i+1 on the left", and the same for b,
+    // i left and j right give at least i+j; fewer than i+1 and j+1 give at most i+j.
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            if i + j > 0 {
+                let mut clause = vec![sum[i+j-1]];
+                if i > 0 { clause.push(!a[i-1]) }
+                if j > 0 { clause.push(!b[j-1]) }
+                f.add_clause(&clause);
+            }
+            if i + j < lits.len() {
+                let mut clause = vec![!sum[i+j]];
+                if i < a.len() { clause.push(a[i]) }
+                if j < b.len() { clause.push(b[j]) }
+                f.add_clause(&clause);
+            }
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{backend::Solver, solve::Models};
+
+    /// Count the assignments of `n` literals allowed by the constraint
+    fn count(n: usize, constrain: impl Fn(&mut Solver, &[Lit])) -> usize {
+        let mut solver = Solver::new();
+        let lits: Vec<Lit> = (0..n).map(|_| solver.new_lit()).collect();
+        constrain(&mut solver, &lits);
+
+        Models::new(&mut solver, lits.iter().map(|l| l.var()).collect()).try_count().unwrap()
+    }
+
+    fn binomial(n: usize, k: usize) -> usize {
+        (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+    }
+
+    #[test]
+    fn at_most_one() {
+        for n in [1, 5, 8, 9, 17, 40] {
+            assert_eq!(count(n, |s, l| s.at_most_one(l)), n + 1, "{}", n);
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let n = 5;
+        for encoding in [Encoding::Binomial, Encoding::Sequential, Encoding::Totalizer] {
+            for k in 0..=n+1 {
+                let exactly = if k <= n { binomial(n, k) } else { 0 };
+                let at_most: usize = (0..=k.min(n)).map(|j| binomial(n, j)).sum();
+                let at_least: usize = (k..=n).map(|j| binomial(n, j)).sum();
+
+                assert_eq!(count(n, |s, l| s.exactly_k(l, k, encoding)), exactly, "{:?} exactly {}", encoding, k);
+                assert_eq!(count(n, |s, l| s.at_most_k(l, k, encoding)), at_most, "{:?} at most {}", encoding, k);
+                assert_eq!(count(n, |s, l| s.at_least_k(l, k, encoding)), at_least, "{:?} at least {}", encoding, k);
+            }
+        }
+    }
+}
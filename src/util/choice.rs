@@ -1,46 +1,198 @@
-pub struct Choose(Option<Vec<bool>>);
+use std::ops::Range;
 
+/// The ways to choose `k` items among `n`, as masks of `n` booleans, with the chosen
+/// items first: `[true, true, false]`, then `[true, false, true]`, and so on. The mask
+/// is updated in place, so it is only borrowed until the next choice, and no choice
+/// allocates anything.
+///
+/// Choices are numbered in this order, from 0 to `binomial(n, k) - 1`: any of them can
+/// be reached directly, to draw a random one without going through the others.
+///
+/// Since every choice borrows the mask, this cannot be an [`Iterator`]. It still counts
+/// its choices like [`Combinations`] does, with [`len`](Choose::len) and
+/// [`size_hint`](Choose::size_hint).
+pub struct Choose {
+    mask: Vec<bool>,
+    k: usize,
+    started: bool,
+    done: bool,
+    /// Number of choices not seen yet
+    remaining: usize,
+}
+
+/// Number of ways to choose `k` items among `n`, or `usize::MAX` if there are more.
+/// Ranks are only meaningful below that.
+pub fn binomial(n: usize, k: usize) -> usize {
+    if k > n { return 0 }
+    let k = k.min(n - k);
+    // Each partial product is itself a binomial coefficient, so the division is exact
+    (0..k).try_fold(1u128, |acc, i| Some(acc.checked_mul((n - i) as u128)? / (i + 1) as u128))
+        .and_then(|b| b.try_into().ok())
+        .unwrap_or(usize::MAX)
+}
+
+/// The indices chosen by choice number `rank` of `k` items among `n`, in increasing order
+fn unrank(n: usize, k: usize, mut rank: usize) -> impl Iterator<Item=usize> {
+    let mut next = 0;
+    (0..k).map(move |i| {
+        // Choices where the i-th index is `next` come before those where it is larger
+        loop {
+            let with_next = binomial(n - next - 1, k - i - 1);
+            if rank < with_next { break }
+            rank -= with_next;
+            next += 1;
+        }
+        next += 1;
+        next - 1
+    })
+}
 
 impl Choose {
     pub fn new(n: usize, k: usize) -> Self {
-        if k > n { return Choose(None) }
-        let mut state = vec![false; n];
-        for b in &mut state[0..k] { *b = true }
-        Choose(Some(state))
+        let mut mask = vec![false; n];
+        for b in mask.iter_mut().take(k) { *b = true }
+        Choose { mask, k, started: false, done: k > n, remaining: binomial(n, k) }
     }
 
-}
+    /// Number of choices left
+    pub fn len(&self) -> usize {
+        self.remaining
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
 
-fn advance(state: &Vec<bool>) -> Option<Vec<bool>> {
-    let mut seek = state.iter().cloned().enumerate().rev(); 
-    let zero = seek.find(|b| !b.1)?.0;
-    let one = seek.find(|b| b.1)?.0;
-    let tail = state.len() - zero;
+    /// Bounds on the number of choices left, exact like those of [`Iterator::size_hint`]
+    /// for an [`ExactSizeIterator`]
+    pub fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 
-    let mut r = state.clone();
-    r[one] = false;
-    for b in &mut r[zero+1..] { *b = false }
-    for b in &mut r[one+1..][..tail] { *b = true }
+    /// The next choice, or `None` once they have all been seen
+    pub fn next_choice(&mut self) -> Option<&[bool]> {
+        if self.started && !self.done {
+            self.done = !advance(&mut self.mask);
+        }
+        self.started = true;
+        if self.done { return None }
+        self.remaining -= 1;
+        Some(&self.mask)
+    }
 
-    Some(r)
+    /// Skip `i` choices and return the next one, like [`Iterator::nth`], without going
+    /// through the skipped ones
+    pub fn nth_choice(&mut self, i: usize) -> Option<&[bool]> {
+        if i >= self.remaining {
+            self.done = true;
+            self.remaining = 0;
+            return None
+        }
+        let n = self.mask.len();
+        let rank = binomial(n, self.k) - self.remaining + i;
+        self.mask.fill(false);
+        for j in unrank(n, self.k, rank) { self.mask[j] = true }
+        self.started = true;
+        self.remaining -= i + 1;
+        Some(&self.mask)
+    }
+
+    /// Choice number `rank` of `k` items among `n`, or `None` if there are not so many
+    pub fn unrank(n: usize, k: usize, rank: usize) -> Option<Vec<bool>> {
+        Choose::new(n, k).nth_choice(rank).map(<[bool]>::to_vec)
+    }
+
+    /// Call `f` with every choice in turn
+    pub fn for_each(mut self, mut f: impl FnMut(&[bool])) {
+        while let Some(mask) = self.next_choice() {
+            f(mask)
+        }
+    }
+}
+
+/// The ways to choose `K` distinct indices in a range, as sorted arrays in lexicographic
+/// order: `[0, 1, 2]`, then `[0, 1, 3]`, and so on. Unlike [`Choose`], the chosen indices
+/// are listed rather than masked, which suits small `K` known in advance, such as
+/// the pairs or triples of cells of a line.
+pub struct Combinations<const K: usize> {
+    next: Option<[usize; K]>,
+    range: Range<usize>,
+    remaining: usize,
 }
 
-impl Iterator for Choose {
-    type Item = Vec<bool>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let current = self.0.take()?;
-        self.0 = advance(&current);
+/// All the `K`-combinations of the indices in `range`
+pub fn combinations<const K: usize>(range: Range<usize>) -> Combinations<K> {
+    let first = std::array::from_fn(|i| range.start + i);
+    let fits = range.start + K <= range.end;
+    let remaining = binomial(range.len(), K);
+    Combinations { next: fits.then_some(first), range, remaining }
+}
+
+impl<const K: usize> Iterator for Combinations<K> {
+    type Item = [usize; K];
+
+    fn next(&mut self) -> Option<[usize; K]> {
+        let current = self.next?;
+        // Move the last index that can still move, and bring the following ones behind it
+        let mut next = current;
+        let end = self.range.end;
+        self.next = (0..K).rev().find(|&i| next[i] < end - K + i).map(|i| {
+            next[i] += 1;
+            for j in i + 1..K { next[j] = next[j - 1] + 1 }
+            next
+        });
+        self.remaining -= 1;
         Some(current)
-    } 
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn nth(&mut self, i: usize) -> Option<[usize; K]> {
+        if i >= self.remaining {
+            self.next = None;
+            self.remaining = 0;
+            return None
+        }
+        let n = self.range.len();
+        let rank = binomial(n, K) - self.remaining + i;
+        let mut indices = unrank(n, K, rank).map(|j| self.range.start + j);
+        self.next = Some(std::array::from_fn(|_| indices.next().expect("K indices")));
+        self.remaining -= i;
+        self.next()
+    }
+}
+
+impl<const K: usize> ExactSizeIterator for Combinations<K> {}
+
+/// Move the last chosen item that can move one step further, and bring the ones
+/// after it right behind it. Returns `false`, leaving the mask as it is, after the last choice.
+fn advance(mask: &mut [bool]) -> bool {
+    let mut seek = mask.iter().copied().enumerate().rev();
+    let Some((zero, _)) = seek.find(|b| !b.1) else { return false };
+    let Some((one, _)) = seek.find(|b| b.1) else { return false };
+    let tail = mask.len() - zero;
+
+    mask[one] = false;
+    for b in &mut mask[zero+1..] { *b = false }
+    for b in &mut mask[one+1..][..tail] { *b = true }
+    true
 }
 
 #[cfg(test)]
 mod test {
-    use super::Choose;
+    use super::{binomial, combinations, Choose};
+
+    fn choices(n: usize, k: usize) -> Vec<Vec<bool>> {
+        let mut r = vec![];
+        Choose::new(n, k).for_each(|c| r.push(c.to_vec()));
+        r
+    }
 
     #[test]
     fn choose_5_2() {
-        let choices: Vec<Vec<bool>> = Choose::new(5, 2).collect();
+        let choices = choices(5, 2);
         let ptrs: Vec<&[bool]> = choices.iter().map(|v| &**v).collect();
 
         assert_eq!(&ptrs, &[ &[true, true, false, false, false],
@@ -55,4 +207,69 @@ mod test {
                              &[false, false, false, true, true],
                              ]);
     }
+
+    #[test]
+    fn index_combinations() {
+        let triples: Vec<[usize; 3]> = combinations(2..6).collect();
+        assert_eq!(triples, [[2, 3, 4], [2, 3, 5], [2, 4, 5], [3, 4, 5]]);
+        assert_eq!(combinations::<2>(0..10).count(), 45);
+        assert_eq!(combinations::<0>(0..3).collect::<Vec<_>>(), [[]]);
+        assert_eq!(combinations::<3>(0..2).count(), 0);
+
+        // The same order as the masks
+        let masks: Vec<Vec<usize>> = {
+            let mut r = vec![];
+            Choose::new(6, 3).for_each(|c| r.push((0..6).filter(|&i| c[i]).collect()));
+            r
+        };
+        let lists: Vec<Vec<usize>> = combinations::<3>(0..6).map(|c| c.to_vec()).collect();
+        assert_eq!(lists, masks);
+    }
+
+    #[test]
+    fn ranks() {
+        assert_eq!(binomial(5, 2), 10);
+        assert_eq!(binomial(60, 30), 118264581564861424);
+        assert_eq!(binomial(3, 4), 0);
+        assert_eq!(binomial(0, 0), 1);
+        assert_eq!(binomial(200, 100), usize::MAX);
+
+        let all = choices(6, 3);
+        for (rank, choice) in all.iter().enumerate() {
+            assert_eq!(Choose::unrank(6, 3, rank).as_ref(), Some(choice));
+        }
+        assert_eq!(Choose::unrank(6, 3, 20), None);
+
+        let mut choose = Choose::new(6, 3);
+        assert_eq!(choose.len(), 20);
+        choose.next_choice();
+        assert_eq!(choose.nth_choice(4), Some(&all[5][..]));
+        assert_eq!(choose.next_choice(), Some(&all[6][..]));
+        assert_eq!(choose.len(), 13);
+        assert_eq!(choose.size_hint(), (13, Some(13)));
+        assert_eq!(choose.size_hint(), combinations::<3>(0..6).skip(7).size_hint());
+        assert_eq!(choose.nth_choice(13), None);
+        assert!(choose.is_empty() && choose.next_choice().is_none());
+
+        let mut pairs = combinations::<2>(3..8);
+        assert_eq!(pairs.len(), 10);
+        assert_eq!(pairs.nth(3), Some([3, 7]));
+        assert_eq!(pairs.size_hint(), (6, Some(6)));
+        assert_eq!(pairs.next(), Some([4, 5]));
+        assert_eq!(pairs.nth(5), None);
+        assert_eq!(pairs.next(), None);
+    }
+
+    #[test]
+    fn edge_cases() {
+        assert_eq!(choices(5, 0), vec![vec![false, false, false, false, false]]);
+        assert_eq!(choices(5, 5), vec![vec![true, true, true, true, true]]);
+        assert_eq!(choices(0, 0), vec![Vec::<bool>::new()]);
+        assert!(choices(2, 3).is_empty());
+
+        let mut choose = Choose::new(3, 1);
+        for _ in 0..3 { assert!(choose.next_choice().is_some()) }
+        assert!(choose.next_choice().is_none());
+        assert!(choose.next_choice().is_none());
+    }
 }
@@ -0,0 +1,149 @@
+//! Connectivity constraints: the cells holding a true literal must form a single
+//! connected region, as needed by games like Nurikabe, Hitori, Yin-Yang or Archipel.
+//!
+//! The encoding propagates reachability from a root, one step at a time. The root is the
+//! first true node, and `reach[k][i]` can only hold if node `i` is true, and either
+//! was already reached at step `k-1`, or has a neighbor that was. Every true node must
+//! be reached after as many steps as there are nodes, which is always enough.
+
+use varisat::{ExtendFormula, Lit};
+
+use super::{coord::Coord, matrix::Matrix};
+
+pub trait ConnectFormula: ExtendFormula {
+
+    /// Require the true `nodes` to form a connected subgraph of the graph given by `edges`,
+    /// where an edge joins two indices of `nodes`. No true node at all counts as connected.
+    fn add_connected(&mut self, nodes: &[Lit], edges: &[(usize, usize)]) {
+        let mut neighbors = vec![vec![]; nodes.len()];
+        for &(a, b) in edges {
+            neighbors[a].push((b, None));
+            neighbors[b].push((a, None));
+        }
+        connect(self, nodes, &neighbors);
+    }
+
+    /// Require the true `nodes` to be connected through the true `edges`, where an edge
+    /// joins two indices of `nodes` and can only be used when its literal is true.
+    fn add_connected_by(&mut self, nodes: &[Lit], edges: &[(usize, usize, Lit)]) {
+        let mut neighbors = vec![vec![]; nodes.len()];
+        for &(a, b, e) in edges {
+            neighbors[a].push((b, Some(e)));
+            neighbors[b].push((a, Some(e)));
+        }
+        connect(self, nodes, &neighbors);
+    }
+
+    /// Require the true cells of `grid` to form a single region, connected through
+    /// the edges of the cells.
+    fn add_connected_grid(&mut self, grid: &Matrix<Lit>) {
+        let (_, w) = grid.shape();
+        let index = |c: Coord| c.x * w + c.y;
+
+        let nodes: Vec<Lit> = grid.iter().copied().collect();
+        let edges: Vec<_> = grid.coords()
+            .flat_map(|c| grid.adjacent(c).into_iter()
+                .filter(move |&n| n > c)
+                .map(move |n| (index(c), index(n))))
+            .collect();
+
+        self.add_connected(&nodes, &edges);
+    }
+}
+
+impl<T: ExtendFormula> ConnectFormula for T {}
+
+/// Propagate reachability from the first true node along `neighbors`, each neighbor
+/// coming with the literal of the edge leading to it, if the edge can be missing.
+fn connect<F: ExtendFormula>(f: &mut F, nodes: &[Lit], neighbors: &[Vec<(usize, Option<Lit>)>]) {
+    let n = nodes.len();
+
+    // before[i] holds if some node preceding i is true
+    let before: Vec<Lit> = (0..n).map(|_| f.new_lit()).collect();
+    for i in 1..n {
+        f.add_clause(&[!nodes[i-1], before[i]]);
+        f.add_clause(&[!before[i-1], before[i]]);
+    }
+
+    // Only the first true node is reached at step 0
+    let mut reach: Vec<Lit> = (0..n).map(|_| f.new_lit()).collect();
+    for i in 0..n {
+        f.add_clause(&[!reach[i], nodes[i]]);
+        f.add_clause(&[!reach[i], !before[i]]);
+    }
+
+    for _ in 1..n {
+        let next: Vec<Lit> = (0..n).map(|_| f.new_lit()).collect();
+        for i in 0..n {
+            f.add_clause(&[!next[i], nodes[i]]);
+
+            let mut justified = vec![!next[i], reach[i]];
+            for &(j, edge) in &neighbors[i] {
+                match edge {
+                    None => justified.push(reach[j]),
+                    Some(e) => {
+                        // Reached through the edge: reach[j] & e
+                        let through = f.new_lit();
+                        f.add_clause(&[!through, reach[j]]);
+                        f.add_clause(&[!through, e]);
+                        justified.push(through);
+                    },
+                }
+            }
+            f.add_clause(&justified);
+        }
+        reach = next;
+    }
+
+    for i in 0..n {
+        f.add_clause(&[!nodes[i], reach[i]]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{backend::Solver, region::Region, solve::{solve_assuming, Models}};
+
+    /// Every model of the grid, as the list of its true cells
+    fn models(shape: (usize, usize)) -> Vec<Vec<Coord>> {
+        let mut solver = Solver::new();
+        let lits = solver.new_var_iter(shape.0 * shape.1).map(|v| v.positive()).collect();
+        let grid = Matrix::new(lits, shape).unwrap();
+        solver.add_connected_grid(&grid);
+
+        let vars = grid.iter().map(|l| l.var()).collect();
+        let mut found: Vec<Vec<Coord>> = Models::new(&mut solver, vars)
+            .map(Result::unwrap)
+            .map(|m| grid.coords().filter(|&c| m.contains(&grid[c])).collect())
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn connected_subsets() {
+        let shape = (2, 3);
+        let cells: Vec<_> = Matrix::new(vec![(); 6], shape).unwrap().coords().collect();
+
+        let mut expected: Vec<Vec<Coord>> = (0..1 << cells.len())
+            .map(|bits: usize| cells.iter().enumerate().filter(|(i, _)| bits & (1 << i) != 0).map(|(_, &c)| c).collect())
+            .filter(|subset: &Vec<_>| subset.is_empty() || Region(subset.clone()).is_connected())
+            .collect();
+        expected.sort();
+
+        assert_eq!(models(shape), expected);
+    }
+
+    #[test]
+    fn path() {
+        // A path of 4 nodes, with both ends true: the middle ones are forced
+        let mut solver = Solver::new();
+        let nodes: Vec<Lit> = (0..4).map(|_| solver.new_lit()).collect();
+        solver.add_connected(&nodes, &[(0,1), (1,2), (2,3)]);
+
+        let m = solve_assuming(&mut solver, &[nodes[0], nodes[3]]).unwrap().unwrap();
+        assert!(m.contains(&nodes[1]) && m.contains(&nodes[2]));
+        assert!(solve_assuming(&mut solver, &[nodes[0], !nodes[2], nodes[3]]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,233 @@
+//! Positions on a grid. A [`Coord`] holds a row `x` and a column `y`, in the same order
+//! as the indices of a [`Matrix`](super::matrix::Matrix), and converts to and from
+//! `(x, y)` tuples, which methods such as `Matrix::neighbors` also accept.
+
+use std::{borrow::Cow, fmt::Display};
+
+/// A cell of a grid, at row `x` and column `y`. Coordinates are ordered row by row.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Coord {
+    pub x: usize,
+    pub y: usize,
+}
+
+/// The eight directions to a neighboring cell. Rows grow downwards, columns to the right.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction {
+    /// The directions sharing an edge with the cell
+    pub const ORTHOGONAL: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+    /// The directions sharing only a corner with the cell
+    pub const DIAGONAL: [Direction; 4] = [Direction::UpRight, Direction::DownRight, Direction::DownLeft, Direction::UpLeft];
+
+    /// All the directions, clockwise from `Up`
+    pub const ALL: [Direction; 8] = [
+        Direction::Up, Direction::UpRight, Direction::Right, Direction::DownRight,
+        Direction::Down, Direction::DownLeft, Direction::Left, Direction::UpLeft,
+    ];
+
+    /// The change in row and column when moving one step in this direction
+    pub fn delta(self) -> (isize, isize) {
+        use Direction::*;
+        match self {
+            Up => (-1, 0),
+            UpRight => (-1, 1),
+            Right => (0, 1),
+            DownRight => (1, 1),
+            Down => (1, 0),
+            DownLeft => (1, -1),
+            Left => (0, -1),
+            UpLeft => (-1, -1),
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        let i = Direction::ALL.iter().position(|&d| d == self).expect("listed in ALL");
+        Direction::ALL[(i + 4) % 8]
+    }
+}
+
+impl Coord {
+    pub const fn new(x: usize, y: usize) -> Self {
+        Coord { x, y }
+    }
+
+    /// The cell `dx` rows and `dy` columns away, unless that goes below 0
+    pub fn offset(self, dx: isize, dy: isize) -> Option<Coord> {
+        Some(Coord { x: self.x.checked_add_signed(dx)?, y: self.y.checked_add_signed(dy)? })
+    }
+
+    /// The next cell in `direction`, on an unbounded grid
+    pub fn neighbor(self, direction: Direction) -> Option<Coord> {
+        let (dx, dy) = direction.delta();
+        self.offset(dx, dy)
+    }
+
+    /// The next cell in `direction`, if it is still within a grid of the given shape
+    pub fn step(self, direction: Direction, shape: (usize, usize)) -> Option<Coord> {
+        self.neighbor(direction).filter(|c| c.within(shape))
+    }
+
+    /// True if the cell belongs to a grid of the given shape
+    pub fn within(self, (h, w): (usize, usize)) -> bool {
+        self.x < h && self.y < w
+    }
+
+    /// Number of orthogonal steps between the two cells
+    pub fn manhattan(self, other: Coord) -> usize {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    /// Number of king moves between the two cells: 1 for every neighbor, diagonals included
+    pub fn chebyshev(self, other: Coord) -> usize {
+        self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
+    }
+
+    /// The cells sharing an edge with this one, within a grid of the given shape,
+    /// in row-major order
+    pub fn adjacent(self, shape: (usize, usize)) -> impl Iterator<Item=Coord> {
+        [Direction::Up, Direction::Left, Direction::Right, Direction::Down].into_iter()
+            .filter_map(move |d| self.step(d, shape))
+    }
+}
+
+/// The cells around any given one, as offsets from it. Games differ on what a neighbor
+/// is: cells sharing an edge, a corner, either of them, the cell itself included or not,
+/// or even a knight's move away. A toroidal neighborhood wraps around the edges of the
+/// grid instead of stopping there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Neighborhood {
+    offsets: Cow<'static, [(isize, isize)]>,
+    toroidal: bool,
+}
+
+impl Neighborhood {
+    /// The cells sharing an edge
+    pub const ORTHOGONAL: Neighborhood = Neighborhood::fixed(&[(-1, 0), (0, -1), (0, 1), (1, 0)]);
+
+    /// The cells sharing only a corner
+    pub const DIAGONAL: Neighborhood = Neighborhood::fixed(&[(-1, -1), (-1, 1), (1, -1), (1, 1)]);
+
+    /// The cells sharing an edge or a corner, one king move away
+    pub const KING: Neighborhood = Neighborhood::fixed(&[
+        (-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1),
+    ]);
+
+    /// The 3×3 block centered on the cell, the cell included
+    pub const BLOCK: Neighborhood = Neighborhood::fixed(&[
+        (-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 0), (0, 1), (1, -1), (1, 0), (1, 1),
+    ]);
+
+    const fn fixed(offsets: &'static [(isize, isize)]) -> Self {
+        Neighborhood { offsets: Cow::Borrowed(offsets), toroidal: false }
+    }
+
+    /// The cells at the given (row, column) offsets
+    pub fn custom(offsets: impl Into<Vec<(isize, isize)>>) -> Self {
+        Neighborhood { offsets: Cow::Owned(offsets.into()), toroidal: false }
+    }
+
+    /// The same neighborhood, wrapping around the edges of the grid
+    pub fn toroidal(self) -> Self {
+        Neighborhood { toroidal: true, ..self }
+    }
+
+    /// The neighbors of `pos` within a grid of the given shape, in row-major order.
+    /// On a toroidal grid smaller than the neighborhood, a cell reached by two offsets
+    /// is only listed once.
+    pub fn around(&self, pos: Coord, shape: (usize, usize)) -> Vec<Coord> {
+        let wrap = |v: usize, d: isize, len: usize| match self.toroidal {
+            true if len > 0 => Some((v as isize + d).rem_euclid(len as isize) as usize),
+            _ => v.checked_add_signed(d).filter(|&v| v < len),
+        };
+        let mut cells: Vec<Coord> = self.offsets.iter()
+            .filter_map(|&(dx, dy)| Some(Coord::new(wrap(pos.x, dx, shape.0)?, wrap(pos.y, dy, shape.1)?)))
+            .collect();
+        cells.sort();
+        cells.dedup();
+        cells
+    }
+}
+
+impl From<(usize, usize)> for Coord {
+    fn from((x, y): (usize, usize)) -> Self {
+        Coord { x, y }
+    }
+}
+
+impl From<Coord> for (usize, usize) {
+    fn from(c: Coord) -> Self {
+        (c.x, c.y)
+    }
+}
+
+impl PartialEq<(usize, usize)> for Coord {
+    fn eq(&self, other: &(usize, usize)) -> bool {
+        (self.x, self.y) == *other
+    }
+}
+
+/// Printed as `(x,y)`, like the tuples
+impl Display for Coord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({},{})", self.x, self.y)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn steps() {
+        let shape = (3, 4);
+        let c = Coord::new(0, 3);
+        assert_eq!(c.step(Direction::Left, shape), Some(Coord::new(0, 2)));
+        assert_eq!(c.step(Direction::Up, shape), None);
+        assert_eq!(c.step(Direction::Right, shape), None);
+        assert_eq!(c.step(Direction::DownLeft, shape), Some(Coord::new(1, 2)));
+        assert_eq!(c.adjacent(shape).collect::<Vec<_>>(), [Coord::new(0, 2), Coord::new(1, 3)]);
+
+        for d in Direction::ALL {
+            let back = c.offset(5, 5).and_then(|m| m.step(d, (10, 10))).and_then(|m| m.step(d.opposite(), (10, 10)));
+            assert_eq!(back, c.offset(5, 5));
+        }
+    }
+
+    #[test]
+    fn neighborhoods() {
+        let shape = (3, 4);
+        let corner = Coord::new(0, 0);
+        let list = |v: Vec<Coord>| v.into_iter().map(<(usize, usize)>::from).collect::<Vec<_>>();
+        assert_eq!(list(Neighborhood::ORTHOGONAL.around(corner, shape)), [(0, 1), (1, 0)]);
+        assert_eq!(list(Neighborhood::DIAGONAL.around(corner, shape)), [(1, 1)]);
+        assert_eq!(list(Neighborhood::KING.around(corner, shape)), [(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(Neighborhood::BLOCK.around(Coord::new(1, 1), shape).len(), 9);
+        assert_eq!(list(Neighborhood::ORTHOGONAL.toroidal().around(corner, shape)), [(0, 1), (0, 3), (1, 0), (2, 0)]);
+        assert_eq!(Neighborhood::KING.toroidal().around(corner, (2, 2)).len(), 3);
+
+        let knight = Neighborhood::custom([(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)]);
+        assert_eq!(list(knight.around(corner, shape)), [(1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn distances() {
+        let (a, b) = (Coord::new(1, 1), Coord::new(3, 0));
+        assert_eq!(a.manhattan(b), 3);
+        assert_eq!(a.chebyshev(b), 2);
+        assert_eq!(Coord::from((2, 5)), (2, 5));
+        assert_eq!(Coord::new(2, 5).to_string(), "(2,5)");
+        assert!(Coord::new(0, 9) < Coord::new(1, 0));
+    }
+}
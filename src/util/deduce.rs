@@ -0,0 +1,79 @@
+//! Human-style solving. Instead of handing the whole puzzle to the SAT solver,
+//! simple local rules are applied one at a time, and every filled cell is recorded
+//! along with the reason it was forced. This only solves easy puzzles, but it
+//! can show *how* they are solved.
+
+use super::{coord::Coord, matrix::Matrix};
+
+/// A single deduction: a cell, the value it must hold, and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step<T> {
+    pub cell: Coord,
+    pub value: T,
+    pub reason: String,
+}
+
+/// A rule looks at the partially filled grid, and returns the next cell it can fill, if any.
+pub type Rule<'a, T> = &'a dyn Fn(&Matrix<Option<T>>) -> Option<Step<T>>;
+
+/// The outcome of a deduction run: the steps taken in order,
+/// and the grid as filled by these steps.
+#[derive(Clone, Debug)]
+pub struct Deduction<T> {
+    pub steps: Vec<Step<T>>,
+    pub grid: Matrix<Option<T>>,
+}
+
+impl<T> Deduction<T> {
+    /// True if the rules were enough to fill every cell
+    pub fn is_complete(&self) -> bool {
+        self.grid.iter().all(Option::is_some)
+    }
+}
+
+/// Fill the grid by applying the rules until none of them makes progress.
+/// Rules are tried in order, and the search restarts from the first rule after every
+/// step, so that simpler rules are always preferred.
+pub fn deduce<T: Clone>(mut grid: Matrix<Option<T>>, rules: &[Rule<T>]) -> Deduction<T> {
+    let mut steps = vec![];
+
+    while let Some(step) = rules.iter().find_map(|rule| rule(&grid)) {
+        grid[step.cell] = Some(step.value.clone());
+        steps.push(step);
+    }
+
+    Deduction { steps, grid }
+}
+
+/// All the rows, then all the columns of a grid with the given shape,
+/// each with a name suitable for explanations.
+pub fn lines(shape: (usize, usize)) -> Vec<(String, Vec<Coord>)> {
+    let (h, w) = shape;
+    let rows = (0..h).map(|x| (format!("row {}", x), (0..w).map(|y| Coord::new(x, y)).collect()));
+    let columns = (0..w).map(|y| (format!("column {}", y), (0..h).map(|x| Coord::new(x, y)).collect()));
+    rows.chain(columns).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::matrix::umat;
+
+    #[test]
+    fn propagate() {
+        // Copy the value of the cell on the left
+        let copy = |g: &Matrix<Option<u8>>| {
+            g.indices()
+             .filter(|&(x, y)| y > 0 && g[(x,y)].is_none())
+             .find_map(|(x, y)| Some(Step { cell: Coord::new(x, y), value: g[x][y-1]?, reason: "copy".into() }))
+        };
+
+        let mut grid = umat![None; (2, 3)];
+        grid[0][0] = Some(1);
+
+        let d = deduce(grid, &[&copy]);
+        assert_eq!(d.steps.len(), 2);
+        assert_eq!(d.steps[1], Step { cell: Coord::new(0, 2), value: 1, reason: "copy".into() });
+        assert!(!d.is_complete());
+    }
+}
@@ -0,0 +1,231 @@
+//! Boolean expressions over literals, written with the usual operators, such as
+//! `(a & b) | !c` or `a.implies(b ^ c)`, and turned into clauses by the Tseitin
+//! transformation: every compound subexpression gets a helper literal equivalent to it,
+//! defined by a few clauses, so the formula stays linear in the size of the expression.
+
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use varisat::{ExtendFormula, Lit};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Const(bool),
+    Lit(Lit),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    Implies(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// True when all the expressions are, including when there are none
+    pub fn all(exprs: impl IntoIterator<Item=impl Into<Expr>>) -> Expr {
+        Expr::And(exprs.into_iter().map(Into::into).collect())
+    }
+
+    /// True when one of the expressions is, so never when there are none
+    pub fn any(exprs: impl IntoIterator<Item=impl Into<Expr>>) -> Expr {
+        Expr::Or(exprs.into_iter().map(Into::into).collect())
+    }
+
+    pub fn implies(self, other: impl Into<Expr>) -> Expr {
+        Expr::Implies(Box::new(self), Box::new(other.into()))
+    }
+
+    /// The value of the expression, given the value of every literal
+    pub fn eval(&self, value: &impl Fn(Lit) -> bool) -> bool {
+        match self {
+            Expr::Const(b) => *b,
+            Expr::Lit(l) => value(*l),
+            Expr::Not(e) => !e.eval(value),
+            Expr::And(es) => es.iter().all(|e| e.eval(value)),
+            Expr::Or(es) => es.iter().any(|e| e.eval(value)),
+            Expr::Xor(a, b) => a.eval(value) != b.eval(value),
+            Expr::Implies(a, b) => !a.eval(value) || b.eval(value),
+        }
+    }
+}
+
+impl From<Lit> for Expr {
+    fn from(lit: Lit) -> Self {
+        Expr::Lit(lit)
+    }
+}
+
+impl From<bool> for Expr {
+    fn from(b: bool) -> Self {
+        Expr::Const(b)
+    }
+}
+
+impl Not for Expr {
+    type Output = Expr;
+
+    fn not(self) -> Expr {
+        match self {
+            Expr::Lit(l) => Expr::Lit(!l),
+            Expr::Not(e) => *e,
+            e => Expr::Not(Box::new(e)),
+        }
+    }
+}
+
+/// Chains of `&` are gathered in a single conjunction
+impl<T: Into<Expr>> BitAnd<T> for Expr {
+    type Output = Expr;
+
+    fn bitand(self, other: T) -> Expr {
+        let mut terms = match self {
+            Expr::And(es) => es,
+            e => vec![e],
+        };
+        terms.push(other.into());
+        Expr::And(terms)
+    }
+}
+
+/// Chains of `|` are gathered in a single disjunction
+impl<T: Into<Expr>> BitOr<T> for Expr {
+    type Output = Expr;
+
+    fn bitor(self, other: T) -> Expr {
+        let mut terms = match self {
+            Expr::Or(es) => es,
+            e => vec![e],
+        };
+        terms.push(other.into());
+        Expr::Or(terms)
+    }
+}
+
+impl<T: Into<Expr>> BitXor<T> for Expr {
+    type Output = Expr;
+
+    fn bitxor(self, other: T) -> Expr {
+        Expr::Xor(Box::new(self), Box::new(other.into()))
+    }
+}
+
+pub trait ExprFormula: ExtendFormula {
+
+    /// Require `expr` to be true. Conjunctions are split into separate constraints,
+    /// and a disjunction becomes a single clause, with helpers for its compound terms only.
+    fn add_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Const(true) => {},
+            Expr::And(es) => for e in es { self.add_expr(e) },
+            Expr::Or(es) => {
+                let clause: Vec<_> = es.iter().map(|e| tseitin(self, e)).collect();
+                self.add_clause(&clause);
+            },
+            e => {
+                let lit = tseitin(self, e);
+                self.add_clause(&[lit]);
+            },
+        }
+    }
+
+    /// A literal equivalent to `expr`, which can be used in further constraints
+    fn expr_lit(&mut self, expr: &Expr) -> Lit {
+        tseitin(self, expr)
+    }
+}
+
+impl<T: ExtendFormula> ExprFormula for T {}
+
+/// Return a literal equivalent to `expr`, adding the clauses defining the helpers
+fn tseitin<F: ExtendFormula>(f: &mut F, expr: &Expr) -> Lit {
+    match expr {
+        Expr::Lit(l) => *l,
+        Expr::Not(e) => !tseitin(f, e),
+        Expr::Const(b) => {
+            let v = f.new_lit();
+            f.add_clause(&[if *b { v } else { !v }]);
+            v
+        },
+        Expr::And(es) => {
+            let terms: Vec<_> = es.iter().map(|e| tseitin(f, e)).collect();
+            let v = f.new_lit();
+            // v => every term, and every term => v
+            let mut clause = vec![v];
+            for &t in &terms {
+                f.add_clause(&[!v, t]);
+                clause.push(!t);
+            }
+            f.add_clause(&clause);
+            v
+        },
+        Expr::Or(es) => {
+            let terms: Vec<_> = es.iter().map(|e| tseitin(f, e)).collect();
+            let v = f.new_lit();
+            // any term => v, and v => some term
+            let mut clause = vec![!v];
+            for &t in &terms {
+                f.add_clause(&[v, !t]);
+                clause.push(t);
+            }
+            f.add_clause(&clause);
+            v
+        },
+        Expr::Xor(a, b) => {
+            let (a, b) = (tseitin(f, a), tseitin(f, b));
+            let v = f.new_lit();
+            f.add_clause(&[!v, a, b]);
+            f.add_clause(&[!v, !a, !b]);
+            f.add_clause(&[v, !a, b]);
+            f.add_clause(&[v, a, !b]);
+            v
+        },
+        Expr::Implies(a, b) => {
+            let (a, b) = (tseitin(f, a), tseitin(f, b));
+            let v = f.new_lit();
+            f.add_clause(&[!v, !a, b]);
+            f.add_clause(&[v, a]);
+            f.add_clause(&[v, !b]);
+            v
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{backend::Solver, solve::Models};
+
+    /// Compare the models of the formula over the literals with the truth table of `expr`
+    fn check(n: usize, build: impl Fn(&[Expr]) -> Expr) {
+        let mut solver = Solver::new();
+        let lits: Vec<Lit> = (0..n).map(|_| solver.new_lit()).collect();
+        let expr = build(&lits.iter().map(|&l| Expr::from(l)).collect::<Vec<_>>());
+        solver.add_expr(&expr);
+
+        let expected = (0..1usize << n)
+            .filter(|bits| expr.eval(&|l: Lit| (bits >> l.index() & 1 == 1) == l.is_positive()))
+            .count();
+        let found = Models::new(&mut solver, lits.iter().map(|l| l.var()).collect()).try_count().unwrap();
+        assert_eq!(found, expected, "{:?}", expr);
+    }
+
+    #[test]
+    fn truth_tables() {
+        check(2, |x| x[0].clone() & x[1].clone());
+        check(3, |x| x[0].clone() | x[1].clone() | !x[2].clone());
+        check(3, |x| x[0].clone() ^ x[1].clone() ^ x[2].clone());
+        check(3, |x| x[0].clone().implies(x[1].clone() & x[2].clone()));
+        check(4, |x| !((x[0].clone() | x[1].clone()) & (x[2].clone() ^ x[3].clone())));
+        check(3, |x| Expr::any(x.to_vec()).implies(Expr::all([x[0].clone(), Expr::from(false)]) | x[2].clone()));
+        check(2, |x| x[0].clone() & !x[0].clone() | x[1].clone());
+    }
+
+    #[test]
+    fn helper_literal() {
+        let mut solver = Solver::new();
+        let (a, b) = (solver.new_lit(), solver.new_lit());
+        let both = solver.expr_lit(&(Expr::from(a) & b));
+        let mut models = Models::new(&mut solver, vec![a.var(), b.var()]).assuming(&[both]);
+        let model = models.next().unwrap().unwrap();
+        assert!(model.contains(&a) && model.contains(&b));
+        assert!(models.next().is_none());
+    }
+}
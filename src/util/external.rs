@@ -0,0 +1,190 @@
+//! An external SAT solver run as a subprocess, such as kissat or cryptominisat.
+//!
+//! Every call to `solve` writes the whole formula in DIMACS CNF format to a temporary
+//! file, with the assumptions as unit clauses, and runs the solver on it. The answer is
+//! read back from the standard output, in the format of the SAT competitions: an
+//! `s SATISFIABLE` or `s UNSATISFIABLE` line, and `v` lines listing the model. The exit
+//! status must agree, 10 for satisfiable and 20 for unsatisfiable: anything else, such
+//! as `s UNKNOWN` after a timeout or a crash, is an error.
+//!
+//! Nothing is kept between calls, so this only pays off on hard instances.
+
+use std::{fs, io::Write, path::Path, process::Command, sync::atomic::{AtomicUsize, Ordering}};
+
+use varisat::{Lit, Var};
+
+use super::backend::{SatBackend, BackendError};
+
+pub struct External {
+    command: String,
+    args: Vec<String>,
+    vars: usize,
+    clauses: Vec<Vec<Lit>>,
+    assumptions: Vec<Lit>,
+    model: Option<Vec<Lit>>,
+    core: Option<Vec<Lit>>,
+}
+
+impl External {
+    /// A solver running `command` with the given arguments, followed by the path of the CNF file
+    pub fn new(command: impl Into<String>, args: &[&str]) -> Self {
+        External {
+            command: command.into(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            vars: 0,
+            clauses: vec![],
+            assumptions: vec![],
+            model: None,
+            core: None,
+        }
+    }
+
+    /// Build from a command line such as `kissat -q`, split on whitespace
+    pub fn from_command_line(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let command = words.next()?;
+        let args: Vec<_> = words.collect();
+        Some(Self::new(command, &args))
+    }
+
+    fn dimacs(&self) -> String {
+        let mut out = format!("p cnf {} {}\n", self.vars, self.clauses.len() + self.assumptions.len());
+        let units = self.assumptions.iter().map(std::slice::from_ref);
+        for clause in self.clauses.iter().map(|c| &c[..]).chain(units) {
+            for lit in clause {
+                out.push_str(&lit.to_dimacs().to_string());
+                out.push(' ');
+            }
+            out.push_str("0\n");
+        }
+        out
+    }
+}
+
+static FILES: AtomicUsize = AtomicUsize::new(0);
+
+impl SatBackend for External {
+    fn new_var(&mut self) -> Var {
+        self.vars += 1;
+        Var::from_index(self.vars - 1)
+    }
+
+    fn add_clause(&mut self, clause: &[Lit]) {
+        self.vars = clause.iter().map(|l| l.index() + 1).fold(self.vars, usize::max);
+        self.clauses.push(clause.to_vec());
+    }
+
+    fn assume(&mut self, assumptions: &[Lit]) {
+        self.vars = assumptions.iter().map(|l| l.index() + 1).fold(self.vars, usize::max);
+        self.assumptions = assumptions.to_vec();
+    }
+
+    fn solve(&mut self) -> Result<bool, BackendError> {
+        let file = std::env::temp_dir().join(format!("multilogic-{}-{}.cnf", std::process::id(), FILES.fetch_add(1, Ordering::Relaxed)));
+        let result = self.run(&file);
+        let _ = fs::remove_file(&file);
+
+        let assumptions = std::mem::take(&mut self.assumptions);
+        match result? {
+            Some(model) => { self.model = Some(model); self.core = None; Ok(true) },
+            // Without support from the solver, all the assumptions are the core
+            None => { self.model = None; self.core = Some(assumptions); Ok(false) },
+        }
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        self.model.clone()
+    }
+
+    fn failed_core(&self) -> Option<Vec<Lit>> {
+        self.core.clone()
+    }
+}
+
+impl External {
+    fn run(&self, file: &Path) -> Result<Option<Vec<Lit>>, BackendError> {
+        fs::File::create(file)?.write_all(self.dimacs().as_bytes())?;
+
+        let output = Command::new(&self.command).args(&self.args).arg(file).output()?;
+        parse_output(&String::from_utf8_lossy(&output.stdout), output.status.code(), self.vars)
+    }
+}
+
+/// Read the answer of a solver, which exited with `code`. Variables missing from
+/// the model are false.
+fn parse_output(output: &str, code: Option<i32>, vars: usize) -> Result<Option<Vec<Lit>>, BackendError> {
+    let mut status = None;
+    let mut model = vec![None; vars];
+
+    for line in output.lines() {
+        if let Some(s) = line.strip_prefix("s ") {
+            status = match s.trim() {
+                "SATISFIABLE" => Some(true),
+                "UNSATISFIABLE" => Some(false),
+                other => return Err(BackendError::External(format!("the solver answered {:?}", other))),
+            };
+        } else if let Some(values) = line.strip_prefix("v ") {
+            for value in values.split_whitespace() {
+                let n: isize = value.parse()
+                    .map_err(|_| BackendError::External(format!("invalid value {:?}", value)))?;
+                if n == 0 { continue }
+                let lit = Lit::from_dimacs(n);
+                if let Some(slot) = model.get_mut(lit.index()) { *slot = Some(lit) }
+            }
+        }
+    }
+
+    match (status, code) {
+        (Some(true), Some(10)) => Ok(Some(model.into_iter().enumerate()
+            .map(|(i, l)| l.unwrap_or(Var::from_index(i).negative()))
+            .collect())),
+        (Some(false), Some(20)) => Ok(None),
+        (None, _) => Err(BackendError::External("no answer from the solver".into())),
+        (Some(_), Some(code)) => Err(BackendError::External(format!("the solver exited with status {}", code))),
+        (Some(_), None) => Err(BackendError::External("the solver was killed".into())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let model = parse_output("c comment\ns SATISFIABLE\nv 1 -2\nv 3 0\n", Some(10), 4).unwrap().unwrap();
+        assert_eq!(model, [1, -2, 3, -4].map(Lit::from_dimacs));
+        assert_eq!(parse_output("s UNSATISFIABLE\n", Some(20), 4).unwrap(), None);
+        assert!(parse_output("", Some(0), 4).is_err());
+        assert!(parse_output("s UNKNOWN\n", Some(0), 4).is_err());
+        // The exit status must agree with the answer
+        assert!(parse_output("s UNSATISFIABLE\n", Some(1), 4).is_err());
+        assert!(parse_output("s SATISFIABLE\nv 0\n", Some(20), 4).is_err());
+        assert!(parse_output("s SATISFIABLE\nv 0\n", None, 4).is_err());
+    }
+
+    #[test]
+    fn dimacs() {
+        let mut solver = External::new("true", &[]);
+        let (a, b) = (solver.new_var().positive(), solver.new_var().positive());
+        solver.add_clause(&[a, !b]);
+        solver.assume(&[b]);
+        assert_eq!(solver.dimacs(), "p cnf 2 2\n1 -2 0\n2 0\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn subprocess() {
+        // A fake solver, ignoring the file passed as $0
+        let mut solver = External::new("sh", &["-c", "echo 's SATISFIABLE'; echo 'v -1 2 0'; exit 10"]);
+        let (a, b) = (solver.new_var().positive(), solver.new_var().positive());
+        solver.add_clause(&[a, b]);
+        assert!(solver.solve().unwrap());
+        assert_eq!(solver.model().unwrap(), vec![!a, b]);
+
+        let mut solver = External::new("sh", &["-c", "echo 's UNSATISFIABLE'; exit 20"]);
+        let a = solver.new_var().positive();
+        solver.assume(&[a]);
+        assert!(!solver.solve().unwrap());
+        assert_eq!(solver.failed_core().unwrap(), vec![a]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Lines drawn on grids, as sets of selected edges between orthogonally adjacent nodes:
+//! a single closed loop (Slitherlink, Masyu, Yajilin, Country Road), or a simple path
+//! between two endpoints (Numberlink). The nodes are the points or the cells of the
+//! puzzle, depending on the game, which only changes the shape of the [`EdgeGrid`].
+//!
+//! Both are built from degree constraints on the nodes, which keep the lines from
+//! branching, and a [connectivity](super::connect) constraint through the selected
+//! edges, which rules out extra loops.
+
+use varisat::{ExtendFormula, Lit};
+
+use super::{connect::ConnectFormula, coord::Coord, matrix::{Matrix, umat}};
+
+/// The edges of a grid graph, each with the literal telling if it is selected
+pub struct EdgeGrid {
+    shape: (usize, usize),
+    edges: Vec<(Coord, Coord, Lit)>,
+}
+
+impl EdgeGrid {
+    /// A fresh literal for every pair of orthogonally adjacent nodes
+    pub fn new(f: &mut impl ExtendFormula, shape: (usize, usize)) -> Self {
+        let nodes = umat![(); shape];
+        let edges = nodes.coords()
+            .flat_map(|c| c.adjacent(shape)
+                .filter(move |&n| n > c)
+                .map(move |n| (c, n)))
+            .map(|(a, b)| (a, b, f.new_lit()))
+            .collect();
+        EdgeGrid { shape, edges }
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// Every edge, with its two ends in order
+    pub fn edges(&self) -> &[(Coord, Coord, Lit)] {
+        &self.edges
+    }
+
+    /// The literal of the edge between two nodes, if they are adjacent
+    pub fn edge(&self, a: Coord, b: Coord) -> Option<Lit> {
+        self.edges.iter()
+            .find(|&&(x, y, _)| (x, y) == (a, b) || (x, y) == (b, a))
+            .map(|&(_, _, e)| e)
+    }
+
+    /// The literals of the edges around `node`
+    pub fn incident(&self, node: Coord) -> Vec<Lit> {
+        self.edges.iter()
+            .filter(|&&(a, b, _)| a == node || b == node)
+            .map(|&(_, _, e)| e)
+            .collect()
+    }
+
+    fn index(&self, c: Coord) -> usize {
+        c.x * self.shape.1 + c.y
+    }
+}
+
+pub trait GraphFormula: ExtendFormula {
+
+    /// The number of true `lits` is one of `degrees`. Every forbidden assignment gets
+    /// its own clause, which is meant for the few edges around a node.
+    fn add_degree(&mut self, lits: &[Lit], degrees: &[usize]) {
+        for bits in 0..1usize << lits.len() {
+            if degrees.contains(&(bits.count_ones() as usize)) { continue }
+            let clause: Vec<_> = lits.iter().enumerate()
+                .map(|(i, &l)| if bits & (1 << i) != 0 { !l } else { l })
+                .collect();
+            self.add_clause(&clause);
+        }
+    }
+
+    /// Require the selected edges to form a single closed loop, with at least one edge.
+    /// Returns, for every node, a literal telling if the loop goes through it.
+    fn add_single_loop(&mut self, grid: &EdgeGrid) -> Matrix<Lit> {
+        let on = add_nodes(self, grid);
+        for c in on.coords() {
+            self.add_degree(&grid.incident(c), &[0, 2]);
+        }
+        let nodes: Vec<Lit> = on.iter().copied().collect();
+        self.add_clause(&nodes);
+        add_connected_edges(self, grid, &nodes);
+        on
+    }
+
+    /// Require the selected edges to form a simple path from `start` to `end`.
+    /// Returns, for every node, a literal telling if the path goes through it.
+    fn add_path(&mut self, grid: &EdgeGrid, start: Coord, end: Coord) -> Matrix<Lit> {
+        let on = add_nodes(self, grid);
+        for c in on.coords() {
+            let degrees: &[usize] = if c == start || c == end { &[1] } else { &[0, 2] };
+            self.add_degree(&grid.incident(c), degrees);
+        }
+        let nodes: Vec<Lit> = on.iter().copied().collect();
+        add_connected_edges(self, grid, &nodes);
+        on
+    }
+}
+
+impl<T: ExtendFormula> GraphFormula for T {}
+
+/// A literal for every node, true if and only if one of its edges is selected
+fn add_nodes<F: ExtendFormula>(f: &mut F, grid: &EdgeGrid) -> Matrix<Lit> {
+    let lits = (0..grid.shape.0 * grid.shape.1).map(|_| f.new_lit()).collect();
+    let on = Matrix::new(lits, grid.shape).expect("one literal per node");
+    for c in on.coords() {
+        let incident = grid.incident(c);
+        for &e in &incident {
+            f.add_clause(&[!e, on[c]]);
+        }
+        let mut clause = incident;
+        clause.push(!on[c]);
+        f.add_clause(&clause);
+    }
+    on
+}
+
+fn add_connected_edges<F: ExtendFormula>(f: &mut F, grid: &EdgeGrid, nodes: &[Lit]) {
+    let edges: Vec<_> = grid.edges.iter()
+        .map(|&(a, b, e)| (grid.index(a), grid.index(b), e))
+        .collect();
+    f.add_connected_by(nodes, &edges);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{backend::Solver, solve::Models};
+
+    fn count(shape: (usize, usize), constrain: impl Fn(&mut Solver, &EdgeGrid)) -> usize {
+        let mut solver = Solver::new();
+        let grid = EdgeGrid::new(&mut solver, shape);
+        constrain(&mut solver, &grid);
+        let vars = grid.edges().iter().map(|&(_, _, e)| e.var()).collect();
+        Models::new(&mut solver, vars).try_count().unwrap()
+    }
+
+    /// Simple paths from `at` to `end`, by depth-first search
+    fn paths(shape: (usize, usize), at: Coord, end: Coord, visited: &mut Vec<Coord>) -> usize {
+        if at == end { return 1 }
+        visited.push(at);
+        let mut n = 0;
+        for next in at.adjacent(shape) {
+            if !visited.contains(&next) {
+                n += paths(shape, next, end, visited);
+            }
+        }
+        visited.pop();
+        n
+    }
+
+    #[test]
+    fn loops() {
+        // The cycles of a 3x3 grid graph: 4 unit squares, 4 dominoes, 4 L shapes and the border
+        assert_eq!(count((3, 3), |s, g| { s.add_single_loop(g); }), 13);
+        assert_eq!(count((2, 2), |s, g| { s.add_single_loop(g); }), 1);
+        assert_eq!(count((1, 4), |s, g| { s.add_single_loop(g); }), 0);
+    }
+
+    #[test]
+    fn simple_paths() {
+        for (shape, start, end) in [((2, 3), (0, 0), (1, 2)), ((3, 3), (0, 0), (2, 2)), ((3, 3), (0, 1), (1, 1))] {
+            let (start, end) = (Coord::from(start), Coord::from(end));
+            let expected = paths(shape, start, end, &mut vec![]);
+            assert_eq!(count(shape, |s, g| { s.add_path(g, start, end); }), expected, "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn degrees() {
+        let mut solver = Solver::new();
+        let lits: Vec<Lit> = (0..4).map(|_| solver.new_lit()).collect();
+        solver.add_degree(&lits, &[1, 3]);
+        let found = Models::new(&mut solver, lits.iter().map(|l| l.var()).collect()).try_count().unwrap();
+        assert_eq!(found, 4 + 4);
+    }
+}
@@ -0,0 +1,145 @@
+//! Reading grids drawn with one character per cell, the text format of most games.
+//! Empty lines are skipped, every other line is a row of the grid, and all the rows
+//! must have the same length. Errors tell where in the grid the problem was found.
+//!
+//! Cell types implementing [`CellChar`] get a matrix of them parsed and printed
+//! for free, through the `FromStr` and `Display` implementations of [`Matrix`].
+
+use std::{fmt::{Display, Write}, str::FromStr};
+
+use thiserror::Error;
+
+use super::{coord::Coord, matrix::Matrix};
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum GridError {
+    #[error("empty grid")]
+    Empty,
+    #[error("row {row} has {found} cells, expected {expected}")]
+    UnequalLine { row: usize, expected: usize, found: usize },
+    #[error("invalid character {ch:?} at {at}")]
+    InvalidChar { ch: char, at: Coord },
+}
+
+/// Parse a grid, turning every character into a cell with `cell`, which returns
+/// `None` for the characters that are not allowed.
+pub fn parse_grid<T>(s: &str, cell: impl Fn(char) -> Option<T>) -> Result<Matrix<T>, GridError> {
+    let mut cells = vec![];
+    let mut width = None;
+    let mut h = 0;
+
+    for (x, line) in s.lines().filter(|l| !l.is_empty()).enumerate() {
+        let mut w = 0;
+        for (y, ch) in line.chars().enumerate() {
+            cells.push(cell(ch).ok_or(GridError::InvalidChar { ch, at: Coord::new(x, y) })?);
+            w += 1;
+        }
+
+        let expected = *width.get_or_insert(w);
+        if w != expected {
+            return Err(GridError::UnequalLine { row: x, expected, found: w })
+        }
+        h += 1;
+    }
+
+    let w = width.ok_or(GridError::Empty)?;
+    Ok(Matrix::new(cells, (h, w)).expect("rows have the same length"))
+}
+
+/// A cell drawn as a single character
+pub trait CellChar: Sized {
+    /// The cell drawn as `c`, or `None` if the character is not allowed
+    fn from_char(c: char) -> Option<Self>;
+
+    /// The character drawing the cell, which `from_char` reads back
+    fn to_char(&self) -> char;
+}
+
+/// `0` or `1`
+impl CellChar for bool {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '1' => Some(true),
+            '0' => Some(false),
+            _ => None,
+        }
+    }
+
+    fn to_char(&self) -> char {
+        if *self { '1' } else { '0' }
+    }
+}
+
+/// A single decimal digit. Larger values cannot be drawn and show as `?`
+impl CellChar for u8 {
+    fn from_char(c: char) -> Option<Self> {
+        Some(c.to_digit(10)? as u8)
+    }
+
+    fn to_char(&self) -> char {
+        char::from_digit(*self as u32, 10).unwrap_or('?')
+    }
+}
+
+/// An empty cell is drawn as `.`, and also read from `-` or a space
+impl<T: CellChar> CellChar for Option<T> {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' | '-' | ' ' => Some(None),
+            _ => Some(Some(T::from_char(c)?)),
+        }
+    }
+
+    fn to_char(&self) -> char {
+        self.as_ref().map_or('.', T::to_char)
+    }
+}
+
+/// Parses a grid with [`parse_grid`], as printed by the `Display` implementation
+impl<T: CellChar> FromStr for Matrix<T> {
+    type Err = GridError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_grid(s, T::from_char)
+    }
+}
+
+/// One line per row, one character per cell
+impl<T: CellChar> Display for Matrix<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in self.lines() {
+            for cell in line {
+                f.write_char(cell.to_char())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn errors() {
+        let bit = bool::from_char;
+        let grid = parse_grid("01\n\n10\n11\n", bit).unwrap();
+        assert_eq!(grid.shape(), (3, 2));
+        assert!(grid[1][0] && !grid[1][1]);
+
+        assert_eq!(parse_grid("\n\n", bit), Err(GridError::Empty));
+        assert_eq!(parse_grid("01\n1\n", bit), Err(GridError::UnequalLine { row: 1, expected: 2, found: 1 }));
+        assert_eq!(parse_grid("01\n1x\n", bit), Err(GridError::InvalidChar { ch: 'x', at: Coord::new(1, 1) }));
+        assert_eq!(parse_grid("01\n1x\n", bit).unwrap_err().to_string(), "invalid character 'x' at (1,1)");
+    }
+
+    #[test]
+    fn cell_chars() {
+        let grid: Matrix<Option<u8>> = "1.3\n-4 \n".parse().unwrap();
+        assert_eq!(grid, Matrix::new(vec![Some(1), None, Some(3), None, Some(4), None], (2, 3)).unwrap());
+        assert_eq!(grid.to_string(), "1.3\n.4.\n");
+        assert_eq!(Matrix::new(vec![7u8, 12], (1, 2)).unwrap().to_string(), "7?\n");
+        assert_eq!("01\n1x\n".parse::<Matrix<bool>>(), Err(GridError::InvalidChar { ch: 'x', at: Coord::new(1, 1) }));
+    }
+}
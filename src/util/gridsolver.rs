@@ -0,0 +1,138 @@
+//! A solver for grid games, which hands out whole grids of variables and reads models
+//! back as grids of values. Games encode their rules on these grids with the usual
+//! [`ExtendFormula`] methods, reached through the inner [`Solver`], and boolean cells
+//! can be mixed with integer ones from [`integer`](super::integer).
+//!
+//! Binero, K-Doku, Stars and Voisimage are solved this way. Kakuro, whose grids have
+//! holes and whose cells are all integers, uses an [`integer::Session`] instead, which
+//! reads its models back as grids of optional values.
+//!
+//! Either way, a grid is encoded once into the solver of a session, which can then be
+//! solved repeatedly under different hypotheses about some cells: assumptions only last
+//! for one call, while the clauses, and everything the solver learnt from them, are kept.
+//! See [sessions](super::solve#sessions) for how they enumerate solutions.
+
+use std::{collections::BTreeSet, ops::{Deref, DerefMut, RangeInclusive}};
+
+use varisat::{ExtendFormula, Lit, Var};
+
+use super::{backend::{BackendError, Solver}, integer::{self, IntFormula}, matrix::Matrix, solve::solve_assuming, vargrid::VarGrid};
+
+#[derive(Default)]
+pub struct GridSolver {
+    solver: Solver,
+}
+
+/// The values of the variables in a model, read through the grids that hold them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridModel(BTreeSet<Lit>);
+
+impl GridSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A grid of fresh boolean variables
+    pub fn bool_grid(&mut self, shape: (usize, usize)) -> Matrix<Var> {
+        let cells = self.solver.new_var_iter(shape.0 * shape.1).collect();
+        Matrix::new(cells, shape).expect("one variable per cell")
+    }
+
+    /// A grid of fresh boolean variables, `depth` per cell, stored as a [`VarGrid`]
+    pub fn var_grid(&mut self, shape: (usize, usize), depth: usize) -> VarGrid {
+        VarGrid::new(&mut self.solver, shape, depth)
+    }
+
+    /// A grid of fresh integer variables, each taking exactly one value in `range`
+    pub fn int_grid(&mut self, shape: (usize, usize), range: RangeInclusive<usize>) -> Matrix<integer::Var> {
+        let cells = (0..shape.0 * shape.1)
+            .map(|_| self.solver.new_int(range.clone()))
+            .collect();
+        Matrix::new(cells, shape).expect("one variable per cell")
+    }
+
+    /// Solve under `assumptions`. See [`solve_assuming`].
+    pub fn solve(&mut self, assumptions: &[Lit]) -> Result<Option<GridModel>, BackendError> {
+        Ok(solve_assuming(&mut self.solver, assumptions)?.map(GridModel::from))
+    }
+}
+
+impl Deref for GridSolver {
+    type Target = Solver;
+
+    fn deref(&self) -> &Solver {
+        &self.solver
+    }
+}
+
+impl DerefMut for GridSolver {
+    fn deref_mut(&mut self) -> &mut Solver {
+        &mut self.solver
+    }
+}
+
+impl From<Vec<Lit>> for GridModel {
+    fn from(model: Vec<Lit>) -> Self {
+        GridModel(model.into_iter().collect())
+    }
+}
+
+impl GridModel {
+    /// True if `lit` holds in the model
+    pub fn holds(&self, lit: Lit) -> bool {
+        self.0.contains(&lit)
+    }
+
+    pub fn bools(&self, grid: &Matrix<Var>) -> Matrix<bool> {
+        grid.map(|v| self.holds(v.positive()))
+    }
+
+    /// The value of `var`, one-hot or binary
+    pub fn int(&self, var: &integer::Var) -> usize {
+        var.range()
+            .find(|&n| var.is(n).into_iter().all(|l| self.holds(l)))
+            .expect("integer variables take exactly one value")
+    }
+
+    pub fn ints(&self, grid: &Matrix<integer::Var>) -> Matrix<usize> {
+        grid.map(|v| self.int(v))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latin_square() {
+        let mut solver = GridSolver::new();
+        let grid = solver.int_grid((3, 3), 1..=3);
+        let shaded = solver.bool_grid((3, 3));
+
+        for (x, y) in grid.indices() {
+            for (i, j) in grid.indices().filter(|&(i, j)| (i == x) != (j == y)) {
+                solver.add_not_equal(&grid[(x,y)], &grid[i][j]);
+            }
+            // The cells holding a 1 are shaded
+            let one = grid[(x,y)].lit(1).unwrap();
+            solver.add_clause(&[!one, shaded[(x,y)].positive()]);
+            solver.add_clause(&[one, shaded[(x,y)].negative()]);
+        }
+        solver.add_value(&grid[0][0], 2);
+
+        // Binary variables have no literal per value, but are read all the same
+        let sum = solver.new_binary_int(0..=5);
+        assert_eq!(sum.lit(3), None);
+        solver.add_value(&sum, 5);
+
+        let model = solver.solve(&[grid[1][1].lit(3).unwrap()]).unwrap().unwrap();
+        assert_eq!(model.int(&sum), 5);
+        let values = model.ints(&grid);
+        assert_eq!(values[0][0], 2);
+        assert_eq!(values[1][1], 3);
+        assert_eq!(model.bools(&shaded), values.map(|&n| n == 1));
+
+        assert_eq!(grid[0][0].lit(4), None);
+        assert!(solver.solve(&[grid[0][1].lit(2).unwrap()]).unwrap().is_none());
+    }
+}
@@ -1,12 +1,33 @@
-use std::ops::{Index, RangeInclusive};
+use std::{borrow::Cow, collections::BTreeSet, ops::RangeInclusive};
 
+use itertools::Itertools;
+use rand::Rng;
 use varisat::{self, ExtendFormula, CnfFormula, Lit};
-use super::{intersect, solve::DnfFormula};
+use super::{interval::{self, Interval}, backend::{BackendError, Solver}, card::CardFormula, matrix::Matrix, solve::{DnfFormula, solve_assuming, backbone, random_model}};
 
 #[derive(Clone,Debug)]
 pub struct Var {
     range: RangeInclusive<usize>,
-    values: Vec<Lit>, // cannot be empty
+    repr: Repr,
+}
+
+#[derive(Clone,Debug)]
+enum Repr {
+    /// One literal per value, exactly one of them being true. Cannot be empty.
+    OneHot(Vec<Lit>),
+    /// The bits of the value minus the start of the range, lowest first
+    Binary(Vec<Lit>),
+}
+
+/// How a [`Problem`] encodes the variables holding sums
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// One literal per value, which propagates well but grows with the range
+    #[default]
+    OneHot,
+    /// One literal per bit, added with ripple-carry adders. Other constraints on such
+    /// variables first give them one literal per value, tied to the bits.
+    Binary,
 }
 
 impl Var {
@@ -14,16 +35,56 @@ impl Var {
         self.range.clone()
     }
 
-    fn values(&self) -> impl Iterator<Item=(usize, &Lit)> + '_{
-        self.range().zip(&self.values)
+    pub fn is_binary(&self) -> bool {
+        matches!(self.repr, Repr::Binary(_))
     }
-}
 
-impl Index<usize> for Var {
-    type Output = Lit;
+    /// The literal telling if the variable takes the value `n`. Binary variables have
+    /// none, see [`Var::is`] instead, and neither do values out of range.
+    pub fn lit(&self, n: usize) -> Option<Lit> {
+        match &self.repr {
+            Repr::OneHot(values) => values.get(n.checked_sub(*self.range.start())?).copied(),
+            Repr::Binary(_) => None,
+        }
+    }
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.values[index - self.range.start()]
+    /// The literals which all hold when the variable takes the value `n`: a single one
+    /// for one-hot variables, or the bits of `n` for binary ones.
+    pub fn is(&self, n: usize) -> Vec<Lit> {
+        match &self.repr {
+            Repr::OneHot(values) => {
+                assert!(self.range.contains(&n), "{} out of range {:?}", n, self.range);
+                vec![values[n - self.range.start()]]
+            },
+            Repr::Binary(bits) => {
+                assert!(self.range.contains(&n), "{} out of range {:?}", n, self.range);
+                let offset = n - self.range.start();
+                bits.iter().enumerate()
+                    .map(|(i, &b)| if offset >> i & 1 == 1 { b } else { !b })
+                    .collect()
+            },
+        }
+    }
+
+    /// Every literal of the variable
+    fn lits(&self) -> &[Lit] {
+        match &self.repr {
+            Repr::OneHot(lits) | Repr::Binary(lits) => lits,
+        }
+    }
+
+    /// The values of a one-hot variable, with their literals. Binary variables go
+    /// through [`unary`] first.
+    fn values(&self) -> impl Iterator<Item=(usize, &Lit)> + '_ {
+        match &self.repr {
+            Repr::OneHot(values) => self.range().zip(values),
+            Repr::Binary(_) => unreachable!("binary variables go through unary first"),
+        }
+    }
+
+    /// The literal of the value `n` of a one-hot variable, such as those made by [`unary`]
+    fn one_hot(&self, n: usize) -> Lit {
+        self.lit(n).expect("one-hot variable, with a value in range")
     }
 }
 
@@ -34,18 +95,50 @@ pub struct Model {
 
 impl Model {
     pub fn value(&self, var: &Var) -> usize {
-        for (val, term) in var.values() {
-            if self.inner.contains(term) {
-                return val
-            }
+        self.try_value(var).expect("SAT solver returned invalid solution")
+    }
+
+    /// The value of `var`, or `None` if it has none in the model, which can only
+    /// happen if the model comes from another formula.
+    pub fn try_value(&self, var: &Var) -> Option<usize> {
+        match &var.repr {
+            Repr::OneHot(_) => var.values()
+                .find(|(_, term)| self.inner.contains(term))
+                .map(|(val, _)| val),
+            Repr::Binary(bits) => bits.iter().enumerate()
+                .try_fold(*var.range.start(), |n, (i, &b)| {
+                    if self.holds(b) { Some(n + (1 << i)) }
+                    else if self.holds(!b) { Some(n) }
+                    else { None }
+                }),
         }
-        panic!("SAT solver returned invalid solution")
+    }
+
+    /// The value of every variable in a grid, leaving empty cells empty
+    pub fn values(&self, grid: &Matrix<Option<Var>>) -> Matrix<Option<usize>> {
+        grid.map(|cell| cell.as_ref().map(|var| self.value(var)))
+    }
+
+    /// True if `lit` holds in the model
+    pub fn holds(&self, lit: Lit) -> bool {
+        self.inner.contains(&lit)
     }
 }
 
 #[derive(Clone,Debug)]
 pub struct Problem {
     inner:  CnfFormula,
+    encoding: Encoding,
+    /// The ranges of the variables declared since the last call to [`Problem::define`]
+    declared: Vec<Interval>,
+    /// The constraints posted on them, not encoded yet
+    posted: Vec<Posted>,
+}
+
+/// A problem loaded into a SAT solver, that can be solved repeatedly
+/// under different hypotheses about the values of its variables.
+pub struct Session {
+    solver: Solver,
 }
 
 impl Default for Problem {
@@ -54,72 +147,642 @@ impl Default for Problem {
     }
 }
 
-impl Problem {
-    pub fn new() -> Self {
-        Self {
-            inner: CnfFormula::new()
-        }
-    }
+/// The integer encodings, for any formula. A [`Problem`] builds a formula with them,
+/// but they can also be mixed with boolean variables in the same formula.
+pub trait IntFormula: ExtendFormula {
 
-    pub fn new_var(&mut self, range: RangeInclusive<usize>) -> Var {
+    /// A new integer variable, with one literal per value in `range`,
+    /// exactly one of them being true.
+    fn new_int(&mut self, range: RangeInclusive<usize>) -> Var {
         let values: Vec<Lit> = range.clone()
-            .map(|_n| self.inner.new_lit())
+            .map(|_n| self.new_lit())
             .collect();
 
-        // at least one case is true
-        self.inner.add_clause(&values);
+        // exactly one case is true
+        self.add_exactly_one(&values);
 
-        // cases are mutually exclusive
-        for (i,a) in values.iter().enumerate() {
-            for b in &values[i+1..] {
-                self.inner.add_clause(&[ a.var().negative(), b.var().negative()]);
-            }
+        Var { range, repr: Repr::OneHot(values) }
+    }
+
+    /// A new integer variable taking a value in `range`, with one literal per bit
+    fn new_binary_int(&mut self, range: RangeInclusive<usize>) -> Var {
+        let max = range.end() - range.start();
+        let bits: Vec<Lit> = (0..width(max)).map(|_| self.new_lit()).collect();
+
+        // Above max, some bit is set where max has none, with the same bits as max before it
+        for i in (0..bits.len()).filter(|i| max >> i & 1 == 0) {
+            let mut clause = vec![!bits[i]];
+            clause.extend((i+1..bits.len()).map(|j| if max >> j & 1 == 1 { !bits[j] } else { bits[j] }));
+            self.add_clause(&clause);
         }
 
-        Var { range, values }
+        Var { range, repr: Repr::Binary(bits) }
     }
 
-    pub fn sum(&mut self, a: &Var, b: &Var) -> Var {
-        let ar = a.range();
-        let br = b.range();
-        let rr = (ar.start() + br.start())..= (ar.end() + br.end());
-        let r = self.new_var(rr);
+    /// A new integer variable holding `a + b`. It is binary if one of them is, see
+    /// [`IntFormula::add_binary_sum`].
+    fn add_sum(&mut self, a: &Var, b: &Var) -> Var {
+        if a.is_binary() || b.is_binary() {
+            return self.add_binary_sum(a, b)
+        }
+
+        let r = self.new_int(interval::sum(&a.range(), &b.range()));
 
         let mut buffer = vec![];
 
         for (ax, av) in a.values() {
             for (bx, bv) in b.values() {
-                buffer.push([*av, *bv, r[ax+bx]]);
+                buffer.push([*av, *bv, r.one_hot(ax+bx)]);
             }
         }
 
-        self.inner.add_dnf(buffer);
+        self.add_dnf(buffer);
 
         r
     }
-    
-    pub fn not_equals(&mut self, a: &Var, b: &Var) {
-        for i in intersect(a.range(), b.range()) {
-            self.inner.add_clause(&[a[i].var().negative(), b[i].var().negative()]);
+
+    /// A new integer variable holding the sum of all the `vars`, added as a balanced tree:
+    /// each half is summed on its own, which keeps the intermediate ranges small.
+    /// The empty sum is `0`.
+    fn add_sum_all(&mut self, vars: &[Var]) -> Var {
+        match vars {
+            [] => self.new_int(0..=0),
+            [v] => v.clone(),
+            _ => {
+                let (left, right) = vars.split_at(vars.len() / 2);
+                let (left, right) = (self.add_sum_all(left), self.add_sum_all(right));
+                self.add_sum(&left, &right)
+            },
+        }
+    }
+
+    /// A new binary integer variable holding `a + b`, with a ripple-carry adder
+    /// on the bits of `a` and `b`, which are made binary first if needed.
+    fn add_binary_sum(&mut self, a: &Var, b: &Var) -> Var {
+        let (x, y) = (bits(self, a), bits(self, b));
+        let zero = self.new_lit();
+        self.add_clause(&[!zero]);
+
+        let mut carry = zero;
+        let mut sum = vec![];
+
+        for i in 0..x.len().max(y.len()) {
+            let inputs = [x.get(i).copied().unwrap_or(zero), y.get(i).copied().unwrap_or(zero), carry];
+            let (s, c) = (self.new_lit(), self.new_lit());
+
+            // The sum bit is set for an odd number of inputs, the carry for two or more
+            for k in 0..8usize {
+                let ones = k.count_ones();
+                let case: Vec<Lit> = (0..3)
+                    .map(|j| if k >> j & 1 == 1 { !inputs[j] } else { inputs[j] })
+                    .collect();
+                for (out, set) in [(s, ones % 2 == 1), (c, ones >= 2)] {
+                    let mut clause = case.clone();
+                    clause.push(if set { out } else { !out });
+                    self.add_clause(&clause);
+                }
+            }
+
+            sum.push(s);
+            carry = c;
+        }
+        sum.push(carry);
+
+        Var { range: interval::sum(&a.range(), &b.range()), repr: Repr::Binary(sum) }
+    }
+
+    /// A new integer variable holding `a * b`
+    fn add_product(&mut self, a: &Var, b: &Var) -> Var {
+        combine(self, a, b, |x, y| x * y)
+    }
+
+    /// A new integer variable holding the product of all the `vars`, multiplied
+    /// one at a time. The empty product is `1`.
+    fn add_product_all(&mut self, vars: &[Var]) -> Var {
+        match vars.split_first() {
+            None => self.new_int(1..=1),
+            Some((first, rest)) => rest.iter()
+                .fold(first.clone(), |p, v| self.add_product(&p, v)),
+        }
+    }
+
+    /// A new integer variable holding `|a - b|`
+    fn add_abs_diff(&mut self, a: &Var, b: &Var) -> Var {
+        combine(self, a, b, usize::abs_diff)
+    }
+
+    /// A new integer variable holding the smallest value of the `vars`, which cannot be empty
+    fn add_min(&mut self, vars: &[Var]) -> Var {
+        let (first, rest) = vars.split_first().expect("the minimum of some variables");
+        rest.iter().fold(first.clone(), |m, v| combine(self, &m, v, usize::min))
+    }
+
+    /// A new integer variable holding the largest value of the `vars`, which cannot be empty
+    fn add_max(&mut self, vars: &[Var]) -> Var {
+        let (first, rest) = vars.split_first().expect("the maximum of some variables");
+        rest.iter().fold(first.clone(), |m, v| combine(self, &m, v, usize::max))
+    }
+
+    /// Require `|a - b| >= k`: each value of `a` implies one of the values of `b` far enough from it
+    fn add_diff_at_least(&mut self, a: &Var, b: &Var, k: usize) {
+        let (a, b) = (unary(self, a), unary(self, b));
+        for (i, &ai) in a.values() {
+            let mut clause = vec![!ai];
+            clause.extend(b.values().filter(|&(j, _)| i.abs_diff(j) >= k).map(|(_, &bj)| bj));
+            self.add_clause(&clause);
+        }
+    }
+
+    fn add_not_equal(&mut self, a: &Var, b: &Var) {
+        for i in interval::intersect(&a.range(), &b.range()) {
+            let clause: Vec<Lit> = a.is(i).into_iter().chain(b.is(i)).map(|l| !l).collect();
+            self.add_clause(&clause);
+        }
+    }
+
+    /// Require all the `vars` to take different values. Each value gets a single
+    /// at-most-one constraint over the variables that can take it, instead of a clause
+    /// for every pair of variables. When there are exactly as many values as variables,
+    /// every value must also be taken, which helps propagation.
+    fn add_all_different(&mut self, vars: &[Var]) {
+        let vars: Vec<Cow<Var>> = vars.iter().map(|v| unary(self, v)).collect();
+        let values: BTreeSet<usize> = vars.iter().flat_map(|v| v.range()).collect();
+        for &n in &values {
+            let lits: Vec<Lit> = vars.iter()
+                .filter(|v| v.range().contains(&n))
+                .map(|v| v.one_hot(n))
+                .collect();
+            self.at_most_one(&lits);
+            if values.len() == vars.len() {
+                self.add_clause(&lits);
+            }
+        }
+    }
+
+    /// Require the `vars` to take different values, summing to `total`. Instead of adding
+    /// them up, each set of values with the right sum gets a selector literal, and the
+    /// variables must take the values of one of the sets.
+    fn add_distinct_sum(&mut self, vars: &[Var], total: usize) {
+        self.add_all_different(vars);
+
+        let vars: Vec<Cow<Var>> = vars.iter().map(|v| unary(self, v)).collect();
+        let values: BTreeSet<usize> = vars.iter().flat_map(|v| v.range()).collect();
+        let mut selectors = vec![];
+
+        for set in values.into_iter().combinations(vars.len()).filter(|set| set.iter().sum::<usize>() == total) {
+            let selector = self.new_lit();
+            for var in &vars {
+                let mut clause = vec![!selector];
+                clause.extend(set.iter().filter(|n| var.range().contains(n)).map(|&n| var.one_hot(n)));
+                self.add_clause(&clause);
+            }
+            selectors.push(selector);
+        }
+
+        self.add_clause(&selectors);
+    }
+
+    /// Require `var` to take the value `val`, which is impossible if it is out of its range
+    fn add_value(&mut self, var: &Var, val: usize) {
+        if !var.range().contains(&val) {
+            return self.add_clause(&[])
+        }
+        for l in var.is(val) {
+            self.add_clause(&[l]);
+        }
+    }
+
+    /// Require `a < b`
+    fn add_less_than(&mut self, a: &Var, b: &Var) {
+        add_order(self, a, b, 1)
+    }
+
+    /// Require `a <= b`
+    fn add_less_equal(&mut self, a: &Var, b: &Var) {
+        add_order(self, a, b, 0)
+    }
+
+    /// Require `a` and `b` to take the same value
+    fn add_equal_vars(&mut self, a: &Var, b: &Var) {
+        let (a, b) = (unary(self, a), unary(self, b));
+        for (x, y) in [(&a, &b), (&b, &a)] {
+            for (i, &xi) in x.values() {
+                let mut clause = vec![!xi];
+                if y.range().contains(&i) { clause.push(y.one_hot(i)) }
+                self.add_clause(&clause);
+            }
+        }
+    }
+
+    /// Add the constraints built by `constraint` on the guarded formula, which only need
+    /// to hold when `guard` is true. Variables created inside, such as sums, are left
+    /// free when it is false.
+    fn add_implied<R>(&mut self, guard: Lit, constraint: impl FnOnce(&mut Guarded<'_, Self>) -> R) -> R {
+        constraint(&mut Guarded { formula: self, guard })
+    }
+}
+
+impl<T: ExtendFormula> IntFormula for T {}
+
+/// A formula where every clause gets `not(guard)` as an additional way out, so that
+/// the constraints added to it only hold when `guard` is true. See [`IntFormula::add_implied`].
+pub struct Guarded<'f, F> {
+    formula: &'f mut F,
+    guard: Lit,
+}
+
+impl<F: ExtendFormula> ExtendFormula for Guarded<'_, F> {
+    fn add_clause(&mut self, literals: &[Lit]) {
+        let mut clause = literals.to_vec();
+        clause.push(!self.guard);
+        self.formula.add_clause(&clause)
+    }
+
+    fn new_var(&mut self) -> varisat::Var {
+        self.formula.new_var()
+    }
+}
+
+/// A new integer variable holding `op(a, b)`. Its range goes from the smallest to the
+/// largest result, but only the values that `op` can reach get a literal of their own.
+fn combine<F: ExtendFormula>(f: &mut F, a: &Var, b: &Var, op: impl Fn(usize, usize) -> usize) -> Var {
+    let results: BTreeSet<usize> = a.range()
+        .flat_map(|x| b.range().map(move |y| (x, y)))
+        .map(|(x, y)| op(x, y))
+        .collect();
+    let r = new_sparse_int(f, &results);
+    let (a, b) = (unary(f, a), unary(f, b));
+
+    let mut buffer = vec![];
+
+    for (ax, av) in a.values() {
+        for (bx, bv) in b.values() {
+            buffer.push([*av, *bv, r.one_hot(op(ax, bx))]);
+        }
+    }
+
+    f.add_dnf(buffer);
+
+    r
+}
+
+/// A new integer variable which can only take the given `values`, from the smallest
+/// to the largest. The values in between share a single literal, which is always false.
+fn new_sparse_int<F: ExtendFormula>(f: &mut F, values: &BTreeSet<usize>) -> Var {
+    let (&min, &max) = (values.first().expect("some value"), values.last().expect("some value"));
+    let none = f.new_lit();
+    f.add_clause(&[!none]);
+
+    let values: Vec<Lit> = (min..=max)
+        .map(|n| if values.contains(&n) { f.new_lit() } else { none })
+        .collect();
+    let possible: Vec<Lit> = values.iter().copied().filter(|&l| l != none).collect();
+
+    f.add_exactly_one(&possible);
+
+    Var { range: min..=max, repr: Repr::OneHot(values) }
+}
+
+/// A constraint posted on declared variables, known by their index, which
+/// [`Problem::define`] uses to narrow their ranges before encoding it
+#[derive(Clone,Debug)]
+enum Posted {
+    /// The variables take different values, summing to the total
+    DistinctSum(Vec<usize>, usize),
+    /// The variables sum to the total
+    Sum(Vec<usize>, usize),
+}
+
+impl Posted {
+    /// The values that the `i`-th of its variables can take, so that the others can make
+    /// up the rest of the total within `ranges`. Only the bounds of the others are used,
+    /// so some of the values might still be impossible.
+    fn bounds(&self, i: usize, ranges: &[Interval]) -> Interval {
+        let (Posted::DistinctSum(vars, total) | Posted::Sum(vars, total)) = self;
+        let others = move || vars.iter().enumerate().filter(move |&(j, _)| j != i).map(move |(_, &v)| &ranges[v]);
+        let range = &ranges[vars[i]];
+
+        let fits = |v: usize| -> bool {
+            let Some(rest) = total.checked_sub(v) else { return false };
+            match self {
+                Posted::Sum(..) => interval::sum_all(others()).contains(&rest),
+                // The others take distinct values, other than v, from any of their ranges
+                Posted::DistinctSum(..) => {
+                    let values: BTreeSet<usize> = others().flat_map(|r| r.clone()).filter(|&n| n != v).collect();
+                    let count = vars.len() - 1;
+                    let low: usize = values.iter().take(count).sum();
+                    let high: usize = values.iter().rev().take(count).sum();
+                    values.len() >= count && (low..=high).contains(&rest)
+                },
+            }
+        };
+
+        let Some(first) = range.clone().find(|&v| fits(v)) else { return interval::empty() };
+        let last = range.clone().rev().find(|&v| fits(v)).unwrap_or(first);
+        first..=last
+    }
+}
+
+/// Bounds consistency: shrink the `ranges` to the values allowed by each of the `posted`
+/// constraints, until none of them changes. A range left empty has no value, and the
+/// problem no solution.
+fn narrow(ranges: &mut [Interval], posted: &[Posted]) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for constraint in posted {
+            let (Posted::DistinctSum(vars, _) | Posted::Sum(vars, _)) = constraint;
+            for (i, &v) in vars.iter().enumerate() {
+                let bounds = constraint.bounds(i, ranges);
+                let narrowed = interval::intersect(&ranges[v], &bounds);
+                if narrowed != ranges[v] {
+                    ranges[v] = narrowed;
+                    changed = true;
+                }
+            }
+        }
+    }
+}
+
+/// The number of bits needed to write `n`
+fn width(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()) as usize
+}
+
+/// The variable itself if it is one-hot, or else a new one-hot variable tied to its bits
+fn unary<'v, F: ExtendFormula>(f: &mut F, var: &'v Var) -> Cow<'v, Var> {
+    if !var.is_binary() { return Cow::Borrowed(var) }
+
+    let r = f.new_int(var.range());
+    for n in var.range() {
+        let bits = var.is(n);
+        for &b in &bits {
+            f.add_clause(&[!r.one_hot(n), b]);
+        }
+        let mut clause: Vec<Lit> = bits.iter().map(|&b| !b).collect();
+        clause.push(r.one_hot(n));
+        f.add_clause(&clause);
+    }
+    Cow::Owned(r)
+}
+
+/// The bits of the variable minus the start of its range, tied to its values if it is one-hot
+fn bits<F: ExtendFormula>(f: &mut F, var: &Var) -> Vec<Lit> {
+    if let Repr::Binary(bits) = &var.repr { return bits.clone() }
+
+    let start = *var.range().start();
+    // Empty ranges have no value, and need no bit
+    let bits: Vec<Lit> = (0..width(var.range().end().saturating_sub(start))).map(|_| f.new_lit()).collect();
+    for (n, &v) in var.values() {
+        for (i, &b) in bits.iter().enumerate() {
+            f.add_clause(&[!v, if (n - start) >> i & 1 == 1 { b } else { !b }]);
+        }
+    }
+    bits
+}
+
+/// Require `a + gap <= b`: each value of `a` implies one of the values of `b` far enough above it
+fn add_order<F: ExtendFormula>(f: &mut F, a: &Var, b: &Var, gap: usize) {
+    let (a, b) = (unary(f, a), unary(f, b));
+    for (i, &ai) in a.values() {
+        let mut clause = vec![!ai];
+        clause.extend(b.values().filter(|&(j, _)| j >= i + gap).map(|(_, &bj)| bj));
+        f.add_clause(&clause);
+    }
+}
+
+impl Problem {
+    pub fn new() -> Self {
+        Self::with_encoding(Encoding::default())
+    }
+
+    /// A problem where sums are encoded as chosen
+    pub fn with_encoding(encoding: Encoding) -> Self {
+        Self {
+            inner: CnfFormula::new(),
+            encoding,
+            declared: vec![],
+            posted: vec![],
+        }
+    }
+
+    /// The clauses built so far
+    pub fn formula(&self) -> &CnfFormula {
+        &self.inner
+    }
+
+    /// A new boolean literal, such as a guard for [`Problem::implies`]
+    pub fn new_lit(&mut self) -> Lit {
+        self.inner.new_lit()
+    }
+
+    pub fn new_var(&mut self, range: RangeInclusive<usize>) -> Var {
+        self.inner.new_int(range)
+    }
+
+    /// Declare a variable taking a value in `range`, without creating it yet, so that
+    /// the constraints posted on it can narrow its range first. Returns its index among
+    /// the variables created by the next call to [`Problem::define`].
+    pub fn declare(&mut self, range: RangeInclusive<usize>) -> usize {
+        self.declared.push(range);
+        self.declared.len() - 1
+    }
+
+    /// Require the declared variables `vars` to take different values, summing to `total`.
+    /// See [`Problem::distinct_sum`].
+    pub fn post_distinct_sum(&mut self, vars: &[usize], total: usize) {
+        self.posted.push(Posted::DistinctSum(vars.to_vec(), total))
+    }
+
+    /// Require the declared variables `vars` to sum to `total`
+    pub fn post_sum(&mut self, vars: &[usize], total: usize) {
+        self.posted.push(Posted::Sum(vars.to_vec(), total))
+    }
+
+    /// Create the declared variables, and encode the constraints posted on them.
+    /// Their ranges are first narrowed to the values that fit the bounds of the
+    /// other variables of each constraint, such as 1 to 3 for two distinct digits
+    /// summing to 4, which keeps their values and the sets of values with the right
+    /// sum out of the formula.
+    pub fn define(&mut self) -> Vec<Var> {
+        let mut ranges = std::mem::take(&mut self.declared);
+        let posted = std::mem::take(&mut self.posted);
+        narrow(&mut ranges, &posted);
+
+        let vars: Vec<Var> = ranges.into_iter().map(|range| self.new_var(range)).collect();
+        for constraint in posted {
+            match constraint {
+                Posted::DistinctSum(indices, total) => {
+                    let terms: Vec<Var> = indices.iter().map(|&i| vars[i].clone()).collect();
+                    self.distinct_sum(&terms, total)
+                },
+                Posted::Sum(indices, total) => {
+                    let terms: Vec<Var> = indices.iter().map(|&i| vars[i].clone()).collect();
+                    let sum = self.sum_all(&terms);
+                    self.equals(&sum, total)
+                },
+            }
         }
+        vars
+    }
+
+    pub fn sum(&mut self, a: &Var, b: &Var) -> Var {
+        match self.encoding {
+            Encoding::OneHot => self.inner.add_sum(a, b),
+            Encoding::Binary => self.inner.add_binary_sum(a, b),
+        }
+    }
+
+    /// A new variable holding the sum of all the `vars`, added as a balanced tree
+    /// of sums in the encoding of the problem. See [`IntFormula::add_sum_all`].
+    pub fn sum_all(&mut self, vars: &[Var]) -> Var {
+        match vars {
+            [] => self.new_var(0..=0),
+            [v] => v.clone(),
+            _ => {
+                let (left, right) = vars.split_at(vars.len() / 2);
+                let (left, right) = (self.sum_all(left), self.sum_all(right));
+                self.sum(&left, &right)
+            },
+        }
+    }
+
+    pub fn distinct_sum(&mut self, vars: &[Var], total: usize) {
+        self.inner.add_distinct_sum(vars, total)
+    }
+
+    pub fn product(&mut self, a: &Var, b: &Var) -> Var {
+        self.inner.add_product(a, b)
+    }
+
+    pub fn product_all(&mut self, vars: &[Var]) -> Var {
+        self.inner.add_product_all(vars)
+    }
+
+    pub fn abs_diff(&mut self, a: &Var, b: &Var) -> Var {
+        self.inner.add_abs_diff(a, b)
+    }
+
+    pub fn min(&mut self, vars: &[Var]) -> Var {
+        self.inner.add_min(vars)
+    }
+
+    pub fn max(&mut self, vars: &[Var]) -> Var {
+        self.inner.add_max(vars)
+    }
+
+    pub fn diff_at_least(&mut self, a: &Var, b: &Var, k: usize) {
+        self.inner.add_diff_at_least(a, b, k)
+    }
+
+    pub fn not_equals(&mut self, a: &Var, b: &Var) {
+        self.inner.add_not_equal(a, b)
+    }
+
+    pub fn all_different(&mut self, vars: &[Var]) {
+        self.inner.add_all_different(vars)
     }
 
     pub fn equals(&mut self, var: &Var, val: usize) {
-        self.inner.add_clause(&[var[val]])
+        self.inner.add_value(var, val)
     }
 
-    pub fn solve(&self) -> Option<Model> {
-        let mut solver = varisat::Solver::new();
+    pub fn less_than(&mut self, a: &Var, b: &Var) {
+        self.inner.add_less_than(a, b)
+    }
+
+    pub fn less_equal(&mut self, a: &Var, b: &Var) {
+        self.inner.add_less_equal(a, b)
+    }
+
+    pub fn equals_var(&mut self, a: &Var, b: &Var) {
+        self.inner.add_equal_vars(a, b)
+    }
+
+    /// Add constraints that only need to hold when `guard` is true. See [`IntFormula::add_implied`].
+    pub fn implies<R>(&mut self, guard: Lit, constraint: impl FnOnce(&mut Guarded<'_, CnfFormula>) -> R) -> R {
+        self.inner.add_implied(guard, constraint)
+    }
+
+    /// Forbid a combination of values: at least one of the given variables
+    /// must take a value different from the one it is paired with.
+    /// Values outside the range of their variable are already excluded.
+    pub fn exclude<'v>(&mut self, assignment: impl IntoIterator<Item=(&'v Var, usize)>) {
+        if let Some(clause) = exclusion(assignment) {
+            self.inner.add_clause(&clause)
+        }
+    }
+
+    /// A solution of the problem, if it has any
+    pub fn solve(&self) -> Result<Option<Model>, BackendError> {
+        let mut solver = Solver::new();
         solver.add_formula(&self.inner);
-        solver.solve().expect("Solver error");
-        Some(Model { inner: solver.model()? })
+        solver.solve()?;
+        Ok(solver.model().map(|inner| Model { inner }))
     }
 
+    /// Load the problem into a solver, for incremental solving.
+    pub fn session(&self) -> Session {
+        let mut solver = Solver::new();
+        solver.add_formula(&self.inner);
+        Session { solver }
+    }
+
+}
+
+/// The clause forbidding a combination of values, or `None` if one of them
+/// is out of the range of its variable, so that it never happens anyway.
+fn exclusion<'v>(assignment: impl IntoIterator<Item=(&'v Var, usize)>) -> Option<Vec<Lit>> {
+    let mut clause = vec![];
+    for (var, val) in assignment {
+        if !var.range().contains(&val) { return None }
+        clause.extend(var.is(val).into_iter().map(|l| !l));
+    }
+    Some(clause)
+}
+
+impl Session {
+    /// Solve, assuming that every listed variable takes the value it is paired with.
+    /// There is no solution if one of the values is out of the range of its variable.
+    pub fn solve<'v>(&mut self, assumptions: impl IntoIterator<Item=(&'v Var, usize)>) -> Result<Option<Model>, BackendError> {
+        let assumptions: Vec<(&Var, usize)> = assumptions.into_iter().collect();
+        if assumptions.iter().any(|(var, val)| !var.range().contains(val)) {
+            return Ok(None)
+        }
+        let assumptions: Vec<Lit> = assumptions.into_iter()
+            .flat_map(|(var, val)| var.is(val))
+            .collect();
+        Ok(solve_assuming(&mut self.solver, &assumptions)?.map(|inner| Model { inner }))
+    }
+
+    /// Draw a solution at random, giving random values to `vars` first.
+    /// See [`random_model`].
+    pub fn solve_random<'v>(&mut self, vars: impl IntoIterator<Item=&'v Var>, rng: &mut impl Rng) -> Result<Option<Model>, BackendError> {
+        let vars: Vec<varisat::Var> = vars.into_iter()
+            .flat_map(|var| var.lits().iter().map(|l| l.var()))
+            .collect();
+        Ok(random_model(&mut self.solver, &[], &vars, rng)?.map(|inner| Model { inner }))
+    }
+
+    /// Among `candidates`, value literals (such as `var.lit(v)`) that hold in a known
+    /// solution, find those that hold in every solution. See [`backbone`].
+    pub fn backbone<K>(&mut self, candidates: Vec<(K, Lit)>) -> Result<Vec<K>, BackendError> {
+        backbone(&mut self.solver, &[], candidates)
+    }
+
+    /// Forbid a combination of values for all subsequent calls.
+    /// See [`Problem::exclude`].
+    pub fn exclude<'v>(&mut self, assignment: impl IntoIterator<Item=(&'v Var, usize)>) {
+        if let Some(clause) = exclusion(assignment) {
+            self.solver.add_clause(&clause)
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::util::solve::Models;
 
     #[test]
     fn one_variable() {
@@ -128,7 +791,7 @@ mod test {
         let d = ip.new_var(1..=6);
         ip.equals(&d, 5);
 
-        let m = ip.solve().unwrap();
+        let m = ip.solve().unwrap().unwrap();
         assert_eq!(m.value(&d), 5);
 
     }
@@ -142,12 +805,198 @@ mod test {
         let s = ip.sum(&a, &b);
         ip.equals(&s, 14);
 
-        let m = ip.solve().unwrap();
+        let m = ip.solve().unwrap().unwrap();
         assert_eq!(m.value(&a), 6);
         assert_eq!(m.value(&b), 8);
 
     }
 
+    #[test]
+    fn products() {
+        // A KenKen cage: three different digits with a product of 24
+        let mut ip = Problem::new();
+        let [a, b, c] = [(); 3].map(|_| ip.new_var(1..=4));
+        ip.all_different(&[a.clone(), b.clone(), c.clone()]);
+        let p = ip.product_all(&[a.clone(), b.clone(), c.clone()]);
+        assert_eq!(p.range(), 1..=64);
+        ip.equals(&p, 24);
+
+        let m = ip.solve().unwrap().unwrap();
+        let mut abc = [&a, &b, &c].map(|v| m.value(v));
+        abc.sort();
+        assert_eq!(abc, [2, 3, 4]);
+
+        // No product of two digits is 11
+        let mut ip = Problem::new();
+        let a = ip.new_var(1..=9);
+        let b = ip.new_var(1..=9);
+        let p = ip.product(&a, &b);
+        let mut session = ip.session();
+        assert!(session.solve([(&p, 11)]).unwrap().is_none());
+        assert_eq!(session.solve([(&a, 7), (&b, 6)]).unwrap().map(|m| m.value(&p)), Some(42));
+    }
+
+    #[test]
+    fn differences() {
+        // A kropki white dot: consecutive digits
+        let mut ip = Problem::new();
+        let a = ip.new_var(1..=9);
+        let b = ip.new_var(3..=5);
+        let d = ip.abs_diff(&a, &b);
+        assert_eq!(d.range(), 0..=6);
+        ip.equals(&d, 1);
+        let mut session = ip.session();
+        assert_eq!(session.solve([(&a, 6)]).unwrap().map(|m| m.value(&b)), Some(5));
+        assert_eq!(session.solve([(&a, 2)]).unwrap().map(|m| m.value(&b)), Some(3));
+        assert!(session.solve([(&a, 8)]).unwrap().is_none());
+
+        // Digits at least 3 apart
+        let mut ip = Problem::new();
+        let a = ip.new_var(1..=4);
+        let b = ip.new_var(1..=4);
+        ip.diff_at_least(&a, &b, 3);
+        let mut session = ip.session();
+        assert_eq!(session.solve([(&a, 1)]).unwrap().map(|m| m.value(&b)), Some(4));
+        assert_eq!(session.solve([(&b, 1)]).unwrap().map(|m| m.value(&a)), Some(4));
+        assert!(session.solve([(&a, 2)]).unwrap().is_none());
+    }
+
+    #[test]
+    fn extrema() {
+        let mut ip = Problem::new();
+        let a = ip.new_var(1..=9);
+        let b = ip.new_var(3..=5);
+        let c = ip.new_var(2..=7);
+        let vars = [a.clone(), b.clone(), c.clone()];
+        let low = ip.min(&vars);
+        let high = ip.max(&vars);
+        assert_eq!(low.range(), 1..=5);
+        assert_eq!(high.range(), 3..=9);
+
+        let mut session = ip.session();
+        let m = session.solve([(&a, 6), (&b, 4), (&c, 2)]).unwrap().unwrap();
+        assert_eq!((m.value(&low), m.value(&high)), (2, 6));
+
+        // The largest is 3, which only b can be sure to reach
+        let m = session.solve([(&high, 3)]).unwrap().unwrap();
+        assert_eq!(m.value(&b), 3);
+        assert!(vars.iter().all(|v| m.value(v) <= 3));
+        assert!(session.solve([(&low, 5), (&high, 4)]).unwrap().is_none());
+    }
+
+    #[test]
+    fn binary() {
+        let mut ip = Problem::with_encoding(Encoding::Binary);
+        let [a, b, c] = [(); 3].map(|_| ip.new_var(1..=9));
+        ip.all_different(&[a.clone(), b.clone(), c.clone()]);
+        let r = ip.sum(&a, &b);
+        let r = ip.sum(&r, &c);
+        assert!(r.is_binary());
+        assert_eq!(r.range(), 3..=27);
+        ip.equals(&r, 24);
+
+        let m = ip.solve().unwrap().unwrap();
+        let mut abc = [&a, &b, &c].map(|v| m.value(v));
+        abc.sort();
+        assert_eq!(abc, [7, 8, 9]);
+
+        let mut session = ip.session();
+        assert!(session.solve([(&r, 24), (&a, 6)]).unwrap().is_none());
+        let s = Problem::new().sum(&r, &a);
+        assert!(s.is_binary());
+
+        // Binary variables between the powers of two
+        let mut solver = Solver::new();
+        let v = solver.new_binary_int(3..=8);
+        let count = Models::new(&mut solver, v.lits().iter().map(|l| l.var()).collect()).try_count().unwrap();
+        assert_eq!(count, 6);
+
+        let mut solver = Solver::new();
+        let v = solver.new_binary_int(3..=8);
+        let w = solver.new_binary_int(2..=5);
+        solver.add_less_than(&w, &v);
+        solver.add_value(&v, 3);
+        let model = Model { inner: solve_assuming(&mut solver, &[]).unwrap().unwrap() };
+        assert_eq!((model.value(&v), model.value(&w)), (3, 2));
+    }
+
+    #[test]
+    fn long_sums() {
+        for encoding in [Encoding::OneHot, Encoding::Binary] {
+            let mut ip = Problem::with_encoding(encoding);
+            let vars: Vec<Var> = (0..7).map(|_| ip.new_var(1..=9)).collect();
+            ip.all_different(&vars);
+            let total = ip.sum_all(&vars);
+            assert_eq!(total.range(), 7..=63);
+            ip.equals(&total, 29);
+
+            let m = ip.solve().unwrap().unwrap();
+            let mut digits: Vec<usize> = vars.iter().map(|v| m.value(v)).collect();
+            digits.sort();
+            assert_eq!(digits, [1, 2, 3, 4, 5, 6, 8]);
+
+            assert_eq!(ip.sum_all(&[]).range(), 0..=0);
+        }
+    }
+
+    #[test]
+    fn narrowing() {
+        // The cases of a single run, on digits
+        let narrowed = |count: usize, total: usize| {
+            let mut ranges = vec![1..=9; count];
+            narrow(&mut ranges, &[Posted::DistinctSum((0..count).collect(), total)]);
+            ranges[0].clone()
+        };
+        assert_eq!(narrowed(2, 4), 1..=3);
+        assert_eq!(narrowed(2, 17), 8..=9);
+        assert_eq!(narrowed(3, 7), 1..=4);
+        assert_eq!(narrowed(4, 20), 1..=9);
+        assert_eq!(narrowed(1, 6), 6..=6);
+        assert!(narrowed(2, 2).is_empty());
+        assert_eq!(narrowed(9, 45), 1..=9);
+        assert!(narrowed(10, 45).is_empty());
+
+        // A cell shared by two runs takes the bounds of both, and passes them on
+        let mut ranges = vec![1..=9; 3];
+        narrow(&mut ranges, &[Posted::DistinctSum(vec![0, 1], 4), Posted::Sum(vec![0, 2], 11)]);
+        assert_eq!(ranges, [3..=3, 1..=1, 8..=8]);
+
+        // Posted constraints are encoded on the narrowed variables
+        let mut ip = Problem::new();
+        let (a, b) = (ip.declare(1..=9), ip.declare(1..=9));
+        ip.post_distinct_sum(&[a, b], 17);
+        let vars = ip.define();
+        assert_eq!(vars[a].range(), 8..=9);
+        let mut session = ip.session();
+        assert_eq!(session.solve([(&vars[b], 8)]).unwrap().unwrap().value(&vars[a]), 9);
+        assert!(session.solve([(&vars[a], 7)]).unwrap().is_none());
+
+        let mut ip = Problem::new();
+        let (a, b) = (ip.declare(1..=9), ip.declare(1..=9));
+        ip.post_distinct_sum(&[a, b], 2);
+        assert!(ip.define()[a].range().is_empty());
+        assert!(ip.solve().unwrap().is_none());
+    }
+
+    #[test]
+    fn distinct_sums() {
+        let mut ip = Problem::new();
+        let vars: Vec<Var> = (0..3).map(|_| ip.new_var(1..=9)).collect();
+        ip.distinct_sum(&vars, 23);
+        let mut session = ip.session();
+
+        let m = session.solve([(&vars[0], 9)]).unwrap().unwrap();
+        let mut digits: Vec<usize> = vars.iter().map(|v| m.value(v)).collect();
+        digits.sort();
+        assert_eq!(digits, [6, 8, 9]);
+        assert!(session.solve([(&vars[0], 5)]).unwrap().is_none());
+
+        let mut ip = Problem::new();
+        let vars: Vec<Var> = (0..2).map(|_| ip.new_var(1..=9)).collect();
+        ip.distinct_sum(&vars, 18);
+        assert!(ip.solve().unwrap().is_none());
+    }
+
     #[test]
     fn distinct_numbers() {
 
@@ -166,7 +1015,7 @@ mod test {
 
         ip.equals(&r, 7);
 
-        let m = ip.solve().unwrap();
+        let m = ip.solve().unwrap().unwrap();
 
         let mut abc = [&a, &b, &c].map(|v| m.value(v));
         abc.sort();
@@ -175,4 +1024,145 @@ mod test {
 
     }
 
+    #[test]
+    fn all_different() {
+        fn count(n: usize, range: RangeInclusive<usize>) -> usize {
+            let mut solver = Solver::new();
+            let vars: Vec<Var> = (0..n).map(|_| solver.new_int(range.clone())).collect();
+            solver.add_all_different(&vars);
+            let lits = vars.iter().flat_map(|v| v.lits().iter().map(|l| l.var())).collect();
+            Models::new(&mut solver, lits).try_count().unwrap()
+        }
+        assert_eq!(count(3, 1..=3), 6);
+        assert_eq!(count(3, 1..=4), 24);
+        assert_eq!(count(5, 1..=5), 120);
+        assert_eq!(count(6, 1..=7), 5040);
+        assert_eq!(count(4, 1..=3), 0);
+    }
+
+    #[test]
+    fn ordering() {
+        // A Futoshiki row: a < b < c with values from 1 to 3
+        let mut ip = Problem::new();
+        let [a, b, c] = [(); 3].map(|_| ip.new_var(1..=3));
+        ip.less_than(&a, &b);
+        ip.less_than(&b, &c);
+        let m = ip.solve().unwrap().unwrap();
+        assert_eq!([&a, &b, &c].map(|v| m.value(v)), [1, 2, 3]);
+
+        let mut ip = Problem::new();
+        let a = ip.new_var(1..=5);
+        let b = ip.new_var(3..=4);
+        ip.less_equal(&a, &b);
+        let mut session = ip.session();
+        assert!(session.solve([(&a, 4), (&b, 4)]).unwrap().is_some());
+        assert!(session.solve([(&a, 5)]).unwrap().is_none());
+
+        ip.less_than(&b, &a);
+        assert!(ip.solve().unwrap().is_none());
+    }
+
+    #[test]
+    fn equal_variables() {
+        let mut ip = Problem::new();
+        let a = ip.new_var(1..=6);
+        let b = ip.new_var(4..=9);
+        let four = ip.new_var(4..=4);
+        ip.equals_var(&a, &b);
+        ip.not_equals(&a, &four);
+
+        let mut session = ip.session();
+        assert_eq!(session.solve([(&a, 6)]).unwrap().map(|m| m.value(&b)), Some(6));
+        assert!(session.solve([(&a, 3)]).unwrap().is_none());
+        assert!(session.solve([(&b, 7)]).unwrap().is_none());
+        let m = session.solve([]).unwrap().unwrap();
+        assert!([5, 6].contains(&m.value(&b)));
+    }
+
+    #[test]
+    fn implication() {
+        // A shaded cell counts in the sum of its row
+        let mut ip = Problem::new();
+        let shaded = ip.new_lit();
+        let a = ip.new_var(1..=5);
+        let b = ip.new_var(1..=5);
+        let s = ip.sum(&a, &b);
+        let total = ip.new_var(1..=10);
+        ip.implies(shaded, |f| f.add_equal_vars(&s, &total));
+        ip.implies(!shaded, |f| f.add_equal_vars(&a, &total));
+
+        let mut session = ip.session();
+        let m = session.solve([(&a, 2), (&b, 4), (&total, 6)]).unwrap().unwrap();
+        assert!(m.holds(shaded));
+        let m = session.solve([(&a, 2), (&b, 4), (&total, 2)]).unwrap().unwrap();
+        assert!(m.holds(!shaded));
+        assert!(session.solve([(&a, 2), (&b, 4), (&total, 5)]).unwrap().is_none());
+
+        // An impossible constraint only rules out its guard
+        let mut ip = Problem::new();
+        let guard = ip.new_lit();
+        let a = ip.new_var(1..=3);
+        let d = ip.implies(guard, |f| {
+            let d = f.new_int(4..=5);
+            f.add_less_than(&d, &a);
+            d
+        });
+        let m = ip.solve().unwrap().unwrap();
+        assert!(m.holds(!guard));
+        assert!(ip.session().solve([(&d, 4), (&a, 1)]).unwrap().is_some());
+    }
+
+    #[test]
+    fn decode() {
+        let mut ip = Problem::new();
+        let a = ip.new_var(1..=3);
+        let b = ip.new_var(2..=3);
+        ip.less_than(&a, &b);
+        let grid = Matrix::new(vec![Some(a.clone()), None, Some(b.clone())], (1, 3)).unwrap();
+
+        let m = ip.session().solve([(&a, 2)]).unwrap().unwrap();
+        assert_eq!(m.values(&grid)[0], [Some(2), None, Some(3)]);
+
+        // A variable of a larger problem, beyond those of the model
+        let mut other = Problem::new();
+        other.new_var(1..=9);
+        let c = other.new_var(1..=9);
+        assert_eq!(m.try_value(&c), None);
+    }
+
+    #[test]
+    fn exclusion() {
+        let mut ip = Problem::new();
+
+        let a = ip.new_var(1..=2);
+        let b = ip.new_var(1..=2);
+        ip.not_equals(&a, &b);
+        ip.exclude([(&a, 1), (&b, 2)]);
+
+        let m = ip.solve().unwrap().unwrap();
+        assert_eq!((m.value(&a), m.value(&b)), (2, 1));
+
+        ip.exclude([(&a, 2), (&b, 1)]);
+        assert!(ip.solve().unwrap().is_none());
+    }
+
+    #[test]
+    fn session() {
+        let mut ip = Problem::new();
+
+        let a = ip.new_var(1..=9);
+        let b = ip.new_var(1..=9);
+        let s = ip.sum(&a, &b);
+        ip.equals(&s, 10);
+
+        let mut session = ip.session();
+        let m = session.solve([(&a, 3)]).unwrap().unwrap();
+        assert_eq!(m.value(&b), 7);
+
+        let m = session.solve([(&b, 1)]).unwrap().unwrap();
+        assert_eq!(m.value(&a), 9);
+
+        assert!(session.solve([(&a, 5), (&b, 4)]).unwrap().is_none());
+    }
+
 }
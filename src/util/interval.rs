@@ -0,0 +1,80 @@
+//! Operations on the ranges of values of integer variables, as `RangeInclusive<usize>`.
+//! A range whose start is past its end is empty, as for the standard type, and all the
+//! empty ranges are treated alike: any of them is returned as `1..=0`.
+
+use std::ops::RangeInclusive;
+
+pub type Interval = RangeInclusive<usize>;
+
+/// The canonical empty interval
+pub fn empty() -> Interval {
+    RangeInclusive::new(1, 0)
+}
+
+/// The values in both intervals
+pub fn intersect(a: &Interval, b: &Interval) -> Interval {
+    let range = *a.start().max(b.start())..=*a.end().min(b.end());
+    if range.is_empty() { empty() } else { range }
+}
+
+/// The smallest interval holding both, including the values between them
+pub fn union(a: &Interval, b: &Interval) -> Interval {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => empty(),
+        (true, false) => b.clone(),
+        (false, true) => a.clone(),
+        (false, false) => *a.start().min(b.start())..=*a.end().max(b.end()),
+    }
+}
+
+/// True if every value of `inner` is in `outer`
+pub fn contains(outer: &Interval, inner: &Interval) -> bool {
+    inner.is_empty() || (outer.start() <= inner.start() && inner.end() <= outer.end())
+}
+
+/// Every value moved by `offset`, or `None` if one of them goes below 0
+pub fn shift(a: &Interval, offset: isize) -> Option<Interval> {
+    if a.is_empty() { return Some(empty()) }
+    Some(a.start().checked_add_signed(offset)?..=a.end().checked_add_signed(offset)?)
+}
+
+/// The values of `x + y` for `x` in `a` and `y` in `b`
+pub fn sum(a: &Interval, b: &Interval) -> Interval {
+    if a.is_empty() || b.is_empty() { return empty() }
+    (a.start() + b.start())..=(a.end() + b.end())
+}
+
+/// The values of a sum with a term in each interval, `0..=0` for no interval at all
+pub fn sum_all<'a>(intervals: impl IntoIterator<Item=&'a Interval>) -> Interval {
+    intervals.into_iter().fold(0..=0, |acc, i| sum(&acc, i))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn operations() {
+        assert_eq!(intersect(&(1..=6), &(4..=9)), 4..=6);
+        assert_eq!(intersect(&(4..=9), &(1..=6)), 4..=6);
+        assert_eq!(intersect(&(1..=3), &(2..=2)), 2..=2);
+        assert!(intersect(&(1..=3), &(5..=9)).is_empty());
+
+        assert_eq!(union(&(1..=3), &(5..=9)), 1..=9);
+        assert_eq!(union(&empty(), &(5..=9)), 5..=9);
+        assert!(union(&(3..=1), &empty()).is_empty());
+
+        assert!(contains(&(1..=9), &(2..=9)));
+        assert!(!contains(&(1..=9), &(0..=3)));
+        assert!(contains(&(4..=4), &(7..=3)));
+
+        assert_eq!(shift(&(2..=5), -2), Some(0..=3));
+        assert_eq!(shift(&(2..=5), -3), None);
+        assert_eq!(shift(&(2..=5), 4), Some(6..=9));
+
+        assert_eq!(sum(&(1..=9), &(2..=3)), 3..=12);
+        assert!(sum(&(1..=9), &empty()).is_empty());
+        assert_eq!(sum_all(&[1..=9, 1..=9, 0..=1]), 2..=19);
+        assert_eq!(sum_all(&[]), 0..=0);
+    }
+}
@@ -0,0 +1,151 @@
+//! A backend over the IPASIR C interface, the incremental API implemented by most modern
+//! SAT solvers (CaDiCaL, Kissat's ancestors, Glucose, MiniSat...). Assumptions, models
+//! and failed assumptions go straight through, so the solver keeps what it learnt
+//! between calls, which matters on large puzzles.
+//!
+//! Only built with the `ipasir` feature. The build script links CaDiCaL by default;
+//! set `IPASIR_LIB` to the name of another library, and `IPASIR_LIB_DIR` to the
+//! directory holding it if it is not installed system-wide.
+
+use std::os::raw::{c_int, c_void};
+
+use varisat::{Lit, Var};
+
+use super::backend::{SatBackend, BackendError};
+
+extern "C" {
+    fn ipasir_init() -> *mut c_void;
+    fn ipasir_release(solver: *mut c_void);
+    fn ipasir_add(solver: *mut c_void, lit_or_zero: c_int);
+    fn ipasir_assume(solver: *mut c_void, lit: c_int);
+    fn ipasir_solve(solver: *mut c_void) -> c_int;
+    fn ipasir_val(solver: *mut c_void, lit: c_int) -> c_int;
+    fn ipasir_failed(solver: *mut c_void, lit: c_int) -> c_int;
+}
+
+pub struct Ipasir {
+    solver: *mut c_void,
+    vars: usize,
+    assumptions: Vec<Lit>,
+    model: Option<Vec<Lit>>,
+    core: Option<Vec<Lit>>,
+}
+
+impl Default for Ipasir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ipasir {
+    pub fn new() -> Self {
+        // SAFETY: no precondition, the solver is released on drop
+        let solver = unsafe { ipasir_init() };
+        Ipasir { solver, vars: 0, assumptions: vec![], model: None, core: None }
+    }
+
+    fn see(&mut self, lit: Lit) -> c_int {
+        self.vars = self.vars.max(lit.index() + 1);
+        lit.to_dimacs() as c_int
+    }
+}
+
+impl Drop for Ipasir {
+    fn drop(&mut self) {
+        // SAFETY: the pointer comes from ipasir_init, and is not used afterwards
+        unsafe { ipasir_release(self.solver) }
+    }
+}
+
+impl SatBackend for Ipasir {
+    fn new_var(&mut self) -> Var {
+        self.vars += 1;
+        Var::from_index(self.vars - 1)
+    }
+
+    fn add_clause(&mut self, clause: &[Lit]) {
+        for &lit in clause {
+            let lit = self.see(lit);
+            // SAFETY: valid solver, and a non-zero literal
+            unsafe { ipasir_add(self.solver, lit) }
+        }
+        // SAFETY: terminating the clause
+        unsafe { ipasir_add(self.solver, 0) }
+    }
+
+    fn assume(&mut self, assumptions: &[Lit]) {
+        self.assumptions = assumptions.to_vec();
+    }
+
+    fn solve(&mut self) -> Result<bool, BackendError> {
+        let assumptions = std::mem::take(&mut self.assumptions);
+        for &lit in &assumptions {
+            let lit = self.see(lit);
+            // SAFETY: valid solver, and a non-zero literal
+            unsafe { ipasir_assume(self.solver, lit) }
+        }
+
+        // SAFETY: valid solver. Values and failed literals are read right after,
+        // while the solver is still in the resulting state.
+        match unsafe { ipasir_solve(self.solver) } {
+            10 => {
+                let model = (0..self.vars).map(Var::from_index)
+                    .map(|v| v.lit(unsafe { ipasir_val(self.solver, v.positive().to_dimacs() as c_int) } > 0))
+                    .collect();
+                self.model = Some(model);
+                self.core = None;
+                Ok(true)
+            },
+            20 => {
+                let core = assumptions.into_iter()
+                    .filter(|l| unsafe { ipasir_failed(self.solver, l.to_dimacs() as c_int) } != 0)
+                    .collect();
+                self.model = None;
+                self.core = Some(core);
+                Ok(false)
+            },
+            other => Err(BackendError::External(format!("IPASIR solver returned {}", other))),
+        }
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        self.model.clone()
+    }
+
+    fn failed_core(&self) -> Option<Vec<Lit>> {
+        self.core.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::backend::with_backend;
+
+    #[test]
+    fn incremental() {
+        let mut solver = Ipasir::new();
+        let (a, b) = (solver.new_var().positive(), solver.new_var().positive());
+        solver.add_clause(&[a, b]);
+
+        solver.assume(&[!a]);
+        assert!(solver.solve().unwrap());
+        assert_eq!(solver.model().unwrap(), vec![!a, b]);
+
+        solver.add_clause(&[!b]);
+        solver.assume(&[!a, b]);
+        assert!(!solver.solve().unwrap());
+        assert!(solver.failed_core().unwrap().iter().all(|l| [!a, b].contains(l)));
+
+        // Assumptions only last for one call
+        assert!(solver.solve().unwrap());
+        assert_eq!(solver.model().unwrap(), vec![a, !b]);
+    }
+
+    #[test]
+    fn games() {
+        let problem: crate::binero::Problem = "00..\n....\n....\n....\n".parse().unwrap();
+        let solution = with_backend(|| Box::new(Ipasir::new()), || problem.solve()).unwrap().unwrap();
+        assert!(problem.verify(&solution).is_ok());
+    }
+}
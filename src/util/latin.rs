@@ -0,0 +1,107 @@
+//! Latin squares: N×N grids where every row and every column holds each of N values
+//! exactly once. K-Doku, Sudoku, Futoshiki or Skyscrapers are Latin squares with extra
+//! rules, which only need to be added on top of the variables built here.
+
+use std::collections::BTreeSet;
+
+use varisat::{ExtendFormula, Lit, Var};
+
+use super::{matrix::Matrix, solve::DnfFormula, vargrid::VarGrid};
+
+/// The variables of a Latin square, one per cell and value, with the rules of
+/// Latin squares already added to the formula. Values go from `0` to `N-1`.
+#[derive(Clone, Debug)]
+pub struct Latin {
+    vars: VarGrid,
+}
+
+impl Latin {
+    pub fn new(f: &mut impl ExtendFormula, size: usize) -> Self {
+        let vars = VarGrid::new(f, (size, size), size);
+        let latin = Latin { vars };
+
+        for x in 0..size {
+            for y in 0..size {
+                // Each cell has exactly one value
+                let cell: Vec<Lit> = latin.cell(x, y).map(|v| v.positive()).collect();
+                f.add_exactly_one(&cell);
+            }
+        }
+
+        // Each row and each column contains each value at least once. They cannot contain
+        // a value twice: by the pigeonhole principle, another one would be missing.
+        for i in 0..size {
+            for z in 0..size {
+                let row: Vec<Lit> = (0..size).map(|y| latin.var(i, y, z).positive()).collect();
+                let column: Vec<Lit> = (0..size).map(|x| latin.var(x, i, z).positive()).collect();
+                f.add_clause(&row);
+                f.add_clause(&column);
+            }
+        }
+
+        latin
+    }
+
+    pub fn size(&self) -> usize {
+        self.vars.depth()
+    }
+
+    /// The variable telling if the cell (x,y) holds the value `z`
+    pub fn var(&self, x: usize, y: usize, z: usize) -> Var {
+        self.vars.var(x, y, z)
+    }
+
+    /// The variables of the cell (x,y), one per value
+    pub fn cell(&self, x: usize, y: usize) -> impl Iterator<Item=Var> {
+        self.vars.cell(x, y)
+    }
+
+    /// All the variables of the square
+    pub fn vars(&self) -> &VarGrid {
+        &self.vars
+    }
+
+    /// The value of every cell in a model
+    pub fn decode(&self, model: &[Lit]) -> Matrix<usize> {
+        let model: BTreeSet<Lit> = model.iter().copied().collect();
+        let size = self.size();
+        Matrix::from_fn((size, size), |x, y| self.cell(x, y)
+            .position(|v| model.contains(&v.positive()))
+            .expect("every cell has a value"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{backend::Solver, solve::{solve_assuming, Models}};
+
+    fn count(size: usize) -> usize {
+        let mut solver = Solver::new();
+        let latin = Latin::new(&mut solver, size);
+        Models::new(&mut solver, latin.vars().vars().collect()).try_count().unwrap()
+    }
+
+    #[test]
+    fn squares() {
+        assert_eq!(count(1), 1);
+        assert_eq!(count(2), 2);
+        assert_eq!(count(3), 12);
+        assert_eq!(count(4), 576);
+    }
+
+    #[test]
+    fn decode() {
+        let mut solver = Solver::new();
+        solver.new_lit();
+        let latin = Latin::new(&mut solver, 5);
+        let model = solve_assuming(&mut solver, &[latin.var(2, 3, 4).positive()]).unwrap().unwrap();
+        let square = latin.decode(&model);
+        assert_eq!(square[2][3], 4);
+        for i in 0..5 {
+            let mut row: Vec<_> = square[i].to_vec();
+            row.sort();
+            assert_eq!(row, [0, 1, 2, 3, 4]);
+        }
+    }
+}
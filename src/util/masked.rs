@@ -0,0 +1,164 @@
+//! Boards that are not full rectangles. Kakuro grids have blocks between their runs,
+//! samurai sudokus leave gaps between their overlapping squares, and crossword-like
+//! layouts have black cells. A [`MaskedMatrix`] is a rectangle with holes: only the
+//! cells of the board hold a value, and neighbors and runs never cross a hole.
+
+use std::ops::{Index, IndexMut, Range};
+
+use super::{coord::{Coord, Neighborhood}, matrix::Matrix};
+
+/// A board drawn on a rectangle, where the cells out of the board are `None`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaskedMatrix<T>(pub Matrix<Option<T>>);
+
+/// A maximal segment of consecutive board cells in a row or a column
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    /// True for a column, false for a row
+    pub vertical: bool,
+    /// Index of the row or the column
+    pub index: usize,
+    /// The cells of the run, along the row or the column
+    pub range: Range<usize>,
+}
+
+impl Run {
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    /// The cells of the run, in order
+    pub fn cells(&self) -> impl Iterator<Item=Coord> + '_ {
+        self.range.clone()
+            .map(|i| if self.vertical { Coord::new(i, self.index) } else { Coord::new(self.index, i) })
+    }
+}
+
+impl<T> MaskedMatrix<T> {
+    /// The board made of the cells set in `mask`, computing the value of each of
+    /// them from its coordinates
+    pub fn from_mask(mask: &Matrix<bool>, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        MaskedMatrix(Matrix::from_fn(mask.shape(), |x, y| mask[(x, y)].then(|| f(x, y))))
+    }
+
+    /// Shape of the enclosing rectangle
+    pub fn shape(&self) -> (usize, usize) {
+        self.0.shape()
+    }
+
+    /// True if the cell is on the board, false for holes and cells out of the rectangle
+    pub fn contains(&self, pos: impl Into<Coord>) -> bool {
+        self.get(pos).is_some()
+    }
+
+    /// The value of a cell, if it is on the board
+    pub fn get(&self, pos: impl Into<Coord>) -> Option<&T> {
+        let Coord { x, y } = pos.into();
+        self.0.get(x, y)?.as_ref()
+    }
+
+    /// The value of a cell, mutably, if it is on the board
+    pub fn get_mut(&mut self, pos: impl Into<Coord>) -> Option<&mut T> {
+        let Coord { x, y } = pos.into();
+        self.0.get_mut(x, y)?.as_mut()
+    }
+
+    /// The cells of the board, in row-major order
+    pub fn coords(&self) -> impl Iterator<Item=Coord> + '_ {
+        self.0.coords().filter(|&c| self.0[c].is_some())
+    }
+
+    /// The values of the board, along with their cells, in row-major order
+    pub fn iter(&self) -> impl Iterator<Item=(Coord, &T)> {
+        self.0.enumerate().filter_map(|(pos, v)| Some((Coord::from(pos), v.as_ref()?)))
+    }
+
+    /// The board cells of the given neighborhood of a location, in row-major order
+    pub fn neighborhood(&self, pos: impl Into<Coord>, neighborhood: &Neighborhood) -> Vec<Coord> {
+        let mut cells = self.0.neighborhood(pos, neighborhood);
+        cells.retain(|&c| self.contains(c));
+        cells
+    }
+
+    /// The board cells sharing an edge with the given location, in row-major order
+    pub fn adjacent(&self, pos: impl Into<Coord>) -> Vec<Coord> {
+        self.neighborhood(pos, &Neighborhood::ORTHOGONAL)
+    }
+
+    /// The runs of the board, in rows then in columns, a single cell being a run
+    pub fn runs(&self) -> Vec<Run> {
+        let (h, w) = self.shape();
+        let mut runs = vec![];
+
+        for vertical in [false, true] {
+            let (outer, inner) = if vertical { (w, h) } else { (h, w) };
+            for index in 0..outer {
+                let on = |i: usize| if vertical { self.contains((i, index)) } else { self.contains((index, i)) };
+                let mut i = 0;
+                while i < inner {
+                    let start = i;
+                    while i < inner && on(i) { i += 1 }
+                    if i > start {
+                        runs.push(Run { vertical, index, range: start..i });
+                    }
+                    i += 1;
+                }
+            }
+        }
+
+        runs
+    }
+
+    /// Creates a new board with the same holes by applying a closure to every value
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> MaskedMatrix<U> {
+        MaskedMatrix(self.0.map(|v| v.as_ref().map(&mut f)))
+    }
+
+    /// The cells of the board
+    pub fn mask(&self) -> Matrix<bool> {
+        self.0.map(Option::is_some)
+    }
+}
+
+/// Panics on a hole
+impl<T> Index<Coord> for MaskedMatrix<T> {
+    type Output = T;
+
+    fn index(&self, c: Coord) -> &T {
+        self.get(c).unwrap_or_else(|| panic!("{} is not on the board", c))
+    }
+}
+
+impl<T> IndexMut<Coord> for MaskedMatrix<T> {
+    fn index_mut(&mut self, c: Coord) -> &mut T {
+        self.get_mut(c).unwrap_or_else(|| panic!("{} is not on the board", c))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::matrix::mat;
+
+    #[test]
+    fn board() {
+        // An L shape, with a hole in the corner
+        let mask = mat![true, false, true; true, true, true];
+        let mut board = MaskedMatrix::from_mask(&mask, |x, y| 10 * x + y);
+        assert_eq!(board.mask(), mask);
+        assert!(!board.contains((0, 1)) && !board.contains((2, 0)));
+        assert_eq!(board[Coord::new(1, 2)], 12);
+        board[Coord::new(0, 0)] = 5;
+        assert_eq!(board.iter().map(|(_, &v)| v).collect::<Vec<_>>(), [5, 2, 10, 11, 12]);
+        assert_eq!(board.adjacent((1, 1)), [(1, 0), (1, 2)]);
+        assert_eq!(board.neighborhood((0, 0), &Neighborhood::KING), [(1, 0), (1, 1)]);
+
+        let runs: Vec<_> = board.runs().iter().map(|r| (r.vertical, r.index, r.range.clone())).collect();
+        assert_eq!(runs, [(false, 0, 0..1), (false, 0, 2..3), (false, 1, 0..3), (true, 0, 0..2), (true, 1, 1..2), (true, 2, 0..2)]);
+        assert_eq!(board.runs()[2].cells().collect::<Vec<_>>(), [(1, 0), (1, 1), (1, 2)]);
+    }
+}
@@ -1,17 +1,21 @@
 //! A large number of logical games, by virtue of existing on paper, use 2-dimensional structures.
 //! This module implement packed matrices, without the overhead of supporting multiple dimensions.
 
-use std::ops::{Index, IndexMut};
+use std::ops::{Index, IndexMut, Range};
 
 use thiserror::Error;
 
+use super::coord::{Coord, Neighborhood};
+
 /// A Matrix of dynamic size, with elements in `T`.
 /// Indexing exposes rows as slices. Individual elements of matrix `m`
-/// can be accessed with `m[x][y]`.
-#[derive(Clone,Debug,PartialEq,Eq)]
+/// can be accessed with `m[x][y]`, `m[(x,y)]` or `m[coord]`.
+#[derive(Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
 pub struct Matrix<T> {
     stride: usize,
     vec: Vec<T>,
+    /// Number of rows, which the length of `vec` cannot tell when there are no columns
+    rows: usize,
 }
 
 #[derive(PartialEq, Eq, Debug,Error)]
@@ -24,7 +28,33 @@ impl <T> Matrix<T> {
     /// Will fail if the length of `vec` doesn't match the requested shape.
     pub fn new(vec: Vec<T>, shape: (usize,usize)) -> Result<Self, ShapeError> {
         if vec.len() != shape.0 * shape.1 { return Err(ShapeError) }
-        Ok(Self { vec, stride: shape.1 })
+        Ok(Self { vec, stride: shape.1, rows: shape.0 })
+    }
+
+    /// Create a new matrix from its rows, which must all have the same length.
+    /// Will fail if they don't, or if there is no element at all.
+    pub fn from_rows(rows: impl IntoIterator<Item=Vec<T>>) -> Result<Self, ShapeError> {
+        let mut vec = vec![];
+        let mut stride = None;
+        let mut count = 0;
+        for row in rows {
+            if *stride.get_or_insert(row.len()) != row.len() { return Err(ShapeError) }
+            vec.extend(row);
+            count += 1;
+        }
+        match stride {
+            Some(stride) if stride > 0 => Ok(Self { vec, stride, rows: count }),
+            _ => Err(ShapeError),
+        }
+    }
+
+    /// Create a new matrix of the given shape, computing each element from its
+    /// coordinates (x,y), in row-major order.
+    pub fn from_fn(shape: (usize,usize), mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let vec = (0..shape.0).flat_map(|x| (0..shape.1).map(move |y| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+        Self { vec, stride: shape.1, rows: shape.0 }
     }
 
     /// Total number of elements in the matrix. Equal to `shape.0 * shape.1`
@@ -34,21 +64,46 @@ impl <T> Matrix<T> {
 
     /// Shape of the matrix
     pub fn shape(&self) -> (usize, usize) {
-        (self.vec.len() / self.stride, self.stride)
+        (self.rows, self.stride)
+    }
+
+    /// The element at (x,y), or `None` if that is out of the matrix
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        let (h, w) = self.shape();
+        if x < h && y < w { Some(&self.vec[x * self.stride + y]) } else { None }
+    }
+
+    /// The element at (x,y), mutably, or `None` if that is out of the matrix
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        let (h, w) = self.shape();
+        if x < h && y < w { Some(&mut self.vec[x * self.stride + y]) } else { None }
     }
 
     /// Creates a new matrix of the same shape by applying a closure to every element
     pub fn map<U,F>(&self, f: F) -> Matrix<U>
         where F: FnMut(&T) -> U
     {
-        Matrix { vec: self.vec.iter().map(f).collect(), stride: self.stride }
+        Matrix { vec: self.vec.iter().map(f).collect(), stride: self.stride, rows: self.rows }
     }
 
     /// Iterates over the matrix rows
     pub fn lines(&self) -> impl Iterator<Item=&[T]> + '_ {
-        (0..self.vec.len())
-            .step_by(self.stride)
-            .map(|i| &self.vec[i..][..self.stride])
+        (0..self.rows).map(|x| &self[x])
+    }
+
+    /// Iterate over all the elements in row-major order
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.vec.iter()
+    }
+
+    /// Iterate mutably over all the elements in row-major order
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.vec.iter_mut()
+    }
+
+    /// Iterate over all the elements in row-major order, along with their coordinates
+    pub fn enumerate(&self) -> impl Iterator<Item=((usize,usize), &T)> {
+        self.indices().zip(&self.vec)
     }
 
     /// Iterate over all the coordinate pairs in row-major order
@@ -57,22 +112,25 @@ impl <T> Matrix<T> {
         (0..h).flat_map(move |x| (0..w).map(move |y| (x,y)))
     }
 
-    /// Lists all the neighbors of the given location, truncating at the edge.
-    pub fn neighbors(&self, pos: (usize, usize)) -> Vec<(usize,usize)> {
-        let (x,y) = pos;
-        let (h, w) = self.shape();
-        let mut neighs = Vec::with_capacity(9);
+    /// Iterate over all the cells in row-major order
+    pub fn coords(&self) -> impl Iterator<Item=Coord> {
+        self.indices().map(Coord::from)
+    }
 
-        let mut row = |x| {
-            if y > 0 { neighs.push((x, y-1)) };
-            neighs.push((x, y));
-            if y+1 < w { neighs.push((x, y+1))};
-        };
+    /// Lists the cells of the given neighborhood of a location, in row-major order
+    pub fn neighborhood(&self, pos: impl Into<Coord>, neighborhood: &Neighborhood) -> Vec<Coord> {
+        neighborhood.around(pos.into(), self.shape())
+    }
+
+    /// Lists all the neighbors of the given location, itself included, truncating at
+    /// the edge. Neighbors come in row-major order.
+    pub fn neighbors(&self, pos: impl Into<Coord>) -> Vec<Coord> {
+        self.neighborhood(pos, &Neighborhood::BLOCK)
+    }
 
-        if x > 0 { row(x-1) };
-        row(x);
-        if x+1 < h { row(x+1) };
-        neighs
+    /// Lists the cells sharing an edge with the given location, in row-major order.
+    pub fn adjacent(&self, pos: impl Into<Coord>) -> Vec<Coord> {
+        self.neighborhood(pos, &Neighborhood::ORTHOGONAL)
     }
 
     /// Create a new matrix by applying in parallel an operation to every pair of elements from
@@ -87,9 +145,230 @@ impl <T> Matrix<T> {
         Ok(Matrix {
             stride: self.stride,
             vec: self.vec.iter().zip(&other.vec).map(f).collect(),
+            rows: self.rows,
         })
     }
 
+    /// A borrowed view of the rectangle made of the given rows and columns, such as a
+    /// box of a sudoku. Panics if the rectangle doesn't fit in the matrix.
+    pub fn view(&self, rows: Range<usize>, cols: Range<usize>) -> View<'_, T> {
+        self.check_view(&rows, &cols);
+        View { matrix: self, rows, cols }
+    }
+
+    /// A mutable view of the rectangle made of the given rows and columns. Panics if
+    /// the rectangle doesn't fit in the matrix.
+    pub fn view_mut(&mut self, rows: Range<usize>, cols: Range<usize>) -> ViewMut<'_, T> {
+        self.check_view(&rows, &cols);
+        ViewMut { matrix: self, rows, cols }
+    }
+
+    fn check_view(&self, rows: &Range<usize>, cols: &Range<usize>) {
+        let (h, w) = self.shape();
+        assert!(rows.start <= rows.end && rows.end <= h && cols.start <= cols.end && cols.end <= w,
+            "view {:?}x{:?} out of a {}x{} matrix", rows, cols, h, w);
+    }
+
+    /// Iterates over all the `h`×`w` rectangles of the matrix, overlapping, in row-major
+    /// order of their top left corner. There are none if the matrix is smaller.
+    pub fn windows(&self, h: usize, w: usize) -> impl Iterator<Item=View<'_, T>> {
+        assert!(h > 0 && w > 0, "empty windows");
+        let (mh, mw) = self.shape();
+        let corners = (0..(mh + 1).saturating_sub(h)).flat_map(move |x| (0..(mw + 1).saturating_sub(w)).map(move |y| (x, y)));
+        corners.map(move |(x, y)| self.view(x..x+h, y..y+w))
+    }
+
+    /// A borrowed view of the transposed matrix, without copying the elements: its rows
+    /// are the columns of this one. See [`Matrix::transpose`] for an owned copy.
+    pub fn transposed_view(&self) -> Transposed<'_, T> {
+        Transposed(self)
+    }
+
+}
+
+impl <T: Clone> Matrix<T> {
+    /// The transposed matrix, where the element at (x,y) moves to (y,x). Its rows are
+    /// the columns of this one, which lets row-wise rules be written once and applied
+    /// to both. Matrices without rows or columns keep their other side.
+    pub fn transpose(&self) -> Matrix<T> {
+        let (h, w) = self.shape();
+        Matrix::from_fn((w, h), |y, x| self[x][y].clone())
+    }
+}
+
+/// A matrix seen through its transposition, as returned by [`Matrix::transposed_view`].
+/// Its rows cannot be slices, so they are iterators instead.
+#[derive(Clone, Copy, Debug)]
+pub struct Transposed<'a, T>(&'a Matrix<T>);
+
+impl <'a, T> Transposed<'a, T> {
+    /// Shape of the view, that of the matrix swapped
+    pub fn shape(&self) -> (usize, usize) {
+        let (h, w) = self.0.shape();
+        (w, h)
+    }
+
+    /// Iterates over the rows of the view, i.e. the columns of the matrix
+    pub fn lines(&self) -> impl Iterator<Item=impl Iterator<Item=&'a T>> + 'a {
+        let m = self.0;
+        let (h, w) = m.shape();
+        (0..w).map(move |y| (0..h).map(move |x| &m[x][y]))
+    }
+}
+
+impl <T> Index<Coord> for Transposed<'_, T> {
+    type Output = T;
+
+    fn index(&self, c: Coord) -> &T {
+        &self.0[c.y][c.x]
+    }
+}
+
+/// A rectangle of a matrix, as returned by [`Matrix::view`]. It is indexed like a
+/// matrix, from its own top left corner.
+#[derive(Clone, Debug)]
+pub struct View<'a, T> {
+    matrix: &'a Matrix<T>,
+    rows: Range<usize>,
+    cols: Range<usize>,
+}
+
+impl <'a, T> View<'a, T> {
+    /// Shape of the view
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows.len(), self.cols.len())
+    }
+
+    /// Position of the top left corner of the view in the matrix
+    pub fn origin(&self) -> Coord {
+        Coord::new(self.rows.start, self.cols.start)
+    }
+
+    /// Iterates over the rows of the view
+    pub fn lines(&self) -> impl Iterator<Item=&'a [T]> + 'a {
+        let (matrix, cols) = (self.matrix, self.cols.clone());
+        self.rows.clone().map(move |x| &matrix[x][cols.clone()])
+    }
+
+    /// Iterate over all the elements in row-major order
+    pub fn iter(&self) -> impl Iterator<Item=&'a T> + 'a {
+        self.lines().flatten()
+    }
+
+    /// Iterate over the cells of the view in row-major order, as coordinates in the matrix
+    pub fn coords(&self) -> impl Iterator<Item=Coord> + 'a {
+        let cols = self.cols.clone();
+        self.rows.clone().flat_map(move |x| cols.clone().map(move |y| Coord::new(x, y)))
+    }
+}
+
+impl <T: Clone> View<'_, T> {
+    /// Copy the view into a matrix of its own
+    pub fn to_matrix(&self) -> Matrix<T> {
+        Matrix { vec: self.iter().cloned().collect(), stride: self.cols.len(), rows: self.rows.len() }
+    }
+}
+
+impl <T> Index<usize> for View<'_, T> {
+    type Output = [T];
+
+    fn index(&self, index: usize) -> &[T] {
+        assert!(index < self.rows.len(), "row {} out of a view of {} rows", index, self.rows.len());
+        &self.matrix[self.rows.start + index][self.cols.clone()]
+    }
+}
+
+impl <T> Index<Coord> for View<'_, T> {
+    type Output = T;
+
+    fn index(&self, c: Coord) -> &T {
+        &self[c.x][c.y]
+    }
+}
+
+/// A rectangle of a matrix that can be modified, as returned by [`Matrix::view_mut`].
+/// It is indexed like a matrix, from its own top left corner.
+#[derive(Debug)]
+pub struct ViewMut<'a, T> {
+    matrix: &'a mut Matrix<T>,
+    rows: Range<usize>,
+    cols: Range<usize>,
+}
+
+impl <T> ViewMut<'_, T> {
+    /// Shape of the view
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows.len(), self.cols.len())
+    }
+
+    /// Position of the top left corner of the view in the matrix
+    pub fn origin(&self) -> Coord {
+        Coord::new(self.rows.start, self.cols.start)
+    }
+
+    /// Iterate mutably over all the elements in row-major order
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=&mut T> {
+        // Without columns there is no element, but chunks still need a size
+        let stride = self.matrix.stride.max(1);
+        let cols = self.cols.clone();
+        self.matrix.vec
+            .chunks_mut(stride)
+            .skip(self.rows.start)
+            .take(self.rows.len())
+            .flat_map(move |row| &mut row[cols.clone()])
+    }
+
+    /// Set every element of the view to `value`
+    pub fn fill(&mut self, value: T) where T: Clone {
+        for v in self.iter_mut() {
+            *v = value.clone();
+        }
+    }
+}
+
+impl <T> Index<usize> for ViewMut<'_, T> {
+    type Output = [T];
+
+    fn index(&self, index: usize) -> &[T] {
+        assert!(index < self.rows.len(), "row {} out of a view of {} rows", index, self.rows.len());
+        &self.matrix[self.rows.start + index][self.cols.clone()]
+    }
+}
+
+impl <T> IndexMut<usize> for ViewMut<'_, T> {
+    fn index_mut(&mut self, index: usize) -> &mut [T] {
+        assert!(index < self.rows.len(), "row {} out of a view of {} rows", index, self.rows.len());
+        let cols = self.cols.clone();
+        &mut self.matrix[self.rows.start + index][cols]
+    }
+}
+
+impl <T> Index<Coord> for ViewMut<'_, T> {
+    type Output = T;
+
+    fn index(&self, c: Coord) -> &T {
+        &self[c.x][c.y]
+    }
+}
+
+impl <T> IndexMut<Coord> for ViewMut<'_, T> {
+    fn index_mut(&mut self, c: Coord) -> &mut T {
+        &mut self[c.x][c.y]
+    }
+}
+
+impl <T: Clone + PartialEq> Matrix<T> {
+    /// Compare matrices cell by cell, such as several solutions of the same puzzle:
+    /// the common value of each cell where they all agree, `None` where two of them
+    /// differ. Fails if there is no matrix at all, or if their shapes differ.
+    pub fn agreement(matrices: &[&Matrix<T>]) -> Result<Matrix<Option<T>>, ShapeError> {
+        let (first, others) = matrices.split_first().ok_or(ShapeError)?;
+        let mut result = first.map(|v| Some(v.clone()));
+        for m in others {
+            result = result.zip_with(m, |(a, b)| a.clone().filter(|a| a == b))?;
+        }
+        Ok(result)
+    }
 }
 
 impl <T> Index<usize> for Matrix<T> {
@@ -107,6 +386,62 @@ impl <T> IndexMut<usize> for Matrix<T> {
     }
 }
 
+impl <T> Index<(usize,usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize,usize)) -> &T {
+        &self[x][y]
+    }
+}
+
+impl <T> IndexMut<(usize,usize)> for Matrix<T> {
+    fn index_mut(&mut self, (x, y): (usize,usize)) -> &mut T {
+        &mut self[x][y]
+    }
+}
+
+impl <T> Index<Coord> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, c: Coord) -> &T {
+        &self[c.x][c.y]
+    }
+}
+
+impl <T> IndexMut<Coord> for Matrix<T> {
+    fn index_mut(&mut self, c: Coord) -> &mut T {
+        &mut self[c.x][c.y]
+    }
+}
+
+impl <T> IntoIterator for Matrix<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    /// The elements in row-major order
+    fn into_iter(self) -> Self::IntoIter {
+        self.vec.into_iter()
+    }
+}
+
+impl <'a, T> IntoIterator for &'a Matrix<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl <'a, T> IntoIterator for &'a mut Matrix<T> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
 macro_rules! umat {
     [$e:expr; $shape:expr] => {
         $crate::util::matrix::Matrix::new(vec![$e; $shape.0 * $shape.1], $shape).unwrap()
@@ -134,7 +469,7 @@ pub(crate) use mat;
 
 #[cfg(test)]
 mod test {
-    use crate::util::matrix::ShapeError;
+    use crate::util::{coord::Coord, matrix::ShapeError};
 
     use super::Matrix;
 
@@ -143,6 +478,23 @@ mod test {
         assert_eq!(ShapeError, Matrix::new(vec![1,2,3], (2,2)).unwrap_err())
     }
 
+    #[test]
+    fn constructors() {
+        let m = Matrix::from_rows([vec![1,2,3], vec![4,5,6]]).unwrap();
+        assert_eq!(m, mat![1,2,3; 4,5,6]);
+        assert_eq!(Matrix::from_fn((2,3), |x,y| 3*x + y + 1), m);
+        assert_eq!(Matrix::from_rows([vec![1,2], vec![3]]), Err(ShapeError));
+        assert_eq!(Matrix::<u8>::from_rows([]), Err(ShapeError));
+        assert_eq!(Matrix::<u8>::from_rows([vec![], vec![]]), Err(ShapeError));
+
+        // Rows without any column still count
+        let empty = Matrix::from_fn((3,0), |_, _| 0);
+        assert_eq!(empty.shape(), (3,0));
+        assert_eq!(empty.lines().count(), 3);
+        assert_eq!(empty.get(0, 0), None);
+        assert_eq!(umat![0; (2,0)].map(|v| v + 1).shape(), (2,0));
+    }
+
     #[test]
     fn lines() {
         let m = Matrix::new(vec![1,2,3,4,5,6], (3,2)).unwrap();
@@ -157,6 +509,21 @@ mod test {
         assert_eq!(vec![(0,0),(0,1),(1,0),(1,1),(2,0),(2,1)], idxs);
     }
 
+    #[test]
+    fn iterators() {
+        let mut m = mat![1,2; 3,4; 5,6];
+        assert_eq!(m.iter().copied().collect::<Vec<_>>(), [1,2,3,4,5,6]);
+        assert_eq!(m.enumerate().nth(3), Some(((1,1), &4)));
+        for v in m.iter_mut().filter(|v| **v % 2 == 0) {
+            *v = 0;
+        }
+        for v in &mut m {
+            *v += 1;
+        }
+        assert_eq!((&m).into_iter().sum::<i32>(), 15);
+        assert_eq!(m.into_iter().collect::<Vec<_>>(), [2,1,4,1,6,1]);
+    }
+
     #[test]
     fn access() {
         let m = Matrix::new(vec![1,2,3,4], (2,2)).unwrap();
@@ -164,6 +531,19 @@ mod test {
         assert_eq!(m[0][1], 2);
         assert_eq!(m[1][0], 3);
         assert_eq!(m[1][1], 4);
+        assert_eq!(m[(1,0)], 3);
+
+        let mut m = m;
+        m[(0,1)] = 7;
+        assert_eq!(m[0], [1,7]);
+
+        assert_eq!(m.get(1,1), Some(&4));
+        assert_eq!(m.get(0,2), None);
+        assert_eq!(m.get(2,0), None);
+        assert_eq!(m.get(usize::MAX, usize::MAX), None);
+        *m.get_mut(1,0).unwrap() = 8;
+        assert_eq!(m[1], [8,4]);
+        assert!(m.get_mut(5,0).is_none());
     }
 
     #[test]
@@ -174,4 +554,80 @@ mod test {
         assert_eq!(m.neighbors((1,2)), vec![(0,1),(0,2),(0,3), (1,1), (1,2), (1,3), (2,1), (2,2), (2,3)]);
         assert_eq!(m.neighbors((3,3)), vec![(2,2),(2,3),(3,2),(3,3)]);
     }
+
+    #[test]
+    fn adjacent() {
+        let m = umat![(); (3,4)];
+        assert_eq!(m.adjacent((0,0)), vec![(0,1),(1,0)]);
+        assert_eq!(m.adjacent((1,2)), vec![(0,2),(1,1),(1,3),(2,2)]);
+        assert_eq!(m.adjacent((2,3)), vec![(1,3),(2,2)]);
+    }
+
+    #[test]
+    fn transpose() {
+        let m = mat![1,2,3; 4,5,6];
+        let t = m.transpose();
+        assert_eq!(t, mat![1,4; 2,5; 3,6]);
+        assert_eq!(t.transpose(), m);
+
+        let view = m.transposed_view();
+        assert_eq!(view.shape(), (3,2));
+        assert_eq!(view[Coord::new(2,1)], 6);
+        let lines: Vec<Vec<_>> = view.lines().map(|l| l.copied().collect()).collect();
+        let rows: Vec<Vec<_>> = t.lines().map(|l| l.to_vec()).collect();
+        assert_eq!(lines, rows);
+
+        let empty = umat![0; (0,3)].transpose();
+        assert_eq!(empty.shape(), (3,0));
+        assert_eq!(empty.transpose().shape(), (0,3));
+    }
+
+    #[test]
+    fn views() {
+        let m = mat![1,2,3; 4,5,6; 7,8,9];
+        let v = m.view(1..3, 0..2);
+        assert_eq!(v.shape(), (2,2));
+        assert_eq!(v.origin(), Coord::new(1,0));
+        assert_eq!(&v[1], &[7,8]);
+        assert_eq!(v[Coord::new(0,1)], 5);
+        assert_eq!(v.to_matrix(), mat![4,5; 7,8]);
+        assert_eq!(v.coords().collect::<Vec<_>>(), vec![(1,0),(1,1),(2,0),(2,1)]);
+        assert_eq!(m.view(0..0, 1..3).iter().count(), 0);
+
+        let sums: Vec<i32> = m.windows(2,2).map(|w| w.iter().sum()).collect();
+        assert_eq!(sums, [12, 16, 24, 28]);
+        assert_eq!(m.windows(1,3).map(|w| w.to_matrix()).collect::<Vec<_>>(), [mat![1,2,3], mat![4,5,6], mat![7,8,9]]);
+        assert_eq!(m.windows(4,1).count(), 0);
+    }
+
+    #[test]
+    fn mutable_views() {
+        let mut m = umat![0; (3,4)];
+        let mut v = m.view_mut(1..3, 1..3);
+        assert_eq!((v.shape(), v.origin()), ((2,2), Coord::new(1,1)));
+        v.fill(1);
+        v[Coord::new(1,0)] = 2;
+        v[0][1] = 3;
+        for x in v.iter_mut() { *x *= 10 }
+        assert_eq!(&v[1], &[20, 10]);
+        assert_eq!(m, mat![0,0,0,0; 0,10,30,0; 0,20,10,0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn view_out_of_bounds() {
+        umat![0; (2,2)].view(1..3, 0..1);
+    }
+
+    #[test]
+    fn agreement() {
+        let a = Matrix::new(vec![1,2,3,4], (2,2)).unwrap();
+        let b = Matrix::new(vec![1,5,3,6], (2,2)).unwrap();
+        let c = Matrix::new(vec![1,5,7,6], (2,2)).unwrap();
+        assert_eq!(Matrix::agreement(&[&a, &b]).unwrap(), Matrix::new(vec![Some(1), None, Some(3), None], (2,2)).unwrap());
+        assert_eq!(Matrix::agreement(&[&a, &b, &c]).unwrap(), Matrix::new(vec![Some(1), None, None, None], (2,2)).unwrap());
+        assert_eq!(Matrix::agreement(&[&a]).unwrap(), a.map(|&v| Some(v)));
+        assert_eq!(Matrix::agreement(&[&a, &umat![0; (1,4)]]), Err(ShapeError));
+        assert_eq!(Matrix::<u8>::agreement(&[]), Err(ShapeError));
+    }
 }
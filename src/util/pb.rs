@@ -0,0 +1,145 @@
+//! Pseudo-Boolean constraints: weighted sums of literals compared to a constant,
+//! `Σ w_i x_i ⋈ k`, as needed by Kakurasu, Sumplete or sandwich clues, where each
+//! cell counts for a different amount.
+//!
+//! Sums are encoded through a decision diagram: a node stands for "the terms from `i` on
+//! stay within the bound `k`", and branches on the value of the `i`-th literal. Nodes
+//! with the same `i` and `k` are shared, so the formula stays small when the weights
+//! take few distinct partial sums.
+
+use std::collections::HashMap;
+
+use varisat::{ExtendFormula, Lit};
+
+/// How the weighted sum compares to the constant
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    AtMost,
+    AtLeast,
+    Equal,
+}
+
+pub trait PbFormula: ExtendFormula {
+
+    /// Require the sum of the weights of the true literals in `terms` to relate to `k`.
+    /// Weights may be negative.
+    fn add_linear(&mut self, terms: &[(i64, Lit)], relation: Relation, k: i64) {
+        match relation {
+            Relation::AtMost => at_most(self, terms, k),
+            Relation::AtLeast => {
+                let negated: Vec<_> = terms.iter().map(|&(w, l)| (-w, l)).collect();
+                at_most(self, &negated, -k)
+            },
+            Relation::Equal => {
+                self.add_linear(terms, Relation::AtMost, k);
+                self.add_linear(terms, Relation::AtLeast, k);
+            },
+        }
+    }
+}
+
+impl<T: ExtendFormula> PbFormula for T {}
+
+/// Encode `Σ w_i x_i <= k`
+fn at_most<F: ExtendFormula>(f: &mut F, terms: &[(i64, Lit)], mut k: i64) {
+    // A negative weight is a constant plus a positive weight on the opposite literal:
+    // w.x = w + (-w).!x
+    let mut terms: Vec<(i64, Lit)> = terms.iter()
+        .filter(|&&(w, _)| w != 0)
+        .map(|&(w, l)| if w < 0 { k -= w; (-w, !l) } else { (w, l) })
+        .collect();
+
+    // Heavy terms first, so that bounds are decided early
+    terms.sort_by_key(|&(w, _)| std::cmp::Reverse(w));
+
+    let mut bdd = Bdd { terms: &terms, rest: vec![0; terms.len() + 1], nodes: HashMap::new() };
+    for i in (0..terms.len()).rev() {
+        bdd.rest[i] = bdd.rest[i+1] + terms[i].0;
+    }
+
+    match bdd.node(f, 0, k) {
+        Node::True => {},
+        Node::False => f.add_clause(&[]),
+        Node::Lit(root) => f.add_clause(&[root]),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Node {
+    True,
+    False,
+    Lit(Lit),
+}
+
+struct Bdd<'t> {
+    terms: &'t [(i64, Lit)],
+    /// Sum of the weights from each term on
+    rest: Vec<i64>,
+    nodes: HashMap<(usize, i64), Node>,
+}
+
+impl Bdd<'_> {
+    /// The node holding if the terms from `i` on sum to at most `k`
+    fn node<F: ExtendFormula>(&mut self, f: &mut F, i: usize, k: i64) -> Node {
+        if k < 0 { return Node::False }
+        if self.rest[i] <= k { return Node::True }
+        if let Some(&node) = self.nodes.get(&(i, k)) { return node }
+
+        let (w, x) = self.terms[i];
+        let high = self.node(f, i + 1, k - w);
+        let low = self.node(f, i + 1, k);
+
+        let lit = f.new_lit();
+        for (branch, x) in [(high, !x), (low, x)] {
+            match branch {
+                Node::True => {},
+                Node::False => f.add_clause(&[!lit, x]),
+                Node::Lit(b) => f.add_clause(&[!lit, x, b]),
+            }
+        }
+
+        self.nodes.insert((i, k), Node::Lit(lit));
+        Node::Lit(lit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{backend::Solver, solve::Models};
+
+    #[test]
+    fn brute_force() {
+        let weights = [3, -2, 5, 1, 3];
+
+        for relation in [Relation::AtMost, Relation::AtLeast, Relation::Equal] {
+            for k in -3..=13 {
+                let mut solver = Solver::new();
+                let lits: Vec<Lit> = weights.iter().map(|_| solver.new_lit()).collect();
+                let terms: Vec<_> = weights.iter().copied().zip(lits.iter().copied()).collect();
+                solver.add_linear(&terms, relation, k);
+
+                let mut found = 0;
+                for m in Models::new(&mut solver, lits.iter().map(|l| l.var()).collect()).map(Result::unwrap) {
+                    let sum: i64 = terms.iter().filter(|(_, l)| m.contains(l)).map(|(w, _)| w).sum();
+                    assert!(match relation {
+                        Relation::AtMost => sum <= k,
+                        Relation::AtLeast => sum >= k,
+                        Relation::Equal => sum == k,
+                    }, "{:?} {} with sum {}", relation, k, sum);
+                    found += 1;
+                }
+
+                let expected = (0..1 << weights.len())
+                    .map(|bits: usize| (0..weights.len()).filter(|i| bits & (1 << i) != 0).map(|i| weights[i]).sum::<i64>())
+                    .filter(|&sum| match relation {
+                        Relation::AtMost => sum <= k,
+                        Relation::AtLeast => sum >= k,
+                        Relation::Equal => sum == k,
+                    })
+                    .count();
+                assert_eq!(found, expected, "{:?} {}", relation, k);
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Following the solvers as they work, to tell a long computation from a stuck one.
+//! Within [`with_progress`], every [`Solver`] of the current thread calls the observer
+//! after each call to `solve`, whether the game solves once or thousands of times, and
+//! so do the solvers of the threads the work is spread over in its [`Scope`].
+//!
+//! [`Solver`]: super::backend::Solver
+//! [`Scope`]: super::scope::Scope
+
+use std::{cell::RefCell, sync::Arc};
+
+use super::scope::scoped;
+
+pub(crate) type Observer = Arc<dyn Fn(bool) + Send + Sync>;
+
+thread_local! {
+    static OBSERVER: RefCell<Option<Observer>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, calling `observer` with the outcome of every call to a solver (on this
+/// thread), `true` when a model was found
+pub fn with_progress<R>(observer: impl Fn(bool) + Send + Sync + 'static, f: impl FnOnce() -> R) -> R {
+    within(Some(Arc::new(observer)), f)
+}
+
+/// The observer of this thread
+pub(crate) fn current() -> Option<Observer> {
+    OBSERVER.with(|current| current.borrow().clone())
+}
+
+/// Run `f`, with the solvers of this thread reporting to `observer`
+pub(crate) fn within<R>(observer: Option<Observer>, f: impl FnOnce() -> R) -> R {
+    scoped(&OBSERVER, observer, f).0
+}
+
+/// Tell the observer of this thread, if any, about a call to `solve`
+pub(crate) fn solved(satisfiable: bool) {
+    // Not borrowed during the call, should the observer solve too
+    if let Some(observer) = current() {
+        observer(satisfiable)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::binero;
+
+    #[test]
+    fn observe() {
+        let problem: binero::Problem = "10..\n....\n..1.\n....\n".parse().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let solution = with_progress(move |sat| { assert!(sat); counted.fetch_add(1, Ordering::Relaxed); }, || problem.solve());
+        assert!(solution.unwrap().is_some());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Only within `with_progress`
+        problem.solve().unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
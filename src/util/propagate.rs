@@ -0,0 +1,189 @@
+//! Constraint propagation, run before the SAT encoding is built. Every cell keeps the
+//! set of values it can still take, and cheap local rules remove the values that the
+//! clues rule out, until none of them makes progress. The cells left with a single value
+//! are forced, and can be fixed with unit clauses instead of being searched for.
+//!
+//! The rules are written once for the shapes of constraints games have in common:
+//! lines holding a value a given number of times, possibly confined to a part of them,
+//! lines of distinct values, and areas
+//! whose values must pass a check, such as a sum. Games combine them in a
+//! [`Propagator`] with their own specific rules.
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::{coord::Coord, matrix::Matrix};
+
+/// The values every cell can still take
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Domains<T>(pub Matrix<Vec<T>>);
+
+/// Propagation left a cell without any possible value, so the puzzle has no solution
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[error("no value left for {0}")]
+pub struct Contradiction(pub Coord);
+
+/// A propagator removes values from the domains, and tells whether it removed any
+pub type Propagator<'a, T> = &'a dyn Fn(&mut Domains<T>) -> bool;
+
+impl<T: Clone + PartialEq> Domains<T> {
+    /// Every value for the empty cells, and only the given one for the others
+    pub fn new(givens: &Matrix<Option<T>>, values: &[T]) -> Self {
+        Domains(givens.map(|g| match g {
+            Some(v) => vec![v.clone()],
+            None => values.to_vec(),
+        }))
+    }
+
+    /// The value of a cell, if only one is left
+    pub fn value(&self, c: Coord) -> Option<&T> {
+        match &self.0[c][..] {
+            [v] => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Keep the values of a cell satisfying `keep`. Returns true if any was removed.
+    pub fn retain(&mut self, c: Coord, keep: impl FnMut(&T) -> bool) -> bool {
+        let before = self.0[c].len();
+        self.0[c].retain(keep);
+        self.0[c].len() != before
+    }
+
+    /// Remove `v` from the values of a cell. Returns true if it was there.
+    pub fn remove(&mut self, c: Coord, v: &T) -> bool {
+        self.retain(c, |x| x != v)
+    }
+
+    /// The cells with a single value left
+    pub fn fixed(&self) -> Matrix<Option<T>> {
+        Matrix::from_fn(self.0.shape(), |x, y| self.value(Coord::new(x, y)).cloned())
+    }
+}
+
+/// Apply the propagators until none of them removes anything. Fails as soon as a
+/// cell has no value left.
+pub fn propagate<T: Clone + PartialEq>(domains: &mut Domains<T>, propagators: &[Propagator<T>]) -> Result<(), Contradiction> {
+    loop {
+        let mut changed = false;
+        for propagator in propagators {
+            changed |= propagator(domains);
+        }
+        if let Some(c) = domains.0.coords().find(|&c| domains.0[c].is_empty()) {
+            return Err(Contradiction(c))
+        }
+        if !changed { return Ok(()) }
+    }
+}
+
+/// `value` appears exactly `count` times in `cells`. Once it is fixed that often, the
+/// other cells lose it, and once only `count` cells can hold it, they all take it. When
+/// neither is possible any more, all the cells are emptied, for [`propagate`] to report.
+pub fn count<T: Clone + PartialEq>(domains: &mut Domains<T>, cells: &[Coord], value: &T, count: usize) -> bool {
+    let fixed = cells.iter().filter(|&&c| domains.value(c) == Some(value)).count();
+    let possible: Vec<_> = cells.iter().copied().filter(|&c| domains.0[c].contains(value)).collect();
+
+    let mut changed = false;
+    if fixed > count || possible.len() < count {
+        for &c in cells {
+            changed |= domains.retain(c, |_| false);
+        }
+    } else if fixed == count {
+        for &c in cells {
+            if domains.value(c) != Some(value) {
+                changed |= domains.remove(c, value);
+            }
+        }
+    } else if possible.len() == count {
+        for c in possible {
+            changed |= domains.retain(c, |v| v == value);
+        }
+    }
+    changed
+}
+
+/// `value` appears as many times in `inner` as in `outer`. When all the cells of `inner`
+/// that can hold it lie in `outer`, they are the ones holding it there, and the other
+/// cells of `outer` lose it.
+pub fn confine<T: Clone + PartialEq>(domains: &mut Domains<T>, inner: &[Coord], outer: &[Coord], value: &T) -> bool {
+    if inner.iter().any(|c| domains.0[*c].contains(value) && !outer.contains(c)) { return false }
+
+    let mut changed = false;
+    for &c in outer.iter().filter(|c| !inner.contains(c)) {
+        changed |= domains.remove(c, value);
+    }
+    changed
+}
+
+/// The values of `cells` are all different: a fixed value is removed from the others
+pub fn distinct<T: Clone + PartialEq>(domains: &mut Domains<T>, cells: &[Coord]) -> bool {
+    let mut changed = false;
+    for &c in cells {
+        let Some(v) = domains.value(c).cloned() else { continue };
+        for &other in cells.iter().filter(|&&o| o != c) {
+            changed |= domains.remove(other, &v);
+        }
+    }
+    changed
+}
+
+/// The values of `cells`, in order, must pass `check`: every value that appears in
+/// no combination of the domains passing it is removed. The combinations are all
+/// enumerated, so this is meant for the few cells of a cage.
+pub fn area<T: Clone + PartialEq>(domains: &mut Domains<T>, cells: &[Coord], check: impl Fn(&[T]) -> bool) -> bool {
+    let mut allowed: Vec<Vec<T>> = vec![vec![]; cells.len()];
+    for values in cells.iter().map(|&c| domains.0[c].iter().cloned()).multi_cartesian_product() {
+        if !check(&values) { continue }
+        for (allowed, v) in allowed.iter_mut().zip(values) {
+            if !allowed.contains(&v) { allowed.push(v) }
+        }
+    }
+
+    let mut changed = false;
+    for (&c, allowed) in cells.iter().zip(allowed) {
+        changed |= domains.retain(c, |v| allowed.contains(v));
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{deduce::lines, matrix::umat};
+
+    #[test]
+    fn counts() {
+        // A line of four cells holding two ones, one of which is known
+        let mut givens = umat![None; (1, 4)];
+        givens[0][0] = Some(true);
+        givens[0][1] = Some(false);
+        givens[0][2] = Some(false);
+        let mut domains = Domains::new(&givens, &[false, true]);
+        let line: Vec<_> = (0..4).map(|y| Coord::new(0, y)).collect();
+
+        assert!(count(&mut domains, &line, &true, 2));
+        assert_eq!(domains.value(Coord::new(0, 3)), Some(&true));
+        assert!(!count(&mut domains, &line, &true, 2));
+        assert_eq!(propagate(&mut domains, &[&|d| count(d, &line, &false, 3)]), Err(Contradiction(Coord::new(0, 0))));
+    }
+
+    #[test]
+    fn latin_square() {
+        // Two given cells and a difference in an area are enough to fill a 3x3 Latin square
+        let mut givens = umat![None; (3, 3)];
+        givens[0][0] = Some(1);
+        givens[1][2] = Some(1);
+        let mut domains = Domains::new(&givens, &[1, 2, 3]);
+        let lines: Vec<_> = lines((3, 3)).into_iter().map(|(_, l)| l).collect();
+        let cage = [Coord::new(0, 1), Coord::new(1, 1)];
+
+        let rules = |d: &mut Domains<u8>| {
+            let changed = lines.iter().fold(false, |changed, l| distinct(d, l) | changed);
+            area(d, &cage, |v| v[0] == v[1] + 1) | changed
+        };
+        propagate(&mut domains, &[&rules]).unwrap();
+
+        let square = Matrix::from_rows([vec![1, 3, 2], vec![3, 2, 1], vec![2, 1, 3]]).unwrap();
+        assert_eq!(domains.fixed(), square.map(|&v| Some(v)));
+    }
+}
@@ -0,0 +1,216 @@
+//! Many games divide their grid into regions: K-Doku areas, Stars colors, and the like.
+//! This module implements sets of cells, and partitions of a grid into such sets.
+
+use std::fmt::Write;
+
+use thiserror::Error;
+
+use super::{coord::{Coord, Direction}, matrix::{Matrix, umat}};
+
+/// A set of cells of the grid, in no particular order
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Region(pub Vec<Coord>);
+
+/// A grid divided into regions, numbered from 0. Every cell belongs to exactly one region.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Partition(Matrix<usize>);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RegionError {
+    #[error("cell ({0},{1}) is outside the grid")]
+    OutOfBounds(usize, usize),
+    #[error("cell ({0},{1}) belongs to several regions")]
+    Overlap(usize, usize),
+    #[error("cell ({0},{1}) belongs to no region")]
+    Uncovered(usize, usize),
+    #[error("region {0} has no cell")]
+    Empty(usize),
+}
+
+impl Region {
+    pub fn contains(&self, cell: Coord) -> bool {
+        self.0.contains(&cell)
+    }
+
+    /// Split the region into its connected parts, cells being connected through their edges
+    pub fn components(&self) -> Vec<Region> {
+        let mut cells = self.0.clone();
+        let mut parts = vec![];
+
+        while let Some(start) = cells.pop() {
+            let mut part = vec![start];
+            let mut i = 0;
+            while i < part.len() {
+                let cell = part[i];
+                for n in Direction::ORTHOGONAL.into_iter().filter_map(|d| cell.neighbor(d)) {
+                    if let Some(j) = cells.iter().position(|&c| c == n) {
+                        part.push(cells.swap_remove(j));
+                    }
+                }
+                i += 1;
+            }
+            parts.push(Region(part));
+        }
+
+        parts
+    }
+
+    /// True if the region is made of a single piece. The empty region is not connected.
+    pub fn is_connected(&self) -> bool {
+        self.components().len() == 1
+    }
+}
+
+impl Partition {
+    /// Build a partition from the number of the region of every cell.
+    /// Fails if a number is skipped, leaving a region with no cell.
+    pub fn new(grid: Matrix<usize>) -> Result<Self, RegionError> {
+        let count = grid.iter().max().map_or(0, |&m| m + 1);
+        if let Some(empty) = (0..count).find(|r| !grid.iter().any(|c| c == r)) {
+            return Err(RegionError::Empty(empty))
+        }
+        Ok(Partition(grid))
+    }
+
+    /// Build a partition from a list of regions, which must cover the grid without overlapping
+    pub fn from_regions(shape: (usize, usize), regions: &[Region]) -> Result<Self, RegionError> {
+        let mut grid: Matrix<Option<usize>> = umat![None; shape];
+
+        for (i, region) in regions.iter().enumerate() {
+            if region.0.is_empty() { return Err(RegionError::Empty(i)) }
+            for &Coord { x, y } in &region.0 {
+                if x >= shape.0 || y >= shape.1 { return Err(RegionError::OutOfBounds(x, y)) }
+                if grid[(x,y)].replace(i).is_some() { return Err(RegionError::Overlap(x, y)) }
+            }
+        }
+
+        if let Some((x,y)) = grid.indices().find(|&(x,y)| grid[(x,y)].is_none()) {
+            return Err(RegionError::Uncovered(x, y))
+        }
+        Ok(Partition(grid.map(|r| r.unwrap())))
+    }
+
+    /// The number of the region of every cell
+    pub fn grid(&self) -> &Matrix<usize> {
+        &self.0
+    }
+
+    /// Number of regions
+    pub fn len(&self) -> usize {
+        self.0.iter().max().map_or(0, |&m| m + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn region_of(&self, cell: Coord) -> usize {
+        self.0[cell]
+    }
+
+    /// The cells of every region, each in row-major order
+    pub fn regions(&self) -> Vec<Region> {
+        let mut regions = vec![Region::default(); self.len()];
+        for c in self.0.coords() {
+            regions[self.0[c]].0.push(c);
+        }
+        regions
+    }
+
+    /// The regions sharing an edge with region `r`, in increasing order
+    pub fn adjacent(&self, r: usize) -> Vec<usize> {
+        let mut adjacent: Vec<_> = self.0.coords()
+            .filter(|&c| self.0[c] == r)
+            .flat_map(|c| self.0.adjacent(c))
+            .map(|c| self.0[c])
+            .filter(|&o| o != r)
+            .collect();
+        adjacent.sort();
+        adjacent.dedup();
+        adjacent
+    }
+
+    /// Draw the grid with walls between the regions, showing the character
+    /// given by `content` in every cell:
+    ///
+    /// ```text
+    /// +-+-+
+    /// |1 2|
+    /// +-+-+
+    /// |3|4|
+    /// +-+-+
+    /// ```
+    pub fn borders(&self, content: impl Fn((usize, usize)) -> char) -> String {
+        let (h, w) = self.0.shape();
+        let g = &self.0;
+        let mut out = String::new();
+
+        for x in 0..=h {
+            for y in 0..w {
+                let wall = x == 0 || x == h || g[x-1][y] != g[(x,y)];
+                out.push('+');
+                out.push(if wall { '-' } else { ' ' });
+            }
+            out.push_str("+\n");
+
+            if x == h { break }
+            for y in 0..w {
+                let wall = y == 0 || g[x][y-1] != g[(x,y)];
+                out.push(if wall { '|' } else { ' ' });
+                out.push(content((x,y)));
+            }
+            writeln!(out, "|").unwrap();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::matrix::mat;
+
+    fn region(cells: &[(usize, usize)]) -> Region {
+        Region(cells.iter().copied().map(Coord::from).collect())
+    }
+
+    #[test]
+    fn components() {
+        let r = region(&[(0,0), (0,1), (2,1), (1,1), (3,3)]);
+        let mut parts: Vec<_> = r.components().into_iter().map(|p| { let mut p = p.0; p.sort(); p }).collect();
+        parts.sort();
+        assert_eq!(parts, vec![region(&[(0,0), (0,1), (1,1), (2,1)]).0, region(&[(3,3)]).0]);
+        assert!(!r.is_connected());
+        assert!(region(&[(0,0), (1,0)]).is_connected());
+        assert!(!Region::default().is_connected());
+    }
+
+    #[test]
+    fn partition() {
+        let p = Partition::new(mat![0,0; 1,2]).unwrap();
+        assert_eq!(p.len(), 3);
+        assert_eq!(p.regions(), vec![region(&[(0,0), (0,1)]), region(&[(1,0)]), region(&[(1,1)])]);
+        assert_eq!(p.adjacent(1), vec![0, 2]);
+        assert_eq!(p.region_of(Coord::new(1,1)), 2);
+        assert_eq!(Partition::new(mat![0,0; 2,2]), Err(RegionError::Empty(1)));
+
+        assert_eq!(Partition::from_regions((2,2), &p.regions()), Ok(p.clone()));
+        assert_eq!(Partition::from_regions((2,2), &[region(&[(0,0), (0,1), (1,0)])]), Err(RegionError::Uncovered(1,1)));
+        assert_eq!(Partition::from_regions((2,2), &[region(&[(0,0), (0,0)])]), Err(RegionError::Overlap(0,0)));
+        assert_eq!(Partition::from_regions((2,2), &[region(&[(0,2)])]), Err(RegionError::OutOfBounds(0,2)));
+    }
+
+    #[test]
+    fn borders() {
+        let p = Partition::new(mat![0,0; 1,2]).unwrap();
+        let digits = |(x,y): (usize, usize)| char::from_digit((2 * x + y + 1) as u32, 10).unwrap();
+        assert_eq!(p.borders(digits), "\
++-+-+
+|1 2|
++-+-+
+|3|4|
++-+-+
+");
+    }
+}
@@ -0,0 +1,78 @@
+//! What the solvers of a thread take from their surroundings, without the games passing
+//! it around: the backend, the cancellation token, the statistics being collected and
+//! the progress observer. Each one is set on the current thread for the duration of a
+//! closure, such as within [`with_cancel`](super::cancel::with_cancel), and a [`Scope`]
+//! carries all of them over to the threads the work is spread over.
+
+use std::{cell::RefCell, thread::LocalKey};
+
+use super::{backend::Backend, cancel::{self, CancelToken}, progress, stats};
+
+/// Run `f` with `key` set to `value` on this thread, then put back the previous value,
+/// even if `f` unwinds. Returns the value left in `key` along with the result.
+pub(crate) fn scoped<T: 'static, R>(key: &'static LocalKey<RefCell<T>>, value: T, f: impl FnOnce() -> R) -> (R, T) {
+    struct Restore<T: 'static>(&'static LocalKey<RefCell<T>>, Option<T>);
+    impl<T: 'static> Drop for Restore<T> {
+        fn drop(&mut self) {
+            if let Some(previous) = self.1.take() {
+                self.0.with(|current| current.replace(previous));
+            }
+        }
+    }
+
+    let mut restore = Restore(key, Some(key.with(|current| current.replace(value))));
+    let result = f();
+    let previous = restore.1.take().expect("only taken once");
+    (result, key.with(|current| current.replace(previous)))
+}
+
+/// The surroundings of the solvers of a thread, to run work on other threads as if
+/// it ran on this one
+#[derive(Clone, Default)]
+pub struct Scope {
+    backend: Backend,
+    token: Option<CancelToken>,
+    stats: Option<stats::Shared>,
+    observer: Option<progress::Observer>,
+}
+
+impl Scope {
+    /// The scope of the current thread
+    pub fn current() -> Self {
+        Scope { backend: Backend::current(), token: cancel::current(), stats: stats::current(), observer: progress::current() }
+    }
+
+    /// Run `f` on this thread, in this scope: its solvers use the same backend, add to
+    /// the same statistics, and report to the same observer. Once the token is cancelled,
+    /// unwinds out of `f` instead of returning, up to the `with_cancel` of the thread the
+    /// scope was taken from: rayon resumes the unwinding there.
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        cancel::within(self.token.as_ref(), || {
+            progress::within(self.observer.clone(), || stats::within(self.stats.clone(), || self.backend.run(f)).0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+    use rayon::prelude::*;
+
+    use super::*;
+    use crate::{binero, util::{progress::with_progress, stats::with_stats}};
+
+    #[test]
+    fn workers() {
+        let problem: binero::Problem = "10..\n....\n..1.\n....\n".parse().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let (solved, stats) = with_stats(|| with_progress(move |_| { counted.fetch_add(1, Ordering::Relaxed); }, || {
+            let scope = Scope::current();
+            (0..4).into_par_iter().map(|_| scope.run(|| problem.solve())).collect::<Vec<_>>()
+        }));
+        assert!(solved.iter().all(|s| matches!(s, Ok(Some(_)))));
+        assert_eq!((stats.solvers, stats.solves), (4, 4));
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+    }
+}
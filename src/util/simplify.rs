@@ -0,0 +1,169 @@
+//! Removing redundant clauses before solving. The encodings emit many clauses that
+//! say nothing new: the same clause from two overlapping constraints, or a clause
+//! containing a shorter one, such as a binary clause of an at-most-one next to a longer
+//! clause of a DNF. [`simplify`] drops them, along with repeated literals and clauses
+//! that always hold, which leaves the solver fewer clauses to watch.
+//!
+//! The [`Simplifying`] backend applies it to everything added to a solver before its
+//! first call to `solve`, and [`with_simplification`] turns it on for the solvers
+//! built by a closure, whichever backend they use.
+
+use tracing::debug;
+use varisat::{CnfFormula, ExtendFormula, Lit, Var};
+
+use super::backend::{with_backend, Backend, BackendError, SatBackend};
+
+/// The same formula, without duplicate clauses, clauses that contain another clause,
+/// clauses with both a literal and its negation, and repeated literals. The remaining
+/// clauses are sorted, shortest first, and so are their literals.
+pub fn simplify(formula: &CnfFormula) -> CnfFormula {
+    let mut clauses: Vec<Vec<Lit>> = formula.iter()
+        .map(|clause| {
+            let mut clause = clause.to_vec();
+            clause.sort();
+            clause.dedup();
+            clause
+        })
+        // A literal and its negation are next to each other once sorted
+        .filter(|clause| !clause.windows(2).any(|w| w[0] == !w[1]))
+        .collect();
+    clauses.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    clauses.dedup();
+
+    let mut simplified = CnfFormula::new();
+    simplified.set_var_count(formula.var_count());
+
+    // The empty clause contains all the others
+    if clauses.first().is_some_and(Vec::is_empty) {
+        simplified.add_clause(&[]);
+        return simplified
+    }
+
+    // The kept clauses, by their smallest literal. A clause is only contained in one
+    // of the same length or longer, which come later, and its smallest literal is in it.
+    let mut by_first: Vec<Vec<usize>> = vec![vec![]; 2 * formula.var_count()];
+    let mut kept: Vec<Vec<Lit>> = vec![];
+    for clause in clauses {
+        let subsumed = clause.iter()
+            .any(|l| by_first[l.code()].iter().any(|&k| is_subset(&kept[k], &clause)));
+        if subsumed { continue }
+        by_first[clause[0].code()].push(kept.len());
+        kept.push(clause);
+    }
+
+    for clause in &kept {
+        simplified.add_clause(clause);
+    }
+    simplified
+}
+
+/// True if every literal of `a` is in `b`, both being sorted
+fn is_subset(a: &[Lit], b: &[Lit]) -> bool {
+    let mut b = b.iter();
+    a.iter().all(|l| b.any(|m| m == l))
+}
+
+/// Holds the clauses back until the first call to `solve` or `assume`, and only hands
+/// them to the inner backend once simplified. Clauses added later go straight through,
+/// such as those blocking the models already found.
+pub struct Simplifying {
+    inner: Box<dyn SatBackend>,
+    pending: Option<CnfFormula>,
+}
+
+impl Simplifying {
+    pub fn new(inner: Box<dyn SatBackend>) -> Self {
+        Simplifying { inner, pending: Some(CnfFormula::new()) }
+    }
+
+    fn flush(&mut self) {
+        let Some(formula) = self.pending.take() else { return };
+        let simplified = simplify(&formula);
+        debug!(clauses = formula.len(), kept = simplified.len(), "simplified");
+        // The inner backend has not seen any variable yet, not even those of the
+        // dropped clauses, which it must never hand out again
+        for _ in 0..formula.var_count() {
+            self.inner.new_var();
+        }
+        self.inner.add_formula(&simplified);
+    }
+}
+
+impl SatBackend for Simplifying {
+    fn new_var(&mut self) -> Var {
+        match &mut self.pending {
+            Some(formula) => formula.new_var(),
+            None => self.inner.new_var(),
+        }
+    }
+
+    fn add_clause(&mut self, clause: &[Lit]) {
+        match &mut self.pending {
+            Some(formula) => formula.add_clause(clause),
+            None => self.inner.add_clause(clause),
+        }
+    }
+
+    fn assume(&mut self, assumptions: &[Lit]) {
+        self.flush();
+        self.inner.assume(assumptions)
+    }
+
+    fn solve(&mut self) -> Result<bool, BackendError> {
+        self.flush();
+        self.inner.solve()
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        self.inner.model()
+    }
+
+    fn failed_core(&self) -> Option<Vec<Lit>> {
+        self.inner.failed_core()
+    }
+}
+
+/// Run `f`, with every solver it creates (on this thread) simplifying its clauses
+/// before handing them to the current backend
+pub fn with_simplification<R>(f: impl FnOnce() -> R) -> R {
+    let backend = Backend::current();
+    with_backend(move || Box::new(Simplifying::new(backend.build())), f)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binero, kdoku, util::backend::record};
+
+    fn clauses(formula: &CnfFormula) -> Vec<Vec<isize>> {
+        formula.iter().map(|c| c.iter().map(|l| l.to_dimacs()).collect()).collect()
+    }
+
+    #[test]
+    fn redundant_clauses() {
+        let mut f = CnfFormula::new();
+        for clause in [&[1, 2, 3][..], &[3, 1, 2], &[2, -1], &[2, -1, 4], &[1, 1, 5], &[1, -1, 4], &[-2, 3, 5, 2]] {
+            f.add_clause(&clause.iter().map(|&n| Lit::from_dimacs(n)).collect::<Vec<_>>());
+        }
+        f.set_var_count(6);
+
+        let simplified = simplify(&f);
+        assert_eq!(clauses(&simplified), [vec![1, 5], vec![-1, 2], vec![1, 2, 3]]);
+        assert_eq!(simplified.var_count(), 6);
+
+        f.add_clause(&[]);
+        assert_eq!(clauses(&simplify(&f)), [Vec::<isize>::new()]);
+    }
+
+    #[test]
+    fn same_solutions() {
+        let problem: binero::Problem = "10..\n....\n..1.\n....\n".parse().unwrap();
+        let (formula, _) = record(|| problem.session());
+        assert!(simplify(&formula).len() < formula.len());
+        let all = |p: &binero::Problem| p.count_solutions(Default::default()).unwrap();
+        assert_eq!(with_simplification(|| all(&problem)), all(&problem));
+
+        let constraints = kdoku::parse::constraints("10+ [(0,0),(1,0)]\n2/ [(0,1),(0,2)]\n").unwrap();
+        assert!(with_simplification(|| kdoku::BaseGrid::new().solve(&constraints)).is_ok());
+    }
+}
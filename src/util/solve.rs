@@ -1,6 +1,24 @@
+//! Queries on a formula loaded in a solver beyond a single model: enumerating and
+//! counting its models, drawing one at random, finding what holds in all of them, or
+//! which assumptions contradict each other.
+//!
+//! # Sessions
+//!
+//! The sessions of the games are built on these queries, and enumerate the solutions
+//! of a grid the same way:
+//!  - `exclude(prior)` forbids `prior` as a solution for all the subsequent calls
+//!  - `solve_with(f)` calls `f` with every solution in turn, as soon as it is found,
+//!    excluding each of them once found, until `f` breaks or there are no more. It
+//!    returns the value `f` broke with, if it did, or the first error of the backend.
+//!
+//! The problems of the games offer `solve_with` as well, on a new session every time.
+
+use std::collections::BTreeSet;
+
+use rand::{Rng, seq::SliceRandom};
 use varisat::{ExtendFormula, Lit, Var};
 
-use super::choice::Choose;
+use super::{backend::{BackendError, Solver}, card::{CardFormula, Encoding}};
 
 pub trait DnfFormula: ExtendFormula {
 
@@ -20,30 +38,236 @@ pub trait DnfFormula: ExtendFormula {
     fn add_dnf<T>(&mut self, dnf: impl IntoIterator<Item=T>)
         where T: IntoIterator<Item=Lit>
     {
-            
-        let mut helpers = vec![];
+        let helpers = dnf_helpers(self, dnf);
+        self.add_clause(&helpers);
+    }
+
+    /// Add a constraint in disjunctive normal form, that only needs to hold when `guard` is true.
+    /// Solving with `guard` as an assumption enables the constraint; the final disjunction
+    /// of helper variables simply gets `not(guard)` as an additional way out.
+    fn add_dnf_if<T>(&mut self, guard: Lit, dnf: impl IntoIterator<Item=T>)
+        where T: IntoIterator<Item=Lit>
+    {
+        let mut helpers = dnf_helpers(self, dnf);
+        helpers.push(!guard);
+        self.add_clause(&helpers);
+    }
 
-        for product in dnf {
-            let hv = self.new_var();
-            helpers.push(hv.positive());
+    /// Exactly one of the `lits` is true
+    fn add_exactly_one(&mut self, lits: &[Lit]) {
+        self.add_clause(lits);
+        self.add_at_most_one(lits);
+    }
 
-            let not_hv = hv.negative();
+    /// At most one of the `lits` is true. See [`CardFormula::at_most_one`].
+    fn add_at_most_one(&mut self, lits: &[Lit]) {
+        self.at_most_one(lits)
+    }
 
-            for term in product {
-                self.add_clause(&[not_hv, term])
-            }
+    /// At least `k` of the `lits` are true. See [`CardFormula`] to pick the encoding.
+    fn add_at_least_k(&mut self, lits: &[Lit], k: usize) {
+        self.at_least_k(lits, k, Encoding::default())
+    }
+
+    /// At most `k` of the `lits` are true. See [`CardFormula`] to pick the encoding.
+    fn add_at_most_k(&mut self, lits: &[Lit], k: usize) {
+        self.at_most_k(lits, k, Encoding::default())
+    }
+
+}
+
+impl<T: ExtendFormula> DnfFormula for T {}
+
+/// Create a helper variable implying each product of the DNF, and return them.
+fn dnf_helpers<F, T>(f: &mut F, dnf: impl IntoIterator<Item=T>) -> Vec<Lit>
+    where F: ExtendFormula, T: IntoIterator<Item=Lit>
+{
+    let mut helpers = vec![];
+
+    for product in dnf {
+        let hv = f.new_var();
+        helpers.push(hv.positive());
+
+        let not_hv = hv.negative();
+
+        for term in product {
+            f.add_clause(&[not_hv, term])
         }
+    }
 
-        self.add_clause(&helpers);
+    helpers
+}
+
+/// Switches changing how a game builds its formula
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SolveOptions {
+    /// Only look for one solution out of each class of solutions that are images of each
+    /// other under the symmetries of the grid, among those mapping the clues onto themselves.
+    /// Much faster when counting solutions of very symmetric grids, but then solutions are
+    /// counted up to symmetry, and assumptions may rule out a class that does have a solution
+    /// satisfying them. See [`super::symmetry`].
+    pub break_symmetry: bool,
+    /// Fix the cells forced by simple local rules with unit clauses, found by propagating
+    /// the clues before solving. See [`super::propagate`].
+    pub propagate: bool,
+}
+
+/// Solve the formula loaded in `solver`, under the hypothesis that all the
+/// `assumptions` literals are true. The assumptions only hold for this call, so
+/// the same solver can be queried again with a different set, reusing the clauses
+/// (and everything learnt from them) instead of rebuilding the formula.
+/// Returns `Ok(None)` if there is no model, and an error if the backend failed.
+pub fn solve_assuming(solver: &mut Solver, assumptions: &[Lit]) -> Result<Option<Vec<Lit>>, BackendError> {
+    solver.assume(assumptions);
+    solver.solve()?;
+    Ok(solver.model())
+}
+
+/// Enumerates the models of the formula loaded in a solver, telling them apart by the
+/// values of the `decisions` variables only, such as the cells of a grid, and ignoring
+/// the helper variables of the encoding. After each model, a blocking clause forbids
+/// its assignment of the decisions, so every model differs from the previous ones on
+/// at least one of them. The blocking clauses stay in the solver.
+///
+/// As an iterator, it stops after the first error of the backend.
+pub struct Models<'s> {
+    solver: &'s mut Solver,
+    decisions: Vec<Var>,
+    fixed: Vec<Lit>,
+    failed: bool,
+}
 
+impl<'s> Models<'s> {
+    pub fn new(solver: &'s mut Solver, decisions: Vec<Var>) -> Self {
+        Models { solver, decisions, fixed: vec![], failed: false }
     }
 
-    fn add_popcount(&mut self, vars: &[Var], k: usize) {
-        let clauses = Choose::new(vars.len(), k)
-            .map(|ch| ch.into_iter().zip(vars).map(|(b,v)| v.lit(b)));
-        self.add_dnf(clauses);
+    /// Assume the `fixed` literals in every call
+    pub fn assuming(self, fixed: &[Lit]) -> Self {
+        Models { fixed: fixed.to_vec(), ..self }
     }
 
+    /// The next model, which is then excluded. Returns `None` once all the
+    /// assignments of the decisions have been found.
+    pub fn next_model(&mut self) -> Result<Option<Vec<Lit>>, BackendError> {
+        let Some(model) = solve_assuming(self.solver, &self.fixed)? else { return Ok(None) };
+        let set: BTreeSet<Lit> = model.iter().copied().collect();
+        let blocking: Vec<_> = self.decisions.iter()
+            .map(|&v| v.lit(!set.contains(&v.positive())))
+            .collect();
+        self.solver.add_clause(&blocking);
+        Ok(Some(model))
+    }
+
+    /// The number of models, enumerating all of them
+    pub fn try_count(mut self) -> Result<usize, BackendError> {
+        let mut n = 0;
+        while self.next_model()?.is_some() {
+            n += 1;
+        }
+        Ok(n)
+    }
 }
 
-impl<T: ExtendFormula> DnfFormula for T {}
+impl Iterator for Models<'_> {
+    type Item = Result<Vec<Lit>, BackendError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed { return None }
+        let next = self.next_model();
+        self.failed = next.is_err();
+        next.transpose()
+    }
+}
+
+/// Find a model of the formula chosen at random, rather than the one the solver
+/// happens to reach first. The `fixed` literals are assumed in every call.
+///
+/// The variables are visited in random order, and each one is given a random value,
+/// unless that value leaves no model, in which case it takes the other one. When the
+/// current model already agrees with the chosen value, no solver call is needed.
+/// Every choice comes from `rng`, so a seeded one always gives the same model.
+pub fn random_model(solver: &mut Solver, fixed: &[Lit], vars: &[Var], rng: &mut impl Rng) -> Result<Option<Vec<Lit>>, BackendError> {
+    let Some(model) = solve_assuming(solver, fixed)? else { return Ok(None) };
+    let mut model: BTreeSet<Lit> = model.into_iter().collect();
+    let mut assumptions = fixed.to_vec();
+
+    let mut order = vars.to_vec();
+    order.shuffle(rng);
+
+    for var in order {
+        let lit = var.lit(rng.gen());
+        if !model.contains(&lit) {
+            assumptions.push(lit);
+            match solve_assuming(solver, &assumptions)? {
+                Some(m) => { model = m.into_iter().collect(); continue },
+                None => { assumptions.pop(); },
+            }
+        }
+        // Keep the value of the current model
+        assumptions.push(var.lit(model.contains(&var.positive())));
+    }
+
+    Ok(Some(model.into_iter().collect()))
+}
+
+/// Among `candidates`, literals that all hold in some known model of the formula,
+/// find those that hold in every model (the backbone of the formula), and return
+/// their associated keys. The `fixed` literals are assumed in every call.
+///
+/// Each candidate is probed by solving under the assumption that it is false.
+/// Every model found along the way also clears the other candidates it falsifies,
+/// so most of them never need a dedicated solver call.
+pub fn backbone<K>(solver: &mut Solver, fixed: &[Lit], candidates: Vec<(K, Lit)>) -> Result<Vec<K>, BackendError> {
+    let mut open: Vec<Option<(K, Lit)>> = candidates.into_iter().map(Some).collect();
+    let mut forced = vec![];
+
+    for i in 0..open.len() {
+        let Some((_, lit)) = open[i] else { continue };
+
+        let mut assumptions = fixed.to_vec();
+        assumptions.push(!lit);
+
+        match solve_assuming(solver, &assumptions)? {
+            None => forced.extend(open[i].take().map(|(k,_)| k)),
+            Some(model) => {
+                let model: BTreeSet<Lit> = model.into_iter().collect();
+                for c in &mut open[i..] {
+                    if matches!(c, Some((_, l)) if !model.contains(l)) {
+                        *c = None;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(forced)
+}
+
+/// Shrink a set of assumptions under which the formula is unsatisfiable, to a minimal
+/// one: dropping any literal of the result makes the formula satisfiable again.
+/// Returns `None` if the formula is satisfiable under the given assumptions.
+///
+/// Starts from the failed core reported by the solver, then tries to delete each
+/// literal in turn, putting it back only if the others are no longer contradictory.
+pub fn minimal_core(solver: &mut Solver, assumptions: &[Lit]) -> Result<Option<Vec<Lit>>, BackendError> {
+    if solve_assuming(solver, assumptions)?.is_some() { return Ok(None) }
+    let mut core = solver.failed_core().expect("no failed core");
+
+    let mut i = 0;
+    while i < core.len() {
+        let mut trial = core.clone();
+        trial.remove(i);
+
+        if solve_assuming(solver, &trial)?.is_none() {
+            // Literals known to be necessary are part of any smaller core,
+            // so they stay in front and `i` remains valid.
+            let failed = solver.failed_core().expect("no failed core");
+            core = trial.into_iter().filter(|l| failed.contains(l)).collect();
+        } else {
+            i += 1;
+        }
+    }
+
+    Ok(Some(core))
+}
@@ -0,0 +1,103 @@
+//! Measuring the work of the solvers. Within [`with_stats`], every [`Solver`] created
+//! on the current thread reports the size of its formula and the time spent building
+//! and solving it, without the games having to pass anything around. So do the solvers
+//! of the threads the work is spread over in its [`Scope`].
+//!
+//! [`Solver`]: super::backend::Solver
+//! [`Scope`]: super::scope::Scope
+
+use std::{cell::RefCell, fmt::Display, ops::AddAssign, sync::{Arc, Mutex}, time::Duration};
+
+use super::scope::scoped;
+
+/// What the solvers did, added over all of them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SolveStats {
+    /// Number of solvers created
+    pub solvers: usize,
+    pub variables: usize,
+    pub clauses: usize,
+    /// Number of calls to `solve`
+    pub solves: usize,
+    /// Number of calls to `solve` that found a model
+    pub satisfiable: usize,
+    /// Time from the creation of every solver to its first call to `solve`, spent
+    /// building the formula
+    pub encoding: Duration,
+    /// Time spent in the calls to `solve`
+    pub solving: Duration,
+}
+
+impl AddAssign for SolveStats {
+    fn add_assign(&mut self, other: SolveStats) {
+        self.solvers += other.solvers;
+        self.variables += other.variables;
+        self.clauses += other.clauses;
+        self.solves += other.solves;
+        self.satisfiable += other.satisfiable;
+        self.encoding += other.encoding;
+        self.solving += other.solving;
+    }
+}
+
+/// Prints e.g. `solvers 1, variables 64, clauses 312, solves 2 (1 satisfiable), encoding 1.2ms, solving 0.4ms`
+impl Display for SolveStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "solvers {}, variables {}, clauses {}, solves {} ({} satisfiable), encoding {:.1?}, solving {:.1?}",
+            self.solvers, self.variables, self.clauses, self.solves, self.satisfiable, self.encoding, self.solving)
+    }
+}
+
+/// Statistics added to by several threads
+pub(crate) type Shared = Arc<Mutex<SolveStats>>;
+
+thread_local! {
+    static STATS: RefCell<Option<Shared>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, returning the statistics of the solvers it creates (on this thread) along
+/// with its result. Nested calls also count towards the enclosing one.
+pub fn with_stats<R>(f: impl FnOnce() -> R) -> (R, SolveStats) {
+    let (result, stats) = within(Some(Shared::default()), f);
+    let stats = stats.map_or_else(SolveStats::default, |stats| *stats.lock().unwrap());
+    update(|outer| *outer += stats);
+    (result, stats)
+}
+
+/// The statistics being collected on this thread
+pub(crate) fn current() -> Option<Shared> {
+    STATS.with(|current| current.borrow().clone())
+}
+
+/// Run `f`, with the solvers of this thread adding to `stats`
+pub(crate) fn within<R>(stats: Option<Shared>, f: impl FnOnce() -> R) -> (R, Option<Shared>) {
+    scoped(&STATS, stats, f)
+}
+
+/// Add to the statistics being collected on this thread, if any
+pub(crate) fn update(f: impl FnOnce(&mut SolveStats)) {
+    STATS.with(|current| {
+        if let Some(stats) = &*current.borrow() { f(&mut stats.lock().unwrap()) }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binero;
+
+    #[test]
+    fn counting() {
+        let problem: binero::Problem = "10..\n....\n..1.\n....\n".parse().unwrap();
+        let ((solution, inner), outer) = with_stats(|| with_stats(|| problem.solve()));
+        assert!(solution.unwrap().is_some());
+        assert_eq!(inner, outer);
+        assert_eq!((inner.solvers, inner.solves, inner.satisfiable), (1, 1, 1));
+        assert!(inner.variables >= 16 && inner.clauses > 0);
+
+        // Nothing is collected outside of `with_stats`
+        problem.solve().unwrap();
+        let (_, stats) = with_stats(|| ());
+        assert_eq!(stats, SolveStats::default());
+    }
+}
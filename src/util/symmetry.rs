@@ -0,0 +1,167 @@
+//! Symmetry breaking. When a transformation of the grid maps every solution to another
+//! solution, the solver wastes time finding both. Keeping only the solutions that are
+//! no larger than their images (the lex-leader method) leaves one solution out of each
+//! class of symmetric ones, which is enough to count or enumerate them up to symmetry.
+//!
+//! The comparison reads the cells in row-major order, false being smaller than true.
+
+use varisat::{ExtendFormula, Lit};
+
+use super::matrix::Matrix;
+
+/// A transformation of a grid: an optional transposition, followed by optional flips of
+/// the row and column order. These are the eight rotations and reflections of the grid;
+/// only those without a transposition map a rectangular grid onto itself.
+/// The default is the identity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Symmetry {
+    pub transpose: bool,
+    pub flip_rows: bool,
+    pub flip_columns: bool,
+}
+
+impl Symmetry {
+    pub const ROTATE_90: Symmetry = Symmetry { transpose: true, flip_rows: false, flip_columns: true };
+    pub const ROTATE_180: Symmetry = Symmetry { transpose: false, flip_rows: true, flip_columns: true };
+    pub const ROTATE_270: Symmetry = Symmetry { transpose: true, flip_rows: true, flip_columns: false };
+
+    /// The eight rotations and reflections, the identity first
+    pub fn all() -> Vec<Symmetry> {
+        let mut all = vec![];
+        for transpose in [false, true] {
+            for (flip_rows, flip_columns) in [(false, false), (false, true), (true, false), (true, true)] {
+                all.push(Symmetry { transpose, flip_rows, flip_columns });
+            }
+        }
+        all
+    }
+
+    /// All the symmetries mapping a grid of the given shape onto itself, except the identity
+    pub fn of_shape((h, w): (usize, usize)) -> Vec<Symmetry> {
+        Symmetry::all().into_iter()
+            .filter(|s| !s.is_identity() && (h == w || !s.transpose))
+            .collect()
+    }
+
+    pub fn is_identity(&self) -> bool {
+        !(self.transpose || self.flip_rows || self.flip_columns)
+    }
+
+    /// The shape of the image of a grid of the given shape
+    pub fn shape(&self, (h, w): (usize, usize)) -> (usize, usize) {
+        if self.transpose { (w, h) } else { (h, w) }
+    }
+
+    /// Where `cell` of a grid of the given shape lands in the image
+    pub fn apply(&self, (x, y): (usize, usize), shape: (usize, usize)) -> (usize, usize) {
+        let (h, w) = self.shape(shape);
+        let (x, y) = if self.transpose { (y, x) } else { (x, y) };
+        let x = if self.flip_rows { h - 1 - x } else { x };
+        let y = if self.flip_columns { w - 1 - y } else { y };
+        (x, y)
+    }
+
+    /// The transformed grid, where the content of each cell has moved to its image
+    pub fn image<T: Clone>(&self, grid: &Matrix<T>) -> Matrix<T> {
+        let shape = grid.shape();
+        let mut cells = vec![None; grid.len()];
+        let (_, w) = self.shape(shape);
+        for (x, y) in grid.indices() {
+            let (a, b) = self.apply((x, y), shape);
+            cells[a * w + b] = Some(grid[(x,y)].clone());
+        }
+        Matrix::new(cells.into_iter().map(|c| c.expect("symmetries are bijections")).collect(), self.shape(shape))
+            .expect("inconsistent len and shape")
+    }
+}
+
+/// True if `b` is `a` with its regions renamed, i.e. two cells share a region in `a`
+/// exactly when they share one in `b`.
+pub fn same_regions(a: &Matrix<usize>, b: &Matrix<usize>) -> bool {
+    let mut forward = std::collections::HashMap::new();
+    let mut backward = std::collections::HashMap::new();
+    a.shape() == b.shape() && a.indices().all(|(x, y)| {
+        let (ra, rb) = (a[(x,y)], b[(x,y)]);
+        *forward.entry(ra).or_insert(rb) == rb && *backward.entry(rb).or_insert(ra) == ra
+    })
+}
+
+pub trait SymmetryFormula: ExtendFormula {
+
+    /// Require `a` to be lexicographically at most `b`, false being smaller than true.
+    ///
+    /// A helper literal is forced true as long as the positions seen so far agree,
+    /// and while it holds, `a[i]` may only be true if `b[i]` is.
+    fn add_lex_leq(&mut self, a: &[Lit], b: &[Lit]) {
+        assert_eq!(a.len(), b.len(), "comparing sequences of different lengths");
+
+        let mut equal = self.new_lit();
+        self.add_clause(&[equal]);
+
+        for (&x, &y) in a.iter().zip(b) {
+            self.add_clause(&[!equal, !x, y]);
+
+            let next = self.new_lit();
+            self.add_clause(&[!equal, x, y, next]);
+            self.add_clause(&[!equal, !x, !y, next]);
+            equal = next;
+        }
+    }
+
+    /// Keep only the solutions that are no larger than their image: the literals of
+    /// `grid`, in row-major order, must be at most those of `image`.
+    fn add_lex_leader(&mut self, grid: &Matrix<Lit>, image: &Matrix<Lit>) {
+        let a: Vec<Lit> = grid.iter().copied().collect();
+        let b: Vec<Lit> = image.iter().copied().collect();
+        self.add_lex_leq(&a, &b);
+    }
+}
+
+impl<T: ExtendFormula> SymmetryFormula for T {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{backend::Solver, solve::solve_assuming};
+
+    #[test]
+    fn lex_leq() {
+        for n in 1..=3 {
+            let mut solver = Solver::new();
+            let a: Vec<Lit> = (0..n).map(|_| solver.new_lit()).collect();
+            let b: Vec<Lit> = (0..n).map(|_| solver.new_lit()).collect();
+            solver.add_lex_leq(&a, &b);
+
+            for bits in 0..1 << (2 * n) {
+                let value = |i: usize| bits & (1 << i) != 0;
+                let assumptions: Vec<_> = a.iter().chain(&b).enumerate()
+                    .map(|(i, &l)| if value(i) { l } else { !l })
+                    .collect();
+
+                let va: Vec<bool> = (0..n).map(value).collect();
+                let vb: Vec<bool> = (n..2*n).map(value).collect();
+                assert_eq!(solve_assuming(&mut solver, &assumptions).unwrap().is_some(), va <= vb, "{:?} {:?}", va, vb);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetries() {
+        assert_eq!(Symmetry::of_shape((3, 3)).len(), 7);
+        assert_eq!(Symmetry::of_shape((2, 3)).len(), 3);
+
+        let grid = Matrix::new(vec![0, 1, 2, 3, 4, 5], (2, 3)).unwrap();
+        let flip = Symmetry { transpose: false, flip_rows: false, flip_columns: true };
+        assert_eq!(flip.image(&grid), Matrix::new(vec![2, 1, 0, 5, 4, 3], (2, 3)).unwrap());
+
+        let rotated = Symmetry::ROTATE_90.image(&grid);
+        assert_eq!(rotated, Matrix::new(vec![3, 0, 4, 1, 5, 2], (3, 2)).unwrap());
+        assert_eq!(Symmetry::ROTATE_270.image(&rotated), grid);
+        assert_eq!(Symmetry::ROTATE_180.image(&grid), Symmetry::ROTATE_90.image(&rotated));
+
+        let regions = Matrix::new(vec![0, 1, 1, 0, 0, 1], (2, 3)).unwrap();
+        let rotated = Symmetry { transpose: false, flip_rows: true, flip_columns: true };
+        assert!(same_regions(&regions, &rotated.image(&regions)));
+        assert!(!same_regions(&regions, &flip.image(&regions)));
+    }
+}
@@ -0,0 +1,94 @@
+//! Grids of variables allocated in a single block. Formulas hand out variables with
+//! consecutive indices, so a grid with `depth` variables per cell is entirely described
+//! by its first variable and its dimensions, and the variable of any cell and layer is
+//! computed instead of stored. This matters for the `N×N×N` cubes of Latin squares.
+
+use varisat::{ExtendFormula, Var};
+
+use super::matrix::Matrix;
+
+/// `depth` variables for each cell of a grid, stored in row-major order, with the
+/// variables of a cell next to each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VarGrid {
+    base: usize,
+    shape: (usize, usize),
+    depth: usize,
+}
+
+impl VarGrid {
+    /// Fresh variables for every cell of a grid of the given shape, `depth` per cell
+    pub fn new(f: &mut impl ExtendFormula, shape: (usize, usize), depth: usize) -> Self {
+        let mut base = None;
+        for (i, v) in f.new_var_iter(shape.0 * shape.1 * depth).enumerate() {
+            let first = *base.get_or_insert(v.index());
+            assert_eq!(v.index(), first + i, "variables are allocated in order");
+        }
+        VarGrid { base: base.unwrap_or(0), shape, depth }
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// Number of variables of each cell
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The variable of layer `z` in the cell (x,y)
+    pub fn var(&self, x: usize, y: usize, z: usize) -> Var {
+        assert!(x < self.shape.0 && y < self.shape.1 && z < self.depth, "({}, {}, {}) out of the grid", x, y, z);
+        Var::from_index(self.base + (x * self.shape.1 + y) * self.depth + z)
+    }
+
+    /// The variables of the cell (x,y), one per layer
+    pub fn cell(&self, x: usize, y: usize) -> impl Iterator<Item=Var> {
+        let start = self.var(x, y, 0).index();
+        (start..start + self.depth).map(Var::from_index)
+    }
+
+    /// Every variable of the grid, in order
+    pub fn vars(&self) -> impl Iterator<Item=Var> {
+        let len = self.shape.0 * self.shape.1 * self.depth;
+        (self.base..self.base + len).map(Var::from_index)
+    }
+
+    /// The variables of layer `z`, one per cell, for the functions that work on matrices
+    pub fn layer(&self, z: usize) -> Matrix<Var> {
+        Matrix::from_fn(self.shape, |x, y| self.var(x, y, z))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use varisat::CnfFormula;
+
+    #[test]
+    fn layout() {
+        let mut f = CnfFormula::new();
+        f.new_var();
+        let grid = VarGrid::new(&mut f, (2, 3), 4);
+        let after = f.new_var();
+
+        assert_eq!(grid.var(0, 0, 0).index(), 1);
+        assert_eq!(grid.var(1, 2, 3).index(), 24);
+        assert_eq!(after.index(), 25);
+
+        let all: Vec<Var> = grid.vars().collect();
+        assert_eq!(all.len(), 24);
+        let cells: Vec<Var> = grid.layer(0).indices()
+            .flat_map(|(x, y)| grid.cell(x, y))
+            .collect();
+        assert_eq!(all, cells);
+        assert_eq!(grid.layer(2)[1][0], grid.var(1, 0, 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_the_grid() {
+        let grid = VarGrid::new(&mut CnfFormula::new(), (2, 2), 2);
+        grid.var(0, 0, 2);
+    }
+}
@@ -0,0 +1,60 @@
+//! Checking solutions against the rules of their game directly, without the SAT solver.
+//! This gives an independent way to test the solvers, and tells players which rule
+//! their own solution breaks, and where.
+//!
+//! Every game provides a `verify` function returning the list of [`Violation`]s found.
+
+use std::fmt::Display;
+
+use crate::util::coord::Coord;
+
+/// A rule broken by a solution, with the cells involved
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub rule: String,
+    pub cells: Vec<Coord>,
+}
+
+impl Violation {
+    pub fn new(rule: impl Into<String>, cells: Vec<Coord>) -> Self {
+        Violation { rule: rule.into(), cells }
+    }
+}
+
+/// Displays the rule, followed by the cells, e.g. `row 2 has 3 stars at (2,0) (2,3) (2,5)`
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.rule)?;
+        for (i, cell) in self.cells.iter().enumerate() {
+            f.write_str(if i == 0 { " at " } else { " " })?;
+            write!(f, "{}", cell)?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of a verification: valid if nothing was found
+pub fn verdict(violations: Vec<Violation>) -> Result<(), Vec<Violation>> {
+    if violations.is_empty() { Ok(()) } else { Err(violations) }
+}
+
+/// Fails with a single violation if the solution does not have the shape of the grid,
+/// in which case there is no point checking anything else.
+pub fn same_shape(grid: (usize, usize), solution: (usize, usize)) -> Result<(), Vec<Violation>> {
+    if grid == solution { return Ok(()) }
+    Err(vec![Violation::new(format!("the solution is {}x{}, the grid is {}x{}",
+                                    solution.0, solution.1, grid.0, grid.1), vec![])])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(Violation::new("row 2 has 2 stars", vec![Coord::new(2,0), Coord::new(2,3)]).to_string(), "row 2 has 2 stars at (2,0) (2,3)");
+        assert_eq!(Violation::new("wrong shape", vec![]).to_string(), "wrong shape");
+        assert!(same_shape((4,4), (4,5)).is_err());
+        assert_eq!(verdict(vec![]), Ok(()));
+    }
+}
@@ -1,165 +1,474 @@
-use std::{collections::BTreeSet, str::FromStr, fmt::Write};
+use std::{str::FromStr, ops::ControlFlow};
 
-use crate::util::matrix::{Matrix, ShapeError};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use super::util::{choose, solve::DnfFormula};
-use anyhow::{anyhow, bail};
-use varisat::{Solver, ExtendFormula};
+use crate::generate::Generate;
+use crate::render::{Fill, Scene};
+use crate::transform::Transform;
+use crate::verify::{Violation, verdict, same_shape};
+use crate::util::{backend::{record, BackendError}, gridsolver::{GridSolver, GridModel}, coord::Coord, matrix::{Matrix, ShapeError, umat}, deduce::{deduce, Deduction, Step}, gridparse::{CellChar, GridError}};
 
+use super::util::{card::{CardFormula, Encoding}, integer::IntFormula, solve::{backbone, minimal_core, random_model, Models, SolveOptions}, symmetry::{Symmetry, SymmetryFormula}};
+use thiserror::Error;
+use tracing::{debug_span, info};
+use varisat::{CnfFormula, ExtendFormula, Var, Lit};
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Problem(Matrix<Option<u8>>);
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Solution(Matrix<bool>);
 
+/// Positions of the hints, along with the literal enabling each of them
+type Selectors = Vec<((usize, usize), Lit)>;
+
+/// A Voisimage encoded into a [`GridSolver`], for hypotheses about the color of some cells
+pub struct Session {
+    sat: GridSolver,
+    grid: Matrix<Var>,
+    hints: Selectors,
+}
+
 impl Problem {
     pub fn new(shape: (usize, usize), grid: Vec<Option<u8>>) -> Result<Self, ShapeError> {
         Matrix::new(grid, shape).map(Self)
     }
 
-    pub fn solve(&self) -> Option<Solution> {
+    /// The hint of every cell, if any
+    pub fn grid(&self) -> &Matrix<Option<u8>> {
+        &self.0
+    }
+
+    /// The grid to draw, with the hints
+    pub fn scene(&self) -> Scene {
+        let mut scene = Scene::new(self.0.shape());
+        for (cell, hint) in scene.cells.iter_mut().zip(&self.0) {
+            cell.label = hint.map(|k| k.to_string());
+        }
+        scene
+    }
+
+    /// The grid with a hint in every cell, counting the painted cells of `picture`
+    pub fn from_picture(picture: &Matrix<bool>) -> Self {
+        Self(Matrix::from_fn(picture.shape(), |x, y| {
+            Some(picture.neighbors((x, y)).into_iter().filter(|&c| picture[c]).count() as u8)
+        }))
+    }
+
+    pub fn solve(&self) -> Result<Option<Solution>, BackendError> {
+        self.session().solve(&[])
+    }
+
+    /// Find a picture drawn at random from `seed`, when the hints allow several.
+    /// See [`random_model`].
+    pub fn solve_random(&self, seed: u64) -> Result<Option<Solution>, BackendError> {
+        self.session().solve_random(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Count the pictures matching the hints, by enumerating them.
+    /// With `break_symmetry`, symmetric pictures only count once.
+    pub fn count_solutions(&self, options: SolveOptions) -> Result<usize, BackendError> {
+        self.session_with(options).count()
+    }
+
+    /// Find a picture that differs from `prior` in at least one cell.
+    /// Returns `None` if `prior` is the only picture matching the hints.
+    pub fn solve_excluding(&self, prior: &Solution) -> Result<Option<Solution>, BackendError> {
+        let mut session = self.session();
+        session.exclude(prior);
+        session.solve(&[])
+    }
+
+    /// Call `f` with every picture matching the hints, see [`Session::solve_with`].
+    pub fn solve_with<B>(&self, f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        self.session().solve_with(f)
+    }
+
+    /// List the cells whose color is the same in every picture matching the hints,
+    /// in row-major order. Returns `None` if there is no such picture.
+    pub fn hints(&self) -> Result<Option<Vec<((usize, usize), bool)>>, BackendError> {
+        let mut session = self.session();
+        let Some(s) = session.solve(&[])? else { return Ok(None) };
+
+        let candidates = self.0.indices()
+            .map(|(x,y)| (((x,y), s.0[(x,y)]), session.grid[(x,y)].lit(s.0[(x,y)])))
+            .collect();
+
+        let hints = session.selectors();
+        Ok(Some(backbone(&mut session.sat, &hints, candidates)?))
+    }
+
+    /// When no picture matches the hints, find a minimal set of hints that already
+    /// contradict each other. Returns `None` if the grid has a solution.
+    pub fn conflict(&self) -> Result<Option<Vec<(usize, usize)>>, BackendError> {
+        let mut session = self.session();
+        let selectors = session.selectors();
+        let Some(core) = minimal_core(&mut session.sat, &selectors)? else { return Ok(None) };
+
+        Ok(Some(session.hints.into_iter()
+            .filter(|(_, s)| core.contains(s))
+            .map(|(pos, _)| pos)
+            .collect()))
+    }
+
+    /// Remove redundant hints: find a minimal subset of them that still leads to the same
+    /// unique picture. Removing any further hint from the result allows another picture.
+    /// Returns `None` if the hints do not match exactly one picture.
+    pub fn minimize(&self) -> Result<Option<Problem>, BackendError> {
+        let mut session = self.session();
+        let Some(s) = session.solve(&[])? else { return Ok(None) };
+
+        // With the only picture forbidden, the hints become contradictory,
+        // and a minimal conflict is a minimal set of hints keeping the picture unique.
+        session.exclude(&s);
+        let selectors = session.selectors();
+        let Some(core) = minimal_core(&mut session.sat, &selectors)? else { return Ok(None) };
+
+        let mut grid = self.0.map(|_| None);
+        for ((x,y), _) in session.hints.into_iter().filter(|(_, s)| core.contains(s)) {
+            grid[(x,y)] = self.0[(x,y)];
+        }
+        Ok(Some(Problem(grid)))
+    }
+
+    /// Check `solution` against the hints directly, without the SAT solver.
+    /// Lists every hint that does not match if it is not valid.
+    pub fn verify(&self, solution: &Solution) -> Result<(), Vec<Violation>> {
+        let picture = &solution.0;
+        same_shape(self.0.shape(), picture.shape())?;
+
+        let violations = self.0.coords()
+            .filter_map(|pos| {
+                let k = self.0[pos]? as usize;
+                let painted = picture.neighbors(pos).into_iter().filter(|&c| picture[c]).count();
+                (painted != k).then(|| Violation::new(format!("the hint {} sees {} painted cells", k, painted), vec![pos]))
+            })
+            .collect();
+
+        verdict(violations)
+    }
+
+    /// Paint the picture using only simple human rules, recording every step:
+    ///  - once a hint counts enough painted cells, the rest of its neighborhood is blank
+    ///  - when a hint needs every undecided cell of its neighborhood, they are all painted
+    ///  - when the undecided cells of a hint all belong to the neighborhood of
+    ///    another hint, the difference between the two tells about the remaining cells
+    pub fn deduce(&self) -> Deduction<bool> {
+        let clues = |g: &Matrix<Option<bool>>| self.clue_counts(g);
+        let count = |g: &Matrix<Option<bool>>| count(&clues(g));
+        let overlap = |g: &Matrix<Option<bool>>| overlap(&clues(g));
+        deduce(umat![None; self.0.shape()], &[&count, &overlap])
+    }
+
+    /// For every hint, its position, the number of cells it still needs painted,
+    /// and the cells of its neighborhood that are still undecided
+    fn clue_counts(&self, grid: &Matrix<Option<bool>>) -> Vec<ClueCount> {
+        grid.coords()
+            .filter_map(|pos| {
+                let k = self.0[pos]? as usize;
+                let neighs = grid.neighbors(pos);
+                let painted = neighs.iter().filter(|&&c| grid[c] == Some(true)).count();
+                let free = neighs.into_iter().filter(|&c| grid[c].is_none()).collect();
+                Some(ClueCount { pos, needed: k.saturating_sub(painted), free })
+            })
+            .collect()
+    }
+
+    /// The formula of this grid, to inspect or export it without solving, along with
+    /// the variable of each cell, true for a painted cell. The hints are unit clauses.
+    pub fn encode(&self) -> (CnfFormula, Matrix<Var>) {
+        let (mut formula, (_, grid, hints)) = record(|| self.encode_solver());
+        for (_, selector) in hints {
+            formula.add_clause(&[selector]);
+        }
+        (formula, grid)
+    }
+
+    /// Encode the grid into a [`Session`].
+    pub fn session(&self) -> Session {
+        self.session_with(SolveOptions::default())
+    }
+
+    /// Encode the grid into a [`Session`], with the given options.
+    pub fn session_with(&self, options: SolveOptions) -> Session {
+        let (mut sat, grid, hints) = self.encode_solver();
+
+        if options.break_symmetry {
+            let lits = grid.map(|v| v.positive());
+            for symmetry in self.symmetries() {
+                sat.add_lex_leader(&lits, &symmetry.image(&lits));
+            }
+        }
+
+        Session { sat, grid, hints }
+    }
+
+    /// The symmetries of the grid mapping every hint onto the same hint
+    fn symmetries(&self) -> Vec<Symmetry> {
+        Symmetry::of_shape(self.0.shape()).into_iter()
+            .filter(|s| s.image(&self.0) == self.0)
+            .collect()
+    }
+
+    fn encode_solver(&self) -> (GridSolver, Matrix<Var>, Selectors) {
         let shape = self.0.shape();
+        let _span = debug_span!("encode constraints", ?shape).entered();
 
-        let mut sat = Solver::new();
-        let cells: Vec<_> = sat.new_var_iter(shape.0 * shape.1).collect();
-        let grid = Matrix::new(cells, shape).unwrap();
+        let mut sat = GridSolver::new();
+        let grid = sat.bool_grid(shape);
+        let mut hints = vec![];
         
         for (x,y) in grid.indices() {
 
-            if let Some(k) = self.0[x][y] {
+            if let Some(k) = self.0[(x,y)] {
 
-                let mut clause = vec![];
-                let neighs = self.0.neighbors((x,y));
+                let neighs: Vec<Lit> = self.0.neighbors((x,y)).iter()
+                    .map(|&c| grid[c].positive())
+                    .collect();
 
-                choose(neighs.len(), k as usize, |bitmap| {
-                    let alt = neighs.iter()
-                        .zip(bitmap)
-                        .map(|(&(x,y), &b)| grid[x][y].lit(b))
-                        .collect::<Vec<_>>();
-                    clause.push(alt);
-                });
-
-                sat.add_dnf(clause);
+                // Exactly k painted neighbors, with a counter instead of every choice of them
+                let selector = sat.new_lit();
+                sat.add_implied(selector, |f| f.exactly_k(&neighs, k as usize, Encoding::default()));
+                hints.push(((x,y), selector));
 
             }
 
         }
 
-        sat.solve().expect("solver");
+        info!(?shape, hints = hints.len(), clauses = sat.clauses(), "encoded");
+        (sat, grid, hints)
+    }
+}
+
+impl Solution {
+    /// The painted cells of the picture
+    pub fn grid(&self) -> &Matrix<bool> {
+        &self.0
+    }
+
+    /// The picture to draw, with the hints of the problem it solves
+    pub fn scene(&self, problem: &Problem) -> Scene {
+        let mut scene = problem.scene();
+        for (cell, &painted) in scene.cells.iter_mut().zip(&self.0) {
+            if painted { cell.fill = Fill::Shaded }
+        }
+        scene
+    }
+}
+
+impl Session {
+    /// Solve the grid, assuming that every listed cell is painted (`true`) or blank (`false`).
+    /// A cell out of the grid cannot be either, and makes the grid unsatisfiable.
+    pub fn solve(&mut self, assumptions: &[((usize, usize), bool)]) -> Result<Option<Solution>, BackendError> {
+        let mut lits = self.selectors();
+        for &((x,y), b) in assumptions {
+            let Some(var) = self.grid.get(x, y) else { return Ok(None) };
+            lits.push(var.lit(b));
+        }
 
+        Ok(self.sat.solve(&lits)?.map(|m| self.decode(&m)))
+    }
 
-        let good: BTreeSet<_> = sat.model()?
-            .into_iter()
-            .filter(|l| l.is_positive())
-            .map(|l| l.var())
+    /// Solve the grid, drawing a picture at random. See [`random_model`].
+    pub fn solve_random(&mut self, rng: &mut impl Rng) -> Result<Option<Solution>, BackendError> {
+        let selectors = self.selectors();
+        let vars: Vec<_> = self.grid.iter().copied().collect();
+        let m = random_model(&mut self.sat, &selectors, &vars, rng)?;
+        Ok(m.map(|m| self.decode(&m.into())))
+    }
+
+    /// Count the pictures, excluding each of them once found. See [`Models`].
+    pub fn count(&mut self) -> Result<usize, BackendError> {
+        let selectors = self.selectors();
+        let vars: Vec<_> = self.grid.iter().copied().collect();
+        Models::new(&mut self.sat, vars).assuming(&selectors).try_count()
+    }
+
+    fn decode(&self, model: &GridModel) -> Solution {
+        Solution(model.bools(&self.grid))
+    }
+
+    /// Call `f` with every picture matching the hints left, see [sessions](crate::util::solve#sessions).
+    pub fn solve_with<B>(&mut self, mut f: impl FnMut(Solution) -> ControlFlow<B>) -> Result<ControlFlow<B>, BackendError> {
+        while let Some(s) = self.solve(&[])? {
+            self.exclude(&s);
+            if let ControlFlow::Break(b) = f(s) { return Ok(ControlFlow::Break(b)) }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Rule out the picture `prior` from now on.
+    pub fn exclude(&mut self, prior: &Solution) {
+        let blocking: Vec<_> = self.grid.indices()
+            .map(|(x,y)| self.grid[(x,y)].lit(!prior.0[(x,y)]))
             .collect();
+        self.sat.add_clause(&blocking);
+    }
 
-        let grid = grid.map(|var| good.contains(var));
+    fn selectors(&self) -> Vec<Lit> {
+        self.hints.iter().map(|&(_, s)| s).collect()
+    }
+}
 
-        Some(Solution(grid))
+struct ClueCount {
+    pos: Coord,
+    needed: usize,
+    free: Vec<Coord>,
+}
+
+fn count(clues: &[ClueCount]) -> Option<Step<bool>> {
+    for c in clues {
+        let Some(&cell) = c.free.first() else { continue };
+        if c.needed == 0 {
+            return Some(Step { cell, value: false, reason: format!("the hint at {} is complete", c.pos) })
+        }
+        if c.needed == c.free.len() {
+            return Some(Step { cell, value: true, reason: format!("the hint at {} needs all its remaining cells", c.pos) })
+        }
     }
+    None
 }
 
-impl std::fmt::Display for Problem {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for line in self.0.lines() {
-            for cell in line {
-                let c = match cell {
-                    None => '.',
-                    Some(n) => char::from_digit(*n as u32, 10).unwrap(),
-                };
-                f.write_char(c)?;
-            }
-            f.write_char('\n')?
+fn overlap(clues: &[ClueCount]) -> Option<Step<bool>> {
+    for inner in clues.iter().filter(|c| !c.free.is_empty()) {
+        for outer in clues {
+            if outer.pos == inner.pos || !inner.free.iter().all(|c| outer.free.contains(c)) { continue }
+
+            // `inner` paints exactly `inner.needed` cells within the free cells of `outer`,
+            // the others have to be found among the remaining ones.
+            let rest: Vec<_> = outer.free.iter().filter(|c| !inner.free.contains(c)).collect();
+            let Some(extra) = outer.needed.checked_sub(inner.needed) else { continue };
+            let Some(&&cell) = rest.first() else { continue };
+
+            let value = if extra == 0 { false } else if extra == rest.len() { true } else { continue };
+            return Some(Step {
+                cell,
+                value,
+                reason: format!("the hint at {} needs {} more than the hint at {}", outer.pos, extra, inner.pos),
+            })
         }
-        Ok(())
     }
+    None
 }
 
-impl FromStr for Problem {
-    type Err = anyhow::Error;
+/// Voisimages are generated from a given picture rather than a random one:
+/// the shape is the picture itself, and clues are hints about it.
+/// `generate` then turns any small image into a puzzle, provided the
+/// hints in all the cells are enough to recover it.
+impl Generate for Problem {
+    type Shape = Matrix<bool>;
+    type Solution = Solution;
+    type Clue = ((usize, usize), u8);
+
+    fn random_solution(picture: &Matrix<bool>, _: &mut StdRng) -> Result<Option<Solution>, BackendError> {
+        Ok(Some(Solution(picture.clone())))
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut w = None;
-        let mut h = 0;
-        let mut grid = vec![];
-
-        for line in s.lines() {
-            let len = line.len();
-            if *w.get_or_insert(len) != len {
-                bail!("Unequal line")
-            }
+    fn clues(_: &Matrix<bool>, solution: &Solution) -> Vec<Self::Clue> {
+        let full = Problem::from_picture(&solution.0);
+        full.0.enumerate().filter_map(|(pos, &v)| Some((pos, v?))).collect()
+    }
 
-            for ch in line.chars() {
-                let cell = match ch {
-                    '.' => None,
-                    '0'..='9' => Some(ch.to_digit(10).unwrap() as u8),
-                    other => bail!("Invalid character {:?}", other),
-                };
-                grid.push(cell);
-            }
-            h += 1;
+    fn from_clues(picture: &Matrix<bool>, _: &Solution, clues: &[Self::Clue]) -> Self {
+        let mut grid = umat![None; picture.shape()];
+        for &((x,y), k) in clues {
+            grid[(x,y)] = Some(k);
         }
-        let w = w.ok_or(anyhow!("Empty grid"))?;
-
+        Problem(grid)
+    }
 
-        Ok(Self::new((h,w), grid)?)
+    fn is_unique(&self) -> Result<bool, BackendError> {
+        let Some(s) = self.solve()? else { return Ok(false) };
+        Ok(self.solve_excluding(&s)?.is_none())
+    }
 
+    fn is_easy(&self) -> bool {
+        self.deduce().is_complete()
     }
 }
 
-impl std::fmt::Display for Solution {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for line in self.0.lines() {
-            for &cell in line {
-                f.write_char(if cell { '█' } else { '░' })?;
-            }
-            f.write_char('\n')?
-        }
-        Ok(())
+/// Hints count painted cells, and have no labels to rename
+impl Transform for Problem {
+    fn transform(&self, symmetry: Symmetry) -> Self {
+        Problem(symmetry.image(&self.0))
+    }
+
+    fn relabelings(&self) -> Vec<Self> {
+        vec![self.clone()]
     }
 }
 
-pub mod color {
+impl Transform for Solution {
+    fn transform(&self, symmetry: Symmetry) -> Self {
+        Solution(symmetry.image(&self.0))
+    }
 
-    use termcolor::{BufferWriter, ColorSpec, Color, WriteColor};
-    use std::io::Write;
+    fn relabelings(&self) -> Vec<Self> {
+        vec![self.clone()]
+    }
+}
 
-    use super::*;
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
-    #[derive(Debug)]
-    pub struct Pretty<'a>(pub &'a Problem, pub &'a Solution);
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error(transparent)]
+    Grid(#[from] GridError),
+}
 
-    impl Pretty<'_> {
+impl FromStr for Problem {
+    type Err = ParseError;
 
-        pub fn color_fmt(&self, w: BufferWriter) -> Result<(), std::io::Error> {
-            let mut buf = w.buffer();
-            let scheme = |b| {if b { Color::White } else { Color::Black }};
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.parse()?))
+    }
+}
 
-            for (ps, ss) in self.0.0.lines().zip(self.1.0.lines()) {
-                for (p, s) in ps.iter().zip(ss) {
-                    let mut color = ColorSpec::new();
-                    color.set_bold(true)
-                         .set_bg(Some(scheme(*s)))
-                         .set_fg(Some(scheme(!*s)));
+/// A cell of a picture, painted or blank
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Pixel(bool);
+
+/// `█` for a painted cell and `░` for a blank one, also read from `#` and `.`
+impl CellChar for Pixel {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '█' | '#' => Some(Pixel(true)),
+            '░' | '.' => Some(Pixel(false)),
+            _ => None,
+        }
+    }
 
+    fn to_char(&self) -> char {
+        if self.0 { '█' } else { '░' }
+    }
+}
 
-                    buf.set_color(&color)?;
-                    write!(buf, "{}", match p { Some(k) => char::from_digit(*k as u32, 10).unwrap(), None => ' ' })?;
-                }
-                buf.reset()?;
-                writeln!(buf)?;
-            }
-            w.print(&buf)
-        }
+/// Parses a picture, as printed by the `Display` implementation, or using `#` for painted
+/// cells and `.` for blank ones.
+impl FromStr for Solution {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pixels: Matrix<Pixel> = s.parse()?;
+        Ok(Solution(pixels.map(|p| p.0)))
     }
 }
 
+impl std::fmt::Display for Solution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.map(|&b| Pixel(b)).fmt(f)
+    }
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::util::matrix::mat;
 
     fn parse(input: &str) {
         input.parse::<Problem>().unwrap();
@@ -167,12 +476,12 @@ mod test {
 
     fn solve(input: &str) {
         let p: Problem = input.parse().unwrap();
-        p.solve().unwrap();
+        p.solve().unwrap().unwrap();
     }
 
     fn print(input: &str, solution: &str) {
         let p: Problem = input.parse().unwrap();
-        let s = p.solve().unwrap();
+        let s = p.solve().unwrap().unwrap();
         let out = format!("{}", s);
         assert_eq!(out, solution);
     }
@@ -223,6 +532,80 @@ mod test {
 
     }
 
+    #[test]
+    fn alternative() {
+        let p: Problem = "\
+1.
+..
+".parse().unwrap();
+        let first = p.solve().unwrap().unwrap();
+        let second = p.solve_excluding(&first).unwrap().unwrap();
+        assert_ne!(first, second);
+
+        let p: Problem = "\
+4.
+..
+".parse().unwrap();
+        let s = p.solve().unwrap().unwrap();
+        assert!(p.solve_excluding(&s).unwrap().is_none());
+    }
+
+    #[test]
+    fn symmetry() {
+        // A single painted cell: in the center, on a corner or on an edge
+        let p: Problem = "\
+...
+.1.
+...
+".parse().unwrap();
+        assert_eq!(p.count_solutions(SolveOptions::default()).unwrap(), 9);
+        assert_eq!(p.count_solutions(SolveOptions { break_symmetry: true, ..Default::default() }).unwrap(), 3);
+
+        // Only the transposition keeps the hints in place
+        let p: Problem = "\
+1..
+.1.
+...
+".parse().unwrap();
+        assert_eq!(p.symmetries(), vec![Symmetry { transpose: true, flip_rows: false, flip_columns: false }]);
+    }
+
+    #[test]
+    fn assumptions() {
+        let p: Problem = "\
+2.
+..
+".parse().unwrap();
+        let mut session = p.session();
+
+        let s = session.solve(&[((0,1), true), ((1,0), true)]).unwrap().unwrap();
+        assert_eq!(s.0, mat![false, true; true, false]);
+
+        assert!(session.solve(&[((0,1), true), ((1,0), true), ((1,1), true)]).unwrap().is_none());
+        assert!(session.solve(&[((0,0), false), ((1,1), false)]).unwrap().is_some());
+        assert!(session.solve(&[((2,0), false)]).unwrap().is_none());
+    }
+
+    #[test]
+    fn hints() {
+        let p: Problem = "\
+0..
+...
+...
+".parse().unwrap();
+        assert_eq!(p.hints().unwrap().unwrap(), vec![((0,0), false), ((0,1), false), ((1,0), false), ((1,1), false)]);
+    }
+
+    #[test]
+    fn conflict() {
+        let p: Problem = "\
+0.1
+...
+..4
+".parse().unwrap();
+        assert_eq!(p.conflict().unwrap().unwrap(), vec![(0,0), (2,2)]);
+    }
+
     mod small {
 
         use crate::util::matrix::mat;
@@ -256,7 +639,7 @@ mod test {
 
         #[test]
         fn solve() {
-            assert_eq!(problem().solve().unwrap(), solution());
+            assert_eq!(problem().solve().unwrap().unwrap(), solution());
         }
 
         #[test]
@@ -264,11 +647,68 @@ mod test {
             assert_eq!(&format!("{}", solution()), SOLUTION_STRING);
         }
 
+        #[test]
+        fn no_conflict() {
+            assert!(problem().conflict().unwrap().is_none());
+        }
 
+        #[test]
+        fn minimize() {
+            let m = problem().minimize().unwrap().unwrap();
+            let kept: Vec<_> = m.0.indices().filter(|&(x,y)| m.0[(x,y)].is_some()).collect();
+            assert!(kept.len() < 9);
+
+            let s = m.solve().unwrap().unwrap();
+            assert_eq!(s, solution());
+            assert!(m.solve_excluding(&s).unwrap().is_none());
+
+            // Every remaining hint is needed
+            for (x,y) in kept {
+                let mut fewer = m.clone();
+                fewer.0[(x,y)] = None;
+                let s = fewer.solve().unwrap().unwrap();
+                assert!(fewer.solve_excluding(&s).unwrap().is_some());
+            }
+        }
+
+        #[test]
+        fn picture() {
+            let s: Solution = SOLUTION_STRING.parse().unwrap();
+            assert_eq!(s, solution());
+            assert_eq!(Problem::from_picture(&s.0), problem());
+        }
+
+        #[test]
+        fn verify() {
+            assert_eq!(problem().verify(&solution()), Ok(()));
+
+            let mut s = solution();
+            s.0[2][2] = true;
+            assert_eq!(problem().verify(&s).unwrap_err(), vec![
+                Violation::new("the hint 5 sees 6 painted cells", vec![Coord::new(1,1)]),
+                Violation::new("the hint 3 sees 4 painted cells", vec![Coord::new(1,2)]),
+                Violation::new("the hint 3 sees 4 painted cells", vec![Coord::new(2,1)]),
+                Violation::new("the hint 1 sees 2 painted cells", vec![Coord::new(2,2)]),
+            ]);
+
+            let wide: Solution = "##\n..\n".parse().unwrap();
+            assert!(problem().verify(&wide).is_err());
+        }
+
+        #[test]
+        fn generate() {
+            use crate::generate::{generate, Difficulty};
+
+            for difficulty in [Difficulty::Easy, Difficulty::Hard] {
+                let p: Problem = generate(&solution().0, difficulty, 3).unwrap().unwrap();
+                assert!(p.meets(difficulty).unwrap());
+                assert_eq!(p.solve().unwrap().unwrap(), solution());
+            }
+        }
     }
 
     mod large {
-        use super::Problem;
+        use super::{Problem, Solution};
         const SAMPLE: &str = "\
 ....4......6....4..20......0.0.
 .4....4.0.6...1....2.0..0......
@@ -325,6 +765,14 @@ mod test {
     #[test] fn solve() { super::solve(SAMPLE) }
     #[test] fn print() { super::print(SAMPLE, SOLUTION) }
 
+    #[test]
+    fn deduction() {
+        let p: Problem = SAMPLE.parse().unwrap();
+        let d = p.deduce();
+        assert!(d.is_complete());
+        assert_eq!(Solution(d.grid.map(|c| c.unwrap())).to_string(), SOLUTION);
+    }
+
     }
 
 }